@@ -0,0 +1,8 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate parity_wasm;
+
+fuzz_target!(|data: &[u8]| {
+	parity_wasm::fuzz::analyze(data);
+});
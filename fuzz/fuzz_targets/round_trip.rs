@@ -0,0 +1,10 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate parity_wasm;
+
+fuzz_target!(|data: &[u8]| {
+	if let Err(error) = parity_wasm::fuzz::round_trip(data) {
+		panic!("{}", error);
+	}
+});
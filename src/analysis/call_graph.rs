@@ -0,0 +1,168 @@
+use alloc::vec::Vec;
+use crate::elements::{FunctionType, Module, Instruction, Internal, ImportCountType};
+
+/// Call from one function to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CallEdge {
+	/// Index of the called function.
+	pub callee: u32,
+	/// Whether the call is a `call_indirect` which may reach the function.
+	pub indirect: bool,
+}
+
+/// Calls between the functions of a module, imported ones included.
+///
+/// A `call_indirect` may reach every function placed in a table by an element segment
+/// whose signature matches the call's type. Functions put in tables by the host or
+/// through imported or exported tables are not known to the module, so they are not
+/// accounted for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallGraph {
+	imported: u32,
+	/// Calls of every function, by function index.
+	callees: Vec<Vec<CallEdge>>,
+}
+
+/// Call graph of `module`.
+///
+/// Calls to functions which do not exist are left out.
+pub fn call_graph(module: &Module) -> CallGraph {
+	let imported = module.import_count(ImportCountType::Function) as u32;
+	let functions = module.functions_space();
+
+	let mut in_tables: Vec<u32> = module.elements_section().map(|s| s.entries()).unwrap_or(&[]).iter()
+		.flat_map(|segment| segment.members().iter().cloned())
+		.filter(|&index| (index as usize) < functions)
+		.collect();
+	in_tables.sort_unstable();
+	in_tables.dedup();
+	let in_tables: Vec<(u32, Option<&FunctionType>)> = in_tables.into_iter()
+		.map(|index| (index, module.function_type(index)))
+		.collect();
+
+	let mut callees = vec![Vec::new(); imported as usize];
+	for body in module.code_section().map(|s| s.bodies()).unwrap_or(&[]) {
+		let mut calls = Vec::new();
+		for instruction in body.code().elements() {
+			match *instruction {
+				Instruction::Call(callee) if (callee as usize) < functions =>
+					calls.push(CallEdge { callee, indirect: false }),
+				Instruction::CallIndirect(type_ref, _) => {
+					let call_type = match module.signature(type_ref) {
+						Some(call_type) => call_type,
+						None => continue,
+					};
+					calls.extend(in_tables.iter()
+						.filter(|&&(_, signature)| signature == Some(call_type))
+						.map(|&(callee, _)| CallEdge { callee, indirect: true }));
+				},
+				_ => {},
+			}
+		}
+		calls.sort_unstable();
+		calls.dedup();
+		callees.push(calls);
+	}
+	callees.resize(functions, Vec::new());
+	CallGraph { imported, callees }
+}
+
+impl CallGraph {
+	/// Number of functions, imported ones included.
+	pub fn len(&self) -> usize { self.callees.len() }
+
+	/// Whether the module has no functions.
+	pub fn is_empty(&self) -> bool { self.callees.is_empty() }
+
+	/// Whether the function at `index` is imported.
+	pub fn is_imported(&self, index: u32) -> bool { index < self.imported }
+
+	/// Calls made by the function at `index`, ordered by callee.
+	pub fn callees(&self, index: u32) -> &[CallEdge] {
+		self.callees.get(index as usize).map(|callees| &callees[..]).unwrap_or(&[])
+	}
+
+	/// Functions which call the function at `index`, directly or not.
+	pub fn callers(&self, index: u32) -> Vec<u32> {
+		(0..self.callees.len() as u32)
+			.filter(|&caller| self.callees(caller).iter().any(|edge| edge.callee == index))
+			.collect()
+	}
+
+	/// Functions reachable from `roots`, the roots included, ordered by index.
+	pub fn reachable(&self, roots: &[u32]) -> Vec<u32> {
+		let mut reached = vec![false; self.callees.len()];
+		let mut pending: Vec<u32> = roots.iter().cloned().filter(|&root| (root as usize) < reached.len()).collect();
+		while let Some(index) = pending.pop() {
+			if reached[index as usize] {
+				continue;
+			}
+			reached[index as usize] = true;
+			pending.extend(self.callees(index).iter().map(|edge| edge.callee));
+		}
+		(0..reached.len() as u32).filter(|&index| reached[index as usize]).collect()
+	}
+
+	/// Imported functions reachable from `roots`, ordered by index.
+	///
+	/// These are the host functions the module can call when entered at the roots.
+	pub fn reachable_imports(&self, roots: &[u32]) -> Vec<u32> {
+		self.reachable(roots).into_iter().filter(|&index| self.is_imported(index)).collect()
+	}
+}
+
+/// Functions `module` can be entered at: its exported functions and its start function.
+pub fn entry_points(module: &Module) -> Vec<u32> {
+	let mut roots: Vec<u32> = module.export_section().map(|s| s.entries()).unwrap_or(&[]).iter()
+		.filter_map(|export| match *export.internal() {
+			Internal::Function(index) => Some(index),
+			_ => None,
+		})
+		.chain(module.start_section())
+		.collect();
+	roots.sort_unstable();
+	roots.dedup();
+	roots
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{call_graph, entry_points, CallEdge};
+	use crate::builder;
+	use crate::elements::{Instruction::*, Instructions, ValueType};
+
+	#[test]
+	fn direct_and_indirect() {
+		let module = builder::module()
+			.import().module("env").field("log").external().func(0).build()
+			.import().module("env").field("abort").external().func(0).build()
+			// 2: exported, calls `log` and indirectly 3.
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![Call(0), I32Const(0), CallIndirect(1, 0), End])).build()
+				.build()
+			// 3: in the table.
+			.function().signature().with_param(ValueType::I32).build()
+				.body().build()
+				.build()
+			// 4: calls `abort`, but is only reachable as a table entry of another type.
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![Call(1), End])).build()
+				.build()
+			.table().with_min(2).with_element(0, vec![3, 4]).build()
+			.export().field("main").internal().func(2).build()
+			.build();
+
+		let graph = call_graph(&module);
+		assert_eq!(graph.len(), 5);
+		assert_eq!(graph.callees(2), &[
+			CallEdge { callee: 0, indirect: false },
+			CallEdge { callee: 3, indirect: true },
+		][..]);
+		assert_eq!(graph.callers(1), vec![4]);
+
+		let roots = entry_points(&module);
+		assert_eq!(roots, vec![2]);
+		assert_eq!(graph.reachable(&roots), vec![0, 2, 3]);
+		assert_eq!(graph.reachable_imports(&roots), vec![0]);
+	}
+}
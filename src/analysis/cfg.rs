@@ -0,0 +1,320 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+use crate::elements::{BodyStructure, BranchTarget, Error, FuncBody, Instruction};
+
+/// Straight-line run of instructions, entered at its first one only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+	instructions: Range<usize>,
+	successors: Vec<usize>,
+	returns: bool,
+}
+
+impl BasicBlock {
+	/// Indices of the instructions of the block in the body.
+	pub fn instructions(&self) -> Range<usize> { self.instructions.clone() }
+
+	/// Blocks control may continue at after this one, by index in `ControlFlowGraph::blocks`.
+	pub fn successors(&self) -> &[usize] { &self.successors }
+
+	/// Whether control may return from the function at the end of the block.
+	pub fn returns(&self) -> bool { self.returns }
+}
+
+/// Control-flow graph of a function body.
+///
+/// Edges follow `br`, `br_if`, `br_table`, `if`, `else` and `return`; `unreachable`
+/// ends a block without successors. Exception handling is not modelled: `throw` and
+/// the handlers of `try` blocks get no edges of their own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+	/// Blocks, ordered by their first instruction; the entry comes first.
+	blocks: Vec<BasicBlock>,
+}
+
+impl ControlFlowGraph {
+	/// Graph of `body`. Bodies with unbalanced blocks or invalid branches are an error.
+	pub fn new(body: &FuncBody) -> Result<Self, Error> {
+		let code = body.code().elements();
+		let structure = BodyStructure::new(code)?;
+
+		// Blocks start at the entry, at branch targets, and after instructions which
+		// don't just fall through.
+		let mut leaders = vec![false; code.len()];
+		leaders[0] = true;
+		for block in structure.blocks() {
+			leaders[block.label()] = true;
+		}
+		for (index, instruction) in code.iter().enumerate() {
+			if ends_block(instruction) {
+				if let Some(next) = leaders.get_mut(index + 1) {
+					*next = true;
+				}
+			}
+		}
+		let starts: Vec<usize> = (0..code.len()).filter(|&index| leaders[index]).collect();
+
+		let block_at = |index: usize| starts.binary_search(&index).expect("branches continue at leaders; qed");
+		let mut blocks = Vec::with_capacity(starts.len());
+		for (block, &start) in starts.iter().enumerate() {
+			let end = starts.get(block + 1).cloned().unwrap_or(code.len());
+			let last = end - 1;
+			let mut successors = Vec::new();
+			let mut returns = false;
+			{
+				let mut branch_to = |target: BranchTarget| match target {
+					BranchTarget::Block(target) => successors.push(block_at(structure.blocks()[target].label())),
+					BranchTarget::Function => returns = true,
+				};
+				let fallthrough = if end < code.len() { Some(block_at(end)) } else { None };
+
+				match code[last] {
+					Instruction::Br(depth) => branch_to(target(&structure, last, depth)),
+					Instruction::BrIf(depth) => {
+						branch_to(target(&structure, last, depth));
+						successors.extend(fallthrough);
+					},
+					Instruction::BrTable(ref table) => {
+						for depth in table.depths() {
+							branch_to(target(&structure, last, depth));
+						}
+					},
+					Instruction::Return => returns = true,
+					Instruction::Unreachable => {},
+					Instruction::If(_) => {
+						let if_block = structure.blocks()[structure.opened_by(last).expect("`if` opens a block; qed")];
+						successors.extend(fallthrough);
+						successors.push(block_at(if_block.else_().map(|else_| else_ + 1).unwrap_or(if_block.end())));
+					},
+					Instruction::Else => {
+						let if_block = structure.blocks().iter()
+							.find(|block| block.else_() == Some(last))
+							.expect("`else` belongs to an `if` block; qed");
+						successors.push(block_at(if_block.end()));
+					},
+					// The last block ends with the `end` of the body.
+					_ if end == code.len() => returns = true,
+					_ => successors.extend(fallthrough),
+				}
+			}
+			successors.sort_unstable();
+			successors.dedup();
+			blocks.push(BasicBlock { instructions: start..end, successors, returns });
+		}
+		Ok(ControlFlowGraph { blocks })
+	}
+
+	/// Blocks of the graph, ordered by their first instruction; the entry comes first.
+	pub fn blocks(&self) -> &[BasicBlock] { &self.blocks }
+
+	/// Block holding the instruction at `index`.
+	pub fn block_of(&self, index: usize) -> Option<usize> {
+		if index >= self.blocks.last()?.instructions.end {
+			return None;
+		}
+		match self.blocks.binary_search_by_key(&index, |block| block.instructions.start) {
+			Ok(block) => Some(block),
+			Err(next) => Some(next - 1),
+		}
+	}
+
+	/// Blocks control may come from to `block`.
+	pub fn predecessors(&self, block: usize) -> Vec<usize> {
+		(0..self.blocks.len())
+			.filter(|&predecessor| self.blocks[predecessor].successors.contains(&block))
+			.collect()
+	}
+
+	/// Blocks in reverse postorder from the entry; unreachable blocks are left out.
+	pub fn reverse_postorder(&self) -> Vec<usize> {
+		let mut visited = vec![false; self.blocks.len()];
+		let mut order = Vec::with_capacity(self.blocks.len());
+		// Depth-first, with an explicit stack of (block, successors visited so far).
+		let mut stack = vec![(0, 0)];
+		visited[0] = true;
+		while let Some(&mut (block, ref mut next)) = stack.last_mut() {
+			match self.blocks[block].successors.get(*next) {
+				Some(&successor) => {
+					*next += 1;
+					if !visited[successor] {
+						visited[successor] = true;
+						stack.push((successor, 0));
+					}
+				},
+				None => {
+					order.push(block);
+					stack.pop();
+				},
+			}
+		}
+		order.reverse();
+		order
+	}
+
+	/// Dominator tree of the graph.
+	pub fn dominators(&self) -> Dominators {
+		// Cooper, Harvey and Kennedy, "A Simple, Fast Dominance Algorithm".
+		let order = self.reverse_postorder();
+		let mut position = vec![usize::MAX; self.blocks.len()];
+		for (index, &block) in order.iter().enumerate() {
+			position[block] = index;
+		}
+		let predecessors: Vec<Vec<usize>> = (0..self.blocks.len()).map(|block| self.predecessors(block)).collect();
+
+		let mut idom: Vec<Option<usize>> = vec![None; self.blocks.len()];
+		idom[0] = Some(0);
+		let mut changed = true;
+		while changed {
+			changed = false;
+			for &block in order.iter().skip(1) {
+				let mut new_idom = None;
+				for &predecessor in &predecessors[block] {
+					if idom[predecessor].is_none() {
+						continue;
+					}
+					new_idom = Some(match new_idom {
+						None => predecessor,
+						Some(current) => intersect(&idom, &position, predecessor, current),
+					});
+				}
+				if new_idom.is_some() && idom[block] != new_idom {
+					idom[block] = new_idom;
+					changed = true;
+				}
+			}
+		}
+		Dominators { idom }
+	}
+}
+
+fn ends_block(instruction: &Instruction) -> bool {
+	matches!(
+		*instruction,
+		Instruction::Br(_) | Instruction::BrIf(_) | Instruction::BrTable(_) |
+		Instruction::Return | Instruction::Unreachable | Instruction::If(_) | Instruction::Else
+	)
+}
+
+fn target(structure: &BodyStructure, index: usize, depth: u32) -> BranchTarget {
+	structure.branch_target(index, depth).expect("branch depths are checked by `BodyStructure::new`; qed")
+}
+
+fn intersect(idom: &[Option<usize>], position: &[usize], mut a: usize, mut b: usize) -> usize {
+	while a != b {
+		while position[a] > position[b] {
+			a = idom[a].expect("processed blocks have a dominator; qed");
+		}
+		while position[b] > position[a] {
+			b = idom[b].expect("processed blocks have a dominator; qed");
+		}
+	}
+	a
+}
+
+/// Dominator tree of a `ControlFlowGraph`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dominators {
+	idom: Vec<Option<usize>>,
+}
+
+impl Dominators {
+	/// Immediate dominator of `block`; `None` for the entry and unreachable blocks.
+	pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+		match self.idom.get(block)? {
+			Some(idom) if *idom != block => Some(*idom),
+			_ => None,
+		}
+	}
+
+	/// Whether every path from the entry to `block` passes through `dominator`.
+	///
+	/// Every reachable block dominates itself; unreachable blocks are dominated by none.
+	pub fn dominates(&self, dominator: usize, block: usize) -> bool {
+		if self.idom.get(block).cloned().flatten().is_none() {
+			return false;
+		}
+		let mut current = block;
+		loop {
+			if current == dominator {
+				return true;
+			}
+			match self.immediate_dominator(current) {
+				Some(idom) => current = idom,
+				None => return false,
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ControlFlowGraph;
+	use crate::elements::{BlockType, FuncBody, Instruction::*, Instructions};
+
+	fn graph(code: Vec<crate::elements::Instruction>) -> ControlFlowGraph {
+		ControlFlowGraph::new(&FuncBody::new(vec![], Instructions::new(code))).expect("body to be valid")
+	}
+
+	#[test]
+	fn if_else() {
+		let cfg = graph(vec![
+			GetLocal(0),
+			If(BlockType::NoResult),
+			Nop,
+			Else,
+			Nop,
+			End,
+			End,
+		]);
+		let ranges: Vec<_> = cfg.blocks().iter().map(|block| block.instructions()).collect();
+		assert_eq!(ranges, vec![0..2, 2..4, 4..5, 5..7]);
+		assert_eq!(cfg.blocks()[0].successors(), &[1, 2]);
+		assert_eq!(cfg.blocks()[1].successors(), &[3]);
+		assert_eq!(cfg.blocks()[2].successors(), &[3]);
+		assert!(cfg.blocks()[3].returns());
+		assert_eq!(cfg.predecessors(3), vec![1, 2]);
+
+		let dominators = cfg.dominators();
+		assert_eq!(dominators.immediate_dominator(3), Some(0));
+		assert!(!dominators.dominates(1, 3));
+		assert!(dominators.dominates(0, 2));
+	}
+
+	#[test]
+	fn corpus() {
+		let module: crate::elements::Module = crate::elements::deserialize_file("./res/cases/v1/test5.wasm")
+			.expect("module to deserialize");
+		for body in module.code_section().expect("code section").bodies() {
+			let cfg = ControlFlowGraph::new(body).expect("body to be valid");
+			let dominators = cfg.dominators();
+			for block in cfg.reverse_postorder() {
+				assert!(dominators.dominates(0, block));
+			}
+		}
+	}
+
+	#[test]
+	fn loop_and_dead_code() {
+		let cfg = graph(vec![
+			Loop(BlockType::NoResult),
+			GetLocal(0),
+			BrIf(0),
+			Return,
+			Nop,
+			End,
+			End,
+		]);
+		let ranges: Vec<_> = cfg.blocks().iter().map(|block| block.instructions()).collect();
+		assert_eq!(ranges, vec![0..3, 3..4, 4..7]);
+		assert_eq!(cfg.blocks()[0].successors(), &[0, 1]);
+		assert!(cfg.blocks()[1].returns());
+		assert_eq!(cfg.block_of(2), Some(0));
+		assert_eq!(cfg.block_of(7), None);
+
+		let dominators = cfg.dominators();
+		assert!(dominators.dominates(0, 1));
+		assert!(!dominators.dominates(0, 2));
+		assert_eq!(dominators.immediate_dominator(0), None);
+		assert_eq!(cfg.reverse_postorder(), vec![0, 1]);
+	}
+}
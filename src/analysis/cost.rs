@@ -0,0 +1,276 @@
+use alloc::vec::Vec;
+use crate::elements::{Error, ImportCountType, Instruction, Module};
+use super::ControlFlowGraph;
+
+/// Cost of executing instructions, in units of the model's choosing.
+pub trait CostModel {
+	/// Cost of executing `instruction` once.
+	fn cost(&self, instruction: &Instruction) -> u64;
+}
+
+/// Model in which every instruction costs one, so that costs are instruction counts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UnitCost;
+
+impl CostModel for UnitCost {
+	fn cost(&self, _instruction: &Instruction) -> u64 { 1 }
+}
+
+/// Class of instructions sharing a cost in a `CostTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InstructionClass {
+	/// Blocks, branches, `drop`, `select` and `nop`.
+	Control,
+	/// `call` and `call_indirect`; the callee is not included.
+	Call,
+	/// Local and global variable accesses.
+	Variable,
+	/// Constants.
+	Const,
+	/// Memory loads.
+	Load,
+	/// Memory stores.
+	Store,
+	/// `memory.size`.
+	MemorySize,
+	/// `memory.grow`.
+	MemoryGrow,
+	/// Integer arithmetic, bitwise and comparison operators, sign extension included.
+	Integer,
+	/// Integer division and remainder.
+	Division,
+	/// Floating point operators and conversions involving floats.
+	Float,
+	/// Instructions of the atomics, SIMD, bulk memory and exception handling proposals.
+	Proposal,
+}
+
+const CLASS_COUNT: usize = InstructionClass::Proposal as usize + 1;
+
+impl InstructionClass {
+	/// Class of `instruction`.
+	pub fn of(instruction: &Instruction) -> Self {
+		use crate::elements::Instruction::*;
+		match *instruction {
+			Unreachable | Nop | Block(_) | Loop(_) | If(_) | Else | End | Br(_) | BrIf(_) | BrTable(_) |
+			Return | Drop | Select => InstructionClass::Control,
+			Call(_) | CallIndirect(_, _) => InstructionClass::Call,
+			GetLocal(_) | SetLocal(_) | TeeLocal(_) | GetGlobal(_) | SetGlobal(_) => InstructionClass::Variable,
+			I32Load(_, _) | I64Load(_, _) | F32Load(_, _) | F64Load(_, _) | I32Load8S(_, _) | I32Load8U(_, _) |
+			I32Load16S(_, _) | I32Load16U(_, _) | I64Load8S(_, _) | I64Load8U(_, _) | I64Load16S(_, _) |
+			I64Load16U(_, _) | I64Load32S(_, _) | I64Load32U(_, _) => InstructionClass::Load,
+			I32Store(_, _) | I64Store(_, _) | F32Store(_, _) | F64Store(_, _) | I32Store8(_, _) |
+			I32Store16(_, _) | I64Store8(_, _) | I64Store16(_, _) | I64Store32(_, _) => InstructionClass::Store,
+			CurrentMemory(_) => InstructionClass::MemorySize,
+			GrowMemory(_) => InstructionClass::MemoryGrow,
+			I32Const(_) | I64Const(_) | F32Const(_) | F64Const(_) => InstructionClass::Const,
+			I32DivS | I32DivU | I32RemS | I32RemU | I64DivS | I64DivU | I64RemS | I64RemU => InstructionClass::Division,
+			I32Eqz | I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU |
+			I64Eqz | I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU |
+			I32Clz | I32Ctz | I32Popcnt | I32Add | I32Sub | I32Mul | I32And | I32Or | I32Xor | I32Shl |
+			I32ShrS | I32ShrU | I32Rotl | I32Rotr | I64Clz | I64Ctz | I64Popcnt | I64Add | I64Sub | I64Mul |
+			I64And | I64Or | I64Xor | I64Shl | I64ShrS | I64ShrU | I64Rotl | I64Rotr | I32WrapI64 |
+			I64ExtendSI32 | I64ExtendUI32 => InstructionClass::Integer,
+			#[cfg(feature="sign_ext")]
+			SignExt(_) => InstructionClass::Integer,
+			F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge | F64Eq | F64Ne | F64Lt | F64Gt | F64Le | F64Ge |
+			F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt | F32Add | F32Sub | F32Mul |
+			F32Div | F32Min | F32Max | F32Copysign | F64Abs | F64Neg | F64Ceil | F64Floor | F64Trunc |
+			F64Nearest | F64Sqrt | F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign |
+			I32TruncSF32 | I32TruncUF32 | I32TruncSF64 | I32TruncUF64 | I64TruncSF32 | I64TruncUF32 |
+			I64TruncSF64 | I64TruncUF64 | F32ConvertSI32 | F32ConvertUI32 | F32ConvertSI64 | F32ConvertUI64 |
+			F32DemoteF64 | F64ConvertSI32 | F64ConvertUI32 | F64ConvertSI64 | F64ConvertUI64 | F64PromoteF32 |
+			I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => InstructionClass::Float,
+			#[cfg(feature="atomics")]
+			Atomics(_) => InstructionClass::Proposal,
+			#[cfg(feature="simd")]
+			Simd(_) => InstructionClass::Proposal,
+			#[cfg(feature="bulk")]
+			Bulk(_) => InstructionClass::Proposal,
+			#[cfg(feature="exceptions")]
+			Exceptions(_) => InstructionClass::Proposal,
+		}
+	}
+}
+
+/// Model charging a fixed cost per class of instructions.
+///
+/// Sharing one table between fuel metering at run time and estimates ahead of it
+/// keeps both in agreement. Costs of instructions whose work depends on their operands,
+/// like `memory.grow` or `memory.copy`, are only their base cost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CostTable {
+	costs: [u64; CLASS_COUNT],
+}
+
+impl CostTable {
+	/// Table charging `cost` for every instruction.
+	pub fn uniform(cost: u64) -> Self {
+		CostTable { costs: [cost; CLASS_COUNT] }
+	}
+
+	/// Cost of the instructions of `class`.
+	pub fn get(&self, class: InstructionClass) -> u64 {
+		self.costs[class as usize]
+	}
+
+	/// Set the cost of the instructions of `class`.
+	pub fn set(&mut self, class: InstructionClass, cost: u64) {
+		self.costs[class as usize] = cost;
+	}
+
+	/// Set the costs of the measured classes from benchmark timings.
+	///
+	/// Every sample is the time one instruction of the class takes, in any time unit,
+	/// and its cost becomes that time divided by `unit`, rounded to the nearest integer
+	/// but at least one. Classes without samples keep their cost.
+	pub fn calibrate(&mut self, samples: &[(InstructionClass, u64)], unit: u64) {
+		let unit = unit.max(1);
+		for &(class, time) in samples {
+			self.set(class, (time.saturating_add(unit / 2) / unit).max(1));
+		}
+	}
+}
+
+impl Default for CostTable {
+	/// Costs roughly proportional to those of compiled code, with the cheapest
+	/// instructions costing one.
+	fn default() -> Self {
+		let mut table = CostTable::uniform(1);
+		table.set(InstructionClass::Call, 10);
+		table.set(InstructionClass::Load, 3);
+		table.set(InstructionClass::Store, 3);
+		table.set(InstructionClass::MemoryGrow, 1000);
+		table.set(InstructionClass::Division, 8);
+		table.set(InstructionClass::Float, 2);
+		table.set(InstructionClass::Proposal, 4);
+		table
+	}
+}
+
+impl CostModel for CostTable {
+	fn cost(&self, instruction: &Instruction) -> u64 {
+		self.get(InstructionClass::of(instruction))
+	}
+}
+
+/// Static cost estimate of a function body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FunctionCost {
+	/// Index of the function.
+	pub index: u32,
+	/// Most expensive path through the body which runs every loop body at most once.
+	///
+	/// Callees are not included: each call costs what the model makes `call` cost.
+	pub max_acyclic_cost: u64,
+	/// Whether the body has loops, leaving the cost of its executions unbounded.
+	pub has_loops: bool,
+}
+
+/// Estimate the cost of every function body of `module` under `model`.
+///
+/// Bodies with unbalanced blocks or invalid branches are an error.
+pub fn estimate_costs<M: CostModel + ?Sized>(module: &Module, model: &M) -> Result<Vec<FunctionCost>, Error> {
+	let imported = module.import_count(ImportCountType::Function) as u32;
+	let mut costs = Vec::new();
+	for (index, body) in module.code_section().map(|s| s.bodies()).unwrap_or(&[]).iter().enumerate() {
+		let code = body.code().elements();
+		let cfg = ControlFlowGraph::new(body)?;
+		let blocks = cfg.blocks();
+
+		// Blocks are ordered by their start, and only branches to loops go backwards, so
+		// the forward edges are acyclic and this order is a topological one.
+		let mut has_loops = false;
+		let mut path: Vec<Option<u64>> = vec![None; blocks.len()];
+		path[0] = Some(0);
+		let mut max_acyclic_cost = 0;
+		for (block, basic_block) in blocks.iter().enumerate() {
+			let before = match path[block] {
+				Some(before) => before,
+				None => continue,
+			};
+			let cost = basic_block.instructions()
+				.fold(before, |total, instruction| total.saturating_add(model.cost(&code[instruction])));
+			max_acyclic_cost = max_acyclic_cost.max(cost);
+			for &successor in basic_block.successors() {
+				if successor <= block {
+					has_loops = true;
+					continue;
+				}
+				path[successor] = Some(path[successor].map_or(cost, |other| other.max(cost)));
+			}
+		}
+
+		costs.push(FunctionCost { index: imported + index as u32, max_acyclic_cost, has_loops });
+	}
+	Ok(costs)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{estimate_costs, CostModel, CostTable, FunctionCost, InstructionClass, UnitCost};
+	use crate::builder;
+	use crate::elements::{BlockType, Instruction, Instruction::*, Instructions};
+
+	struct CallsAreExpensive;
+
+	impl CostModel for CallsAreExpensive {
+		fn cost(&self, instruction: &Instruction) -> u64 {
+			match *instruction {
+				Call(_) => 100,
+				_ => 1,
+			}
+		}
+	}
+
+	#[test]
+	fn branches_and_loops() {
+		let module = builder::module()
+			.import().module("env").field("log").external().func(0).build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![
+					I32Const(0),
+					If(BlockType::NoResult),
+					Call(0),
+					Else,
+					Nop,
+					Nop,
+					End,
+					End,
+				])).build()
+				.build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![
+					Loop(BlockType::NoResult),
+					I32Const(1),
+					BrIf(0),
+					End,
+					End,
+				])).build()
+				.build()
+			.build();
+
+		assert_eq!(estimate_costs(&module, &UnitCost).expect("bodies to be valid"), vec![
+			FunctionCost { index: 1, max_acyclic_cost: 6, has_loops: false },
+			FunctionCost { index: 2, max_acyclic_cost: 5, has_loops: true },
+		]);
+		let costs = estimate_costs(&module, &CallsAreExpensive).expect("bodies to be valid");
+		assert_eq!(costs[0].max_acyclic_cost, 105);
+	}
+
+	#[test]
+	fn cost_table() {
+		let mut table = CostTable::default();
+		assert_eq!(table.cost(&I32Add), 1);
+		assert_eq!(table.cost(&I64DivU), 8);
+		assert_eq!(table.cost(&F32Sqrt), 2);
+		assert_eq!(InstructionClass::of(&I32Store8(0, 0)), InstructionClass::Store);
+
+		// Timings in picoseconds, with a unit of 300ps.
+		table.calibrate(&[(InstructionClass::Integer, 250), (InstructionClass::Division, 4000), (InstructionClass::Const, 10)], 300);
+		assert_eq!(table.get(InstructionClass::Integer), 1);
+		assert_eq!(table.get(InstructionClass::Division), 13);
+		assert_eq!(table.get(InstructionClass::Const), 1);
+		assert_eq!(table.get(InstructionClass::Call), 10);
+	}
+}
@@ -0,0 +1,132 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use crate::elements::{External, FunctionType, Module, Type};
+
+/// Function a host provides for modules to import.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostFunction {
+	/// Module name the function is imported from.
+	pub module: String,
+	/// Field name of the function.
+	pub field: String,
+	/// Signature of the function.
+	pub signature: FunctionType,
+}
+
+impl HostFunction {
+	/// Function `field` of host module `module`, with `signature`.
+	pub fn new(module: &str, field: &str, signature: FunctionType) -> Self {
+		HostFunction { module: module.into(), field: field.into(), signature }
+	}
+}
+
+/// Function import which the host functions don't satisfy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportMismatch {
+	/// No host function has the name of the import.
+	Missing {
+		/// Module name of the import.
+		module: String,
+		/// Field name of the import.
+		field: String,
+	},
+	/// Host function of the name has another signature.
+	Signature {
+		/// Module name of the import.
+		module: String,
+		/// Field name of the import.
+		field: String,
+		/// Signature the module imports the function with.
+		imported: FunctionType,
+		/// Signature of the host function.
+		provided: FunctionType,
+	},
+	/// Import refers to a type which does not exist.
+	InvalidType {
+		/// Module name of the import.
+		module: String,
+		/// Field name of the import.
+		field: String,
+		/// Index of the missing type.
+		type_ref: u32,
+	},
+}
+
+impl fmt::Display for ImportMismatch {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ImportMismatch::Missing { ref module, ref field } =>
+				write!(f, "Host provides no function {}.{}", module, field),
+			ImportMismatch::Signature { ref module, ref field, ref imported, ref provided } =>
+				write!(f, "Function {}.{} is imported as {}, but the host provides {}", module, field, imported, provided),
+			ImportMismatch::InvalidType { ref module, ref field, type_ref } =>
+				write!(f, "Function {}.{} is imported with type {}, which does not exist", module, field, type_ref),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for ImportMismatch {}
+
+/// Check every function import of `module` against the functions the host provides.
+///
+/// All mismatches are reported at once, in the order of the imports. Imports other
+/// than functions are not checked.
+pub fn check_imports(module: &Module, host: &[HostFunction]) -> Result<(), Vec<ImportMismatch>> {
+	let types = module.type_section().map(|s| s.types()).unwrap_or(&[]);
+	let mut mismatches = Vec::new();
+	for entry in module.import_section().map(|s| s.entries()).unwrap_or(&[]) {
+		let type_ref = match *entry.external() {
+			External::Function(type_ref) => type_ref,
+			_ => continue,
+		};
+		let (module_name, field) = (String::from(entry.module()), String::from(entry.field()));
+		let imported = match types.get(type_ref as usize) {
+			Some(Type::Function(imported)) => imported,
+			None => {
+				mismatches.push(ImportMismatch::InvalidType { module: module_name, field, type_ref });
+				continue;
+			},
+		};
+		match host.iter().find(|function| function.module == entry.module() && function.field == entry.field()) {
+			None => mismatches.push(ImportMismatch::Missing { module: module_name, field }),
+			Some(function) if function.signature != *imported => mismatches.push(ImportMismatch::Signature {
+				module: module_name,
+				field,
+				imported: imported.clone(),
+				provided: function.signature.clone(),
+			}),
+			Some(_) => {},
+		}
+	}
+	if mismatches.is_empty() { Ok(()) } else { Err(mismatches) }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{check_imports, HostFunction, ImportMismatch};
+	use crate::builder;
+	use crate::elements::{FunctionType, ValueType};
+
+	#[test]
+	fn mismatches() {
+		let module = builder::module()
+			.function().signature().with_param(ValueType::I32).build().body().build().build()
+			.import().module("env").field("log").external().func(0).build()
+			.import().module("env").field("read").external().func(0).build()
+			.import().module("env").field("abort").external().func(0).build()
+			.import().module("env").field("memory").external().memory(1, None).build()
+			.build();
+		let log = FunctionType::new(vec![ValueType::I32], vec![]);
+		let read = FunctionType::new(vec![ValueType::I32], vec![ValueType::I32]);
+		let host = vec![
+			HostFunction::new("env", "log", log.clone()),
+			HostFunction::new("env", "read", read.clone()),
+		];
+
+		assert_eq!(check_imports(&module, &host), Err(vec![
+			ImportMismatch::Signature { module: "env".into(), field: "read".into(), imported: log, provided: read },
+			ImportMismatch::Missing { module: "env".into(), field: "abort".into() },
+		]));
+	}
+}
@@ -0,0 +1,135 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use crate::elements::{Module, ResolvedImport};
+
+/// What to do with an import, as decided by an `ImportPolicy`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportDecision {
+	/// Keep the import as it is.
+	Allow,
+	/// Reject the module because of the import, for the given reason.
+	Deny(String),
+	/// Import from another module name and field instead.
+	Redirect {
+		/// Module name to import from.
+		module: String,
+		/// Field name to import.
+		field: String,
+	},
+}
+
+/// Policy deciding, import by import, what a module may import.
+///
+/// Closures taking a `ResolvedImport` are policies too.
+pub trait ImportPolicy {
+	/// Decision for `import`.
+	fn decide(&self, import: &ResolvedImport<'_>) -> ImportDecision;
+}
+
+impl<F: Fn(&ResolvedImport<'_>) -> ImportDecision> ImportPolicy for F {
+	fn decide(&self, import: &ResolvedImport<'_>) -> ImportDecision {
+		self(import)
+	}
+}
+
+/// Import denied by an `ImportPolicy`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportViolation {
+	/// Module name of the import.
+	pub module: String,
+	/// Field name of the import.
+	pub field: String,
+	/// Reason given by the policy.
+	pub reason: String,
+}
+
+impl fmt::Display for ImportViolation {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Import {}.{} is denied: {}", self.module, self.field, self.reason)
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for ImportViolation {}
+
+/// Consult `policy` for every import of `module`, and apply its redirections.
+///
+/// All denied imports are reported at once, in module order, and the module is left
+/// untouched if there is any. Otherwise the number of redirected imports is returned.
+/// Meant to run before instantiation, so that the resolver only sees allowed imports.
+pub fn apply_import_policy<P: ImportPolicy + ?Sized>(module: &mut Module, policy: &P) -> Result<usize, Vec<ImportViolation>> {
+	let mut violations = Vec::new();
+	let mut redirects = Vec::new();
+	for (position, import) in module.imports().enumerate() {
+		match policy.decide(&import) {
+			ImportDecision::Allow => {},
+			ImportDecision::Deny(reason) => violations.push(ImportViolation {
+				module: import.module.into(),
+				field: import.field.into(),
+				reason,
+			}),
+			ImportDecision::Redirect { module, field } => redirects.push((position, module, field)),
+		}
+	}
+	if !violations.is_empty() {
+		return Err(violations);
+	}
+
+	let redirected = redirects.len();
+	if let Some(import_section) = module.import_section_mut() {
+		for (position, module, field) in redirects {
+			let entry = &mut import_section.entries_mut()[position];
+			*entry.module_mut() = module;
+			*entry.field_mut() = field;
+		}
+	}
+	Ok(redirected)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{apply_import_policy, ImportDecision, ImportViolation};
+	use crate::builder;
+	use crate::elements::{ExternType, ResolvedImport};
+
+	fn plugin_policy(import: &ResolvedImport<'_>) -> ImportDecision {
+		match (import.module, import.ty) {
+			("plugin_api", _) => ImportDecision::Allow,
+			("env", Some(ExternType::Memory(_))) => ImportDecision::Redirect {
+				module: "plugin_api".into(),
+				field: "memory".into(),
+			},
+			_ => ImportDecision::Deny("only plugin_api may be imported".into()),
+		}
+	}
+
+	#[test]
+	fn policy() {
+		let mut module = builder::module()
+			.function().signature().build().body().build().build()
+			.import().module("plugin_api").field("log").external().func(0).build()
+			.import().module("env").field("memory").external().memory(1, None).build()
+			.build();
+		assert_eq!(apply_import_policy(&mut module, &plugin_policy), Ok(1));
+		let entry = &module.import_section().expect("import section").entries()[1];
+		assert_eq!((entry.module(), entry.field()), ("plugin_api", "memory"));
+
+		let mut module = builder::module()
+			.function().signature().build().body().build().build()
+			.import().module("wasi").field("fd_write").external().func(0).build()
+			.import().module("env").field("memory").external().memory(1, None).build()
+			.import().module("env").field("abort").external().func(0).build()
+			.build();
+		let original = module.clone();
+		let violation = |module: &str, field: &str| ImportViolation {
+			module: module.into(),
+			field: field.into(),
+			reason: "only plugin_api may be imported".into(),
+		};
+		assert_eq!(apply_import_policy(&mut module, &plugin_policy), Err(vec![
+			violation("wasi", "fd_write"),
+			violation("env", "abort"),
+		]));
+		assert_eq!(module, original);
+	}
+}
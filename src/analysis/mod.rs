@@ -0,0 +1,13 @@
+//! Analyses of function bodies and modules.
+
+mod cfg;
+mod call_graph;
+mod cost;
+mod host_abi;
+mod import_policy;
+
+pub use self::cfg::{BasicBlock, ControlFlowGraph, Dominators};
+pub use self::call_graph::{call_graph, entry_points, CallEdge, CallGraph};
+pub use self::cost::{estimate_costs, CostModel, CostTable, FunctionCost, InstructionClass, UnitCost};
+pub use self::host_abi::{check_imports, HostFunction, ImportMismatch};
+pub use self::import_policy::{apply_import_policy, ImportDecision, ImportPolicy, ImportViolation};
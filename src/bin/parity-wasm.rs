@@ -0,0 +1,78 @@
+//! Command line front end to the crate, built with the `cli` feature.
+//!
+//! Every subcommand is a thin wrapper around the public API, so this also serves
+//! as a reference for using it.
+
+use std::{env, fs, process};
+
+use parity_wasm::elements::{
+	self, DeserializeConfig, ImportCountType, Module, StripConfig, deserialize_buffer_detailed,
+};
+
+const USAGE: &str = "\
+Usage: parity-wasm <command> <input.wasm> [options]
+
+Commands:
+    dump <input>                  Print the module as text
+    validate <input>              Check that the module decodes and its segments fit
+    strip <input> <output> [--all-custom]
+                                  Remove names, debug info and tool sections
+    names <input>                 Print the function names of the name section";
+
+fn fail(message: &str) -> ! {
+	eprintln!("{}", message);
+	process::exit(1);
+}
+
+fn load(path: &str) -> Module {
+	let bytes = fs::read(path).unwrap_or_else(|error| fail(&format!("Cannot read {}: {}", path, error)));
+	deserialize_buffer_detailed(&bytes).unwrap_or_else(|error| fail(&format!("{}: {}", path, error)))
+}
+
+fn validate(module: &Module) -> Result<(), String> {
+	DeserializeConfig::default().check(module).map_err(|error| error.to_string())?;
+	// Offsets may depend on imported globals, whose values only the host knows.
+	if module.import_count(ImportCountType::Global) == 0 {
+		module.check_segment_bounds(&[]).map_err(|errors| {
+			errors.iter().map(|error| error.to_string()).collect::<Vec<_>>().join("\n")
+		})?;
+	}
+	Ok(())
+}
+
+fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+	let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+	match args.as_slice() {
+		["dump", input] => print!("{}", load(input).to_text()),
+		["validate", input] => match validate(&load(input)) {
+			Ok(()) => println!("{}: valid", input),
+			Err(errors) => fail(&format!("{}:\n{}", input, errors)),
+		},
+		["strip", input, output, rest @ ..] => {
+			let config = match rest {
+				[] => StripConfig::default(),
+				["--all-custom"] => StripConfig { all_custom: true, ..StripConfig::default() },
+				_ => fail(USAGE),
+			};
+			let mut module = load(input);
+			let saved = module.strip(config).unwrap_or_else(|error| fail(&error.to_string()));
+			elements::serialize_to_file(output, module).unwrap_or_else(|error| fail(&error.to_string()));
+			println!("{} bytes saved", saved);
+		},
+		["names", input] => {
+			let module = load(input).parse_names().unwrap_or_else(|(errors, module)| {
+				for (_, error) in errors {
+					eprintln!("Name section ignored: {}", error);
+				}
+				module
+			});
+			let names = module.names_section().and_then(|names| names.functions());
+			for (index, name) in names.iter().flat_map(|names| names.names()) {
+				println!("{} {}", index, name);
+			}
+		},
+		_ => fail(USAGE),
+	}
+}
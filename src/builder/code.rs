@@ -1,8 +1,11 @@
-use alloc::vec::Vec;
+#![allow(clippy::new_without_default, clippy::redundant_field_names)]
+
+use alloc::{string::String, vec::Vec};
 use crate::elements;
 use super::{
 	invoke::{Invoke, Identity},
 	misc::{ValueTypeBuilder, ValueTypesBuilder},
+	instructions::{instructions, InstructionsBuilder, Label},
 };
 
 /// Signature template description
@@ -251,6 +254,15 @@ impl<F> FuncBodyBuilder<F> where F: Invoke<elements::FuncBody> {
 		self
 	}
 
+	/// Set code of the function, built with `f` from structured constructs
+	///
+	/// See `builder::instructions`.
+	pub fn with_code<C>(self, f: C) -> Self
+		where C: FnOnce(InstructionsBuilder, Label) -> InstructionsBuilder
+	{
+		self.with_instructions(instructions(f))
+	}
+
 	/// Finish current builder spawning resulting struct
 	pub fn build(self) -> F::Result {
 		self.callback.invoke(self.body)
@@ -265,6 +277,10 @@ pub struct FunctionDefinition {
 	pub signature: Signature,
 	/// Body (code) of the function
 	pub code: elements::FuncBody,
+	/// Name of the function, for the name section
+	pub name: Option<String>,
+	/// Names of locals by index, parameters included, for the name section
+	pub local_names: Vec<(u32, String)>,
 }
 
 impl Default for FunctionDefinition {
@@ -273,6 +289,8 @@ impl Default for FunctionDefinition {
 			is_main: false,
 			signature: Signature::TypeReference(0),
 			code: elements::FuncBody::empty(),
+			name: None,
+			local_names: Vec::new(),
 		}
 	}
 }
@@ -305,6 +323,18 @@ impl<F> FunctionBuilder<F> where F: Invoke<FunctionDefinition> {
 		self
 	}
 
+	/// Set the name of the function in the name section
+	pub fn name(mut self, name: &str) -> Self {
+		self.func.name = Some(name.into());
+		self
+	}
+
+	/// Set the name of local `index`, counting parameters first, in the name section
+	pub fn local_name(mut self, index: u32, name: &str) -> Self {
+		self.func.local_names.push((index, name.into()));
+		self
+	}
+
 	/// Start signature builder of the function
 	pub fn signature(self) -> SignatureBuilder<Self> {
 		SignatureBuilder::with_callback(self)
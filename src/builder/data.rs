@@ -1,3 +1,5 @@
+#![allow(clippy::new_without_default, clippy::redundant_field_names)]
+
 use alloc::vec::Vec;
 use super::invoke::{Identity, Invoke};
 use crate::elements;
@@ -7,7 +9,7 @@ pub struct DataSegmentBuilder<F=Identity> {
 	callback: F,
 	// todo: add mapper once multiple memory refs possible
 	mem_index: u32,
-	offset: elements::InitExpr,
+	offset: Option<elements::InitExpr>,
 	value: Vec<u8>,
 }
 
@@ -24,14 +26,32 @@ impl<F> DataSegmentBuilder<F> {
 		DataSegmentBuilder {
 			callback: callback,
 			mem_index: 0,
-			offset: elements::InitExpr::empty(),
+			offset: Some(elements::InitExpr::empty()),
 			value: Vec::new(),
 		}
 	}
 
 	/// Set offset initialization instruction. `End` instruction will be added automatically.
 	pub fn offset(mut self, instruction: elements::Instruction) -> Self {
-		self.offset = elements::InitExpr::new(vec![instruction, elements::Instruction::End]);
+		self.offset = Some(elements::InitExpr::new(vec![instruction, elements::Instruction::End]));
+		self
+	}
+
+	/// Set the offset to the value of the (imported) global `index`
+	pub fn offset_global(self, index: u32) -> Self {
+		self.offset(elements::Instruction::GetGlobal(index))
+	}
+
+	/// Set the offset to a whole initialization expression, which must end with `End`
+	pub fn offset_expr(mut self, init_expr: elements::InitExpr) -> Self {
+		self.offset = Some(init_expr);
+		self
+	}
+
+	/// Make the segment passive, so that it is only used by `memory.init`
+	#[cfg(feature="bulk")]
+	pub fn passive(mut self) -> Self {
+		self.offset = None;
 		self
 	}
 
@@ -45,12 +65,12 @@ impl<F> DataSegmentBuilder<F> {
 impl<F> DataSegmentBuilder<F> where F: Invoke<elements::DataSegment> {
 	/// Finish current builder, spawning resulting struct
 	pub fn build(self) -> F::Result {
-		self.callback.invoke(
-			elements::DataSegment::new(
-				self.mem_index,
-				Some(self.offset),
-				self.value,
-			)
-		)
+		#[cfg(feature="bulk")]
+		let passive = self.offset.is_none();
+		#[allow(unused_mut)]
+		let mut segment = elements::DataSegment::new(self.mem_index, self.offset, self.value);
+		#[cfg(feature="bulk")]
+		segment.set_passive(passive);
+		self.callback.invoke(segment)
 	}
 }
@@ -0,0 +1,120 @@
+use alloc::vec::Vec;
+use super::invoke::{Identity, Invoke};
+use crate::elements;
+
+/// Element segment builder
+pub struct ElementSegmentBuilder<F=Identity> {
+	callback: F,
+	table_index: u32,
+	offset: Option<elements::InitExpr>,
+	members: Vec<u32>,
+}
+
+impl ElementSegmentBuilder {
+	/// New element segment builder
+	pub fn new() -> Self {
+		ElementSegmentBuilder::with_callback(Identity)
+	}
+}
+
+impl Default for ElementSegmentBuilder {
+	fn default() -> Self {
+		ElementSegmentBuilder::new()
+	}
+}
+
+impl<F> ElementSegmentBuilder<F> {
+	/// New element segment builder inside the chain context
+	///
+	/// The segment is active on table `0` at offset `0` until told otherwise.
+	pub fn with_callback(callback: F) -> Self {
+		ElementSegmentBuilder {
+			callback,
+			table_index: 0,
+			offset: Some(elements::InitExpr::new(vec![
+				elements::Instruction::I32Const(0),
+				elements::Instruction::End,
+			])),
+			members: Vec::new(),
+		}
+	}
+
+	/// Set the table the segment initializes
+	pub fn table(mut self, index: u32) -> Self {
+		self.table_index = index;
+		self
+	}
+
+	/// Set offset initialization instruction. `End` instruction will be added automatically.
+	pub fn offset(mut self, instruction: elements::Instruction) -> Self {
+		self.offset = Some(elements::InitExpr::new(vec![instruction, elements::Instruction::End]));
+		self
+	}
+
+	/// Set the offset to the value of the (imported) global `index`
+	pub fn offset_global(self, index: u32) -> Self {
+		self.offset(elements::Instruction::GetGlobal(index))
+	}
+
+	/// Make the segment passive, so that it is only used by `table.init`
+	#[cfg(feature="bulk")]
+	pub fn passive(mut self) -> Self {
+		self.offset = None;
+		self
+	}
+
+	/// Add a function index to the segment
+	pub fn member(mut self, func_index: u32) -> Self {
+		self.members.push(func_index);
+		self
+	}
+
+	/// Set/override the function indices of the segment
+	pub fn members(mut self, func_indices: Vec<u32>) -> Self {
+		self.members = func_indices;
+		self
+	}
+}
+
+impl<F> ElementSegmentBuilder<F> where F: Invoke<elements::ElementSegment> {
+	/// Finish current builder, spawning resulting struct
+	pub fn build(self) -> F::Result {
+		#[cfg(feature="bulk")]
+		let passive = self.offset.is_none();
+		#[allow(unused_mut)]
+		let mut segment = elements::ElementSegment::new(self.table_index, self.offset, self.members);
+		#[cfg(feature="bulk")]
+		segment.set_passive(passive);
+		self.callback.invoke(segment)
+	}
+}
+
+/// New builder for element segment
+pub fn element_segment() -> ElementSegmentBuilder {
+	ElementSegmentBuilder::new()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::element_segment;
+	use crate::elements;
+
+	#[test]
+	fn example() {
+		let segment = element_segment().offset(elements::Instruction::I32Const(2)).member(0).member(1).build();
+		assert_eq!(segment.index(), 0);
+		assert_eq!(segment.members(), &[0, 1]);
+		assert_eq!(
+			segment.offset().as_ref().map(|offset| offset.code()),
+			Some(&[elements::Instruction::I32Const(2), elements::Instruction::End][..]),
+		);
+	}
+
+	#[cfg(feature="bulk")]
+	#[test]
+	fn passive() {
+		let segment = element_segment().passive().members(vec![3]).build();
+		assert!(segment.passive());
+		assert!(segment.offset().is_none());
+	}
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::new_without_default, clippy::redundant_field_names)]
+
 use alloc::{borrow::ToOwned, string::String};
 use super::invoke::{Invoke, Identity};
 use crate::elements;
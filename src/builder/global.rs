@@ -1,3 +1,9 @@
+#![allow(
+	clippy::bool_assert_comparison,
+	clippy::new_without_default,
+	clippy::redundant_field_names,
+)]
+
 use super::{
 	invoke::{Invoke, Identity},
 	misc::ValueTypeBuilder,
@@ -49,6 +55,33 @@ impl<F> GlobalBuilder<F> {
 		self
 	}
 
+	/// Initialize the global to the constant `value`, setting its type to `i32`
+	pub fn init_i32(self, value: i32) -> Self {
+		self.with_type(elements::ValueType::I32).init_expr(elements::Instruction::I32Const(value))
+	}
+
+	/// Initialize the global to the constant `value`, setting its type to `i64`
+	pub fn init_i64(self, value: i64) -> Self {
+		self.with_type(elements::ValueType::I64).init_expr(elements::Instruction::I64Const(value))
+	}
+
+	/// Initialize the global to the constant `value`, setting its type to `f32`
+	pub fn init_f32(self, value: f32) -> Self {
+		self.with_type(elements::ValueType::F32).init_expr(elements::Instruction::F32Const(value.to_bits()))
+	}
+
+	/// Initialize the global to the constant `value`, setting its type to `f64`
+	pub fn init_f64(self, value: f64) -> Self {
+		self.with_type(elements::ValueType::F64).init_expr(elements::Instruction::F64Const(value.to_bits()))
+	}
+
+	/// Initialize the global to the value of the (imported) global `index`
+	///
+	/// The type is left as is, since it has to match the one of the other global.
+	pub fn init_global(self, index: u32) -> Self {
+		self.init_expr(elements::Instruction::GetGlobal(index))
+	}
+
 	/// Start value type builder
 	pub fn value_type(self) -> ValueTypeBuilder<Self> {
 		ValueTypeBuilder::with_callback(self)
@@ -90,4 +123,15 @@ mod tests {
 		assert_eq!(entry.global_type().content_type(), elements::ValueType::I32);
 		assert_eq!(entry.global_type().is_mutable(), false);
 	}
+
+	#[test]
+	fn init_helpers() {
+		let entry = global().mutable().init_f64(1.5).build();
+		assert_eq!(entry.global_type().content_type(), elements::ValueType::F64);
+		assert_eq!(entry.global_type().is_mutable(), true);
+		assert_eq!(
+			entry.init_expr().code(),
+			&[elements::Instruction::F64Const(1.5f64.to_bits()), elements::Instruction::End][..],
+		);
+	}
 }
@@ -0,0 +1,361 @@
+use alloc::{boxed::Box, vec::Vec};
+use crate::elements::{self, BlockType, BrTableData, Instruction};
+
+/// Branch target of a block, loop or `if`, or of the function body itself.
+///
+/// Labels are handed out by `InstructionsBuilder` when a construct is opened and
+/// resolve to relative branch depths wherever they are branched to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label {
+	id: usize,
+}
+
+/// Builder of structured function code, computing branch depths from labels
+///
+/// # Examples
+///
+/// ```
+/// use parity_wasm::builder;
+/// use parity_wasm::elements::{BlockType, Instruction::*};
+///
+/// // Count local 0 down to zero.
+/// let code = builder::instructions(|b, _| b
+///     .block(BlockType::NoResult, |b, done| b
+///         .loop_(BlockType::NoResult, |b, again| b
+///             .instruction(GetLocal(0))
+///             .instruction(I32Eqz)
+///             .br_if(done)
+///             .instruction(GetLocal(0))
+///             .instruction(I32Const(1))
+///             .instruction(I32Sub)
+///             .instruction(SetLocal(0))
+///             .br(again)
+///         )
+///     )
+/// );
+///
+/// assert_eq!(code.elements()[4], BrIf(1));
+/// assert_eq!(code.elements()[9], Br(0));
+/// ```
+#[derive(Debug)]
+pub struct InstructionsBuilder {
+	code: Vec<Instruction>,
+	/// Labels of the enclosing constructs, innermost last.
+	open: Vec<usize>,
+	next_label: usize,
+}
+
+impl InstructionsBuilder {
+	fn new() -> Self {
+		InstructionsBuilder { code: Vec::new(), open: vec![0], next_label: 1 }
+	}
+
+	/// Append a single instruction
+	///
+	/// Structured instructions have to be added with `block`, `loop_` and `if_`
+	/// instead, so that their labels are tracked.
+	pub fn instruction(mut self, instruction: Instruction) -> Self {
+		self.code.push(instruction);
+		self
+	}
+
+	/// Append several instructions
+	pub fn instructions<I: IntoIterator<Item=Instruction>>(mut self, instructions: I) -> Self {
+		self.code.extend(instructions);
+		self
+	}
+
+	/// Append a block whose contents are added by `f`
+	///
+	/// Branching to the label passed to `f` exits the block.
+	pub fn block<C>(self, block_type: BlockType, f: C) -> Self
+		where C: FnOnce(Self, Label) -> Self
+	{
+		self.construct(Instruction::Block(block_type), f)
+	}
+
+	/// Append a loop whose contents are added by `f`
+	///
+	/// Branching to the label passed to `f` starts the next iteration.
+	pub fn loop_<C>(self, block_type: BlockType, f: C) -> Self
+		where C: FnOnce(Self, Label) -> Self
+	{
+		self.construct(Instruction::Loop(block_type), f)
+	}
+
+	/// Append an `if` without `else`, whose contents are added by `then`
+	pub fn if_<C>(self, block_type: BlockType, then: C) -> Self
+		where C: FnOnce(Self, Label) -> Self
+	{
+		self.construct(Instruction::If(block_type), then)
+	}
+
+	/// Append an `if` with both branches, added by `then` and `otherwise`
+	///
+	/// Both branches get the same label, which exits the `if`.
+	pub fn if_else<C, E>(self, block_type: BlockType, then: C, otherwise: E) -> Self
+		where C: FnOnce(Self, Label) -> Self, E: FnOnce(Self, Label) -> Self
+	{
+		self.construct(Instruction::If(block_type), |b, label| {
+			otherwise(then(b, label).instruction(Instruction::Else), label)
+		})
+	}
+
+	/// Append a branch to `label`
+	///
+	/// # Panics
+	///
+	/// Panics if `label` belongs to a construct which is not open.
+	pub fn br(mut self, label: Label) -> Self {
+		let depth = self.depth(label);
+		self.code.push(Instruction::Br(depth));
+		self
+	}
+
+	/// Append a conditional branch to `label`
+	///
+	/// # Panics
+	///
+	/// Panics if `label` belongs to a construct which is not open.
+	pub fn br_if(mut self, label: Label) -> Self {
+		let depth = self.depth(label);
+		self.code.push(Instruction::BrIf(depth));
+		self
+	}
+
+	/// Append a branch to `targets[i]` for index `i`, and to `default` otherwise
+	///
+	/// # Panics
+	///
+	/// Panics if any label belongs to a construct which is not open.
+	pub fn br_table(mut self, targets: &[Label], default: Label) -> Self {
+		let targets = targets.iter().map(|&label| self.depth(label)).collect();
+		let default = self.depth(default);
+		self.code.push(Instruction::BrTable(Box::new(BrTableData::new(targets, default))));
+		self
+	}
+
+	fn construct<C>(mut self, instruction: Instruction, f: C) -> Self
+		where C: FnOnce(Self, Label) -> Self
+	{
+		let label = Label { id: self.next_label };
+		self.next_label += 1;
+		self.code.push(instruction);
+		self.open.push(label.id);
+
+		let mut builder = f(self, label);
+		assert_eq!(builder.open.pop(), Some(label.id), "constructs are closed in order");
+		builder.code.push(Instruction::End);
+		builder
+	}
+
+	fn depth(&self, label: Label) -> u32 {
+		let position = self.open.iter().rposition(|&id| id == label.id)
+			.expect("branch to a label whose construct is not open");
+		(self.open.len() - 1 - position) as u32
+	}
+
+	fn build(self) -> elements::Instructions {
+		let mut code = self.code;
+		code.push(Instruction::End);
+		elements::Instructions::new(code)
+	}
+}
+
+/// Build function code with `f`, ending it with `end`
+///
+/// `f` gets the label of the function body, branching to which returns.
+pub fn instructions<C>(f: C) -> elements::Instructions
+	where C: FnOnce(InstructionsBuilder, Label) -> InstructionsBuilder
+{
+	f(InstructionsBuilder::new(), Label { id: 0 }).build()
+}
+
+/// Build function code from instructions written as their `Instruction` variants
+///
+/// Instructions are separated by commas, which are optional after blocks. `Block`,
+/// `Loop` and `If` take their contents in braces, and `If` may be followed by
+/// `Else` and more braces; the matching `end`s are added, and so is the final one.
+/// Their block type goes in parentheses, as `NoResult`, a value type like `I32`, or
+/// any `BlockType` expression, and may be left out for `NoResult`. Constants can be
+/// written with their type instead, as `i32(1)` or `f32(1.5)`, which converts float
+/// values to their bits.
+///
+/// Every instruction is one step of macro expansion, so very long sequences may need
+/// a higher `recursion_limit`.
+///
+/// # Examples
+///
+/// ```
+/// use parity_wasm::wasm_ops;
+/// use parity_wasm::elements::Instruction::*;
+///
+/// // Count local 0 down to zero, then return 1.5.
+/// let code = wasm_ops![
+///     Block {
+///         Loop {
+///             GetLocal(0),
+///             I32Eqz,
+///             BrIf(1),
+///             GetLocal(0), i32(1), I32Sub, SetLocal(0),
+///             Br(0),
+///         }
+///     }
+///     f32(1.5),
+/// ];
+///
+/// assert_eq!(code.elements()[4], BrIf(1));
+/// assert_eq!(code.elements()[12], F32Const(0x3fc0_0000));
+/// ```
+#[macro_export]
+macro_rules! wasm_ops {
+	(@push $code:ident;) => {};
+	(@push $code:ident; , $($rest:tt)*) => {
+		$crate::wasm_ops!(@push $code; $($rest)*);
+	};
+	(@push $code:ident; Block $(($($block_type:tt)*))? { $($body:tt)* } $($rest:tt)*) => {
+		$code.push($crate::elements::Instruction::Block($crate::wasm_ops!(@block_type $($($block_type)*)?)));
+		$crate::wasm_ops!(@push $code; $($body)*);
+		$code.push($crate::elements::Instruction::End);
+		$crate::wasm_ops!(@push $code; $($rest)*);
+	};
+	(@push $code:ident; Loop $(($($block_type:tt)*))? { $($body:tt)* } $($rest:tt)*) => {
+		$code.push($crate::elements::Instruction::Loop($crate::wasm_ops!(@block_type $($($block_type)*)?)));
+		$crate::wasm_ops!(@push $code; $($body)*);
+		$code.push($crate::elements::Instruction::End);
+		$crate::wasm_ops!(@push $code; $($rest)*);
+	};
+	(@push $code:ident; If $(($($block_type:tt)*))? { $($then:tt)* } Else { $($otherwise:tt)* } $($rest:tt)*) => {
+		$code.push($crate::elements::Instruction::If($crate::wasm_ops!(@block_type $($($block_type)*)?)));
+		$crate::wasm_ops!(@push $code; $($then)*);
+		$code.push($crate::elements::Instruction::Else);
+		$crate::wasm_ops!(@push $code; $($otherwise)*);
+		$code.push($crate::elements::Instruction::End);
+		$crate::wasm_ops!(@push $code; $($rest)*);
+	};
+	(@push $code:ident; If $(($($block_type:tt)*))? { $($then:tt)* } $($rest:tt)*) => {
+		$code.push($crate::elements::Instruction::If($crate::wasm_ops!(@block_type $($($block_type)*)?)));
+		$crate::wasm_ops!(@push $code; $($then)*);
+		$code.push($crate::elements::Instruction::End);
+		$crate::wasm_ops!(@push $code; $($rest)*);
+	};
+	(@push $code:ident; i32($value:expr) $($rest:tt)*) => {
+		$code.push($crate::elements::Instruction::I32Const($value));
+		$crate::wasm_ops!(@push $code; $($rest)*);
+	};
+	(@push $code:ident; i64($value:expr) $($rest:tt)*) => {
+		$code.push($crate::elements::Instruction::I64Const($value));
+		$crate::wasm_ops!(@push $code; $($rest)*);
+	};
+	(@push $code:ident; f32($value:expr) $($rest:tt)*) => {
+		$code.push($crate::elements::Instruction::F32Const(f32::to_bits($value)));
+		$crate::wasm_ops!(@push $code; $($rest)*);
+	};
+	(@push $code:ident; f64($value:expr) $($rest:tt)*) => {
+		$code.push($crate::elements::Instruction::F64Const(f64::to_bits($value)));
+		$crate::wasm_ops!(@push $code; $($rest)*);
+	};
+	(@push $code:ident; $instruction:ident ($($operands:tt)*) $($rest:tt)*) => {
+		$code.push($crate::elements::Instruction::$instruction($($operands)*));
+		$crate::wasm_ops!(@push $code; $($rest)*);
+	};
+	(@push $code:ident; $instruction:ident $($rest:tt)*) => {
+		$code.push($crate::elements::Instruction::$instruction);
+		$crate::wasm_ops!(@push $code; $($rest)*);
+	};
+
+	(@block_type) => { $crate::elements::BlockType::NoResult };
+	(@block_type NoResult) => { $crate::elements::BlockType::NoResult };
+	(@block_type I32) => { $crate::elements::BlockType::Value($crate::elements::ValueType::I32) };
+	(@block_type I64) => { $crate::elements::BlockType::Value($crate::elements::ValueType::I64) };
+	(@block_type F32) => { $crate::elements::BlockType::Value($crate::elements::ValueType::F32) };
+	(@block_type F64) => { $crate::elements::BlockType::Value($crate::elements::ValueType::F64) };
+	(@block_type $block_type:expr) => { $block_type };
+
+	($($code:tt)*) => {{
+		let mut instructions = $crate::elements::Instructions::new(::core::default::Default::default());
+		{
+			let code = instructions.elements_mut();
+			$crate::wasm_ops!(@push code; $($code)*);
+			code.push($crate::elements::Instruction::End);
+		}
+		instructions
+	}};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::instructions;
+	use crate::elements::{BlockType, BrTableData, Instruction::*, Instructions, ValueType};
+
+	#[test]
+	fn depths() {
+		let code = instructions(|b, function| b
+			.block(BlockType::NoResult, |b, outer| b
+				.if_else(BlockType::NoResult,
+					|b, _| b.br(outer),
+					|b, inner| b.br_table(&[inner, outer], function),
+				)
+				.br(function)
+			)
+		);
+		assert_eq!(code, Instructions::new(vec![
+			Block(BlockType::NoResult),
+			If(BlockType::NoResult),
+			Br(1),
+			Else,
+			BrTable(Box::new(BrTableData::new(vec![0, 1], 2))),
+			End,
+			Br(1),
+			End,
+			End,
+		]));
+	}
+
+	#[test]
+	fn ops_macro() {
+		let code = wasm_ops![
+			Block(I32) {
+				GetLocal(0),
+				If {
+					i64(-1), Drop,
+				} Else {
+					Nop
+				}
+				Loop(BlockType::NoResult) {}
+				i32(2),
+			},
+			f64(0.5), Drop,
+			BrTable(Box::new(BrTableData::new(vec![0], 0))),
+		];
+		assert_eq!(code, Instructions::new(vec![
+			Block(BlockType::Value(ValueType::I32)),
+			GetLocal(0),
+			If(BlockType::NoResult),
+			I64Const(-1),
+			Drop,
+			Else,
+			Nop,
+			End,
+			Loop(BlockType::NoResult),
+			End,
+			I32Const(2),
+			End,
+			F64Const(0x3fe0_0000_0000_0000),
+			Drop,
+			BrTable(Box::new(BrTableData::new(vec![0], 0))),
+			End,
+		]));
+		assert_eq!(wasm_ops![], Instructions::empty());
+	}
+
+	#[test]
+	#[should_panic]
+	fn closed_label() {
+		let mut escaped = None;
+		instructions(|b, _| {
+			let b = b.block(BlockType::NoResult, |b, label| { escaped = Some(label); b });
+			b.br(escaped.expect("label to be set"))
+		});
+	}
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::new_without_default, clippy::redundant_field_names)]
+
 use alloc::vec::Vec;
 use crate::elements;
 use super::invoke::{Invoke, Identity};
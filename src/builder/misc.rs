@@ -1,3 +1,5 @@
+#![allow(clippy::redundant_field_names)]
+
 use alloc::vec::Vec;
 use super::invoke::{Invoke, Identity};
 use crate::elements;
@@ -10,15 +10,19 @@ mod table;
 mod export;
 mod global;
 mod data;
+mod element;
+mod instructions;
 
 pub use self::code::{
 	signatures, signature, function, SignatureBuilder, SignaturesBuilder,
 	FunctionBuilder, TypeRefBuilder, FuncBodyBuilder, FunctionDefinition,
 };
 pub use self::data::DataSegmentBuilder;
+pub use self::element::{element_segment, ElementSegmentBuilder};
 pub use self::export::{export, ExportBuilder, ExportInternalBuilder};
 pub use self::global::{global, GlobalBuilder};
 pub use self::import::{import, ImportBuilder};
+pub use self::instructions::{instructions, InstructionsBuilder, Label};
 pub use self::invoke::Identity;
 pub use self::memory::MemoryBuilder;
 pub use self::module::{module, from_module, ModuleBuilder, CodeLocation};
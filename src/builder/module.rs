@@ -1,10 +1,13 @@
-use alloc::vec::Vec;
+#![allow(clippy::len_zero, clippy::new_without_default, clippy::redundant_field_names)]
+
+use alloc::{string::String, vec::Vec};
 use crate::elements;
 use super::{
 	import,
 	export,
 	global,
 	data,
+	element,
 	invoke::{Invoke, Identity},
 	code::{self, SignaturesBuilder, FunctionBuilder},
 	memory::{self, MemoryBuilder},
@@ -25,6 +28,72 @@ pub struct CodeLocation {
 	pub body: u32,
 }
 
+/// Function a name is given to.
+#[derive(PartialEq)]
+enum NameTarget {
+	/// Function defined by the builder, by index of its body, since imports added
+	/// later shift its index.
+	Body(u32),
+	/// Function by index.
+	Function(u32),
+}
+
+/// Names to be put into the name section.
+#[derive(Default, PartialEq)]
+struct Names {
+	module: Option<String>,
+	functions: Vec<(NameTarget, String)>,
+	locals: Vec<(NameTarget, u32, String)>,
+}
+
+impl Names {
+	fn is_empty(&self) -> bool {
+		self.module.is_none() && self.functions.is_empty() && self.locals.is_empty()
+	}
+
+	/// Add the names to the name section among `sections`, creating it if there is none.
+	fn apply(self, imported_functions: u32, sections: &mut Vec<elements::Section>) {
+		if self.is_empty() {
+			return;
+		}
+		let resolve = |target: NameTarget| match target {
+			NameTarget::Body(body) => imported_functions + body,
+			NameTarget::Function(index) => index,
+		};
+
+		let position = match sections.iter().position(|section| matches!(*section, elements::Section::Name(_))) {
+			Some(position) => position,
+			None => {
+				sections.push(elements::Section::Name(elements::NameSection::new(None, None, None)));
+				sections.len() - 1
+			},
+		};
+		let name_section = match sections[position] {
+			elements::Section::Name(ref mut name_section) => name_section,
+			_ => unreachable!("position is of a name section; qed"),
+		};
+
+		if let Some(module) = self.module {
+			*name_section.module_mut() = Some(elements::ModuleNameSubsection::new(module));
+		}
+		if !self.functions.is_empty() {
+			let functions = name_section.functions_mut().get_or_insert_with(Default::default);
+			for (target, name) in self.functions {
+				functions.names_mut().insert(resolve(target), name);
+			}
+		}
+		if !self.locals.is_empty() {
+			let locals = name_section.locals_mut().get_or_insert_with(Default::default);
+			for (target, local, name) in self.locals {
+				let function = resolve(target);
+				let mut names = locals.local_names_mut().remove(function).unwrap_or_default();
+				names.insert(local, name);
+				locals.local_names_mut().insert(function, names);
+			}
+		}
+	}
+}
+
 #[derive(Default, PartialEq)]
 struct ModuleScaffold {
 	pub types: elements::TypeSection,
@@ -32,6 +101,8 @@ struct ModuleScaffold {
 	pub functions: elements::FunctionSection,
 	pub table: elements::TableSection,
 	pub memory: elements::MemorySection,
+	#[cfg(feature="exceptions")]
+	pub tag: elements::TagSection,
 	pub global: elements::GlobalSection,
 	pub export: elements::ExportSection,
 	pub start: Option<u32>,
@@ -39,6 +110,7 @@ struct ModuleScaffold {
 	pub code: elements::CodeSection,
 	pub data: elements::DataSection,
 	pub other: Vec<elements::Section>,
+	names: Names,
 }
 
 impl From<elements::Module> for ModuleScaffold {
@@ -48,6 +120,8 @@ impl From<elements::Module> for ModuleScaffold {
 		let mut funcs: Option<elements::FunctionSection> = None;
 		let mut table: Option<elements::TableSection> = None;
 		let mut memory: Option<elements::MemorySection> = None;
+		#[cfg(feature="exceptions")]
+		let mut tag: Option<elements::TagSection> = None;
 		let mut global: Option<elements::GlobalSection> = None;
 		let mut export: Option<elements::ExportSection> = None;
 		let mut start: Option<u32> = None;
@@ -64,6 +138,8 @@ impl From<elements::Module> for ModuleScaffold {
 				elements::Section::Function(sect) => { funcs = Some(sect); }
 				elements::Section::Table(sect) => { table = Some(sect); }
 				elements::Section::Memory(sect) => { memory = Some(sect); }
+				#[cfg(feature="exceptions")]
+				elements::Section::Tag(sect) => { tag = Some(sect); }
 				elements::Section::Global(sect) => { global = Some(sect); }
 				elements::Section::Export(sect) => { export = Some(sect); }
 				elements::Section::Start(index) => { start = Some(index); }
@@ -80,6 +156,8 @@ impl From<elements::Module> for ModuleScaffold {
 			functions: funcs.unwrap_or_default(),
 			table: table.unwrap_or_default(),
 			memory: memory.unwrap_or_default(),
+			#[cfg(feature="exceptions")]
+			tag: tag.unwrap_or_default(),
 			global: global.unwrap_or_default(),
 			export: export.unwrap_or_default(),
 			start: start,
@@ -87,6 +165,7 @@ impl From<elements::Module> for ModuleScaffold {
 			code: code.unwrap_or_default(),
 			data: data.unwrap_or_default(),
 			other,
+			names: Names::default(),
 		}
 	}
 }
@@ -94,6 +173,9 @@ impl From<elements::Module> for ModuleScaffold {
 impl From<ModuleScaffold> for elements::Module {
 	fn from(module: ModuleScaffold) -> Self {
 		let mut sections = Vec::new();
+		let imported_functions = module.import.entries().iter()
+			.filter(|entry| matches!(*entry.external(), elements::External::Function(_)))
+			.count() as u32;
 
 		let types = module.types;
 		if types.types().len() > 0 {
@@ -115,6 +197,13 @@ impl From<ModuleScaffold> for elements::Module {
 		if memory.entries().len() > 0 {
 			sections.push(elements::Section::Memory(memory));
 		}
+		#[cfg(feature="exceptions")]
+		{
+			let tag = module.tag;
+			if tag.entries().len() > 0 {
+				sections.push(elements::Section::Tag(tag));
+			}
+		}
 		let global = module.global;
 		if global.entries().len() > 0 {
 			sections.push(elements::Section::Global(global));
@@ -139,6 +228,7 @@ impl From<ModuleScaffold> for elements::Module {
 			sections.push(elements::Section::Data(data));
 		}
 		sections.extend(module.other);
+		module.names.apply(imported_functions, &mut sections);
 		elements::Module::new(sections)
 	}
 }
@@ -179,6 +269,24 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
 		self
 	}
 
+	/// Set the name of the module in the name section
+	pub fn with_name(mut self, name: &str) -> Self {
+		self.module.names.module = Some(name.into());
+		self
+	}
+
+	/// Set the name of function `index`, imported ones included, in the name section
+	pub fn with_function_name(mut self, index: u32, name: &str) -> Self {
+		self.module.names.functions.push((NameTarget::Function(index), name.into()));
+		self
+	}
+
+	/// Set the name of local `local` of function `function` in the name section
+	pub fn with_local_name(mut self, function: u32, local: u32, name: &str) -> Self {
+		self.module.names.locals.push((NameTarget::Function(function), local, name.into()));
+		self
+	}
+
 	/// Binds to the type section, creates additional types when required
 	pub fn with_signatures(mut self, bindings: code::SignatureBindings) -> Self {
 		self.push_signatures(bindings);
@@ -202,6 +310,12 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
 		if func.is_main {
 			self.module.start = Some(body_index);
 		}
+		if let Some(name) = func.name {
+			self.module.names.functions.push((NameTarget::Body(body_index), name));
+		}
+		for (local, name) in func.local_names {
+			self.module.names.locals.push((NameTarget::Body(body_index), local, name));
+		}
 
 		CodeLocation {
 			signature: signature_index,
@@ -403,6 +517,38 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
 		data::DataSegmentBuilder::with_callback(self)
 	}
 
+	/// Add element segment to the builder
+	pub fn with_element_segment(mut self, segment: elements::ElementSegment) -> Self {
+		self.module.element.entries_mut().push(segment);
+		self
+	}
+
+	/// Element segment builder
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use parity_wasm::builder;
+	/// use parity_wasm::elements::Instruction::*;
+	///
+	/// let module = builder::module()
+	///    .function()
+	///        .signature().build()
+	///        .body().build()
+	///        .build()
+	///    .table().with_min(4).build()
+	///    .elements()
+	///        .offset(I32Const(2))
+	///        .member(0)
+	///        .build()
+	///    .build();
+	///
+	/// assert_eq!(module.elements_section().expect("element section to exist").entries()[0].members(), &[0]);
+	/// ```
+	pub fn elements(self) -> element::ElementSegmentBuilder<Self> {
+		element::ElementSegmentBuilder::with_callback(self)
+	}
+
 	/// Build module (final step)
 	pub fn build(self) -> F::Result {
 		self.callback.invoke(self.module.into())
@@ -496,6 +642,16 @@ impl<F> Invoke<elements::GlobalEntry> for ModuleBuilder<F>
 	}
 }
 
+impl<F> Invoke<elements::ElementSegment> for ModuleBuilder<F>
+	where F: Invoke<elements::Module>
+{
+	type Result = Self;
+
+	fn invoke(self, segment: elements::ElementSegment) -> Self {
+		self.with_element_segment(segment)
+	}
+}
+
 impl<F> Invoke<elements::DataSegment> for ModuleBuilder<F>
 	where F: Invoke<elements::Module>
 {
@@ -558,6 +714,33 @@ mod tests {
 		assert_eq!(module.code_section().expect("code section to exist").bodies().len(), 1);
 	}
 
+	#[test]
+	fn names() {
+		let module = module()
+			.with_name("example")
+			.function()
+				.name("add")
+				.local_name(0, "lhs")
+				.local_name(1, "rhs")
+				.signature().with_params(vec![elements::ValueType::I32, elements::ValueType::I32]).build()
+				.body().build()
+				.build()
+			.import().module("env").field("log").external().func(0).build()
+			.with_function_name(0, "log")
+			.build();
+
+		let bytes = elements::serialize(module).expect("module to serialize");
+		let module = elements::deserialize_buffer::<elements::Module>(&bytes).expect("module to deserialize")
+			.parse_names().expect("names to parse");
+		let names = module.names_section().expect("name section to exist");
+		assert_eq!(names.module().map(|module| module.name()), Some("example"));
+		let functions = names.functions().expect("function names to exist").names();
+		assert_eq!(functions.get(0).map(|name| &name[..]), Some("log"));
+		assert_eq!(functions.get(1).map(|name| &name[..]), Some("add"));
+		let locals = names.locals().expect("local names to exist").local_names();
+		assert_eq!(locals.get(1).and_then(|locals| locals.get(1)).map(|name| &name[..]), Some("rhs"));
+	}
+
 	#[test]
 	fn export() {
 		let module = module()
@@ -0,0 +1,157 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+use crate::io;
+use super::{Deserialize, Error, Section, CustomSection, VarUint7, VarUint32, serialize};
+use super::module::ModuleReader;
+
+const CUSTOM_SECTION_ID: u8 = 0;
+
+/// Custom section located in the input buffer.
+#[derive(Debug, Clone, PartialEq)]
+struct RawCustom<'a> {
+	name: &'a [u8],
+	/// Whole section, header included.
+	range: Range<usize>,
+}
+
+/// Appends or replaces custom sections of a serialized module without decoding it.
+///
+/// Only section headers and custom section names are read; every other byte is
+/// copied through unchanged. This keeps stamping metadata into a module cheap
+/// regardless of its size, at the cost of not validating anything beyond the
+/// section framing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionAppender<'a> {
+	contents: &'a [u8],
+	customs: Vec<RawCustom<'a>>,
+}
+
+impl<'a> SectionAppender<'a> {
+	/// Locate the sections of a serialized module.
+	pub fn new(contents: &'a [u8]) -> Result<Self, Error> {
+		let mut header = io::Cursor::new(contents);
+		ModuleReader::new(&mut header)?;
+
+		let mut position = header.position();
+		let mut customs = Vec::new();
+		while position < contents.len() {
+			let mut reader = io::Cursor::new(&contents[position..]);
+			let id: u8 = VarUint7::deserialize(&mut reader)?.into();
+			let length: usize = VarUint32::deserialize(&mut reader)?.into();
+			let start = position + reader.position();
+			if length > contents.len() - start {
+				return Err(io::Error::UnexpectedEof.into());
+			}
+			let end = start + length;
+
+			if id == CUSTOM_SECTION_ID {
+				let payload = &contents[start..end];
+				let mut reader = io::Cursor::new(payload);
+				let name_length: usize = VarUint32::deserialize(&mut reader)?.into();
+				let name_start = reader.position();
+				if name_length > payload.len() - name_start {
+					return Err(io::Error::UnexpectedEof.into());
+				}
+				customs.push(RawCustom {
+					name: &payload[name_start..name_start + name_length],
+					range: position..end,
+				});
+			}
+			position = end;
+		}
+
+		Ok(SectionAppender { contents, customs })
+	}
+
+	/// Whether the module has a custom section named `name`.
+	pub fn has_custom(&self, name: &str) -> bool {
+		self.customs.iter().any(|custom| custom.name == name.as_bytes())
+	}
+
+	/// Module with a custom section named `name` added at its end.
+	pub fn append_custom(&self, name: &str, payload: &[u8]) -> Result<Vec<u8>, Error> {
+		let section = custom_section(name, payload)?;
+		let mut out = Vec::with_capacity(self.contents.len() + section.len());
+		out.extend_from_slice(self.contents);
+		out.extend_from_slice(&section);
+		Ok(out)
+	}
+
+	/// Module with the first custom section named `name` replaced in place.
+	///
+	/// Further custom sections of the same name are removed. If there is none, the
+	/// section is added at the end of the module instead.
+	pub fn replace_custom(&self, name: &str, payload: &[u8]) -> Result<Vec<u8>, Error> {
+		if !self.has_custom(name) {
+			return self.append_custom(name, payload);
+		}
+
+		let section = custom_section(name, payload)?;
+		let mut out = Vec::with_capacity(self.contents.len() + section.len());
+		let mut copied = 0;
+		let mut replaced = false;
+		for custom in self.customs.iter().filter(|custom| custom.name == name.as_bytes()) {
+			out.extend_from_slice(&self.contents[copied..custom.range.start]);
+			if !replaced {
+				out.extend_from_slice(&section);
+				replaced = true;
+			}
+			copied = custom.range.end;
+		}
+		out.extend_from_slice(&self.contents[copied..]);
+		Ok(out)
+	}
+}
+
+fn custom_section(name: &str, payload: &[u8]) -> Result<Vec<u8>, Error> {
+	serialize(Section::Custom(CustomSection::new(name.into(), payload.to_vec())))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::SectionAppender;
+	use super::super::{deserialize_buffer, Module, Section};
+
+	fn custom_sections(bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+		let module: Module = deserialize_buffer(bytes).expect("module to be deserialized");
+		module.custom_sections().map(|custom| (custom.name().into(), custom.payload().to_vec())).collect()
+	}
+
+	#[test]
+	fn append_and_replace() {
+		let bytes = std::fs::read("./res/cases/v1/test5.wasm").expect("file to be read");
+		let appender = SectionAppender::new(&bytes).expect("sections to be located");
+		assert!(!appender.has_custom("build-id"));
+
+		let appended = appender.append_custom("build-id", &[1, 2, 3]).expect("section to be appended");
+		assert_eq!(&appended[..bytes.len()], &bytes[..]);
+		assert_eq!(custom_sections(&appended), vec![("build-id".into(), vec![1, 2, 3])]);
+
+		let appender = SectionAppender::new(&appended).expect("sections to be located");
+		let replaced = appender.replace_custom("build-id", &[4; 200]).expect("section to be replaced");
+		assert_eq!(custom_sections(&replaced), vec![("build-id".into(), vec![4; 200])]);
+
+		let module: Module = deserialize_buffer(&replaced).expect("module to be deserialized");
+		let original: Module = deserialize_buffer(&bytes).expect("module to be deserialized");
+		let code = |module: &Module| module.sections().iter().filter(|s| !matches!(**s, Section::Custom(_))).count();
+		assert_eq!(code(&module), code(&original));
+	}
+
+	#[test]
+	fn replace_removes_duplicates() {
+		let bytes = std::fs::read("./res/cases/v1/test5.wasm").expect("file to be read");
+		let bytes = SectionAppender::new(&bytes).expect("sections to be located")
+			.append_custom("meta", &[1]).expect("section to be appended");
+		let bytes = SectionAppender::new(&bytes).expect("sections to be located")
+			.append_custom("meta", &[2]).expect("section to be appended");
+		let bytes = SectionAppender::new(&bytes).expect("sections to be located")
+			.replace_custom("meta", &[3]).expect("section to be replaced");
+		assert_eq!(custom_sections(&bytes), vec![("meta".into(), vec![3])]);
+	}
+
+	#[test]
+	fn truncated() {
+		let bytes = std::fs::read("./res/cases/v1/test5.wasm").expect("file to be read");
+		assert!(SectionAppender::new(&bytes[..bytes.len() - 1]).is_err());
+	}
+}
@@ -0,0 +1,379 @@
+use alloc::vec::Vec;
+use core::fmt;
+use super::{Module, External, GlobalType, InitExpr, ResizableLimits, RuntimeValue, ConstEvalError, ValueType};
+
+/// Size of a memory page, in bytes.
+const PAGE_SIZE: u64 = 65536;
+
+/// Active segment which cannot initialize its memory or table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SegmentBoundsError {
+	/// Globals could not be evaluated, so neither could any offset.
+	///
+	/// This is reported alone, since no segment can be checked.
+	Globals(ConstEvalError),
+	/// Data segment refers to a memory which does not exist.
+	UnknownMemory {
+		/// Index of the data segment.
+		segment: usize,
+		/// Memory it refers to.
+		memory: u32,
+	},
+	/// Element segment refers to a table which does not exist.
+	UnknownTable {
+		/// Index of the element segment.
+		segment: usize,
+		/// Table it refers to.
+		table: u32,
+	},
+	/// Offset of the data segment is not a valid constant expression.
+	DataOffset {
+		/// Index of the data segment.
+		segment: usize,
+		/// Why the offset could not be evaluated.
+		error: ConstEvalError,
+	},
+	/// Offset of the element segment is not a valid constant expression.
+	ElementOffset {
+		/// Index of the element segment.
+		segment: usize,
+		/// Why the offset could not be evaluated.
+		error: ConstEvalError,
+	},
+	/// Data segment does not fit into the initial size of its memory.
+	DataOutOfBounds {
+		/// Index of the data segment.
+		segment: usize,
+		/// Offset the segment is written at, in bytes.
+		offset: u64,
+		/// Length of the segment, in bytes.
+		length: u64,
+		/// Initial size of the memory, in bytes.
+		memory_size: u64,
+	},
+	/// Element segment does not fit into the initial size of its table.
+	ElementOutOfBounds {
+		/// Index of the element segment.
+		segment: usize,
+		/// Offset the segment is written at, in elements.
+		offset: u64,
+		/// Length of the segment, in elements.
+		length: u64,
+		/// Initial size of the table, in elements.
+		table_size: u64,
+	},
+}
+
+impl fmt::Display for SegmentBoundsError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			SegmentBoundsError::Globals(ref error) => write!(f, "Globals cannot be evaluated: {}", error),
+			SegmentBoundsError::UnknownMemory { segment, memory } =>
+				write!(f, "Data segment {} refers to unknown memory {}", segment, memory),
+			SegmentBoundsError::UnknownTable { segment, table } =>
+				write!(f, "Element segment {} refers to unknown table {}", segment, table),
+			SegmentBoundsError::DataOffset { segment, ref error } =>
+				write!(f, "Offset of data segment {} cannot be evaluated: {}", segment, error),
+			SegmentBoundsError::ElementOffset { segment, ref error } =>
+				write!(f, "Offset of element segment {} cannot be evaluated: {}", segment, error),
+			SegmentBoundsError::DataOutOfBounds { segment, offset, length, memory_size } =>
+				write!(f, "Data segment {} of {} bytes at offset {} exceeds memory of {} bytes", segment, length, offset, memory_size),
+			SegmentBoundsError::ElementOutOfBounds { segment, offset, length, table_size } =>
+				write!(f, "Element segment {} of {} elements at offset {} exceeds table of {} elements", segment, length, offset, table_size),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for SegmentBoundsError {}
+
+/// Whether offsets into a memory with these limits are `i64`.
+fn is_64(_limits: &ResizableLimits) -> bool {
+	#[cfg(feature="memory64")]
+	return _limits.is_64();
+	#[cfg(not(feature="memory64"))]
+	false
+}
+
+/// Whether offsets into the memory `index` are `i64`; unknown memories are left for
+/// the caller to report.
+fn wide_memory(module: &Module, index: u32) -> bool {
+	module.memory_type(index).is_some_and(|memory| is_64(memory.limits()))
+}
+
+/// Evaluate an offset, which must be an `i64` if `wide`, and an `i32` otherwise.
+fn evaluate_offset(offset: &InitExpr, wide: bool, globals: &[Option<RuntimeValue>]) -> Result<u64, ConstEvalError> {
+	match (offset.evaluate(|index| globals.get(index as usize).cloned().flatten())?, wide) {
+		(RuntimeValue::I32(offset), false) => Ok(offset as u32 as u64),
+		(RuntimeValue::I64(offset), true) => Ok(offset as u64),
+		_ => Err(ConstEvalError::TypeMismatch),
+	}
+}
+
+/// Resolved placement of an active data segment.
+struct Placement {
+	segment: usize,
+	memory: u32,
+	offset: u64,
+	length: u64,
+}
+
+fn data_placements(module: &Module, globals: &[Option<RuntimeValue>], errors: &mut Vec<SegmentBoundsError>) -> Vec<Placement> {
+	let mut placements = Vec::new();
+	for (segment, entry) in module.data_section().map(|s| s.entries()).unwrap_or(&[]).iter().enumerate() {
+		let offset = match *entry.offset() {
+			Some(ref offset) => offset,
+			None => continue,
+		};
+		match evaluate_offset(offset, wide_memory(module, entry.index()), globals) {
+			Ok(offset) => placements.push(Placement {
+				segment,
+				memory: entry.index(),
+				offset,
+				length: entry.value().len() as u64,
+			}),
+			Err(error) => errors.push(SegmentBoundsError::DataOffset { segment, error }),
+		}
+	}
+	placements
+}
+
+pub(crate) fn check(module: &Module, imported_globals: &[RuntimeValue]) -> Result<(), Vec<SegmentBoundsError>> {
+	let globals = given_offset_globals(module, imported_globals).map_err(|error| vec![SegmentBoundsError::Globals(error)])?;
+	let mut errors = Vec::new();
+	for placement in data_placements(module, &globals, &mut errors) {
+		let segment = placement.segment;
+		match module.memory_type(placement.memory) {
+			None => errors.push(SegmentBoundsError::UnknownMemory { segment, memory: placement.memory }),
+			Some(memory) => {
+				let memory_size = memory.limits().initial() as u64 * PAGE_SIZE;
+				if placement.offset.checked_add(placement.length).is_none_or(|end| end > memory_size) {
+					errors.push(SegmentBoundsError::DataOutOfBounds {
+						segment,
+						offset: placement.offset,
+						length: placement.length,
+						memory_size,
+					});
+				}
+			},
+		}
+	}
+
+	for (segment, entry) in module.elements_section().map(|s| s.entries()).unwrap_or(&[]).iter().enumerate() {
+		let offset = match *entry.offset() {
+			Some(ref offset) => offset,
+			None => continue,
+		};
+		let offset = match evaluate_offset(offset, false, &globals) {
+			Ok(offset) => offset,
+			Err(error) => {
+				errors.push(SegmentBoundsError::ElementOffset { segment, error });
+				continue;
+			},
+		};
+		let length = entry.members().len() as u64;
+		match module.table_type(entry.index()) {
+			None => errors.push(SegmentBoundsError::UnknownTable { segment, table: entry.index() }),
+			Some(table) => {
+				let table_size = table.limits().initial() as u64;
+				if offset.checked_add(length).is_none_or(|end| end > table_size) {
+					errors.push(SegmentBoundsError::ElementOutOfBounds { segment, offset, length, table_size });
+				}
+			},
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(())
+	} else {
+		Err(errors)
+	}
+}
+
+pub(crate) fn overlapping_data(module: &Module, imported_globals: &[RuntimeValue]) -> Result<Vec<(usize, usize)>, Vec<SegmentBoundsError>> {
+	let globals = given_offset_globals(module, imported_globals).map_err(|error| vec![SegmentBoundsError::Globals(error)])?;
+	let mut errors = Vec::new();
+	let placements = data_placements(module, &globals, &mut errors);
+	if !errors.is_empty() {
+		return Err(errors);
+	}
+
+	let mut overlaps = Vec::new();
+	for (i, first) in placements.iter().enumerate() {
+		for second in &placements[i + 1..] {
+			if first.memory == second.memory
+				&& first.length > 0 && second.length > 0
+				&& first.offset < second.offset.saturating_add(second.length)
+				&& second.offset < first.offset.saturating_add(first.length)
+			{
+				overlaps.push((first.segment, second.segment));
+			}
+		}
+	}
+	Ok(overlaps)
+}
+
+fn imported_global_types(module: &Module) -> impl Iterator<Item=&GlobalType> {
+	module.import_section().map(|s| s.entries()).unwrap_or(&[]).iter()
+		.filter_map(|entry| match *entry.external() {
+			External::Global(ref global_type) => Some(global_type),
+			_ => None,
+		})
+}
+
+/// Values of the globals a segment offset may refer to, the immutable imported ones,
+/// given the values of every imported global.
+fn given_offset_globals(module: &Module, imported: &[RuntimeValue]) -> Result<Vec<Option<RuntimeValue>>, ConstEvalError> {
+	let mut values = imported.iter();
+	let mut globals = Vec::new();
+	for global_type in imported_global_types(module) {
+		let value = *values.next().ok_or(ConstEvalError::ImportCountMismatch)?;
+		if value.value_type() != global_type.content_type() {
+			return Err(ConstEvalError::TypeMismatch);
+		}
+		globals.push(if global_type.is_mutable() { None } else { Some(value) });
+	}
+	if values.next().is_some() {
+		return Err(ConstEvalError::ImportCountMismatch);
+	}
+	Ok(globals)
+}
+
+/// Stand-in values for the globals a segment offset may refer to: the immutable
+/// imported ones. The values themselves are not known before instantiation.
+fn offset_globals(module: &Module) -> Vec<Option<RuntimeValue>> {
+	imported_global_types(module)
+		.map(|global_type| match global_type.content_type() {
+			_ if global_type.is_mutable() => None,
+			ValueType::I32 => Some(RuntimeValue::I32(0)),
+			ValueType::I64 => Some(RuntimeValue::I64(0)),
+			ValueType::F32 => Some(RuntimeValue::F32(0)),
+			ValueType::F64 => Some(RuntimeValue::F64(0)),
+			#[cfg(feature="simd")]
+			ValueType::V128 => None,
+		})
+		.collect()
+}
+
+pub(crate) fn check_offsets(module: &Module) -> Result<(), Vec<SegmentBoundsError>> {
+	let globals = offset_globals(module);
+	let mut errors = Vec::new();
+	for (segment, entry) in module.data_section().map(|s| s.entries()).unwrap_or(&[]).iter().enumerate() {
+		if let Some(ref offset) = *entry.offset() {
+			if let Err(error) = evaluate_offset(offset, wide_memory(module, entry.index()), &globals) {
+				errors.push(SegmentBoundsError::DataOffset { segment, error });
+			}
+		}
+	}
+	for (segment, entry) in module.elements_section().map(|s| s.entries()).unwrap_or(&[]).iter().enumerate() {
+		if let Some(ref offset) = *entry.offset() {
+			if let Err(error) = evaluate_offset(offset, false, &globals) {
+				errors.push(SegmentBoundsError::ElementOffset { segment, error });
+			}
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(())
+	} else {
+		Err(errors)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::SegmentBoundsError;
+	use crate::builder;
+	use crate::elements::{ConstEvalError, Instruction::*, RuntimeValue, ValueType};
+
+	#[test]
+	fn data_bounds() {
+		let module = builder::module()
+			.import().module("env").field("base").external().global(ValueType::I32, false).build()
+			.memory().with_min(1).build()
+			.data().offset(I32Const(65530)).value(vec![0; 6]).build()
+			.data().offset(I32Const(65530)).value(vec![0; 7]).build()
+			.data().offset_global(0).value(vec![0; 4]).build()
+			.build();
+
+		assert_eq!(module.check_segment_bounds(&[RuntimeValue::I32(0)]), Err(vec![
+			SegmentBoundsError::DataOutOfBounds { segment: 1, offset: 65530, length: 7, memory_size: 65536 },
+		]));
+		assert_eq!(module.overlapping_data_segments(&[RuntimeValue::I32(65532)]), Ok(vec![(0, 1), (0, 2), (1, 2)]));
+		assert_eq!(module.overlapping_data_segments(&[RuntimeValue::I32(0)]), Ok(vec![(0, 1)]));
+	}
+
+	#[test]
+	fn offsets_near_4gib() {
+		// Offsets and sizes are computed in 64 bits, so that they can't wrap on 32-bit hosts.
+		let module = builder::module()
+			.memory().with_min(65536).build()
+			.data().offset(I32Const(-16)).value(vec![0; 16]).build()
+			.data().offset(I32Const(-16)).value(vec![0; 17]).build()
+			.build();
+
+		assert_eq!(module.check_segment_bounds(&[]), Err(vec![
+			SegmentBoundsError::DataOutOfBounds { segment: 1, offset: 0xffff_fff0, length: 17, memory_size: 1 << 32 },
+		]));
+	}
+
+	#[test]
+	fn offsets() {
+		let module = builder::module()
+			.import().module("env").field("base").external().global(ValueType::I32, false).build()
+			.import().module("env").field("counter").external().global(ValueType::I32, true).build()
+			.global().value_type().i32().init_expr(I32Const(0)).build()
+			.memory().with_min(1).build()
+			.data().offset_global(0).value(vec![0; 4]).build()
+			.data().offset_global(1).value(vec![0; 4]).build()
+			.data().offset_global(2).value(vec![0; 4]).build()
+			.data().offset(F32Const(0)).value(vec![0; 4]).build()
+			.data().offset(I64Const(0)).value(vec![0; 4]).build()
+			.build();
+
+		let errors = vec![
+			SegmentBoundsError::DataOffset { segment: 1, error: ConstEvalError::UnknownGlobal(1) },
+			SegmentBoundsError::DataOffset { segment: 2, error: ConstEvalError::UnknownGlobal(2) },
+			SegmentBoundsError::DataOffset { segment: 3, error: ConstEvalError::TypeMismatch },
+			SegmentBoundsError::DataOffset { segment: 4, error: ConstEvalError::TypeMismatch },
+		];
+		assert_eq!(module.check_segment_offsets(), Err(errors.clone()));
+		// Given values of the imported globals, offsets follow the same rules.
+		assert_eq!(module.check_segment_bounds(&[RuntimeValue::I32(0), RuntimeValue::I32(0)]), Err(errors));
+		assert_eq!(
+			module.check_segment_bounds(&[RuntimeValue::I32(0)]),
+			Err(vec![SegmentBoundsError::Globals(ConstEvalError::ImportCountMismatch)]),
+		);
+	}
+
+	#[cfg(feature="bulk")]
+	#[test]
+	fn passive_segments() {
+		let module = builder::module()
+			.import().module("env").field("base").external().global(ValueType::I32, false).build()
+			.memory().with_min(1).build()
+			.data().offset_global(0).value(vec![1]).build()
+			.data().passive().value(vec![2]).build()
+			.build();
+		assert_eq!(module.check_segment_offsets(), Ok(()));
+
+		let segments = module.data_section().expect("data section").entries();
+		assert!(!segments[0].passive());
+		assert!(segments[1].passive() && segments[1].offset().is_none());
+	}
+
+	#[test]
+	fn element_bounds() {
+		let module = builder::module()
+			.function().signature().build().body().build().build()
+			.table().with_min(2).build()
+			.elements().offset(I32Const(1)).members(vec![0, 0]).build()
+			.elements().table(1).member(0).build()
+			.build();
+
+		assert_eq!(module.check_segment_bounds(&[]), Err(vec![
+			SegmentBoundsError::ElementOutOfBounds { segment: 0, offset: 1, length: 2, table_size: 2 },
+			SegmentBoundsError::UnknownTable { segment: 1, table: 1 },
+		]));
+	}
+}
@@ -0,0 +1,93 @@
+use alloc::vec::Vec;
+use super::{Error, Module, Section, deserialize_buffer, serialize};
+
+/// Whether the section is dynamic linking metadata, which has to come first.
+fn is_dylink(section: &Section) -> bool {
+	match *section {
+		Section::Dylink(_) => true,
+		Section::Custom(ref custom) => custom.name() == "dylink.0" || custom.name() == "dylink",
+		_ => false,
+	}
+}
+
+impl Module {
+	/// Put the sections into canonical order.
+	///
+	/// Known sections are ordered as the spec requires. The dylink section comes first,
+	/// and every other custom or unparsed section after the known ones, keeping their
+	/// relative order.
+	pub fn canonicalize(&mut self) {
+		self.sections_mut().sort_by_key(canonical_rank);
+	}
+}
+
+/// Position of the section in canonical order; the sort is stable, so that sections of
+/// the same rank keep their relative order.
+pub(crate) fn canonical_rank(section: &Section) -> u8 {
+	if is_dylink(section) {
+		0
+	} else if section.order() != 0 {
+		section.position()
+	} else {
+		u8::MAX
+	}
+}
+
+/// Serialize `module` in canonical form.
+///
+/// Besides the section order of `Module::canonicalize`, every integer is written in
+/// its shortest LEB128 encoding, as the serializer always does. Payloads of custom and
+/// unparsed sections are written as they are. Relocatable modules rely on padded
+/// encodings in their code and should not be canonicalized.
+pub fn serialize_canonical(mut module: Module) -> Result<Vec<u8>, Error> {
+	module.canonicalize();
+	serialize(module)
+}
+
+/// Whether `bytes` are a module in the canonical form of `serialize_canonical`.
+pub fn is_canonical(bytes: &[u8]) -> bool {
+	let module: Module = match deserialize_buffer(bytes) {
+		Ok(module) => module,
+		Err(_) => return false,
+	};
+	match serialize_canonical(module) {
+		Ok(canonical) => canonical == bytes,
+		Err(_) => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{is_canonical, serialize_canonical};
+	use crate::builder;
+	use crate::elements::{serialize, Section};
+
+	#[test]
+	fn canonical_order() {
+		let mut module = builder::module()
+			.function().signature().build().body().build().build()
+			.build();
+		module.set_custom_section("name", vec![0]);
+		module.sections_mut().insert(0, Section::Custom(crate::elements::CustomSection::new("producers".into(), vec![0])));
+		module.set_custom_section("dylink.0", vec![]);
+
+		let bytes = serialize(module.clone()).expect("module to serialize");
+		assert!(!is_canonical(&bytes));
+
+		let canonical = serialize_canonical(module).expect("module to serialize");
+		assert!(is_canonical(&canonical));
+		let module: crate::elements::Module = crate::elements::deserialize_buffer(&canonical).expect("module to deserialize");
+		let names: Vec<_> = module.custom_sections().map(|custom| custom.name()).collect();
+		assert_eq!(names, vec!["dylink.0", "producers", "name"]);
+		assert!(matches!(module.sections()[1], Section::Type(_)));
+	}
+
+	#[test]
+	fn overlong_leb() {
+		// Type section with its size written as a two byte LEB128.
+		let bytes = [0, b'a', b's', b'm', 1, 0, 0, 0, 1, 0x81, 0x00, 0];
+		assert!(crate::elements::deserialize_buffer::<crate::elements::Module>(&bytes).is_ok());
+		assert!(!is_canonical(&bytes));
+		assert!(is_canonical(&[0, b'a', b's', b'm', 1, 0, 0, 0, 1, 1, 0]));
+	}
+}
@@ -0,0 +1,352 @@
+//! Readers and writers of the primitive encodings of the binary format.
+//!
+//! Meant for encoding and decoding custom sections and other payloads this crate
+//! does not know about, on top of byte slices and vectors. Everything implementing
+//! `Deserialize` or `Serialize`, like `ResizableLimits`, `ValueType` or whole
+//! sections, can be read and written along with the primitives.
+
+use alloc::{string::String, vec::Vec};
+use core::convert::TryFrom;
+use crate::io;
+use super::{
+	CountedList, CountedListWriter, Deserialize, Error, Serialize, Uint32, Uint64, Uint8, VarInt32,
+	VarInt64, VarUint32, VarUint64,
+};
+
+#[cfg(feature = "reduced-stack-buffer")]
+const SUBSECTION_BUFFER_LENGTH: usize = 256;
+
+#[cfg(not(feature = "reduced-stack-buffer"))]
+const SUBSECTION_BUFFER_LENGTH: usize = 16384;
+
+/// Reader of encoded values from a byte slice.
+///
+/// Reads which fail consume nothing.
+///
+/// # Examples
+///
+/// ```
+/// use parity_wasm::elements::codec::Reader;
+///
+/// let mut reader = Reader::new(&[0xe5, 0x8e, 0x26, 0x7f, 2, b'o', b'k']);
+/// assert_eq!(reader.var_u32().unwrap(), 624485);
+/// assert_eq!(reader.var_i32().unwrap(), -1);
+/// assert_eq!(reader.string().unwrap(), "ok");
+/// assert!(reader.is_empty());
+/// ```
+pub struct Reader<'a> {
+	bytes: &'a [u8],
+	position: usize,
+}
+
+impl<'a> Reader<'a> {
+	/// Reader starting at the beginning of `bytes`.
+	pub fn new(bytes: &'a [u8]) -> Self {
+		Reader { bytes, position: 0 }
+	}
+
+	/// Number of bytes read so far.
+	pub fn position(&self) -> usize {
+		self.position
+	}
+
+	/// Bytes which are not read yet.
+	pub fn remaining(&self) -> &'a [u8] {
+		&self.bytes[self.position..]
+	}
+
+	/// Whether every byte is read.
+	pub fn is_empty(&self) -> bool {
+		self.remaining().is_empty()
+	}
+
+	/// Read any value of the binary format.
+	pub fn read<T: Deserialize<Error=Error>>(&mut self) -> Result<T, Error> {
+		let mut cursor = io::Cursor::new(self.remaining());
+		let value = T::deserialize(&mut cursor)?;
+		self.position += cursor.position();
+		Ok(value)
+	}
+
+	/// Read a vector of values, prefixed with their count.
+	pub fn vec<T: Deserialize<Error=Error>>(&mut self) -> Result<Vec<T>, Error> {
+		Ok(self.read::<CountedList<T>>()?.into_inner())
+	}
+
+	/// Read a byte.
+	pub fn u8(&mut self) -> Result<u8, Error> {
+		Ok(self.read::<Uint8>()?.into())
+	}
+
+	/// Read a little endian `u32`.
+	pub fn u32(&mut self) -> Result<u32, Error> {
+		Ok(self.read::<Uint32>()?.into())
+	}
+
+	/// Read a little endian `u64`.
+	pub fn u64(&mut self) -> Result<u64, Error> {
+		Ok(self.read::<Uint64>()?.into())
+	}
+
+	/// Read an unsigned LEB128 `u32`.
+	pub fn var_u32(&mut self) -> Result<u32, Error> {
+		Ok(self.read::<VarUint32>()?.into())
+	}
+
+	/// Read an unsigned LEB128 `u64`.
+	pub fn var_u64(&mut self) -> Result<u64, Error> {
+		Ok(self.read::<VarUint64>()?.into())
+	}
+
+	/// Read a signed LEB128 `i32`.
+	pub fn var_i32(&mut self) -> Result<i32, Error> {
+		Ok(self.read::<VarInt32>()?.into())
+	}
+
+	/// Read a signed LEB128 `i64`.
+	pub fn var_i64(&mut self) -> Result<i64, Error> {
+		Ok(self.read::<VarInt64>()?.into())
+	}
+
+	/// Read a UTF-8 string, prefixed with its length.
+	pub fn string(&mut self) -> Result<String, Error> {
+		self.read()
+	}
+
+	/// Read bytes, prefixed with their length, like the payload of a subsection.
+	pub fn bytes(&mut self) -> Result<&'a [u8], Error> {
+		let start = self.position;
+		let len = self.var_u32()? as usize;
+		let remaining = self.remaining();
+		if len > remaining.len() {
+			self.position = start;
+			return Err(Error::UnexpectedEof);
+		}
+		self.position += len;
+		Ok(&remaining[..len])
+	}
+
+	/// Read a subsection, as in the linking and dylink sections: its type, and its
+	/// payload prefixed with its length.
+	pub fn subsection(&mut self) -> Result<(u8, &'a [u8]), Error> {
+		let start = self.position;
+		let id = self.u8()?;
+		match self.bytes() {
+			Ok(payload) => Ok((id, payload)),
+			Err(e) => {
+				self.position = start;
+				Err(e)
+			},
+		}
+	}
+}
+
+/// Read subsections until the input runs out, which it may only do between them.
+pub(crate) fn read_subsections<R: io::Read>(rdr: &mut R) -> Result<Vec<(u8, Vec<u8>)>, Error> {
+	let mut subsections = Vec::new();
+	loop {
+		let id = match Uint8::deserialize(rdr) {
+			Ok(id) => id.into(),
+			Err(Error::UnexpectedEof) => return Ok(subsections),
+			Err(e) => return Err(e),
+		};
+		let length: usize = VarUint32::deserialize(rdr)?.into();
+		subsections.push((id, buffered_read!(SUBSECTION_BUFFER_LENGTH, length, rdr)));
+	}
+}
+
+/// Decode the payload of a subsection with `parse`, which must read all of it.
+pub(crate) fn parse_subsection<'a, T, F>(payload: &'a [u8], parse: F) -> Result<T, Error>
+	where F: FnOnce(&mut Reader<'a>) -> Result<T, Error>
+{
+	let mut reader = Reader::new(payload);
+	let value = parse(&mut reader)?;
+	if !reader.is_empty() {
+		return Err(io::Error::InvalidData.into());
+	}
+	Ok(value)
+}
+
+/// Writer of encoded values into a byte vector.
+///
+/// # Examples
+///
+/// ```
+/// use parity_wasm::elements::codec::Writer;
+///
+/// let mut payload = Writer::new();
+/// payload.var_u32(624485).string("ok").expect("length to fit in a u32");
+/// let mut subsection = Writer::new();
+/// subsection.u8(1).bytes(&payload.into_inner()).expect("length to fit in a u32");
+/// assert_eq!(subsection.into_inner(), [1, 6, 0xe5, 0x8e, 0x26, 2, b'o', b'k']);
+/// ```
+#[derive(Debug, Default)]
+pub struct Writer {
+	buffer: Vec<u8>,
+}
+
+impl Writer {
+	/// Empty writer.
+	pub fn new() -> Self {
+		Writer::default()
+	}
+
+	/// Bytes written so far.
+	pub fn as_slice(&self) -> &[u8] {
+		&self.buffer
+	}
+
+	/// Bytes written.
+	pub fn into_inner(self) -> Vec<u8> {
+		self.buffer
+	}
+
+	/// Write any value of the binary format.
+	pub fn write<T: Serialize<Error=Error>>(&mut self, value: T) -> Result<&mut Self, Error> {
+		value.serialize(&mut self.buffer)?;
+		Ok(self)
+	}
+
+	/// Write a vector of values, prefixed with their count.
+	pub fn vec<T: Serialize<Error=Error>>(&mut self, values: Vec<T>) -> Result<&mut Self, Error> {
+		self.write(CountedListWriter(values.len(), values.into_iter()))
+	}
+
+	fn put<T: Serialize<Error=Error>>(&mut self, value: T) -> &mut Self {
+		value.serialize(&mut self.buffer).expect("writing primitives to a vector does not fail; qed");
+		self
+	}
+
+	/// Write a byte.
+	pub fn u8(&mut self, value: u8) -> &mut Self {
+		self.put(Uint8::from(value))
+	}
+
+	/// Write a little endian `u32`.
+	pub fn u32(&mut self, value: u32) -> &mut Self {
+		self.put(Uint32::from(value))
+	}
+
+	/// Write a little endian `u64`.
+	pub fn u64(&mut self, value: u64) -> &mut Self {
+		self.put(Uint64::from(value))
+	}
+
+	/// Write an unsigned LEB128 `u32`.
+	pub fn var_u32(&mut self, value: u32) -> &mut Self {
+		self.put(VarUint32::from(value))
+	}
+
+	/// Write an unsigned LEB128 `u64`.
+	pub fn var_u64(&mut self, value: u64) -> &mut Self {
+		self.put(VarUint64::from(value))
+	}
+
+	/// Write a signed LEB128 `i32`.
+	pub fn var_i32(&mut self, value: i32) -> &mut Self {
+		self.put(VarInt32::from(value))
+	}
+
+	/// Write a signed LEB128 `i64`.
+	pub fn var_i64(&mut self, value: i64) -> &mut Self {
+		self.put(VarInt64::from(value))
+	}
+
+	/// Write a string, prefixed with its length.
+	///
+	/// Fails if the string is longer than `u32::MAX` bytes.
+	pub fn string(&mut self, value: &str) -> Result<&mut Self, Error> {
+		self.bytes(value.as_bytes())
+	}
+
+	/// Write bytes, prefixed with their length, like the payload of a subsection.
+	///
+	/// Fails if there are more than `u32::MAX` bytes.
+	pub fn bytes(&mut self, value: &[u8]) -> Result<&mut Self, Error> {
+		let len = u32::try_from(value.len()).map_err(|_| io::Error::InvalidData)?;
+		self.var_u32(len);
+		self.buffer.extend_from_slice(value);
+		Ok(self)
+	}
+
+	/// Write a subsection, as in the linking and dylink sections: its type, and its
+	/// payload prefixed with its length.
+	///
+	/// Fails if the payload is longer than `u32::MAX` bytes.
+	pub fn subsection(&mut self, id: u8, payload: &[u8]) -> Result<&mut Self, Error> {
+		self.u8(id).bytes(payload)
+	}
+}
+
+/// Payload of a subsection holding a vector of `entries`.
+pub(crate) fn counted<T: Serialize<Error=Error>>(entries: Vec<T>) -> Result<Vec<u8>, Error> {
+	let mut payload = Writer::new();
+	payload.vec(entries)?;
+	Ok(payload.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Reader, Writer};
+	use crate::elements::{Error, ResizableLimits, ValueType};
+
+	#[test]
+	fn round_trip() {
+		let mut writer = Writer::new();
+		writer.u8(7).u32(0xdead_beef).u64(u64::MAX).var_u32(u32::MAX).var_u64(1 << 40)
+			.var_i32(i32::MIN).var_i64(-2).string("name").expect("string to be written")
+			.bytes(&[1, 2, 3]).expect("bytes to be written");
+		writer.write(ResizableLimits::new(1, Some(2))).expect("limits to serialize");
+		writer.vec(vec![ValueType::I32, ValueType::F64]).expect("value types to serialize");
+		let bytes = writer.into_inner();
+
+		let mut reader = Reader::new(&bytes);
+		assert_eq!(reader.u8().expect("byte"), 7);
+		assert_eq!(reader.u32().expect("u32"), 0xdead_beef);
+		assert_eq!(reader.u64().expect("u64"), u64::MAX);
+		assert_eq!(reader.var_u32().expect("var_u32"), u32::MAX);
+		assert_eq!(reader.var_u64().expect("var_u64"), 1 << 40);
+		assert_eq!(reader.var_i32().expect("var_i32"), i32::MIN);
+		assert_eq!(reader.var_i64().expect("var_i64"), -2);
+		assert_eq!(reader.string().expect("string"), "name");
+		assert_eq!(reader.bytes().expect("bytes"), &[1, 2, 3]);
+		assert_eq!(reader.read::<ResizableLimits>().expect("limits"), ResizableLimits::new(1, Some(2)));
+		assert_eq!(reader.vec::<ValueType>().expect("value types"), vec![ValueType::I32, ValueType::F64]);
+		assert!(reader.is_empty());
+		assert_eq!(reader.position(), bytes.len());
+	}
+
+	#[test]
+	fn truncated() {
+		let mut reader = Reader::new(&[0x80]);
+		assert!(reader.var_u32().is_err());
+		assert_eq!(reader.position(), 0);
+
+		let mut reader = Reader::new(&[0xff, 0xff, 0xff, 0xff, 0x0f, 0]);
+		assert!(matches!(reader.bytes(), Err(Error::UnexpectedEof)));
+
+		let mut reader = Reader::new(&[3, 1, 2, 3, 4]);
+		assert_eq!(reader.bytes().expect("bytes"), &[1, 2, 3]);
+		assert_eq!(reader.remaining(), &[4]);
+
+		let mut reader = Reader::new(&[8, 2, 1]);
+		assert!(matches!(reader.subsection(), Err(Error::UnexpectedEof)));
+		assert_eq!(reader.position(), 0);
+	}
+
+	#[test]
+	fn subsections() {
+		let mut writer = Writer::new();
+		writer.subsection(5, &[1, 2]).and_then(|writer| writer.subsection(6, &[])).expect("subsections to be written");
+		let bytes = writer.into_inner();
+
+		let mut reader = Reader::new(&bytes);
+		assert_eq!(reader.subsection().expect("subsection"), (5, &[1, 2][..]));
+		assert_eq!(reader.subsection().expect("subsection"), (6, &[][..]));
+		assert!(reader.is_empty());
+
+		assert_eq!(super::read_subsections(&mut &bytes[..]).expect("subsections"), vec![(5, vec![1, 2]), (6, vec![])]);
+		assert!(matches!(super::read_subsections(&mut &bytes[..3]), Err(Error::UnexpectedEof)));
+		assert!(super::parse_subsection(&[1, 2], Reader::u8).is_err());
+	}
+}
@@ -0,0 +1,121 @@
+use alloc::vec::Vec;
+use crate::io;
+use super::{Deserialize, Error, Instruction, Instructions, Serialize};
+
+/// Instructions kept in their binary encoding, decoded one by one on iteration.
+///
+/// An `Instruction` takes 16 bytes or more, plus heap data for `br_table`, while most
+/// encoded instructions take one to three bytes. Converting bodies which are only
+/// read now and then to this form, and back to `Instructions` to edit them, keeps
+/// large modules in a fraction of the memory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CompactInstructions {
+	bytes: Vec<u8>,
+	len: usize,
+}
+
+impl CompactInstructions {
+	/// Encode `instructions`.
+	///
+	/// Fails if any instruction does not decode back from its encoding, like a
+	/// `call_indirect` with a nonzero reserved byte, so that iteration never fails.
+	pub fn new(instructions: &Instructions) -> Result<Self, Error> {
+		let mut bytes = Vec::new();
+		for instruction in instructions.elements() {
+			let start = bytes.len();
+			instruction.clone().serialize(&mut bytes)?;
+			let mut reader = io::Cursor::new(&bytes[start..]);
+			Instruction::deserialize(&mut reader)?;
+			if start + reader.position() != bytes.len() {
+				return Err(io::Error::InvalidData.into());
+			}
+		}
+		bytes.shrink_to_fit();
+		Ok(CompactInstructions { bytes, len: instructions.elements().len() })
+	}
+
+	/// Number of instructions.
+	pub fn len(&self) -> usize { self.len }
+
+	/// Whether there are no instructions.
+	pub fn is_empty(&self) -> bool { self.len == 0 }
+
+	/// Encoded instructions, as in a function body.
+	pub fn bytes(&self) -> &[u8] { &self.bytes }
+
+	/// Decode the instructions one by one.
+	pub fn iter(&self) -> CompactIter<'_> {
+		CompactIter { reader: io::Cursor::new(&self.bytes[..]), remaining: self.len }
+	}
+
+	/// Decode all instructions.
+	pub fn to_instructions(&self) -> Instructions {
+		Instructions::new(self.iter().collect())
+	}
+}
+
+/// Iterator over the instructions of `CompactInstructions`.
+pub struct CompactIter<'a> {
+	reader: io::Cursor<&'a [u8]>,
+	remaining: usize,
+}
+
+impl<'a> Iterator for CompactIter<'a> {
+	type Item = Instruction;
+
+	fn next(&mut self) -> Option<Instruction> {
+		if self.remaining == 0 {
+			return None;
+		}
+		self.remaining -= 1;
+		Some(Instruction::deserialize(&mut self.reader)
+			.expect("bytes are encoded from instructions by `CompactInstructions::new`; qed"))
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		(self.remaining, Some(self.remaining))
+	}
+}
+
+impl<'a> ExactSizeIterator for CompactIter<'a> {}
+
+impl<'a> IntoIterator for &'a CompactInstructions {
+	type Item = Instruction;
+	type IntoIter = CompactIter<'a>;
+
+	fn into_iter(self) -> CompactIter<'a> {
+		self.iter()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::CompactInstructions;
+	use crate::elements::{BrTableData, BlockType, Error, Instruction::*, Instructions};
+
+	#[test]
+	fn round_trip() {
+		let instructions = Instructions::new(vec![
+			Block(BlockType::NoResult),
+			I32Const(-1),
+			BrTable(Box::new(BrTableData { table: vec![0, 0].into_boxed_slice(), default: 0 })),
+			End,
+			I64Const(i64::MIN),
+			Drop,
+			End,
+		]);
+		let compact = CompactInstructions::new(&instructions).expect("instructions to encode");
+		assert_eq!(compact.len(), 7);
+		assert_eq!(compact.iter().len(), 7);
+		assert_eq!(compact.to_instructions(), instructions);
+		assert!(compact.bytes().len() < core::mem::size_of_val(instructions.elements()));
+	}
+
+	#[test]
+	fn undecodable() {
+		let instructions = Instructions::new(vec![I32Const(0), CallIndirect(0, 1), End]);
+		assert!(matches!(CompactInstructions::new(&instructions), Err(Error::InvalidTableReference(1))));
+		let instructions = Instructions::new(vec![I32Const(1), GrowMemory(1), Drop, End]);
+		assert!(CompactInstructions::new(&instructions).is_err());
+	}
+}
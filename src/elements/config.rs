@@ -0,0 +1,311 @@
+use super::{
+	Error, Module, Section, Type, ValueType, BlockType, Instruction, External, ResizableLimits,
+};
+#[cfg(feature="exceptions")]
+use super::Internal;
+
+/// Set of proposals accepted when deserializing a module.
+///
+/// Cargo features decide which proposals the parser understands at all; this
+/// configuration narrows that set down at runtime, so that an embedder can pin the
+/// exact accepted feature set. A module using anything outside of it is rejected
+/// with `Error::FeatureDisabled`, naming the first offending proposal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeserializeConfig {
+	/// Accept sign-extension operators.
+	pub sign_ext: bool,
+	/// Accept SIMD operators and the `v128` value type.
+	pub simd: bool,
+	/// Accept bulk memory operators, passive segments and the data count section.
+	pub bulk_memory: bool,
+	/// Accept atomic operators and shared memories.
+	pub atomics: bool,
+	/// Accept functions with more than one result.
+	pub multi_value: bool,
+	/// Accept exception handling operators, tags and the tag section.
+	pub exceptions: bool,
+	/// Accept 64-bit memories.
+	pub memory64: bool,
+	/// Accept `add`, `sub` and `mul` of `i32` and `i64` in global initializers and
+	/// segment offsets.
+	pub extended_const: bool,
+}
+
+impl DeserializeConfig {
+	/// Configuration accepting only the MVP.
+	pub fn mvp() -> Self {
+		DeserializeConfig {
+			sign_ext: false,
+			simd: false,
+			bulk_memory: false,
+			atomics: false,
+			multi_value: false,
+			exceptions: false,
+			memory64: false,
+			extended_const: false,
+		}
+	}
+
+	/// Check that the module does not use any proposal disabled by this configuration.
+	pub fn check(&self, module: &Module) -> Result<(), Error> {
+		for section in module.sections() {
+			match *section {
+				Section::Type(ref type_section) => {
+					for Type::Function(func) in type_section.types() {
+						if func.results().len() > 1 {
+							self.require(self.multi_value, "multi_value")?;
+						}
+						for value_type in func.params().iter().chain(func.results()) {
+							self.check_value_type(*value_type)?;
+						}
+					}
+				},
+				Section::Import(ref import_section) => {
+					for entry in import_section.entries() {
+						match *entry.external() {
+							External::Function(_) => {},
+							External::Table(ref table) => self.check_limits(table.limits())?,
+							External::Memory(ref memory) => self.check_limits(memory.limits())?,
+							External::Global(ref global) => self.check_value_type(global.content_type())?,
+							#[cfg(feature="exceptions")]
+							External::Tag(_) => self.require(self.exceptions, "exceptions")?,
+						}
+					}
+				},
+				Section::Table(ref table_section) => {
+					for table in table_section.entries() {
+						self.check_limits(table.limits())?;
+					}
+				},
+				Section::Memory(ref memory_section) => {
+					for memory in memory_section.entries() {
+						self.check_limits(memory.limits())?;
+					}
+				},
+				#[cfg(feature="exceptions")]
+				Section::Tag(_) => self.require(self.exceptions, "exceptions")?,
+				Section::Global(ref global_section) => {
+					for entry in global_section.entries() {
+						self.check_value_type(entry.global_type().content_type())?;
+						self.check_init_expr(entry.init_expr().code())?;
+					}
+				},
+				#[cfg(feature="exceptions")]
+				Section::Export(ref export_section) => {
+					for entry in export_section.entries() {
+						if let Internal::Tag(_) = *entry.internal() {
+							self.require(self.exceptions, "exceptions")?;
+						}
+					}
+				},
+				Section::Element(ref element_section) => {
+					for segment in element_section.entries() {
+						#[cfg(feature="bulk")]
+						{
+							if segment.passive() {
+								self.require(self.bulk_memory, "bulk_memory")?;
+							}
+						}
+						if let Some(ref offset) = *segment.offset() {
+							self.check_init_expr(offset.code())?;
+						}
+					}
+				},
+				Section::DataCount(_) => self.require(self.bulk_memory, "bulk_memory")?,
+				Section::Code(ref code_section) => {
+					for body in code_section.bodies() {
+						for local in body.locals() {
+							self.check_value_type(local.value_type())?;
+						}
+						self.check_instructions(body.code().elements())?;
+					}
+				},
+				Section::Data(ref data_section) => {
+					for segment in data_section.entries() {
+						#[cfg(feature="bulk")]
+						{
+							if segment.passive() {
+								self.require(self.bulk_memory, "bulk_memory")?;
+							}
+						}
+						if let Some(ref offset) = *segment.offset() {
+							self.check_init_expr(offset.code())?;
+						}
+					}
+				},
+				_ => {},
+			}
+		}
+		Ok(())
+	}
+
+	fn require(&self, enabled: bool, feature: &'static str) -> Result<(), Error> {
+		if enabled {
+			Ok(())
+		} else {
+			Err(Error::FeatureDisabled(feature))
+		}
+	}
+
+	fn check_value_type(&self, value_type: ValueType) -> Result<(), Error> {
+		match value_type {
+			#[cfg(feature="simd")]
+			ValueType::V128 => self.require(self.simd, "simd"),
+			_ => Ok(()),
+		}
+	}
+
+	fn check_limits(&self, _limits: &ResizableLimits) -> Result<(), Error> {
+		#[cfg(feature="atomics")]
+		{
+			if _limits.shared() {
+				self.require(self.atomics, "atomics")?;
+			}
+		}
+		#[cfg(feature="memory64")]
+		{
+			if _limits.is_64() {
+				self.require(self.memory64, "memory64")?;
+			}
+		}
+		Ok(())
+	}
+
+	fn check_init_expr(&self, instructions: &[Instruction]) -> Result<(), Error> {
+		for instruction in instructions {
+			match *instruction {
+				Instruction::I32Add | Instruction::I32Sub | Instruction::I32Mul |
+				Instruction::I64Add | Instruction::I64Sub | Instruction::I64Mul =>
+					self.require(self.extended_const, "extended_const")?,
+				_ => {},
+			}
+		}
+		self.check_instructions(instructions)
+	}
+
+	fn check_instructions(&self, instructions: &[Instruction]) -> Result<(), Error> {
+		for instruction in instructions {
+			match *instruction {
+				Instruction::Block(BlockType::Value(value_type)) |
+				Instruction::Loop(BlockType::Value(value_type)) |
+				Instruction::If(BlockType::Value(value_type)) => self.check_value_type(value_type)?,
+				#[cfg(feature="atomics")]
+				Instruction::Atomics(_) => self.require(self.atomics, "atomics")?,
+				#[cfg(feature="simd")]
+				Instruction::Simd(_) => self.require(self.simd, "simd")?,
+				#[cfg(feature="sign_ext")]
+				Instruction::SignExt(_) => self.require(self.sign_ext, "sign_ext")?,
+				#[cfg(feature="bulk")]
+				Instruction::Bulk(_) => self.require(self.bulk_memory, "bulk_memory")?,
+				#[cfg(feature="exceptions")]
+				Instruction::Exceptions(_) => self.require(self.exceptions, "exceptions")?,
+				_ => {},
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Default for DeserializeConfig {
+	/// Accept every proposal this build of the crate understands.
+	fn default() -> Self {
+		DeserializeConfig {
+			sign_ext: cfg!(feature="sign_ext"),
+			simd: cfg!(feature="simd"),
+			bulk_memory: cfg!(feature="bulk"),
+			atomics: cfg!(feature="atomics"),
+			multi_value: cfg!(feature="multi_value"),
+			exceptions: cfg!(feature="exceptions"),
+			memory64: cfg!(feature="memory64"),
+			extended_const: true,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::DeserializeConfig;
+	use super::super::{deserialize_buffer_with_config, Error, Section};
+	use crate::builder;
+
+	#[test]
+	fn mvp_module_accepted() {
+		let module = builder::module()
+			.function()
+				.signature().param().i32().build()
+				.body().build()
+				.build()
+			.build();
+		let bytes = module.to_bytes().expect("serialization to succeed");
+
+		assert!(deserialize_buffer_with_config(&bytes, &DeserializeConfig::mvp()).is_ok());
+	}
+
+	#[test]
+	fn data_count_rejected() {
+		let module = builder::module()
+			.with_section(Section::DataCount(0))
+			.build();
+		let bytes = module.to_bytes().expect("serialization to succeed");
+
+		match deserialize_buffer_with_config(&bytes, &DeserializeConfig::mvp()) {
+			Err(Error::FeatureDisabled("bulk_memory")) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+
+		let config = DeserializeConfig { bulk_memory: true, ..DeserializeConfig::mvp() };
+		assert!(deserialize_buffer_with_config(&bytes, &config).is_ok());
+	}
+
+	#[test]
+	fn extended_const() {
+		use super::super::{Instruction::*, InitExpr, ValueType};
+
+		// Offset relative to an imported base, as emitted for position independent code.
+		let module = builder::module()
+			.import().module("env").field("__memory_base").external().global(ValueType::I32, false).build()
+			.memory().with_min(1).build()
+			.data()
+				.offset_expr(InitExpr::new(vec![GetGlobal(0), I32Const(16), I32Add, End]))
+				.value(vec![1, 2, 3])
+				.build()
+			.build();
+		assert!(module.check_segment_offsets().is_ok());
+		let bytes = module.to_bytes().expect("serialization to succeed");
+
+		match deserialize_buffer_with_config(&bytes, &DeserializeConfig::mvp()) {
+			Err(Error::FeatureDisabled("extended_const")) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+		assert!(deserialize_buffer_with_config(&bytes, &DeserializeConfig::default()).is_ok());
+	}
+
+	#[cfg(feature="sign_ext")]
+	#[test]
+	fn sign_ext_rejected() {
+		use super::super::{Instruction, Instructions, SignExtInstruction};
+
+		let module = builder::module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						Instruction::I32Const(0),
+						Instruction::SignExt(SignExtInstruction::I32Extend8S),
+						Instruction::Drop,
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.build();
+		let bytes = module.to_bytes().expect("serialization to succeed");
+
+		match deserialize_buffer_with_config(&bytes, &DeserializeConfig::mvp()) {
+			Err(Error::FeatureDisabled("sign_ext")) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+
+		let config = DeserializeConfig { sign_ext: true, ..DeserializeConfig::mvp() };
+		assert!(deserialize_buffer_with_config(&bytes, &config).is_ok());
+	}
+}
@@ -0,0 +1,250 @@
+use alloc::vec::Vec;
+use core::{convert::TryFrom, fmt};
+use super::{InitExpr, Instruction, Module, External, ValueType};
+
+/// Value of a constant expression.
+///
+/// Floats are kept as their bit patterns, as in `Instruction::F32Const`, so that
+/// NaN payloads survive evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuntimeValue {
+	/// 32-bit integer.
+	I32(i32),
+	/// 64-bit integer.
+	I64(i64),
+	/// 32-bit float, as bits.
+	F32(u32),
+	/// 64-bit float, as bits.
+	F64(u64),
+}
+
+impl RuntimeValue {
+	/// Type of the value.
+	pub fn value_type(&self) -> ValueType {
+		match *self {
+			RuntimeValue::I32(_) => ValueType::I32,
+			RuntimeValue::I64(_) => ValueType::I64,
+			RuntimeValue::F32(_) => ValueType::F32,
+			RuntimeValue::F64(_) => ValueType::F64,
+		}
+	}
+}
+
+macro_rules! impl_conversions {
+	($($ty:ty => $variant:ident, |$from:ident| $into:expr, |$back:ident| $out:expr;)*) => {$(
+		impl From<$ty> for RuntimeValue {
+			fn from($from: $ty) -> Self {
+				RuntimeValue::$variant($into)
+			}
+		}
+
+		/// Fails with the value itself if it is of another type.
+		impl TryFrom<RuntimeValue> for $ty {
+			type Error = RuntimeValue;
+
+			fn try_from(value: RuntimeValue) -> Result<Self, RuntimeValue> {
+				match value {
+					RuntimeValue::$variant($back) => Ok($out),
+					other => Err(other),
+				}
+			}
+		}
+	)*};
+}
+
+impl_conversions! {
+	i32 => I32, |value| value, |value| value;
+	u32 => I32, |value| value as i32, |value| value as u32;
+	i64 => I64, |value| value, |value| value;
+	u64 => I64, |value| value as i64, |value| value as u64;
+	f32 => F32, |value| value.to_bits(), |bits| f32::from_bits(bits);
+	f64 => F64, |value| value.to_bits(), |bits| f64::from_bits(bits);
+}
+
+/// Error of evaluating a constant expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstEvalError {
+	/// Instruction is not allowed in constant expressions.
+	UnsupportedInstruction(Instruction),
+	/// Global is unknown, or its value is not available to the expression.
+	UnknownGlobal(u32),
+	/// Global is mutable, so it has no value before instantiation.
+	MutableGlobal(u32),
+	/// Operands are of the wrong type, or missing.
+	TypeMismatch,
+	/// Expression leaves other than exactly one value on the stack.
+	InvalidResult,
+	/// Number of values given for imported globals does not match the imports.
+	ImportCountMismatch,
+}
+
+impl fmt::Display for ConstEvalError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ConstEvalError::UnsupportedInstruction(ref instruction) =>
+				write!(f, "Instruction {} is not constant", instruction),
+			ConstEvalError::UnknownGlobal(index) => write!(f, "Global {} is not available", index),
+			ConstEvalError::MutableGlobal(index) => write!(f, "Global {} is mutable", index),
+			ConstEvalError::TypeMismatch => write!(f, "Operand type mismatch"),
+			ConstEvalError::InvalidResult => write!(f, "Expression does not produce exactly one value"),
+			ConstEvalError::ImportCountMismatch => write!(f, "Values do not match the imported globals"),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for ConstEvalError {}
+
+impl InitExpr {
+	/// Evaluate the expression, getting values of globals from `global`.
+	///
+	/// Supported are the constants, `get_global` and the integer `add`, `sub` and
+	/// `mul` of the extended constant expressions. `global` returns `None` for globals
+	/// the expression may not refer to; `Module::global_values` provides the usual rules.
+	pub fn evaluate<F>(&self, mut global: F) -> Result<RuntimeValue, ConstEvalError>
+		where F: FnMut(u32) -> Option<RuntimeValue>
+	{
+		let mut stack = Vec::new();
+		let binary_i32 = |stack: &mut Vec<RuntimeValue>, op: fn(i32, i32) -> i32| {
+			match (stack.pop(), stack.pop()) {
+				(Some(RuntimeValue::I32(rhs)), Some(RuntimeValue::I32(lhs))) => {
+					stack.push(RuntimeValue::I32(op(lhs, rhs)));
+					Ok(())
+				},
+				_ => Err(ConstEvalError::TypeMismatch),
+			}
+		};
+		let binary_i64 = |stack: &mut Vec<RuntimeValue>, op: fn(i64, i64) -> i64| {
+			match (stack.pop(), stack.pop()) {
+				(Some(RuntimeValue::I64(rhs)), Some(RuntimeValue::I64(lhs))) => {
+					stack.push(RuntimeValue::I64(op(lhs, rhs)));
+					Ok(())
+				},
+				_ => Err(ConstEvalError::TypeMismatch),
+			}
+		};
+
+		for instruction in self.code() {
+			match *instruction {
+				Instruction::I32Const(value) => stack.push(RuntimeValue::I32(value)),
+				Instruction::I64Const(value) => stack.push(RuntimeValue::I64(value)),
+				Instruction::F32Const(bits) => stack.push(RuntimeValue::F32(bits)),
+				Instruction::F64Const(bits) => stack.push(RuntimeValue::F64(bits)),
+				Instruction::GetGlobal(index) =>
+					stack.push(global(index).ok_or(ConstEvalError::UnknownGlobal(index))?),
+				Instruction::I32Add => binary_i32(&mut stack, i32::wrapping_add)?,
+				Instruction::I32Sub => binary_i32(&mut stack, i32::wrapping_sub)?,
+				Instruction::I32Mul => binary_i32(&mut stack, i32::wrapping_mul)?,
+				Instruction::I64Add => binary_i64(&mut stack, i64::wrapping_add)?,
+				Instruction::I64Sub => binary_i64(&mut stack, i64::wrapping_sub)?,
+				Instruction::I64Mul => binary_i64(&mut stack, i64::wrapping_mul)?,
+				Instruction::End => break,
+				ref other => return Err(ConstEvalError::UnsupportedInstruction(other.clone())),
+			}
+		}
+
+		match stack.len() {
+			1 => Ok(stack[0]),
+			_ => Err(ConstEvalError::InvalidResult),
+		}
+	}
+}
+
+/// Values of every global of `module`, given the values of its imported globals.
+pub(crate) fn global_values(module: &Module, imported: &[RuntimeValue]) -> Result<Vec<RuntimeValue>, ConstEvalError> {
+	let mut values = Vec::new();
+	let mut mutable = Vec::new();
+	let imported_types = module.import_section().map(|s| s.entries()).unwrap_or(&[]).iter()
+		.filter_map(|entry| match *entry.external() {
+			External::Global(ref global_type) => Some(global_type),
+			_ => None,
+		});
+	let mut imported_values = imported.iter();
+	for global_type in imported_types {
+		let value = *imported_values.next().ok_or(ConstEvalError::ImportCountMismatch)?;
+		if value.value_type() != global_type.content_type() {
+			return Err(ConstEvalError::TypeMismatch);
+		}
+		values.push(value);
+		mutable.push(global_type.is_mutable());
+	}
+	if imported_values.next().is_some() {
+		return Err(ConstEvalError::ImportCountMismatch);
+	}
+
+	// Globals may refer to any global before them; mutable ones have no value yet.
+	let mut error = None;
+	for entry in module.global_section().map(|s| s.entries()).unwrap_or(&[]) {
+		let value = entry.init_expr().evaluate(|index| {
+			match mutable.get(index as usize) {
+				Some(&true) => { error = Some(ConstEvalError::MutableGlobal(index)); None },
+				Some(&false) => values.get(index as usize).cloned(),
+				None => None,
+			}
+		});
+		let value = match (value, error.take()) {
+			(_, Some(error)) => return Err(error),
+			(value, None) => value?,
+		};
+		if value.value_type() != entry.global_type().content_type() {
+			return Err(ConstEvalError::TypeMismatch);
+		}
+		values.push(value);
+		mutable.push(entry.global_type().is_mutable());
+	}
+	Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+	use core::convert::TryFrom;
+	use super::{ConstEvalError, RuntimeValue};
+	use crate::builder;
+	use crate::elements::{InitExpr, Instruction::*};
+
+	#[test]
+	fn extended_const() {
+		let expr = InitExpr::new(vec![GetGlobal(0), I32Const(16), I32Mul, I32Const(4), I32Add, End]);
+		assert_eq!(expr.evaluate(|_| Some(RuntimeValue::I32(2))), Ok(RuntimeValue::I32(36)));
+		assert_eq!(expr.evaluate(|_| None), Err(ConstEvalError::UnknownGlobal(0)));
+
+		let expr = InitExpr::new(vec![I32Const(1), I64Const(1), I64Add, End]);
+		assert_eq!(expr.evaluate(|_| None), Err(ConstEvalError::TypeMismatch));
+		let expr = InitExpr::new(vec![I32Const(1), I32Const(1), End]);
+		assert_eq!(expr.evaluate(|_| None), Err(ConstEvalError::InvalidResult));
+	}
+
+	#[test]
+	fn conversions() {
+		assert_eq!(RuntimeValue::from(u32::MAX), RuntimeValue::I32(-1));
+		assert_eq!(u64::try_from(RuntimeValue::I64(-1)), Ok(u64::MAX));
+		assert_eq!(i32::try_from(RuntimeValue::I64(1)), Err(RuntimeValue::I64(1)));
+
+		// NaN payloads survive the round trip through bits.
+		let nan = f32::from_bits(0x7fc0_0001);
+		assert_eq!(RuntimeValue::from(nan), RuntimeValue::F32(0x7fc0_0001));
+		assert_eq!(f32::try_from(RuntimeValue::F32(0x7fc0_0001)).map(f32::to_bits), Ok(0x7fc0_0001));
+		assert_eq!(f64::try_from(RuntimeValue::from(1.5f64)), Ok(1.5));
+	}
+
+	#[test]
+	fn global_values() {
+		let module = builder::module()
+			.import().module("env").field("base").external().global(crate::elements::ValueType::I32, false).build()
+			.import().module("env").field("counter").external().global(crate::elements::ValueType::I32, true).build()
+			.global().init_global(0).build()
+			.global().init_expr(GetGlobal(2)).build()
+			.build();
+		assert_eq!(
+			module.global_values(&[RuntimeValue::I32(8), RuntimeValue::I32(0)]),
+			Ok(vec![RuntimeValue::I32(8), RuntimeValue::I32(0), RuntimeValue::I32(8), RuntimeValue::I32(8)]),
+		);
+		assert_eq!(module.global_values(&[RuntimeValue::I32(8)]), Err(ConstEvalError::ImportCountMismatch));
+
+		let module = builder::module()
+			.import().module("env").field("counter").external().global(crate::elements::ValueType::I32, true).build()
+			.global().init_global(0).build()
+			.build();
+		assert_eq!(module.global_values(&[RuntimeValue::I32(0)]), Err(ConstEvalError::MutableGlobal(0)));
+	}
+}
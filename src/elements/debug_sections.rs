@@ -0,0 +1,247 @@
+use alloc::{string::String, vec::Vec};
+use crate::io;
+use super::{CustomSection, Deserialize, Error, Module, Section, serialize};
+
+/// Prefix of the names of DWARF custom sections.
+const DEBUG_PREFIX: &str = ".debug_";
+
+/// Name of the custom section holding the URL of the module's source map.
+const SOURCE_MAPPING_URL: &str = "sourceMappingURL";
+
+/// Whether `name` is the name of a DWARF custom section.
+pub(crate) fn is_debug_section(name: &str) -> bool {
+	name.starts_with(DEBUG_PREFIX)
+}
+
+impl Module {
+	/// DWARF custom sections, those named `.debug_*`, in module order.
+	pub fn debug_sections(&self) -> impl Iterator<Item=&CustomSection> {
+		self.custom_sections().filter(|section| is_debug_section(section.name()))
+	}
+
+	/// Remove the DWARF custom sections and return them, in module order.
+	pub fn take_debug_sections(&mut self) -> Vec<CustomSection> {
+		let mut taken = Vec::new();
+		let sections = core::mem::take(self.sections_mut());
+		for section in sections {
+			match section {
+				Section::Custom(custom) if is_debug_section(custom.name()) => taken.push(custom),
+				other => self.sections_mut().push(other),
+			}
+		}
+		taken
+	}
+
+	/// Replace the DWARF custom sections with `sections`, which are added at the end of the module.
+	pub fn set_debug_sections(&mut self, sections: Vec<CustomSection>) {
+		self.take_debug_sections();
+		self.sections_mut().extend(sections.into_iter().map(Section::Custom));
+	}
+
+	/// URL of the source map, from the `sourceMappingURL` custom section, if any.
+	pub fn source_mapping_url(&self) -> Result<Option<String>, Error> {
+		let section = match self.custom_sections().find(|section| section.name() == SOURCE_MAPPING_URL) {
+			Some(section) => section,
+			None => return Ok(None),
+		};
+		let mut payload = io::Cursor::new(section.payload());
+		let url = String::deserialize(&mut payload)?;
+		if payload.position() != section.payload().len() {
+			return Err(Error::Other("trailing bytes in sourceMappingURL section"));
+		}
+		Ok(Some(url))
+	}
+
+	/// Set the URL of the source map, replacing an existing `sourceMappingURL` section in place.
+	pub fn set_source_mapping_url(&mut self, url: impl Into<String>) -> Result<(), Error> {
+		let payload = serialize(url.into())?;
+		self.set_custom_section(SOURCE_MAPPING_URL, payload);
+		Ok(())
+	}
+
+	/// Rewrite the URL of the source map with `f`, as when a transformation writes the
+	/// module and its updated source map under new names.
+	///
+	/// Returns whether the module has a `sourceMappingURL` section; without one nothing is changed.
+	pub fn update_source_mapping_url<F: FnOnce(&str) -> String>(&mut self, f: F) -> Result<bool, Error> {
+		match self.source_mapping_url()? {
+			Some(url) => {
+				self.set_source_mapping_url(f(&url))?;
+				Ok(true)
+			},
+			None => Ok(false),
+		}
+	}
+}
+
+/// Translation of code offsets between a module and a transformed copy of it.
+///
+/// Offsets are relative to the start of the code section payload, as DWARF uses
+/// them. Only the offsets of bodies left unchanged by the transformation, and of
+/// their instructions, can be translated; rewriting the DWARF payloads themselves
+/// is up to a DWARF library.
+///
+/// Both modules need code locations, as recorded by `deserialize_buffer_with_locations`.
+/// Serialize the transformed module and decode it again to get them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeOffsetMap {
+	/// Pairs of old and new offsets, sorted by old offset.
+	offsets: Vec<(usize, usize)>,
+}
+
+/// Offset of the code section payload in the module, as recorded with the first body's locations.
+fn code_section_base(module: &Module) -> Option<usize> {
+	Some(module.code_section()?.bodies().first()?.code_locations()?.section())
+}
+
+impl CodeOffsetMap {
+	/// Map offsets of `before` to those of `after`, matching bodies by function index.
+	pub fn new(before: &Module, after: &Module) -> Self {
+		let (old_base, new_base) = match (code_section_base(before), code_section_base(after)) {
+			(Some(old_base), Some(new_base)) => (old_base, new_base),
+			_ => return CodeOffsetMap::default(),
+		};
+		let old_bodies = before.code_section().map(|s| s.bodies()).unwrap_or(&[]);
+		let new_bodies = after.code_section().map(|s| s.bodies()).unwrap_or(&[]);
+
+		let mut offsets = Vec::new();
+		for (old_body, new_body) in old_bodies.iter().zip(new_bodies) {
+			let (old_locations, new_locations) = match (old_body.code_locations(), new_body.code_locations()) {
+				(Some(old_locations), Some(new_locations)) if old_body == new_body => (old_locations, new_locations),
+				_ => continue,
+			};
+			offsets.push((old_locations.body() - old_base, new_locations.body() - new_base));
+			for (old, new) in old_locations.instructions().iter().zip(new_locations.instructions()) {
+				offsets.push((old - old_base, new - new_base));
+			}
+		}
+		offsets.sort_unstable();
+		CodeOffsetMap { offsets }
+	}
+
+	/// New offset of the body or instruction at `offset`, if it was kept.
+	pub fn translate(&self, offset: usize) -> Option<usize> {
+		self.offsets
+			.binary_search_by_key(&offset, |&(old, _)| old)
+			.ok()
+			.map(|index| self.offsets[index].1)
+	}
+
+	/// Number of offsets that can be translated.
+	pub fn len(&self) -> usize {
+		self.offsets.len()
+	}
+
+	/// Whether no offset can be translated.
+	pub fn is_empty(&self) -> bool {
+		self.offsets.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::CodeOffsetMap;
+	use crate::builder;
+	use crate::elements::{deserialize_buffer_with_locations, serialize, Instruction::*, Instructions, Module};
+
+	fn module(first: Vec<crate::elements::Instruction>) -> Module {
+		builder::module()
+			.function().signature().build().body().with_instructions(Instructions::new(first)).build().build()
+			.function().signature().build().body().with_instructions(Instructions::new(vec![Nop, End])).build().build()
+			.build()
+	}
+
+	#[test]
+	fn take_and_set() {
+		let mut module = module(vec![End]);
+		module.set_custom_section(".debug_info", vec![1]);
+		module.set_custom_section("sourceMappingURL", vec![2]);
+		module.set_custom_section(".debug_line", vec![3]);
+
+		let taken = module.take_debug_sections();
+		assert_eq!(taken.iter().map(|section| section.name()).collect::<Vec<_>>(), vec![".debug_info", ".debug_line"]);
+		assert_eq!(module.debug_sections().count(), 0);
+		module.set_debug_sections(taken);
+		assert_eq!(module.debug_sections().count(), 2);
+		assert_eq!(module.custom_sections().count(), 3);
+	}
+
+	#[test]
+	fn source_mapping_url() {
+		let mut module = module(vec![End]);
+		assert_eq!(module.source_mapping_url().expect("no section to parse"), None);
+		assert!(!module.update_source_mapping_url(|url| url.into()).expect("no section to parse"));
+
+		module.set_source_mapping_url("app.wasm.map").expect("url to serialize");
+		let bytes = serialize(module).expect("module to serialize");
+		let mut module: Module = crate::elements::deserialize_buffer(&bytes).expect("module to deserialize");
+		assert_eq!(module.source_mapping_url().expect("url to parse").as_deref(), Some("app.wasm.map"));
+
+		let updated = module.update_source_mapping_url(|url| format!("https://example.com/{}", url));
+		assert!(updated.expect("url to parse"));
+		assert_eq!(module.source_mapping_url().expect("url to parse").as_deref(), Some("https://example.com/app.wasm.map"));
+		assert_eq!(module.custom_sections().count(), 1);
+	}
+
+	#[test]
+	fn offset_map() {
+		let before = serialize(module(vec![End])).expect("module to serialize");
+		let after = serialize(module(vec![Nop, Nop, End])).expect("module to serialize");
+		let before = deserialize_buffer_with_locations(&before).expect("module to deserialize");
+		let after = deserialize_buffer_with_locations(&after).expect("module to deserialize");
+
+		// Payload: body count, then the first body of 3 bytes before and 5 bytes after.
+		let map = CodeOffsetMap::new(&before, &after);
+		assert_eq!(map.len(), 3);
+		assert_eq!(map.translate(1), None);
+		assert_eq!(map.translate(5), Some(7));
+		assert_eq!(map.translate(6), Some(8));
+	}
+
+	/// Five-byte LEB128 encoding of `value`, as some linkers emit to patch sizes in place.
+	fn padded(value: u8) -> Vec<u8> {
+		assert!(value < 0x80);
+		vec![value | 0x80, 0x80, 0x80, 0x80, 0x00]
+	}
+
+	/// `bytes` with the size prefixes and body count of the code section padded.
+	fn pad_code_section(bytes: &[u8]) -> Vec<u8> {
+		let mut padded_bytes = bytes[..8].to_vec();
+		let mut position = 8;
+		while position < bytes.len() {
+			let (id, length) = (bytes[position], bytes[position + 1] as usize);
+			let payload = &bytes[position + 2..position + 2 + length];
+			position += 2 + length;
+			if id != 10 {
+				padded_bytes.extend_from_slice(&bytes[position - 2 - length..position]);
+				continue;
+			}
+			let mut code = padded(payload[0]);
+			let mut body = 1;
+			while body < payload.len() {
+				let length = payload[body] as usize;
+				code.extend(padded(payload[body]));
+				code.extend_from_slice(&payload[body + 1..body + 1 + length]);
+				body += 1 + length;
+			}
+			padded_bytes.push(id);
+			padded_bytes.extend(padded(code.len() as u8));
+			padded_bytes.extend(code);
+		}
+		padded_bytes
+	}
+
+	#[test]
+	fn offset_map_padded_sizes() {
+		let before = pad_code_section(&serialize(module(vec![End])).expect("module to serialize"));
+		let after = serialize(module(vec![Nop, Nop, End])).expect("module to serialize");
+		let before = deserialize_buffer_with_locations(&before).expect("module to deserialize");
+		let after = deserialize_buffer_with_locations(&after).expect("module to deserialize");
+
+		// The second body now starts past a padded count, two padded sizes and the first body.
+		let map = CodeOffsetMap::new(&before, &after);
+		assert_eq!(map.len(), 3);
+		assert_eq!(map.translate(17), Some(7));
+		assert_eq!(map.translate(18), Some(8));
+	}
+}
@@ -0,0 +1,278 @@
+use core::fmt;
+use crate::io;
+use super::{
+	Deserialize, Error, Module, Section, VarUint7, VarUint32, Type, ImportEntry, Func, TableType,
+	MemoryType, GlobalEntry, ExportEntry, ElementSegment, FuncBody, DataSegment,
+	deserialize_buffer,
+};
+use super::module::{ModuleReader, check_section_order};
+
+/// Deserialization error together with where in the input it happened.
+#[derive(Debug, Clone)]
+pub struct DetailedError {
+	error: Error,
+	offset: Option<usize>,
+	section: Option<u8>,
+	entry: Option<usize>,
+	expected: Option<&'static str>,
+	actual: Option<u8>,
+}
+
+impl DetailedError {
+	/// The error itself, as returned by `deserialize_buffer`.
+	pub fn error(&self) -> &Error { &self.error }
+
+	/// Unwrap the error itself.
+	pub fn into_error(self) -> Error { self.error }
+
+	/// Offset of the read which failed, if the error is tied to a position in the input.
+	pub fn offset(&self) -> Option<usize> { self.offset }
+
+	/// Id of the section being parsed, if any.
+	pub fn section_id(&self) -> Option<u8> { self.section }
+
+	/// Index of the entry being parsed within its section, if any.
+	pub fn entry(&self) -> Option<usize> { self.entry }
+
+	/// What was expected in place of the rejected byte, if the error is about a single byte.
+	pub fn expected(&self) -> Option<&'static str> { self.expected }
+
+	/// Byte found at `offset` instead of the expected one, if the error is about a single byte.
+	pub fn actual(&self) -> Option<u8> { self.actual }
+}
+
+impl fmt::Display for DetailedError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.error)?;
+		if let Some(offset) = self.offset {
+			write!(f, " at offset {:#x}", offset)?;
+		}
+		if let Some(id) = self.section {
+			write!(f, " in {} section", section_name(id))?;
+		}
+		if let Some(entry) = self.entry {
+			write!(f, ", entry {}", entry)?;
+		}
+		if let Some(expected) = self.expected {
+			// The error itself reports the byte found instead.
+			write!(f, " (expected {})", expected)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for DetailedError {}
+
+pub(super) fn section_name(id: u8) -> &'static str {
+	match id {
+		0 => "custom",
+		1 => "type",
+		2 => "import",
+		3 => "function",
+		4 => "table",
+		5 => "memory",
+		6 => "global",
+		7 => "export",
+		8 => "start",
+		9 => "element",
+		10 => "code",
+		11 => "data",
+		12 => "data count",
+		13 => "tag",
+		_ => "unknown",
+	}
+}
+
+/// What a byte rejected with `error` should have been, if `error` is about a single byte.
+fn expected_byte(error: &Error) -> Option<&'static str> {
+	match *error {
+		Error::UnknownValueType(_) => Some("a value type"),
+		Error::UnknownTableElementType(_) => Some("a table element type"),
+		Error::UnknownExternalKind(_) | Error::UnknownInternalKind(_) => Some("an external kind"),
+		Error::UnknownOpcode(_) => Some("an opcode"),
+		Error::InvalidVarUint1(_) => Some("0x00 or 0x01"),
+		Error::InvalidSectionId(_) => Some("a section id"),
+		Error::InvalidMemoryReference(_) | Error::InvalidTableReference(_) => Some("0x00"),
+		Error::InvalidLimitsFlags(_) => Some("limits flags"),
+		Error::UnknownFunctionForm(_) => Some("0x60"),
+		_ => None,
+	}
+}
+
+/// Reader remembering where its last read started.
+struct Tracking<'a> {
+	contents: &'a [u8],
+	position: usize,
+	last_read: usize,
+}
+
+impl<'a> Tracking<'a> {
+	fn new(contents: &'a [u8]) -> Self {
+		Tracking { contents, position: 0, last_read: 0 }
+	}
+}
+
+impl<'a> io::Read for Tracking<'a> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+		self.last_read = self.position;
+		let remainder = &self.contents[self.position..];
+		if buf.len() > remainder.len() {
+			return Err(io::Error::UnexpectedEof);
+		}
+		buf.copy_from_slice(&remainder[..buf.len()]);
+		self.position += buf.len();
+		Ok(())
+	}
+}
+
+/// Where decoding fails, relative to the start of the module.
+#[derive(Default)]
+struct Location {
+	offset: Option<usize>,
+	section: Option<u8>,
+	entry: Option<usize>,
+}
+
+/// Find the entry of a section payload which fails to decode.
+fn locate_entry<T: Deserialize>(payload: &[u8], base: usize) -> (Option<usize>, usize) {
+	let mut reader = Tracking::new(payload);
+	let count: u32 = match VarUint32::deserialize(&mut reader) {
+		Ok(count) => count.into(),
+		Err(_) => return (None, base + reader.last_read),
+	};
+	for index in 0..count as usize {
+		if T::deserialize(&mut reader).is_err() {
+			return (Some(index), base + reader.last_read);
+		}
+	}
+	(None, base + reader.position)
+}
+
+fn locate(contents: &[u8]) -> Location {
+	let mut header = Tracking::new(contents);
+	if ModuleReader::new(&mut header).is_err() {
+		return Location { offset: Some(header.last_read), ..Default::default() };
+	}
+
+	let mut position = header.position;
+	let mut last_section_order = 0;
+	while position < contents.len() {
+		let mut reader = Tracking::new(&contents[position..]);
+		let (id, length): (u8, usize) = match (VarUint7::deserialize(&mut reader), VarUint32::deserialize(&mut reader)) {
+			(Ok(id), Ok(length)) => (id.into(), length.into()),
+			_ => return Location { offset: Some(position + reader.last_read), ..Default::default() },
+		};
+		let start = position + reader.position;
+		if length > contents.len() - start {
+			return Location { offset: Some(start), section: Some(id), entry: None };
+		}
+		let end = start + length;
+
+		let mut reader = io::Cursor::new(&contents[position..end]);
+		let section = match Section::deserialize(&mut reader) {
+			Ok(section) => section,
+			Err(_) => {
+				let payload = &contents[start..end];
+				let (entry, offset) = match id {
+					1 => locate_entry::<Type>(payload, start),
+					2 => locate_entry::<ImportEntry>(payload, start),
+					3 => locate_entry::<Func>(payload, start),
+					4 => locate_entry::<TableType>(payload, start),
+					5 => locate_entry::<MemoryType>(payload, start),
+					6 => locate_entry::<GlobalEntry>(payload, start),
+					7 => locate_entry::<ExportEntry>(payload, start),
+					9 => locate_entry::<ElementSegment>(payload, start),
+					10 => locate_entry::<FuncBody>(payload, start),
+					11 => locate_entry::<DataSegment>(payload, start),
+					_ => (None, position),
+				};
+				return Location { offset: Some(offset), section: Some(id), entry };
+			},
+		};
+		if check_section_order(&mut last_section_order, section.order()).is_err() {
+			return Location { offset: Some(position), section: Some(id), entry: None };
+		}
+		position = end;
+	}
+	Location::default()
+}
+
+/// Deserialize module from serialized bytes, locating the error if there is one.
+///
+/// Decoding is as fast as with `deserialize_buffer`; only once it fails, the input is
+/// scanned again to find the section, entry and offset at which it did.
+pub fn deserialize_buffer_detailed(contents: &[u8]) -> Result<Module, DetailedError> {
+	deserialize_buffer(contents).map_err(|error| {
+		let location = locate(contents);
+		let expected = expected_byte(&error);
+		let actual = match (expected, location.offset) {
+			(Some(_), Some(offset)) => contents.get(offset).cloned(),
+			_ => None,
+		};
+		DetailedError {
+			error,
+			offset: location.offset,
+			section: location.section,
+			entry: location.entry,
+			expected,
+			actual,
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::deserialize_buffer_detailed;
+	use crate::builder;
+	use crate::elements::{Error, ValueType};
+
+	#[test]
+	fn locates_bad_value_type() {
+		let mut bytes = builder::module()
+			.function()
+				.signature().with_params(vec![ValueType::I32, ValueType::I64]).build()
+				.body().build()
+				.build()
+			.function()
+				.signature().with_param(ValueType::F32).build()
+				.body().build()
+				.build()
+			.build()
+			.to_bytes()
+			.expect("serialization to succeed");
+
+		// Header, type section id and size, type count, then `0x60 0x02 0x7f 0x7e 0x00`
+		// for the first type and `0x60 0x01` for the second one.
+		let offset = 8 + 2 + 1 + 5 + 2;
+		assert_eq!(bytes[offset], 0x7d);
+		bytes[offset] = 0x7a;
+
+		let error = deserialize_buffer_detailed(&bytes).expect_err("module to be rejected");
+		match *error.error() {
+			// Value types are signed 7-bit integers.
+			Error::UnknownValueType(-6) => {},
+			ref other => panic!("unexpected error: {:?}", other),
+		}
+		assert_eq!(error.offset(), Some(offset));
+		assert_eq!(error.section_id(), Some(1));
+		assert_eq!(error.entry(), Some(1));
+		assert_eq!(error.expected(), Some("a value type"));
+		assert_eq!(error.actual(), Some(0x7a));
+		assert_eq!(
+			error.to_string(),
+			format!(
+				"Invalid or unknown value type 0x7a at offset {:#x} in type section, entry 1 (expected a value type)",
+				offset,
+			),
+		);
+	}
+
+	#[test]
+	fn locates_bad_magic() {
+		let error = deserialize_buffer_detailed(b"\0asn\x01\0\0\0").expect_err("module to be rejected");
+		assert_eq!(error.offset(), Some(0));
+		assert_eq!(error.section_id(), None);
+		assert_eq!(error.expected(), None);
+	}
+}
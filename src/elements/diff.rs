@@ -0,0 +1,330 @@
+use alloc::{collections::{BTreeMap, BTreeSet}, string::String, vec::Vec};
+use core::fmt;
+use super::{Module, External, ExportEntry, ImportEntry, FuncBody, FunctionType, Type};
+
+/// Structural differences between two modules, as returned by `diff`.
+///
+/// Functions and data segments are matched by index, imports by module and field
+/// name and exports by field name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModuleDiff {
+	/// Functions which were added, removed or changed.
+	pub functions: Vec<FunctionDiff>,
+	/// Imports which were added, removed or changed.
+	pub imports: Vec<EntryChange<ImportEntry>>,
+	/// Exports which were added, removed or changed.
+	pub exports: Vec<EntryChange<ExportEntry>>,
+	/// Data segments which were added, removed or changed.
+	pub data: Vec<DataChange>,
+}
+
+impl ModuleDiff {
+	/// Whether the modules do not differ in anything this diff covers.
+	pub fn is_empty(&self) -> bool {
+		self.functions.is_empty() && self.imports.is_empty() && self.exports.is_empty() && self.data.is_empty()
+	}
+}
+
+/// Difference of a function in the function index space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDiff {
+	/// Index of the function.
+	pub index: u32,
+	/// Name of the function from the name section of the new module, or else the old one.
+	pub name: Option<String>,
+	/// What changed.
+	pub change: FunctionChange,
+}
+
+/// Kind of difference of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FunctionChange {
+	/// Function exists in the new module only.
+	Added,
+	/// Function exists in the old module only.
+	Removed,
+	/// Function exists in both modules, but differs.
+	Changed {
+		/// Old and new signature, if the signature changed.
+		signature: Option<(FunctionType, FunctionType)>,
+		/// Whether the body changed, including a function becoming imported or defined.
+		body: bool,
+	},
+}
+
+/// Difference of an import or export entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntryChange<T> {
+	/// Entry exists in the new module only.
+	Added(T),
+	/// Entry exists in the old module only.
+	Removed(T),
+	/// Entry under the same name refers to something else.
+	Changed {
+		/// Entry in the old module.
+		old: T,
+		/// Entry in the new module.
+		new: T,
+	},
+}
+
+/// Difference of a data segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataChange {
+	/// Segment with this index exists in the new module only.
+	Added(usize),
+	/// Segment with this index exists in the old module only.
+	Removed(usize),
+	/// Segment with this index differs in memory, offset or contents.
+	Changed(usize),
+}
+
+/// Function of the index space: its signature, if resolvable, and its body unless imported.
+struct Function<'a> {
+	signature: Option<&'a FunctionType>,
+	body: Option<&'a FuncBody>,
+}
+
+fn functions(module: &Module) -> Vec<Function<'_>> {
+	let types = module.type_section().map(|s| s.types()).unwrap_or(&[]);
+	let signature = |type_ref: u32| types.get(type_ref as usize).map(|Type::Function(function_type)| function_type);
+
+	let mut functions: Vec<_> = module.import_section().map(|s| s.entries()).unwrap_or(&[]).iter()
+		.filter_map(|entry| match *entry.external() {
+			External::Function(type_ref) => Some(Function { signature: signature(type_ref), body: None }),
+			_ => None,
+		})
+		.collect();
+	let bodies = module.code_section().map(|s| s.bodies()).unwrap_or(&[]);
+	for (index, func) in module.function_section().map(|s| s.entries()).unwrap_or(&[]).iter().enumerate() {
+		functions.push(Function { signature: signature(func.type_ref()), body: bodies.get(index) });
+	}
+	functions
+}
+
+fn function_name(module: &Module, index: u32) -> Option<String> {
+	module.names_section()
+		.and_then(|names| names.functions())
+		.and_then(|functions| functions.names().get(index))
+		.cloned()
+}
+
+/// Changes of entries matched by `key`, in the order of the old and then the new entries.
+///
+/// Entries sharing a key are matched with the first new entry of that key.
+fn entry_changes<'a, T, K, F>(old: &'a [T], new: &'a [T], key: F) -> Vec<EntryChange<T>>
+	where T: Clone + PartialEq, K: Ord, F: Fn(&'a T) -> K
+{
+	let mut new_by_key = BTreeMap::new();
+	for new_entry in new {
+		new_by_key.entry(key(new_entry)).or_insert(new_entry);
+	}
+	let old_keys: BTreeSet<_> = old.iter().map(&key).collect();
+
+	let mut changes = Vec::new();
+	for old_entry in old {
+		match new_by_key.get(&key(old_entry)) {
+			None => changes.push(EntryChange::Removed(old_entry.clone())),
+			Some(&new_entry) if new_entry != old_entry =>
+				changes.push(EntryChange::Changed { old: old_entry.clone(), new: new_entry.clone() }),
+			Some(_) => {},
+		}
+	}
+	for new_entry in new {
+		if !old_keys.contains(&key(new_entry)) {
+			changes.push(EntryChange::Added(new_entry.clone()));
+		}
+	}
+	changes
+}
+
+/// Structural differences from module `old` to module `new`.
+///
+/// Only sections as deserialized are compared, so names are only reported if the
+/// name sections were parsed.
+pub fn diff(old: &Module, new: &Module) -> ModuleDiff {
+	let old_functions = functions(old);
+	let new_functions = functions(new);
+	let mut function_diffs = Vec::new();
+	for index in 0..old_functions.len().max(new_functions.len()) {
+		let change = match (old_functions.get(index), new_functions.get(index)) {
+			(Some(_), None) => FunctionChange::Removed,
+			(None, Some(_)) => FunctionChange::Added,
+			(Some(old_function), Some(new_function)) => {
+				let signature = match (old_function.signature, new_function.signature) {
+					(Some(old_signature), Some(new_signature)) if old_signature == new_signature => None,
+					(Some(old_signature), Some(new_signature)) => Some((old_signature.clone(), new_signature.clone())),
+					_ => None,
+				};
+				let body = old_function.body != new_function.body;
+				if signature.is_none() && !body {
+					continue;
+				}
+				FunctionChange::Changed { signature, body }
+			},
+			(None, None) => unreachable!("index is below the length of either"),
+		};
+		let index = index as u32;
+		function_diffs.push(FunctionDiff {
+			index,
+			name: function_name(new, index).or_else(|| function_name(old, index)),
+			change,
+		});
+	}
+
+	let old_data = old.data_section().map(|s| s.entries()).unwrap_or(&[]);
+	let new_data = new.data_section().map(|s| s.entries()).unwrap_or(&[]);
+	let mut data = Vec::new();
+	for index in 0..old_data.len().max(new_data.len()) {
+		match (old_data.get(index), new_data.get(index)) {
+			(Some(_), None) => data.push(DataChange::Removed(index)),
+			(None, Some(_)) => data.push(DataChange::Added(index)),
+			(Some(old_segment), Some(new_segment)) if old_segment != new_segment => data.push(DataChange::Changed(index)),
+			_ => {},
+		}
+	}
+
+	ModuleDiff {
+		functions: function_diffs,
+		imports: entry_changes(
+			old.import_section().map(|s| s.entries()).unwrap_or(&[]),
+			new.import_section().map(|s| s.entries()).unwrap_or(&[]),
+			|entry| (entry.module(), entry.field()),
+		),
+		exports: entry_changes(
+			old.export_section().map(|s| s.entries()).unwrap_or(&[]),
+			new.export_section().map(|s| s.entries()).unwrap_or(&[]),
+			|entry| entry.field(),
+		),
+		data,
+	}
+}
+
+impl fmt::Display for ModuleDiff {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for function in &self.functions {
+			let sign = match function.change {
+				FunctionChange::Added => '+',
+				FunctionChange::Removed => '-',
+				FunctionChange::Changed { .. } => '~',
+			};
+			write!(f, "{} func {}", sign, function.index)?;
+			if let Some(ref name) = function.name {
+				write!(f, " ${}", name)?;
+			}
+			if let FunctionChange::Changed { ref signature, body } = function.change {
+				if let Some((ref old, ref new)) = *signature {
+					write!(f, ": {} -> {}", old, new)?;
+				}
+				if body {
+					write!(f, " (body)")?;
+				}
+			}
+			writeln!(f)?;
+		}
+		for import in &self.imports {
+			match *import {
+				EntryChange::Added(ref entry) => writeln!(f, "+ import {}.{}", entry.module(), entry.field())?,
+				EntryChange::Removed(ref entry) => writeln!(f, "- import {}.{}", entry.module(), entry.field())?,
+				EntryChange::Changed { ref new, .. } => writeln!(f, "~ import {}.{}", new.module(), new.field())?,
+			}
+		}
+		for export in &self.exports {
+			match *export {
+				EntryChange::Added(ref entry) => writeln!(f, "+ export {}", entry.field())?,
+				EntryChange::Removed(ref entry) => writeln!(f, "- export {}", entry.field())?,
+				EntryChange::Changed { ref new, .. } => writeln!(f, "~ export {}", new.field())?,
+			}
+		}
+		for change in &self.data {
+			match *change {
+				DataChange::Added(index) => writeln!(f, "+ data {}", index)?,
+				DataChange::Removed(index) => writeln!(f, "- data {}", index)?,
+				DataChange::Changed(index) => writeln!(f, "~ data {}", index)?,
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::builder;
+	use crate::elements::{Instruction::*, Instructions, Internal, ValueType};
+
+	fn module(value: i32, data: &[u8]) -> Module {
+		builder::module()
+			.import().module("env").field("log").external().func(0).build()
+			.function()
+				.signature().with_param(ValueType::I32).build()
+				.body()
+					.with_instructions(Instructions::new(vec![I32Const(value), Drop, End]))
+					.build()
+				.build()
+			.export().field("main").internal().func(1).build()
+			.memory().build()
+			.data().offset(I32Const(0)).value(data.to_vec()).build()
+			.build()
+	}
+
+	#[test]
+	fn identical() {
+		assert!(diff(&module(1, b"a"), &module(1, b"a")).is_empty());
+	}
+
+	#[test]
+	fn changes() {
+		let old = module(1, b"a");
+		let mut new = module(2, b"b");
+		new.export_section_mut().expect("export section").entries_mut()[0] = ExportEntry::new("main".into(), Internal::Function(0));
+		new.export_section_mut().expect("export section").entries_mut().push(ExportEntry::new("memory".into(), Internal::Memory(0)));
+
+		let diff = diff(&old, &new);
+		assert_eq!(diff.functions, vec![FunctionDiff {
+			index: 1,
+			name: None,
+			change: FunctionChange::Changed { signature: None, body: true },
+		}]);
+		assert_eq!(diff.imports, vec![]);
+		assert_eq!(diff.exports, vec![
+			EntryChange::Changed {
+				old: ExportEntry::new("main".into(), Internal::Function(1)),
+				new: ExportEntry::new("main".into(), Internal::Function(0)),
+			},
+			EntryChange::Added(ExportEntry::new("memory".into(), Internal::Memory(0))),
+		]);
+		assert_eq!(diff.data, vec![DataChange::Changed(0)]);
+		assert_eq!(diff.to_string(), "~ func 1 (body)\n~ export main\n+ export memory\n~ data 0\n");
+	}
+
+	#[test]
+	fn added_function_and_signature() {
+		let old = module(1, b"a");
+		let new = builder::module()
+			.import().module("env").field("log").external().func(0).build()
+			.function()
+				.signature().with_param(ValueType::I64).build()
+				.body()
+					.with_instructions(Instructions::new(vec![I32Const(1), Drop, End]))
+					.build()
+				.build()
+			.function().signature().build().body().build().build()
+			.export().field("main").internal().func(1).build()
+			.memory().build()
+			.data().offset(I32Const(0)).value(b"a".to_vec()).build()
+			.build();
+
+		// The import shares the signature of the defined function, so it changes too.
+		let diff = diff(&old, &new);
+		assert_eq!(diff.functions.iter().map(|function| function.index).collect::<Vec<_>>(), vec![0, 1, 2]);
+		match diff.functions[1].change {
+			FunctionChange::Changed { signature: Some((ref old, ref new)), body: false } => {
+				assert_eq!(old.params(), &[ValueType::I32]);
+				assert_eq!(new.params(), &[ValueType::I64]);
+			},
+			ref other => panic!("unexpected change {:?}", other),
+		}
+		assert_eq!(diff.functions[2].change, FunctionChange::Added);
+	}
+}
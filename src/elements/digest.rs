@@ -0,0 +1,165 @@
+use alloc::vec::Vec;
+use core::fmt;
+use super::{Error, Module, Section, Serialize, serialize};
+use super::canonical::canonical_rank;
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+const FNV_PRIME: u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+
+/// Stable 128-bit digest of module contents.
+///
+/// Computed with FNV-1a over the serialized contents, so it stays the same across
+/// hosts and versions of the crate as long as the encoding does. It is meant as a cache
+/// key, not as a cryptographic hash: it does not resist deliberate collisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Digest(pub [u8; 16]);
+
+impl fmt::Display for Digest {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for byte in &self.0 {
+			write!(f, "{:02x}", byte)?;
+		}
+		Ok(())
+	}
+}
+
+/// Sections a module digest covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SectionFilter {
+	/// Every section.
+	All,
+	/// Every section except custom ones, parsed or not, so that names, producers and
+	/// debug information don't change the digest.
+	NoCustom,
+}
+
+impl SectionFilter {
+	fn includes(&self, section: &Section) -> bool {
+		match *self {
+			SectionFilter::All => true,
+			SectionFilter::NoCustom => section.order() != 0,
+		}
+	}
+}
+
+struct Hasher(u128);
+
+impl Hasher {
+	fn new() -> Self {
+		Hasher(FNV_OFFSET_BASIS)
+	}
+
+	fn update(&mut self, bytes: &[u8]) {
+		for &byte in bytes {
+			self.0 ^= byte as u128;
+			self.0 = self.0.wrapping_mul(FNV_PRIME);
+		}
+	}
+
+	fn update_with<T: Serialize<Error=Error>>(&mut self, value: T) -> Result<(), Error> {
+		self.update(&serialize(value)?);
+		Ok(())
+	}
+
+	fn finish(self) -> Digest {
+		Digest(self.0.to_be_bytes())
+	}
+}
+
+impl Module {
+	/// Digest of the sections selected by `filter`.
+	///
+	/// Sections are taken in the order of `Module::canonicalize`, so modules differing
+	/// only in the order of their known sections have the same digest; the relative
+	/// order of custom sections still counts. Sections are serialized one at a time,
+	/// without serializing the whole module.
+	pub fn digest(&self, filter: SectionFilter) -> Result<Digest, Error> {
+		let mut sections: Vec<&Section> = self.sections().iter().filter(|section| filter.includes(section)).collect();
+		sections.sort_by_key(|section| canonical_rank(section));
+
+		let mut hasher = Hasher::new();
+		hasher.update(&self.version().to_le_bytes());
+		for section in sections {
+			hasher.update_with(section.clone())?;
+		}
+		Ok(hasher.finish())
+	}
+
+	/// Digest of every function body, along with its signature, in the order of the
+	/// code section.
+	///
+	/// Equal bodies of functions with equal signatures have equal digests, wherever
+	/// they are in the module. Indices the body refers to are hashed as they are.
+	pub fn function_digests(&self) -> Result<Vec<Digest>, Error> {
+		let imported = self.import_count(super::ImportCountType::Function) as u32;
+		let bodies = self.code_section().map(|s| s.bodies()).unwrap_or(&[]);
+		let mut digests = Vec::with_capacity(bodies.len());
+		for (index, body) in bodies.iter().enumerate() {
+			let func_type = self.function_type(imported + index as u32)
+				.ok_or(Error::Other("function body without a signature"))?;
+			let mut hasher = Hasher::new();
+			hasher.update_with(func_type.clone())?;
+			hasher.update_with(body.clone())?;
+			digests.push(hasher.finish());
+		}
+		Ok(digests)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Digest, SectionFilter};
+	use crate::builder;
+	use crate::elements::{Instruction::*, Instructions, Module, Section, ValueType};
+
+	fn module() -> Module {
+		builder::module()
+			.function().signature().with_param(ValueType::I32).build()
+				.body().with_instructions(Instructions::new(vec![GetLocal(0), Drop, End])).build()
+				.build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![I32Const(1), Drop, End])).build()
+				.build()
+			.function().signature().with_param(ValueType::I32).build()
+				.body().with_instructions(Instructions::new(vec![GetLocal(0), Drop, End])).build()
+				.build()
+			.export().field("main").internal().func(1).build()
+			.build()
+	}
+
+	#[test]
+	fn sections() {
+		let plain = module();
+		let mut named = module();
+		named.set_custom_section("name", vec![0, 1, 0]);
+		let mut reordered = module();
+		reordered.sections_mut().reverse();
+
+		let digest = plain.digest(SectionFilter::All).expect("module to serialize");
+		assert_ne!(named.digest(SectionFilter::All).expect("module to serialize"), digest);
+		assert_eq!(named.digest(SectionFilter::NoCustom).expect("module to serialize"), digest);
+		assert_eq!(reordered.digest(SectionFilter::All).expect("module to serialize"), digest);
+
+		let mut changed = module();
+		changed.sections_mut().retain(|section| !matches!(*section, Section::Export(_)));
+		assert_ne!(changed.digest(SectionFilter::All).expect("module to serialize"), digest);
+	}
+
+	#[test]
+	fn stable() {
+		// FNV-1a of the version alone.
+		let digest = builder::module().build().digest(SectionFilter::All).expect("module to serialize");
+		assert_eq!(digest, Digest([
+			0x66, 0xa5, 0xc4, 0xc7, 0x0a, 0x75, 0x72, 0x77, 0xb8, 0x06, 0xe8, 0x9a, 0xe3, 0xa8, 0xfe, 0xcc,
+		]));
+		assert_eq!(digest.to_string().len(), 32);
+	}
+
+	#[test]
+	fn functions() {
+		let digests = module().function_digests().expect("bodies to serialize");
+		assert_eq!(digests.len(), 3);
+		assert_eq!(digests[0], digests[2]);
+		assert_ne!(digests[0], digests[1]);
+	}
+}
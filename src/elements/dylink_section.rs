@@ -0,0 +1,259 @@
+use alloc::{string::String, vec::Vec};
+use crate::io;
+
+use super::{Deserialize, Error, Serialize, VarUint32};
+use super::codec::{counted, parse_subsection, read_subsections, Reader, Writer};
+
+const WASM_DYLINK_MEM_INFO: u8 = 1;
+const WASM_DYLINK_NEEDED: u8 = 2;
+const WASM_DYLINK_EXPORT_INFO: u8 = 3;
+const WASM_DYLINK_IMPORT_INFO: u8 = 4;
+
+/// Dynamic linking metadata of a side module (`dylink.0`).
+///
+/// The section has to come first in the module, before any other section.
+/// Subsections are kept in the order they were read in.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DylinkSection {
+	/// Subsections of this section.
+	subsections: Vec<DylinkSubsection>,
+}
+
+impl DylinkSection {
+	/// Creates a new dylink section.
+	pub fn new(subsections: Vec<DylinkSubsection>) -> Self {
+		DylinkSection { subsections }
+	}
+
+	/// Subsections of this section.
+	pub fn subsections(&self) -> &[DylinkSubsection] {
+		&self.subsections
+	}
+
+	/// Subsections of this section (mutable).
+	pub fn subsections_mut(&mut self) -> &mut Vec<DylinkSubsection> {
+		&mut self.subsections
+	}
+
+	/// Memory and table requirements, if given.
+	pub fn mem_info(&self) -> Option<&DylinkMemInfo> {
+		self.subsections.iter().find_map(|subsection| match *subsection {
+			DylinkSubsection::MemInfo(ref mem_info) => Some(mem_info),
+			_ => None,
+		})
+	}
+
+	/// Shared libraries the module depends on.
+	pub fn needed(&self) -> &[String] {
+		self.subsections.iter().find_map(|subsection| match *subsection {
+			DylinkSubsection::Needed(ref needed) => Some(&needed[..]),
+			_ => None,
+		}).unwrap_or(&[])
+	}
+}
+
+impl DylinkSection {
+	/// Deserialize a dylink section.
+	pub fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Error> {
+		let mut subsections = Vec::new();
+		for (id, payload) in read_subsections(rdr)? {
+			subsections.push(match id {
+				WASM_DYLINK_MEM_INFO => DylinkSubsection::MemInfo(parse_subsection(&payload, Reader::read)?),
+				WASM_DYLINK_NEEDED => DylinkSubsection::Needed(parse_subsection(&payload, Reader::vec)?),
+				WASM_DYLINK_EXPORT_INFO => DylinkSubsection::ExportInfo(parse_subsection(&payload, Reader::vec)?),
+				WASM_DYLINK_IMPORT_INFO => DylinkSubsection::ImportInfo(parse_subsection(&payload, Reader::vec)?),
+				id => DylinkSubsection::Unknown { id, payload },
+			});
+		}
+
+		Ok(DylinkSection { subsections })
+	}
+}
+
+impl Serialize for DylinkSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		let mut section = Writer::new();
+		for subsection in self.subsections {
+			match subsection {
+				DylinkSubsection::MemInfo(mem_info) => {
+					let mut payload = Writer::new();
+					payload.write(mem_info)?;
+					section.subsection(WASM_DYLINK_MEM_INFO, payload.as_slice())?
+				},
+				DylinkSubsection::Needed(needed) => section.subsection(WASM_DYLINK_NEEDED, &counted(needed)?)?,
+				DylinkSubsection::ExportInfo(exports) => section.subsection(WASM_DYLINK_EXPORT_INFO, &counted(exports)?)?,
+				DylinkSubsection::ImportInfo(imports) => section.subsection(WASM_DYLINK_IMPORT_INFO, &counted(imports)?)?,
+				DylinkSubsection::Unknown { id, payload } => section.subsection(id, &payload)?,
+			};
+		}
+		wtr.write(section.as_slice())?;
+		Ok(())
+	}
+}
+
+/// Subsection of the dylink section.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DylinkSubsection {
+	/// Memory and table requirements.
+	MemInfo(DylinkMemInfo),
+	/// Shared libraries the module depends on.
+	Needed(Vec<String>),
+	/// Symbol flags of exports.
+	ExportInfo(Vec<DylinkExportInfo>),
+	/// Symbol flags of imports.
+	ImportInfo(Vec<DylinkImportInfo>),
+	/// Subsection this crate does not know about, kept as is.
+	Unknown {
+		/// Subsection type.
+		id: u8,
+		/// Raw bytes of the subsection.
+		payload: Vec<u8>,
+	},
+}
+
+/// Memory and table space a side module needs the loader to reserve.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DylinkMemInfo {
+	/// Size of the module's static data, in bytes.
+	pub memory_size: u32,
+	/// Alignment of the static data, as a power of two.
+	pub memory_alignment: u32,
+	/// Number of table slots the module's functions need.
+	pub table_size: u32,
+	/// Alignment of the table slots, as a power of two.
+	pub table_alignment: u32,
+}
+
+impl Deserialize for DylinkMemInfo {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(DylinkMemInfo {
+			memory_size: VarUint32::deserialize(rdr)?.into(),
+			memory_alignment: VarUint32::deserialize(rdr)?.into(),
+			table_size: VarUint32::deserialize(rdr)?.into(),
+			table_alignment: VarUint32::deserialize(rdr)?.into(),
+		})
+	}
+}
+
+impl Serialize for DylinkMemInfo {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		VarUint32::from(self.memory_size).serialize(wtr)?;
+		VarUint32::from(self.memory_alignment).serialize(wtr)?;
+		VarUint32::from(self.table_size).serialize(wtr)?;
+		VarUint32::from(self.table_alignment).serialize(wtr)?;
+		Ok(())
+	}
+}
+
+/// Symbol flags of an export, as the `SymbolInfo` flags of the linking section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DylinkExportInfo {
+	/// Field name of the export.
+	pub name: String,
+	/// Symbol flags.
+	pub flags: u32,
+}
+
+impl Deserialize for DylinkExportInfo {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(DylinkExportInfo {
+			name: String::deserialize(rdr)?,
+			flags: VarUint32::deserialize(rdr)?.into(),
+		})
+	}
+}
+
+impl Serialize for DylinkExportInfo {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		self.name.serialize(wtr)?;
+		VarUint32::from(self.flags).serialize(wtr)?;
+		Ok(())
+	}
+}
+
+/// Symbol flags of an import, as the `SymbolInfo` flags of the linking section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DylinkImportInfo {
+	/// Module name of the import.
+	pub module: String,
+	/// Field name of the import.
+	pub field: String,
+	/// Symbol flags.
+	pub flags: u32,
+}
+
+impl Deserialize for DylinkImportInfo {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(DylinkImportInfo {
+			module: String::deserialize(rdr)?,
+			field: String::deserialize(rdr)?,
+			flags: VarUint32::deserialize(rdr)?.into(),
+		})
+	}
+}
+
+impl Serialize for DylinkImportInfo {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		self.module.serialize(wtr)?;
+		self.field.serialize(wtr)?;
+		VarUint32::from(self.flags).serialize(wtr)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::{deserialize_buffer, serialize, Module, Section, SymbolInfo};
+	use crate::builder;
+
+	#[test]
+	fn dylink_round_trip() {
+		let dylink = DylinkSection::new(vec![
+			DylinkSubsection::MemInfo(DylinkMemInfo { memory_size: 1024, memory_alignment: 4, table_size: 2, table_alignment: 0 }),
+			DylinkSubsection::Needed(vec!["libc.so".into()]),
+			DylinkSubsection::ImportInfo(vec![DylinkImportInfo {
+				module: "env".into(),
+				field: "malloc".into(),
+				flags: SymbolInfo::BINDING_WEAK,
+			}]),
+		]);
+		let mut module = builder::module().build();
+		module.sections_mut().insert(0, Section::Dylink(dylink.clone()));
+		let bytes = serialize(module).expect("module to serialize");
+
+		let module: Module = deserialize_buffer(&bytes).expect("module to deserialize");
+		let module = module.parse_dylink().expect("dylink section to parse");
+		let parsed = module.dylink_section().expect("dylink section");
+		assert_eq!(parsed, &dylink);
+		assert_eq!(parsed.mem_info().map(|mem_info| mem_info.memory_size), Some(1024));
+		assert_eq!(parsed.needed(), &["libc.so".to_string()][..]);
+		assert_eq!(serialize(module).expect("module to serialize"), bytes);
+	}
+
+	#[test]
+	fn trailing_bytes_in_subsection() {
+		// A needed subsection with one library name and a stray byte.
+		let payload = [WASM_DYLINK_NEEDED, 4, 1, 1, b'c', 0];
+		assert!(DylinkSection::deserialize(&mut &payload[..]).is_err());
+	}
+}
@@ -0,0 +1,217 @@
+use alloc::vec::Vec;
+use crate::analysis::call_graph;
+use super::{Module, External, Instruction, ResizableLimits, ImportCountType};
+
+/// Resources a module needs when instantiated, as far as they follow from the module alone.
+///
+/// Returned by `Module::resource_estimate`, so that a host can decide whether to
+/// instantiate a module before paying for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResourceEstimate {
+	/// Initial size of all memories together, in pages.
+	pub memory_initial_pages: u64,
+	/// Maximum size of all memories together, in pages; `None` if any memory is unbounded.
+	pub memory_maximum_pages: Option<u64>,
+	/// Initial size of all tables together, in elements.
+	pub table_initial_elements: u64,
+	/// Maximum size of all tables together, in elements; `None` if any table is unbounded.
+	pub table_maximum_elements: Option<u64>,
+	/// Number of globals, imported ones included.
+	pub globals: u64,
+	/// Number of instructions of all function bodies, a proxy for the size of translated code.
+	pub instructions: u64,
+	/// Upper bound of the values live on the stack at once, counting locals and operands of
+	/// every frame; `None` if recursion or indirect calls leave the call depth unbounded.
+	pub max_stack_values: Option<u64>,
+}
+
+/// Sum of limits, with the maximum staying `None` once any is unbounded.
+fn add_limits(initial: &mut u64, maximum: &mut Option<u64>, limits: &ResizableLimits) {
+	*initial += limits.initial() as u64;
+	*maximum = match (*maximum, limits.maximum()) {
+		(Some(total), Some(max)) => Some(total + max as u64),
+		_ => None,
+	};
+}
+
+pub(crate) fn estimate(module: &Module) -> ResourceEstimate {
+	let mut estimate = ResourceEstimate {
+		memory_initial_pages: 0,
+		memory_maximum_pages: Some(0),
+		table_initial_elements: 0,
+		table_maximum_elements: Some(0),
+		globals: module.import_count(ImportCountType::Global) as u64,
+		instructions: 0,
+		max_stack_values: None,
+	};
+
+	for entry in module.import_section().map(|s| s.entries()).unwrap_or(&[]) {
+		match *entry.external() {
+			External::Memory(ref memory) =>
+				add_limits(&mut estimate.memory_initial_pages, &mut estimate.memory_maximum_pages, memory.limits()),
+			External::Table(ref table) =>
+				add_limits(&mut estimate.table_initial_elements, &mut estimate.table_maximum_elements, table.limits()),
+			_ => {},
+		}
+	}
+	for memory in module.memory_section().map(|s| s.entries()).unwrap_or(&[]) {
+		add_limits(&mut estimate.memory_initial_pages, &mut estimate.memory_maximum_pages, memory.limits());
+	}
+	for table in module.table_section().map(|s| s.entries()).unwrap_or(&[]) {
+		add_limits(&mut estimate.table_initial_elements, &mut estimate.table_maximum_elements, table.limits());
+	}
+	estimate.globals += module.global_section().map(|s| s.entries().len()).unwrap_or(0) as u64;
+
+	let bodies = module.code_section().map(|s| s.bodies()).unwrap_or(&[]);
+	estimate.instructions = bodies.iter().map(|body| body.code().elements().len() as u64).sum();
+	estimate.max_stack_values = max_stack_values(module);
+	estimate
+}
+
+/// Frame size of every defined function: its parameters, locals and a bound of its
+/// operands; `None` if a function makes indirect calls.
+fn frames(module: &Module) -> Option<Vec<u64>> {
+	let imported = module.import_count(ImportCountType::Function) as u32;
+	let bodies = module.code_section().map(|s| s.bodies()).unwrap_or(&[]);
+	let mut frames = Vec::with_capacity(bodies.len());
+
+	for (index, body) in bodies.iter().enumerate() {
+		let func_type = module.function_type(imported + index as u32)?;
+		let locals: u64 = body.locals().iter().map(|local| local.count() as u64).sum();
+
+		// No instruction pushes more than one value, except calls and `catch`, so this
+		// bounds the operand stack height.
+		let mut operands = 0u64;
+		for instruction in body.code().elements() {
+			operands += match *instruction {
+				Instruction::Call(callee) => module.function_type(callee)?.results().len() as u64,
+				Instruction::CallIndirect(..) => return None,
+				#[cfg(feature="exceptions")]
+				Instruction::Exceptions(super::ExceptionsInstruction::Catch(tag)) =>
+					module.tag_type(tag)?.params().len() as u64,
+				_ => 1,
+			};
+		}
+
+		frames.push(func_type.params().len() as u64 + locals + operands);
+	}
+	Some(frames)
+}
+
+fn max_stack_values(module: &Module) -> Option<u64> {
+	#[derive(Clone, Copy, PartialEq)]
+	enum State {
+		Unvisited,
+		Visiting,
+		Done(u64),
+	}
+
+	let frames = frames(module)?;
+	let graph = call_graph(module);
+	let imported = module.import_count(ImportCountType::Function) as u32;
+	// Defined callees of every defined function, the only ones with frames of their own.
+	let callees: Vec<Vec<u32>> = (0..frames.len() as u32)
+		.map(|index| graph.callees(imported + index).iter()
+			.filter(|edge| !graph.is_imported(edge.callee))
+			.map(|edge| edge.callee)
+			.collect())
+		.collect();
+
+	let mut states = vec![State::Unvisited; frames.len()];
+	let mut max = 0;
+	for root in 0..frames.len() {
+		// Depth-first, with an explicit stack of (function, callees visited so far).
+		let mut stack = vec![(root, 0)];
+		while let Some(&(function, next)) = stack.last() {
+			if next == 0 {
+				if let State::Done(_) = states[function] {
+					stack.pop();
+					continue;
+				}
+				states[function] = State::Visiting;
+			}
+			match callees[function].get(next) {
+				Some(&callee) => {
+					if let Some(top) = stack.last_mut() {
+						top.1 += 1;
+					}
+					let callee = (callee - imported) as usize;
+					match *states.get(callee)? {
+						State::Visiting => return None,
+						State::Unvisited => stack.push((callee, 0)),
+						State::Done(_) => {},
+					}
+				},
+				None => {
+					let deepest = callees[function].iter()
+						.map(|&callee| match states[(callee - imported) as usize] {
+							State::Done(depth) => depth,
+							_ => 0,
+						})
+						.max()
+						.unwrap_or(0);
+					let depth = frames[function] + deepest;
+					states[function] = State::Done(depth);
+					max = max.max(depth);
+					stack.pop();
+				},
+			}
+		}
+	}
+	Some(max)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ResourceEstimate;
+	use crate::builder;
+	use crate::elements::{Instruction::*, Instructions, ValueType, Local};
+
+	#[test]
+	fn estimate() {
+		let module = builder::module()
+			.import().module("env").field("memory").external().memory(1, Some(4)).build()
+			.memory().with_min(2).build()
+			.table().with_min(3).with_max(Some(5)).build()
+			.global().value_type().i32().init_expr(I32Const(0)).build()
+			.function()
+				.signature().with_param(ValueType::I32).with_result(ValueType::I32).build()
+				.body()
+					.with_locals(vec![Local::new(2, ValueType::I64)])
+					.with_instructions(Instructions::new(vec![GetLocal(0), End]))
+					.build()
+				.build()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![I32Const(1), Call(0), Drop, End]))
+					.build()
+				.build()
+			.build();
+
+		assert_eq!(module.resource_estimate(), ResourceEstimate {
+			memory_initial_pages: 3,
+			memory_maximum_pages: None,
+			table_initial_elements: 3,
+			table_maximum_elements: Some(5),
+			globals: 1,
+			instructions: 6,
+			// The callee's frame of 1 parameter, 2 locals and 2 operands on top of the caller's
+			// frame of 4 operands.
+			max_stack_values: Some(9),
+		});
+	}
+
+	#[test]
+	fn recursion_is_unbounded() {
+		let module = builder::module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![Call(0), End]))
+					.build()
+				.build()
+			.build();
+		assert_eq!(module.resource_estimate().max_stack_values, None);
+	}
+}
@@ -1,9 +1,12 @@
+#![allow(clippy::redundant_field_names)]
+
 use alloc::string::String;
 use super::{Deserialize, Serialize, Error, VarUint7, VarUint32};
 use crate::io;
 
 /// Internal reference of the exported entry.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Internal {
 	/// Function reference.
 	Function(u32),
@@ -13,6 +16,9 @@ pub enum Internal {
 	Memory(u32),
 	/// Global reference.
 	Global(u32),
+	/// Exception tag reference.
+	#[cfg(feature="exceptions")]
+	Tag(u32),
 }
 
 impl Deserialize for Internal {
@@ -25,6 +31,8 @@ impl Deserialize for Internal {
 			0x01 => Ok(Internal::Table(VarUint32::deserialize(reader)?.into())),
 			0x02 => Ok(Internal::Memory(VarUint32::deserialize(reader)?.into())),
 			0x03 => Ok(Internal::Global(VarUint32::deserialize(reader)?.into())),
+			#[cfg(feature="exceptions")]
+			0x04 => Ok(Internal::Tag(VarUint32::deserialize(reader)?.into())),
 			_ => Err(Error::UnknownInternalKind(kind.into())),
 		}
 	}
@@ -39,6 +47,8 @@ impl Serialize for Internal {
 			Internal::Table(arg) => (0x01, arg),
 			Internal::Memory(arg) => (0x02, arg),
 			Internal::Global(arg) => (0x03, arg),
+			#[cfg(feature="exceptions")]
+			Internal::Tag(arg) => (0x04, arg),
 		};
 
 		VarUint7::from(bt).serialize(writer)?;
@@ -50,6 +60,7 @@ impl Serialize for Internal {
 
 /// Export entry.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExportEntry {
 	field_str: String,
 	internal: Internal,
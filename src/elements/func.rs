@@ -1,3 +1,9 @@
+#![allow(
+	clippy::redundant_field_names,
+	clippy::unnecessary_lazy_evaluations,
+	clippy::useless_conversion,
+)]
+
 use alloc::vec::Vec;
 use super::{
 	Deserialize, Error, ValueType, VarUint32, CountedList, Instructions,
@@ -7,6 +13,7 @@ use crate::{io, elements::section::SectionReader};
 
 /// Function signature (type reference)
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Func(u32);
 
 impl Func {
@@ -42,6 +49,7 @@ impl Deserialize for Func {
 
 /// Local definition inside the function body.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Local {
 	count: u32,
 	value_type: ValueType,
@@ -80,24 +88,63 @@ impl Serialize for Local {
 	}
 }
 
+/// Byte offsets of a function body and its instructions in the module they were decoded from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CodeLocations {
+	section: usize,
+	body: usize,
+	instructions: Vec<usize>,
+}
+
+impl CodeLocations {
+	/// Offset of the code section payload, past the section's size prefix, which DWARF
+	/// code offsets are relative to.
+	pub fn section(&self) -> usize { self.section }
+
+	/// Offset of the body, past its size prefix.
+	pub fn body(&self) -> usize { self.body }
+
+	/// Offset of each instruction's opcode, in the order of `FuncBody::code`.
+	pub fn instructions(&self) -> &[usize] { &self.instructions }
+}
+
 /// Function body definition.
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Two bodies are equal if their locals and instructions are, regardless of their
+/// code locations.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FuncBody {
 	locals: Vec<Local>,
 	instructions: Instructions,
+	locations: Option<CodeLocations>,
+}
+
+impl PartialEq for FuncBody {
+	fn eq(&self, other: &Self) -> bool {
+		self.locals == other.locals && self.instructions == other.instructions
+	}
 }
 
 impl FuncBody {
 	/// New function body with given `locals` and `instructions`.
 	pub fn new(locals: Vec<Local>, instructions: Instructions) -> Self {
-		FuncBody { locals: locals, instructions: instructions }
+		FuncBody { locals: locals, instructions: instructions, locations: None }
 	}
 
 	/// List of individual instructions.
 	pub fn empty() -> Self {
-		FuncBody { locals: Vec::new(), instructions: Instructions::empty() }
+		FuncBody::new(Vec::new(), Instructions::empty())
 	}
 
+	/// Where the body and its instructions were found in the module, if recorded.
+	///
+	/// Only bodies decoded by `deserialize_buffer_with_locations` or
+	/// `LazyFuncBody::decode_with_locations` carry locations. They are dropped as soon
+	/// as the instructions are borrowed mutably, since they may no longer match.
+	pub fn code_locations(&self) -> Option<&CodeLocations> { self.locations.as_ref() }
+
 	/// Locals declared in function body.
 	pub fn locals(&self) -> &[Local] { &self.locals }
 
@@ -110,7 +157,15 @@ impl FuncBody {
 	pub fn locals_mut(&mut self) -> &mut Vec<Local> { &mut self.locals }
 
 	/// Instruction list of the function body (mutable).
-	pub fn code_mut(&mut self) -> &mut Instructions { &mut self.instructions }
+	pub fn code_mut(&mut self) -> &mut Instructions {
+		self.locations = None;
+		&mut self.instructions
+	}
+
+	/// Instructions and code locations, which the caller drops if it changes anything.
+	pub(crate) fn code_mut_with_locations(&mut self) -> (&mut Instructions, &mut Option<CodeLocations>) {
+		(&mut self.instructions, &mut self.locations)
+	}
 }
 
 impl Deserialize for FuncBody {
@@ -118,7 +173,37 @@ impl Deserialize for FuncBody {
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
 		let mut body_reader = SectionReader::new(reader)?;
-		let locals: Vec<Local> = CountedList::<Local>::deserialize(&mut body_reader)?.into_inner();
+		let body = FuncBody::deserialize_payload(&mut body_reader)?;
+		body_reader.close()?;
+		Ok(body)
+	}
+}
+
+impl FuncBody {
+	/// Deserialize the body contents, without the leading size.
+	pub(crate) fn deserialize_payload<R: io::Read>(reader: &mut R) -> Result<Self, Error> {
+		let locals = Self::deserialize_locals(reader)?;
+		let instructions = Instructions::deserialize(reader)?;
+		Ok(FuncBody::new(locals, instructions))
+	}
+
+	/// Deserialize the body contents, recording code locations relative to `offset`, in
+	/// a code section whose payload starts at `section`.
+	pub(crate) fn deserialize_payload_with_locations(reader: &mut io::Cursor<&[u8]>, section: usize, offset: usize) -> Result<Self, Error> {
+		let locals = Self::deserialize_locals(reader)?;
+		let mut locations = Vec::new();
+		let instructions = Instructions::deserialize_observed(reader, |reader| {
+			locations.push(offset + reader.position());
+		})?;
+		Ok(FuncBody {
+			locals: locals,
+			instructions: instructions,
+			locations: Some(CodeLocations { section, body: offset, instructions: locations }),
+		})
+	}
+
+	fn deserialize_locals<R: io::Read>(reader: &mut R) -> Result<Vec<Local>, Error> {
+		let locals: Vec<Local> = CountedList::<Local>::deserialize(reader)?.into_inner();
 
 		// The specification obliges us to count the total number of local variables while
 		// decoding the binary format.
@@ -127,9 +212,7 @@ impl Deserialize for FuncBody {
 			.try_fold(0u32, |acc, &Local { count, .. }| acc.checked_add(count))
 			.ok_or_else(|| Error::TooManyLocals)?;
 
-		let instructions = Instructions::deserialize(&mut body_reader)?;
-		body_reader.close()?;
-		Ok(FuncBody { locals: locals, instructions: instructions })
+		Ok(locals)
 	}
 }
 
@@ -1,8 +1,11 @@
+#![allow(clippy::redundant_field_names)]
+
 use crate::io;
 use super::{Deserialize, Serialize, Error, GlobalType, InitExpr};
 
 /// Global entry in the module.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalEntry {
 	global_type: GlobalType,
 	init_expr: InitExpr,
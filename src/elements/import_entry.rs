@@ -1,16 +1,20 @@
+#![allow(clippy::redundant_field_names)]
+
 use alloc::string::String;
 use crate::io;
 use super::{
-	Deserialize, Serialize, Error, VarUint7, VarInt7, VarUint32, VarUint1, Uint8,
+	Deserialize, Serialize, Error, VarUint7, VarInt7, VarUint32, VarUint64, VarUint1, Uint8,
 	ValueType, TableElementType
 };
 
 const FLAG_HAS_MAX: u8 = 0x01;
 #[cfg(feature="atomics")]
 const FLAG_SHARED: u8 = 0x02;
+const FLAG_MEMORY64: u8 = 0x04;
 
 /// Global definition struct
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalType {
 	content_type: ValueType,
 	is_mutable: bool,
@@ -57,6 +61,7 @@ impl Serialize for GlobalType {
 
 /// Table entry
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TableType {
 	elem_type: TableElementType,
 	limits: ResizableLimits,
@@ -84,6 +89,13 @@ impl Deserialize for TableType {
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
 		let elem_type = TableElementType::deserialize(reader)?;
 		let limits = ResizableLimits::deserialize(reader)?;
+		#[cfg(feature="memory64")]
+		{
+			// Only memories can be 64-bit.
+			if limits.is_64 {
+				return Err(Error::InvalidLimitsFlags(FLAG_MEMORY64));
+			}
+		}
 		Ok(TableType {
 			elem_type: elem_type,
 			limits: limits,
@@ -100,33 +112,118 @@ impl Serialize for TableType {
 	}
 }
 
+/// Tag (exception) definition.
+///
+/// This is only available if the `exceptions` feature is enabled.
+#[cfg(feature="exceptions")]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TagType {
+	type_ref: u32,
+}
+
+#[cfg(feature="exceptions")]
+impl TagType {
+	/// New exception tag with the given function type index.
+	pub fn new(type_ref: u32) -> Self {
+		TagType { type_ref }
+	}
+
+	/// Index of the function type describing the tag parameters.
+	pub fn type_ref(&self) -> u32 { self.type_ref }
+
+	/// Index of the function type describing the tag parameters (mutable).
+	pub fn type_ref_mut(&mut self) -> &mut u32 { &mut self.type_ref }
+}
+
+#[cfg(feature="exceptions")]
+impl Deserialize for TagType {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		let attribute: u8 = Uint8::deserialize(reader)?.into();
+		if attribute != 0 {
+			return Err(Error::UnknownTagAttribute(attribute));
+		}
+		let type_ref = VarUint32::deserialize(reader)?.into();
+		Ok(TagType { type_ref })
+	}
+}
+
+#[cfg(feature="exceptions")]
+impl Serialize for TagType {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		Uint8::from(0).serialize(writer)?;
+		VarUint32::from(self.type_ref).serialize(writer)
+	}
+}
+
 /// Memory and table limits.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ResizableLimits {
-	initial: u32,
-	maximum: Option<u32>,
+	// Only limits of 64-bit memories can exceed `u32`.
+	initial: u64,
+	maximum: Option<u64>,
 	#[cfg(feature = "atomics")]
 	shared: bool,
+	#[cfg(feature = "memory64")]
+	is_64: bool,
 }
 
 impl ResizableLimits {
 	/// New memory limits definition.
 	pub fn new(min: u32, max: Option<u32>) -> Self {
+		ResizableLimits {
+			initial: min as u64,
+			maximum: max.map(|max| max as u64),
+			#[cfg(feature = "atomics")]
+			shared: false,
+			#[cfg(feature = "memory64")]
+			is_64: false,
+		}
+	}
+
+	/// New limits of a 64-bit memory.
+	///
+	/// This is only available if the `memory64` feature is enabled.
+	#[cfg(feature = "memory64")]
+	pub fn new_64(min: u64, max: Option<u64>) -> Self {
 		ResizableLimits {
 			initial: min,
 			maximum: max,
 			#[cfg(feature = "atomics")]
 			shared: false,
+			is_64: true,
 		}
 	}
+
 	/// Initial size.
-	pub fn initial(&self) -> u32 { self.initial }
+	///
+	/// Sizes of 64-bit memories which do not fit `u32` are saturated, see `initial_64`.
+	pub fn initial(&self) -> u32 { saturate(self.initial) }
 	/// Maximum size.
-	pub fn maximum(&self) -> Option<u32> { self.maximum }
+	///
+	/// Sizes of 64-bit memories which do not fit `u32` are saturated, see `maximum_64`.
+	pub fn maximum(&self) -> Option<u32> { self.maximum.map(saturate) }
+
+	#[cfg(feature = "memory64")]
+	/// Initial size, as encoded for 64-bit memories.
+	pub fn initial_64(&self) -> u64 { self.initial }
+
+	#[cfg(feature = "memory64")]
+	/// Maximum size, as encoded for 64-bit memories.
+	pub fn maximum_64(&self) -> Option<u64> { self.maximum }
 
 	#[cfg(feature = "atomics")]
 	/// Whether or not this is a shared array buffer.
 	pub fn shared(&self) -> bool { self.shared }
+
+	#[cfg(feature = "memory64")]
+	/// Whether or not this memory is indexed with 64-bit addresses.
+	pub fn is_64(&self) -> bool { self.is_64 }
 }
 
 impl Deserialize for ResizableLimits {
@@ -143,22 +240,33 @@ impl Deserialize for ResizableLimits {
 			#[cfg(feature="atomics")]
 			0x03 => {},
 
+			// Memory64 proposal introduces FLAG_MEMORY64 (0x04), which can be combined with
+			// FLAG_HAS_MAX and, if atomics are enabled as well, with FLAG_SHARED.
+			#[cfg(feature="memory64")]
+			0x04 | 0x05 => {},
+			#[cfg(all(feature="memory64", feature="atomics"))]
+			0x07 => {},
+
 			_ => return Err(Error::InvalidLimitsFlags(flags)),
 		}
 
-		let initial = VarUint32::deserialize(reader)?;
+		let is_64 = flags & FLAG_MEMORY64 != 0;
+		let initial = read_limit(reader, is_64)?;
 		let maximum = if flags & FLAG_HAS_MAX != 0 {
-			Some(VarUint32::deserialize(reader)?.into())
+			Some(read_limit(reader, is_64)?)
 		} else {
 			None
 		};
 
 		Ok(ResizableLimits {
-			initial: initial.into(),
+			initial: initial,
 			maximum: maximum,
 
 			#[cfg(feature="atomics")]
 			shared: flags & FLAG_SHARED != 0,
+
+			#[cfg(feature="memory64")]
+			is_64: is_64,
 		})
 	}
 }
@@ -180,17 +288,47 @@ impl Serialize for ResizableLimits {
 				flags |= FLAG_SHARED;
 			}
 		}
+
+		#[cfg(feature="memory64")]
+		let is_64 = self.is_64;
+		#[cfg(not(feature="memory64"))]
+		let is_64 = false;
+		if is_64 {
+			flags |= FLAG_MEMORY64;
+		}
 		Uint8::from(flags).serialize(writer)?;
-		VarUint32::from(self.initial).serialize(writer)?;
+		write_limit(writer, self.initial, is_64)?;
 		if let Some(max) = self.maximum {
-			VarUint32::from(max).serialize(writer)?;
+			write_limit(writer, max, is_64)?;
 		}
 		Ok(())
 	}
 }
 
+fn saturate(limit: u64) -> u32 {
+	limit.min(u32::MAX as u64) as u32
+}
+
+/// Limits of 64-bit memories are encoded as varuint64, others as varuint32.
+fn read_limit<R: io::Read>(reader: &mut R, is_64: bool) -> Result<u64, Error> {
+	if is_64 {
+		Ok(VarUint64::deserialize(reader)?.into())
+	} else {
+		Ok(u32::from(VarUint32::deserialize(reader)?) as u64)
+	}
+}
+
+fn write_limit<W: io::Write>(writer: &mut W, limit: u64, is_64: bool) -> Result<(), Error> {
+	if is_64 {
+		VarUint64::from(limit).serialize(writer)
+	} else {
+		VarUint32::from(limit as u32).serialize(writer)
+	}
+}
+
 /// Memory entry.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemoryType(ResizableLimits);
 
 impl MemoryType {
@@ -200,6 +338,14 @@ impl MemoryType {
 		MemoryType(r)
 	}
 
+	/// New 64-bit memory definition, whose size may exceed `u32` pages.
+	///
+	/// This is only available if the `memory64` feature is enabled.
+	#[cfg(feature = "memory64")]
+	pub fn new_64(min: u64, max: Option<u64>) -> Self {
+		MemoryType(ResizableLimits::new_64(min, max))
+	}
+
 	/// Set the `shared` flag that denotes a memory that can be shared between threads.
 	///
 	/// `false` by default. This is only available if the `atomics` feature is enabled.
@@ -208,6 +354,14 @@ impl MemoryType {
 		self.0.shared = shared;
 	}
 
+	/// Set the `is_64` flag that denotes a memory indexed with 64-bit addresses.
+	///
+	/// `false` by default. This is only available if the `memory64` feature is enabled.
+	#[cfg(feature = "memory64")]
+	pub fn set_is_64(&mut self, is_64: bool) {
+		self.0.is_64 = is_64;
+	}
+
 	/// Limits of the memory entry.
 	pub fn limits(&self) -> &ResizableLimits {
 		&self.0
@@ -232,6 +386,7 @@ impl Serialize for MemoryType {
 
 /// External to local binding.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum External {
 	/// Binds to a function whose type is associated with the given index in the
 	/// type section.
@@ -242,6 +397,9 @@ pub enum External {
 	Memory(MemoryType),
 	/// Describes local global entry to be imported as.
 	Global(GlobalType),
+	/// Describes exception tag to be imported as.
+	#[cfg(feature="exceptions")]
+	Tag(TagType),
 }
 
 impl Deserialize for External {
@@ -254,6 +412,8 @@ impl Deserialize for External {
 			0x01 => Ok(External::Table(TableType::deserialize(reader)?)),
 			0x02 => Ok(External::Memory(MemoryType::deserialize(reader)?)),
 			0x03 => Ok(External::Global(GlobalType::deserialize(reader)?)),
+			#[cfg(feature="exceptions")]
+			0x04 => Ok(External::Tag(TagType::deserialize(reader)?)),
 			_ => Err(Error::UnknownExternalKind(kind.into())),
 		}
 	}
@@ -282,6 +442,11 @@ impl Serialize for External {
 				VarInt7::from(0x03).serialize(writer)?;
 				gt.serialize(writer)?;
 			},
+			#[cfg(feature="exceptions")]
+			Tag(tt) => {
+				VarInt7::from(0x04).serialize(writer)?;
+				tt.serialize(writer)?;
+			},
 		}
 
 		Ok(())
@@ -290,6 +455,7 @@ impl Serialize for External {
 
 /// Import entry.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImportEntry {
 	module_str: String,
 	field_str: String,
@@ -354,3 +520,46 @@ impl Serialize for ImportEntry {
 		self.external.serialize(writer)
 	}
 }
+
+#[cfg(all(test, feature = "memory64"))]
+mod tests {
+	use super::*;
+	use super::super::{deserialize_buffer, serialize};
+
+	#[test]
+	fn memory64_limits() {
+		let mut memory = MemoryType::new(1, Some(0x10000));
+		memory.set_is_64(true);
+
+		let serialized = serialize(memory).expect("serialization to succeed");
+		assert_eq!(serialized, vec![0x05, 0x01, 0x80, 0x80, 0x04]);
+
+		let deserialized: MemoryType = deserialize_buffer(&serialized).expect("deserialization to succeed");
+		assert!(deserialized.limits().is_64());
+		assert_eq!(deserialized.limits().maximum(), Some(0x10000));
+	}
+
+	#[test]
+	fn memory64_large_limits() {
+		let memory = MemoryType::new_64(0x1_0000_0000, None);
+		let serialized = serialize(memory).expect("serialization to succeed");
+		assert_eq!(serialized, vec![0x04, 0x80, 0x80, 0x80, 0x80, 0x10]);
+
+		let deserialized: MemoryType = deserialize_buffer(&serialized).expect("deserialization to succeed");
+		assert_eq!(deserialized, memory);
+		assert_eq!(deserialized.limits().initial_64(), 0x1_0000_0000);
+		assert_eq!(deserialized.limits().initial(), u32::MAX);
+
+		// Limits of 32-bit memories are still varuint32.
+		assert!(deserialize_buffer::<MemoryType>(&[0x00, 0x80, 0x80, 0x80, 0x80, 0x10]).is_err());
+	}
+
+	#[test]
+	fn memory64_table_rejected() {
+		let result = deserialize_buffer::<TableType>(&[0x70, 0x04, 0x01]);
+		match result {
+			Err(Error::InvalidLimitsFlags(0x04)) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+}
@@ -1,3 +1,9 @@
+#![allow(
+	clippy::legacy_numeric_constants,
+	clippy::needless_borrowed_reference,
+	clippy::while_let_on_iterator,
+)]
+
 use alloc::vec::Vec;
 use crate::io;
 
@@ -19,6 +25,7 @@ use core::{
 /// happen in normal data. It would be pretty easy to provide a safe
 /// deserializing mechanism which addressed this problem.
 #[derive(Debug, Default)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IndexMap<T> {
 	/// The number of non-`None` entries in this map.
 	len: usize,
@@ -133,7 +140,7 @@ impl<T> IndexMap<T> {
 	}
 
 	/// Create a non-consuming iterator over this `IndexMap`'s keys and values.
-	pub fn iter(&self) -> Iter<T> {
+	pub fn iter(&self) -> Iter<'_, T> {
 		// Note that this does the right thing because we use `&self`.
 		self.into_iter()
 	}
@@ -157,7 +164,9 @@ impl<T> IndexMap<T> {
 		F: Fn(u32, &mut R) -> Result<T, Error>,
 	{
 		let len: u32 = VarUint32::deserialize(rdr)?.into();
-		let mut map = IndexMap::with_capacity(len as usize);
+		// Every index is checked against `max_entry_space` below, so there is no point
+		// in trusting `len` beyond it.
+		let mut map = IndexMap::with_capacity(min(len as usize, max_entry_space));
 		let mut prev_idx = None;
 		for _ in 0..len {
 			let idx: u32 = VarUint32::deserialize(rdr)?.into();
@@ -0,0 +1,215 @@
+use super::{Module, External, Internal, FunctionType, TableType, MemoryType, GlobalType, Type};
+
+/// Type of an import or export, with indices resolved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExternType<'a> {
+	/// Function of this signature.
+	Function(&'a FunctionType),
+	/// Table of this type.
+	Table(&'a TableType),
+	/// Memory of this type.
+	Memory(&'a MemoryType),
+	/// Global of this type.
+	Global(&'a GlobalType),
+	/// Exception tag whose payload is the parameters of this signature.
+	#[cfg(feature="exceptions")]
+	Tag(&'a FunctionType),
+}
+
+/// Import with its type resolved, as yielded by `Module::imports`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResolvedImport<'a> {
+	/// Module name of the import.
+	pub module: &'a str,
+	/// Field name of the import.
+	pub field: &'a str,
+	/// Index of the import in the index space of its kind.
+	pub index: u32,
+	/// Type of the import, `None` if its signature does not exist.
+	pub ty: Option<ExternType<'a>>,
+}
+
+/// Export with its type resolved, as yielded by `Module::exports`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResolvedExport<'a> {
+	/// Field name of the export.
+	pub field: &'a str,
+	/// Exported item.
+	pub internal: Internal,
+	/// Type of the exported item, `None` if the item or its signature does not exist.
+	pub ty: Option<ExternType<'a>>,
+}
+
+impl Module {
+	/// Function type `type_ref` of the type section, if any.
+	pub(crate) fn signature(&self, type_ref: u32) -> Option<&FunctionType> {
+		match *self.type_section()?.types().get(type_ref as usize)? {
+			Type::Function(ref func_type) => Some(func_type),
+		}
+	}
+
+	/// Entry `index` of an index space: the imports matched by `select`, then the `defined` entries.
+	fn nth_in_space<'a, T, I, F>(&'a self, index: u32, select: F, defined: I) -> Option<T>
+		where F: Fn(&'a External) -> Option<T>, I: IntoIterator<Item=T>
+	{
+		self.import_section().map(|s| s.entries()).unwrap_or(&[]).iter()
+			.filter_map(|entry| select(entry.external()))
+			.chain(defined)
+			.nth(index as usize)
+	}
+
+	/// Type index of the function with the given index in the function space (imported
+	/// functions first), if any.
+	pub(crate) fn function_type_ref(&self, index: u32) -> Option<u32> {
+		self.nth_in_space(
+			index,
+			|external| match *external { External::Function(type_ref) => Some(type_ref), _ => None },
+			self.function_section().map(|s| s.entries()).unwrap_or(&[]).iter().map(|func| func.type_ref()),
+		)
+	}
+
+	/// Type of the table with the given index in the table space (imported tables first), if any.
+	pub fn table_type(&self, index: u32) -> Option<&TableType> {
+		self.nth_in_space(
+			index,
+			|external| match *external { External::Table(ref table) => Some(table), _ => None },
+			self.table_section().map(|s| s.entries()).unwrap_or(&[]),
+		)
+	}
+
+	/// Type of the memory with the given index in the memory space (imported memories first), if any.
+	pub fn memory_type(&self, index: u32) -> Option<&MemoryType> {
+		self.nth_in_space(
+			index,
+			|external| match *external { External::Memory(ref memory) => Some(memory), _ => None },
+			self.memory_section().map(|s| s.entries()).unwrap_or(&[]),
+		)
+	}
+
+	/// Type of the global with the given index in the global space (imported globals first), if any.
+	pub fn global_type(&self, index: u32) -> Option<&GlobalType> {
+		self.nth_in_space(
+			index,
+			|external| match *external { External::Global(ref global) => Some(global), _ => None },
+			self.global_section().map(|s| s.entries()).unwrap_or(&[]).iter().map(|entry| entry.global_type()),
+		)
+	}
+
+	/// Signature of the tag with the given index in the tag space (imported tags first), if any.
+	#[cfg(feature="exceptions")]
+	pub fn tag_type(&self, index: u32) -> Option<&FunctionType> {
+		let tag = self.nth_in_space(
+			index,
+			|external| match *external { External::Tag(ref tag) => Some(tag), _ => None },
+			self.tag_section().map(|s| s.entries()).unwrap_or(&[]),
+		)?;
+		self.signature(tag.type_ref())
+	}
+
+	/// Imports with their types resolved, in module order.
+	pub fn imports(&self) -> impl Iterator<Item=ResolvedImport<'_>> {
+		let mut counts = [0u32; 5];
+		self.import_section().map(|s| s.entries()).unwrap_or(&[]).iter().map(move |entry| {
+			let (kind, ty) = match *entry.external() {
+				External::Function(type_ref) => (0, self.signature(type_ref).map(ExternType::Function)),
+				External::Table(ref table) => (1, Some(ExternType::Table(table))),
+				External::Memory(ref memory) => (2, Some(ExternType::Memory(memory))),
+				External::Global(ref global) => (3, Some(ExternType::Global(global))),
+				#[cfg(feature="exceptions")]
+				External::Tag(ref tag) => (4, self.signature(tag.type_ref()).map(ExternType::Tag)),
+			};
+			let index = counts[kind];
+			counts[kind] += 1;
+			ResolvedImport { module: entry.module(), field: entry.field(), index, ty }
+		})
+	}
+
+	/// Exports with the types of the exported items resolved, in module order.
+	pub fn exports(&self) -> impl Iterator<Item=ResolvedExport<'_>> {
+		self.export_section().map(|s| s.entries()).unwrap_or(&[]).iter().map(move |entry| {
+			let internal = *entry.internal();
+			let ty = match internal {
+				Internal::Function(index) => self.function_type(index).map(ExternType::Function),
+				Internal::Table(index) => self.table_type(index).map(ExternType::Table),
+				Internal::Memory(index) => self.memory_type(index).map(ExternType::Memory),
+				Internal::Global(index) => self.global_type(index).map(ExternType::Global),
+				#[cfg(feature="exceptions")]
+				Internal::Tag(index) => self.tag_type(index).map(ExternType::Tag),
+			};
+			ResolvedExport { field: entry.field(), internal, ty }
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ExternType;
+	use crate::builder;
+	use crate::elements::{ValueType, Internal};
+	use crate::fixtures;
+
+	#[test]
+	fn exports() {
+		let module = fixtures::exports_each_kind();
+		let exports: Vec<_> = module.exports().collect();
+		assert_eq!(exports.len(), 4);
+		match exports[0].ty {
+			Some(ExternType::Function(signature)) => assert!(signature.params().is_empty()),
+			ref other => panic!("unexpected type {:?}", other),
+		}
+		match exports[2].ty {
+			Some(ExternType::Memory(memory)) => assert_eq!(memory.limits().initial(), 1),
+			ref other => panic!("unexpected type {:?}", other),
+		}
+		match exports[3].ty {
+			Some(ExternType::Global(global)) => assert_eq!(global.content_type(), ValueType::I32),
+			ref other => panic!("unexpected type {:?}", other),
+		}
+	}
+
+	#[test]
+	fn imports() {
+		let module = builder::module()
+			.import().module("env").field("memory").external().memory(1, None).build()
+			.import().module("env").field("log").external().func(0).build()
+			.import().module("env").field("base").external().global(ValueType::I64, false).build()
+			.import().module("env").field("abort").external().func(0).build()
+			.function().signature().with_param(ValueType::I32).build().body().build().build()
+			.export().field("log").internal().func(0).build()
+			.build();
+
+		let imports: Vec<_> = module.imports().map(|import| (import.field, import.index)).collect();
+		assert_eq!(imports, vec![("memory", 0), ("log", 0), ("base", 0), ("abort", 1)]);
+		match module.imports().nth(3).and_then(|import| import.ty) {
+			Some(ExternType::Function(signature)) => assert_eq!(signature.params(), &[ValueType::I32]),
+			ref other => panic!("unexpected type {:?}", other),
+		}
+
+		let export = module.exports().next().expect("export");
+		assert_eq!(export.internal, Internal::Function(0));
+		assert!(export.ty.is_some());
+		assert_eq!(module.global_type(0).map(|global| global.content_type()), Some(ValueType::I64));
+		assert_eq!(module.global_type(1), None);
+	}
+
+	#[test]
+	fn mutable_globals() {
+		// Mutable globals may be imported and exported, as Emscripten does with the stack pointer.
+		let module = builder::module()
+			.import().module("env").field("__stack_pointer").external().global(ValueType::I32, true).build()
+			.global().mutable().with_type(ValueType::I64).init_i64(0).build()
+			.export().field("__stack_pointer").internal().global(0).build()
+			.export().field("counter").internal().global(1).build()
+			.build();
+		let bytes = crate::elements::serialize(module).expect("module to serialize");
+		let module: crate::elements::Module = crate::elements::deserialize_buffer(&bytes).expect("module to deserialize");
+
+		let mutable: Vec<_> = module.exports()
+			.map(|export| match export.ty {
+				Some(ExternType::Global(global)) => global.is_mutable(),
+				ref other => panic!("unexpected type {:?}", other),
+			})
+			.collect();
+		assert_eq!(mutable, vec![true, true]);
+	}
+}
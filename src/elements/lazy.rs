@@ -0,0 +1,259 @@
+use alloc::vec::Vec;
+use crate::io;
+use super::{Deserialize, Error, Module, Section, CodeSection, FuncBody, VarUint7, VarUint32};
+use super::module::{ModuleReader, check_section_order};
+
+const CODE_SECTION_ID: u8 = 10;
+const CODE_SECTION_ORDER: u8 = 0x0b;
+
+/// Function body left undecoded, borrowed from the input buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LazyFuncBody<'a> {
+	section: usize,
+	offset: usize,
+	bytes: &'a [u8],
+}
+
+impl<'a> LazyFuncBody<'a> {
+	/// Offset of the body in the input buffer, past its size prefix.
+	pub fn offset(&self) -> usize { self.offset }
+
+	/// Raw body: local declarations followed by the instructions.
+	pub fn bytes(&self) -> &'a [u8] { self.bytes }
+
+	/// Decode the body.
+	pub fn decode(&self) -> Result<FuncBody, Error> {
+		let mut reader = io::Cursor::new(self.bytes);
+		let body = FuncBody::deserialize_payload(&mut reader)?;
+		if reader.position() != self.bytes.len() {
+			return Err(io::Error::InvalidData.into());
+		}
+		Ok(body)
+	}
+
+	/// Decode the body, recording the offsets of its instructions in the input buffer.
+	pub fn decode_with_locations(&self) -> Result<FuncBody, Error> {
+		let mut reader = io::Cursor::new(self.bytes);
+		let body = FuncBody::deserialize_payload_with_locations(&mut reader, self.section, self.offset)?;
+		if reader.position() != self.bytes.len() {
+			return Err(io::Error::InvalidData.into());
+		}
+		Ok(body)
+	}
+}
+
+/// Module whose function bodies are decoded only on demand.
+///
+/// Every section but the code section is decoded as usual; function bodies
+/// are only delimited and kept as ranges of the input buffer. This makes
+/// inspecting imports, exports or signatures of a module cheap regardless of
+/// the amount of code it contains.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LazyModule<'a> {
+	module: Module,
+	code_position: Option<usize>,
+	bodies: Vec<LazyFuncBody<'a>>,
+}
+
+impl<'a> LazyModule<'a> {
+	/// Module with every section except the code section.
+	pub fn module(&self) -> &Module { &self.module }
+
+	/// Undecoded function bodies, in the code section order.
+	pub fn bodies(&self) -> &[LazyFuncBody<'a>] { &self.bodies }
+
+	/// Decode all function bodies, producing the complete module.
+	pub fn into_module(self) -> Result<Module, Error> {
+		self.into_module_with(|body| body.decode())
+	}
+
+	/// Decode all function bodies with `decode`, producing the complete module.
+	pub(crate) fn into_module_with<F>(self, mut decode: F) -> Result<Module, Error>
+		where F: FnMut(&LazyFuncBody<'a>) -> Result<FuncBody, Error>
+	{
+		let mut module = self.module;
+		if let Some(position) = self.code_position {
+			let bodies = self.bodies.iter().map(&mut decode).collect::<Result<Vec<_>, _>>()?;
+			module.sections_mut().insert(position, Section::Code(CodeSection::with_bodies(bodies)));
+		}
+		Ok(module)
+	}
+
+	/// Decode all function bodies on up to `threads` threads, producing the complete module.
+	///
+	/// Bodies are split into contiguous chunks, one per thread. If any body fails to decode,
+	/// the error of the first such body is returned.
+	#[cfg(feature="parallel")]
+	pub fn into_module_parallel(self, threads: usize) -> Result<Module, Error> {
+		let mut module = self.module;
+		if let Some(position) = self.code_position {
+			let lazy_bodies = self.bodies;
+			let chunk_size = lazy_bodies.len().div_ceil(threads.max(1)).max(1);
+			let chunks = std::thread::scope(|scope| {
+				let handles: Vec<_> = lazy_bodies
+					.chunks(chunk_size)
+					.map(|chunk| scope.spawn(move || {
+						chunk.iter().map(LazyFuncBody::decode).collect::<Result<Vec<_>, _>>()
+					}))
+					.collect();
+				handles
+					.into_iter()
+					.map(|handle| handle.join().expect("body decoding does not panic"))
+					.collect::<Vec<_>>()
+			});
+
+			let mut bodies = Vec::with_capacity(lazy_bodies.len());
+			for chunk in chunks {
+				bodies.extend(chunk?);
+			}
+			module.sections_mut().insert(position, Section::Code(CodeSection::with_bodies(bodies)));
+		}
+		Ok(module)
+	}
+}
+
+/// Deserialize module from buffer, leaving function bodies undecoded.
+pub fn deserialize_buffer_lazy<'a>(contents: &'a [u8]) -> Result<LazyModule<'a>, Error> {
+	let mut header = io::Cursor::new(contents);
+	ModuleReader::new(&mut header)?;
+
+	let mut position = header.position();
+	let mut last_section_order = 0;
+	let mut sections = Vec::new();
+	let mut code_position = None;
+	let mut bodies = Vec::new();
+
+	while position < contents.len() {
+		let mut reader = io::Cursor::new(&contents[position..]);
+		let id: u8 = VarUint7::deserialize(&mut reader)?.into();
+
+		if id == CODE_SECTION_ID {
+			check_section_order(&mut last_section_order, CODE_SECTION_ORDER)?;
+			let length: usize = VarUint32::deserialize(&mut reader)?.into();
+			let start = position + reader.position();
+			if length > contents.len() - start {
+				return Err(io::Error::UnexpectedEof.into());
+			}
+			position = start + length;
+			bodies = read_bodies(contents, start, position)?;
+			code_position = Some(sections.len());
+		} else {
+			let mut reader = io::Cursor::new(&contents[position..]);
+			let section = Section::deserialize(&mut reader)?;
+			check_section_order(&mut last_section_order, section.order())?;
+			sections.push(section);
+			position += reader.position();
+		}
+	}
+
+	let module = Module::new(sections);
+	if bodies.len() != module.function_section().map(|fs| fs.entries().len()).unwrap_or(0) {
+		return Err(Error::InconsistentCode);
+	}
+
+	Ok(LazyModule {
+		module,
+		code_position,
+		bodies,
+	})
+}
+
+fn read_bodies<'a>(contents: &'a [u8], start: usize, end: usize) -> Result<Vec<LazyFuncBody<'a>>, Error> {
+	let mut reader = io::Cursor::new(&contents[start..end]);
+	let count: u32 = VarUint32::deserialize(&mut reader)?.into();
+	let mut position = start + reader.position();
+
+	// The count is not trusted for preallocation, since every body takes at least a byte.
+	let mut bodies = Vec::new();
+	for _ in 0..count {
+		let mut reader = io::Cursor::new(&contents[position..end]);
+		let length: usize = VarUint32::deserialize(&mut reader)?.into();
+		let offset = position + reader.position();
+		if length > end - offset {
+			return Err(io::Error::UnexpectedEof.into());
+		}
+		bodies.push(LazyFuncBody { section: start, offset, bytes: &contents[offset..offset + length] });
+		position = offset + length;
+	}
+
+	if position != end {
+		return Err(io::Error::InvalidData.into());
+	}
+	Ok(bodies)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::deserialize_buffer_lazy;
+	use super::super::{deserialize_buffer, Module};
+
+	#[test]
+	fn lazy_matches_eager() {
+		let bytes = std::fs::read("./res/cases/v1/test5.wasm").expect("file to be read");
+		let module: Module = deserialize_buffer(&bytes).expect("module to be deserialized");
+
+		let lazy = deserialize_buffer_lazy(&bytes).expect("module to be deserialized lazily");
+		assert!(lazy.module().code_section().is_none());
+		assert_eq!(lazy.module().export_section(), module.export_section());
+
+		let bodies = module.code_section().expect("code section to be present").bodies();
+		assert_eq!(lazy.bodies().len(), bodies.len());
+		for (lazy_body, body) in lazy.bodies().iter().zip(bodies) {
+			assert_eq!(lazy_body.bytes(), &bytes[lazy_body.offset()..lazy_body.offset() + lazy_body.bytes().len()]);
+			assert_eq!(&lazy_body.decode().expect("body to be decoded"), body);
+		}
+
+		assert_eq!(lazy.into_module().expect("bodies to be decoded"), module);
+	}
+
+	#[cfg(feature="parallel")]
+	#[test]
+	fn parallel_matches_eager() {
+		let bytes = std::fs::read("./res/cases/v1/test5.wasm").expect("file to be read");
+		let module: Module = deserialize_buffer(&bytes).expect("module to be deserialized");
+
+		for threads in [0, 1, 2, 64] {
+			let lazy = deserialize_buffer_lazy(&bytes).expect("module to be deserialized lazily");
+			assert_eq!(lazy.into_module_parallel(threads).expect("bodies to be decoded"), module);
+		}
+	}
+
+	#[test]
+	fn lazy_malformed() {
+		let bytes = std::fs::read("./res/cases/v1/test5.wasm").expect("file to be read");
+		assert!(deserialize_buffer_lazy(&bytes[..bytes.len() - 1]).is_err());
+
+		for name in ["err-duplicate-section", "err-function-without-body", "err-section-too-long"] {
+			let bytes = std::fs::read(format!("./res/cases/v1/{}.wasm", name)).expect("file to be read");
+			assert!(deserialize_buffer_lazy(&bytes).is_err(), "{} should be rejected", name);
+		}
+	}
+
+	#[test]
+	fn code_locations() {
+		use crate::io;
+		use super::super::{deserialize_buffer_with_locations, Deserialize, Instruction};
+
+		let bytes = std::fs::read("./res/cases/v1/test5.wasm").expect("file to be read");
+		let module = deserialize_buffer_with_locations(&bytes).expect("module to be deserialized");
+		let eager: Module = deserialize_buffer(&bytes).expect("module to be deserialized");
+		assert_eq!(module, eager);
+
+		let lazy = deserialize_buffer_lazy(&bytes).expect("module to be deserialized lazily");
+		let bodies = module.code_section().expect("code section").bodies();
+		assert_eq!(bodies.len(), lazy.bodies().len());
+		for (body, lazy_body) in bodies.iter().zip(lazy.bodies()) {
+			let locations = body.code_locations().expect("locations to be recorded");
+			assert_eq!(locations.body(), lazy_body.offset());
+			assert_eq!(locations.instructions().len(), body.code().elements().len());
+			for (&offset, instruction) in locations.instructions().iter().zip(body.code().elements()) {
+				let mut reader = io::Cursor::new(&bytes[offset..]);
+				assert_eq!(&Instruction::deserialize(&mut reader).expect("instruction to decode"), instruction);
+			}
+		}
+
+		let mut body = bodies[0].clone();
+		body.code_mut();
+		assert!(body.code_locations().is_none());
+	}
+}
@@ -0,0 +1,352 @@
+use alloc::vec::Vec;
+use crate::io;
+use super::{
+	Error, Module, FuncBody, Instruction, Instructions, External, ImportCountType, Local, Type,
+	deserialize_buffer_lazy, opcodes,
+};
+use super::codec::Reader;
+use super::lazy::LazyFuncBody;
+
+/// Limits on the size of a module accepted when deserializing it.
+///
+/// The binary format lets a few bytes declare huge counts and sizes, which a consumer
+/// of the module may then be tricked into allocating for. These limits reject such
+/// modules early: the counts of types, imports, functions, globals, exports and data
+/// segments are checked before their sections are decoded. Function bodies are checked
+/// before being decoded, so that an oversized body is never decoded at all, and their
+/// locals, nesting and `br_table` targets while being decoded, before anything is
+/// allocated for them. A module exceeding any of them is rejected with
+/// `Error::LimitExceeded`, naming the limit.
+///
+/// The defaults follow the implementation limits of the WebAssembly JavaScript API,
+/// where there are ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParseLimits {
+	/// Maximum size of the whole module, in bytes.
+	pub max_module_size: u32,
+	/// Maximum number of types.
+	pub max_types: u32,
+	/// Maximum number of parameters of a single type.
+	pub max_params: u32,
+	/// Maximum number of results of a single type.
+	pub max_results: u32,
+	/// Maximum number of imports.
+	pub max_imports: u32,
+	/// Maximum number of exports.
+	pub max_exports: u32,
+	/// Maximum number of globals, imported ones included.
+	pub max_globals: u32,
+	/// Maximum number of data segments.
+	pub max_data_segments: u32,
+	/// Maximum number of functions, imported ones included.
+	pub max_functions: u32,
+	/// Maximum number of locals declared by a single function, parameters excluded.
+	pub max_locals: u32,
+	/// Maximum size of a single function body, in bytes.
+	pub max_body_size: u32,
+	/// Maximum initial size of a table, in elements.
+	pub max_table_initial: u32,
+	/// Maximum initial size of a memory, in pages.
+	pub max_memory_initial: u32,
+	/// Maximum number of targets of a single `br_table`.
+	pub max_br_table_targets: u32,
+	/// Maximum nesting depth of blocks within a function body.
+	pub max_nesting_depth: u32,
+}
+
+impl ParseLimits {
+	/// No limits besides the ones imposed by the binary format itself.
+	pub fn unlimited() -> Self {
+		ParseLimits {
+			max_module_size: u32::MAX,
+			max_types: u32::MAX,
+			max_params: u32::MAX,
+			max_results: u32::MAX,
+			max_imports: u32::MAX,
+			max_exports: u32::MAX,
+			max_globals: u32::MAX,
+			max_data_segments: u32::MAX,
+			max_functions: u32::MAX,
+			max_locals: u32::MAX,
+			max_body_size: u32::MAX,
+			max_table_initial: u32::MAX,
+			max_memory_initial: u32::MAX,
+			max_br_table_targets: u32::MAX,
+			max_nesting_depth: u32::MAX,
+		}
+	}
+
+	/// Deserialize module from serialized bytes, rejecting it as soon as it exceeds these limits.
+	pub fn deserialize_buffer(&self, contents: &[u8]) -> Result<Module, Error> {
+		require(contents.len() as u64 <= self.max_module_size as u64, "max_module_size")?;
+		self.check_counts(contents)?;
+		let lazy = deserialize_buffer_lazy(contents)?;
+		self.check_module(lazy.module(), lazy.bodies().len())?;
+		lazy.into_module_with(|body| self.decode_body(body))
+	}
+
+	/// Check the counts leading the vector sections before anything is allocated for them.
+	///
+	/// Malformed modules are left for the decoding to report, so the scan stops at the
+	/// first section it can't read.
+	fn check_counts(&self, contents: &[u8]) -> Result<(), Error> {
+		let mut reader = Reader::new(contents.get(8..).unwrap_or(&[]));
+		while !reader.is_empty() {
+			let (id, payload) = match reader.subsection() {
+				Ok(section) => section,
+				Err(_) => break,
+			};
+			let count = match Reader::new(payload).var_u32() {
+				Ok(count) => count,
+				Err(_) => break,
+			};
+			match id {
+				1 => require(count <= self.max_types, "max_types")?,
+				2 => require(count <= self.max_imports, "max_imports")?,
+				3 | 10 => require(count <= self.max_functions, "max_functions")?,
+				6 => require(count <= self.max_globals, "max_globals")?,
+				7 => require(count <= self.max_exports, "max_exports")?,
+				11 => require(count <= self.max_data_segments, "max_data_segments")?,
+				_ => {},
+			}
+		}
+		Ok(())
+	}
+
+	fn check_module(&self, module: &Module, bodies: usize) -> Result<(), Error> {
+		let functions = module.import_count(ImportCountType::Function) + bodies;
+		require(functions as u64 <= self.max_functions as u64, "max_functions")?;
+		require(within(module.globals_space(), self.max_globals), "max_globals")?;
+		require(within(module.import_section().map_or(0, |s| s.entries().len()), self.max_imports), "max_imports")?;
+		require(within(module.export_section().map_or(0, |s| s.entries().len()), self.max_exports), "max_exports")?;
+		require(within(module.data_section().map_or(0, |s| s.entries().len()), self.max_data_segments), "max_data_segments")?;
+
+		if let Some(type_section) = module.type_section() {
+			require(within(type_section.types().len(), self.max_types), "max_types")?;
+			for Type::Function(func_type) in type_section.types() {
+				require(within(func_type.params().len(), self.max_params), "max_params")?;
+				require(within(func_type.results().len(), self.max_results), "max_results")?;
+			}
+		}
+
+		if let Some(import_section) = module.import_section() {
+			for entry in import_section.entries() {
+				match *entry.external() {
+					External::Table(ref table) =>
+						require(table.limits().initial() <= self.max_table_initial, "max_table_initial")?,
+					External::Memory(ref memory) =>
+						require(memory.limits().initial() <= self.max_memory_initial, "max_memory_initial")?,
+					_ => {},
+				}
+			}
+		}
+		if let Some(table_section) = module.table_section() {
+			for table in table_section.entries() {
+				require(table.limits().initial() <= self.max_table_initial, "max_table_initial")?;
+			}
+		}
+		if let Some(memory_section) = module.memory_section() {
+			for memory in memory_section.entries() {
+				require(memory.limits().initial() <= self.max_memory_initial, "max_memory_initial")?;
+			}
+		}
+		Ok(())
+	}
+
+	fn decode_body(&self, body: &LazyFuncBody) -> Result<FuncBody, Error> {
+		require(body.bytes().len() as u64 <= self.max_body_size as u64, "max_body_size")?;
+		let mut reader = Reader::new(body.bytes());
+
+		let mut locals = Vec::new();
+		let mut local_count = 0u32;
+		for _ in 0..reader.var_u32()? {
+			let local: Local = reader.read()?;
+			local_count = local_count.checked_add(local.count()).ok_or(Error::TooManyLocals)?;
+			require(local_count <= self.max_locals, "max_locals")?;
+			locals.push(local);
+		}
+
+		let mut instructions = Vec::new();
+		let mut depth = 0u32;
+		loop {
+			// Targets are checked before being decoded.
+			let mut peek = Reader::new(reader.remaining());
+			if peek.u8().ok() == Some(opcodes::BRTABLE) {
+				if let Ok(targets) = peek.var_u32() {
+					require(targets <= self.max_br_table_targets, "max_br_table_targets")?;
+				}
+			}
+
+			let instruction: Instruction = reader.read()?;
+			if instruction.is_block() {
+				depth += 1;
+				require(depth <= self.max_nesting_depth, "max_nesting_depth")?;
+			} else if instruction.is_terminal() {
+				if depth == 0 {
+					instructions.push(instruction);
+					break;
+				}
+				depth -= 1;
+			}
+			instructions.push(instruction);
+		}
+		if !reader.is_empty() {
+			return Err(io::Error::InvalidData.into());
+		}
+		Ok(FuncBody::new(locals, Instructions::new(instructions)))
+	}
+}
+
+impl Default for ParseLimits {
+	fn default() -> Self {
+		ParseLimits {
+			max_module_size: 1_073_741_824,
+			max_types: 1_000_000,
+			max_params: 1_000,
+			max_results: 1_000,
+			max_imports: 100_000,
+			max_exports: 100_000,
+			max_globals: 1_000_000,
+			max_data_segments: 100_000,
+			max_functions: 1_000_000,
+			max_locals: 50_000,
+			max_body_size: 7_654_321,
+			max_table_initial: 10_000_000,
+			max_memory_initial: 65_536,
+			max_br_table_targets: 65_520,
+			max_nesting_depth: 1_024,
+		}
+	}
+}
+
+fn within(count: usize, limit: u32) -> bool {
+	count as u64 <= limit as u64
+}
+
+fn require(within_limit: bool, limit: &'static str) -> Result<(), Error> {
+	if within_limit {
+		Ok(())
+	} else {
+		Err(Error::LimitExceeded(limit))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ParseLimits;
+	use super::super::{
+		deserialize_buffer, deserialize_buffer_with_limits, Error, Module,
+	};
+	use crate::{builder, fixtures};
+
+	fn nested_blocks(depth: usize) -> Vec<u8> {
+		fixtures::nested_blocks(depth).to_bytes().expect("serialization to succeed")
+	}
+
+	#[test]
+	fn default_limits_accept_regular_module() {
+		let bytes = std::fs::read("./res/cases/v1/test5.wasm").expect("file to be read");
+		let module: Module = deserialize_buffer(&bytes).expect("module to be deserialized");
+		let limited = deserialize_buffer_with_limits(&bytes, &ParseLimits::default())
+			.expect("module to be within limits");
+		assert_eq!(limited, module);
+	}
+
+	#[test]
+	fn nesting_depth() {
+		let limits = ParseLimits { max_nesting_depth: 4, ..ParseLimits::unlimited() };
+		assert!(deserialize_buffer_with_limits(&nested_blocks(4), &limits).is_ok());
+		match deserialize_buffer_with_limits(&nested_blocks(5), &limits) {
+			Err(Error::LimitExceeded("max_nesting_depth")) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn memory_initial() {
+		let bytes = fixtures::memory(2).to_bytes().expect("serialization to succeed");
+		let limits = ParseLimits { max_memory_initial: 1, ..ParseLimits::unlimited() };
+		match deserialize_buffer_with_limits(&bytes, &limits) {
+			Err(Error::LimitExceeded("max_memory_initial")) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn locals() {
+		use super::super::{Local, ValueType};
+
+		let bytes = builder::module()
+			.function()
+				.signature().build()
+				.body().with_locals(vec![Local::new(50_001, ValueType::I32)]).build()
+				.build()
+			.build()
+			.to_bytes()
+			.expect("serialization to succeed");
+		match deserialize_buffer_with_limits(&bytes, &ParseLimits::default()) {
+			Err(Error::LimitExceeded("max_locals")) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn br_table_targets() {
+		use super::super::{BrTableData, Instruction::*, Instructions};
+
+		let bytes = builder::module()
+			.function()
+				.signature().build()
+				.body().with_instructions(Instructions::new(vec![
+					I32Const(0), BrTable(Box::new(BrTableData::new(vec![0; 10], 0))), End,
+				])).build()
+				.build()
+			.build()
+			.to_bytes()
+			.expect("serialization to succeed");
+		let limits = |max_br_table_targets| ParseLimits { max_br_table_targets, ..ParseLimits::unlimited() };
+		assert!(deserialize_buffer_with_limits(&bytes, &limits(10)).is_ok());
+		match deserialize_buffer_with_limits(&bytes, &limits(9)) {
+			Err(Error::LimitExceeded("max_br_table_targets")) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn module_limits() {
+		use super::super::ValueType;
+
+		let bytes = builder::module()
+			.function().signature().with_params(vec![ValueType::I32; 3]).build().body().build().build()
+			.export().field("a").internal().func(0).build()
+			.export().field("b").internal().func(0).build()
+			.build()
+			.to_bytes()
+			.expect("serialization to succeed");
+		assert!(deserialize_buffer_with_limits(&bytes, &ParseLimits::default()).is_ok());
+
+		let exceeded = |limits: ParseLimits| match deserialize_buffer_with_limits(&bytes, &limits) {
+			Err(Error::LimitExceeded(limit)) => limit,
+			other => panic!("unexpected result: {:?}", other),
+		};
+		assert_eq!(exceeded(ParseLimits { max_params: 2, ..ParseLimits::unlimited() }), "max_params");
+		assert_eq!(exceeded(ParseLimits { max_exports: 1, ..ParseLimits::unlimited() }), "max_exports");
+		assert_eq!(exceeded(ParseLimits { max_module_size: 16, ..ParseLimits::unlimited() }), "max_module_size");
+	}
+
+	#[test]
+	fn counts_before_decoding() {
+		// A type section declaring 1000 types, of which none follow.
+		let mut bytes = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x02, 0xe8, 0x07];
+		let limits = ParseLimits { max_types: 999, ..ParseLimits::unlimited() };
+		match deserialize_buffer_with_limits(&bytes, &limits) {
+			Err(Error::LimitExceeded("max_types")) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+		assert!(matches!(deserialize_buffer_with_limits(&bytes, &ParseLimits::unlimited()), Err(Error::UnexpectedEof)));
+
+		// Malformed sections are left for decoding to report.
+		bytes.truncate(11);
+		assert!(matches!(deserialize_buffer_with_limits(&bytes, &limits), Err(Error::UnexpectedEof)));
+	}
+}
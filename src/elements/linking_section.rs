@@ -0,0 +1,600 @@
+use alloc::{string::String, vec::Vec};
+use crate::io;
+
+use super::{CountedList, CountedListWriter, Deserialize, Error, Serialize, Uint8, VarUint32, VarUint64};
+use super::codec::{counted, parse_subsection, read_subsections, Reader, Writer};
+
+const LINKING_VERSION: u32 = 2;
+
+const WASM_SEGMENT_INFO: u8 = 5;
+const WASM_INIT_FUNCS: u8 = 6;
+const WASM_COMDAT_INFO: u8 = 7;
+const WASM_SYMBOL_TABLE: u8 = 8;
+
+const SYMTAB_FUNCTION: u8 = 0;
+const SYMTAB_DATA: u8 = 1;
+const SYMTAB_GLOBAL: u8 = 2;
+const SYMTAB_SECTION: u8 = 3;
+const SYMTAB_TAG: u8 = 4;
+const SYMTAB_TABLE: u8 = 5;
+
+const COMDAT_DATA: u8 = 0;
+const COMDAT_FUNCTION: u8 = 1;
+const COMDAT_GLOBAL: u8 = 2;
+const COMDAT_TAG: u8 = 3;
+const COMDAT_TABLE: u8 = 4;
+const COMDAT_SECTION: u8 = 5;
+
+/// Linking metadata of a relocatable object file, as emitted by LLVM (version 2).
+///
+/// Subsections are kept in the order they were read in, so that a parsed section
+/// serializes back into the same bytes.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkingSection {
+	/// Subsections of this section.
+	subsections: Vec<LinkingSubsection>,
+}
+
+impl LinkingSection {
+	/// Creates a new linking section.
+	pub fn new(subsections: Vec<LinkingSubsection>) -> Self {
+		LinkingSection { subsections }
+	}
+
+	/// Subsections of this section.
+	pub fn subsections(&self) -> &[LinkingSubsection] {
+		&self.subsections
+	}
+
+	/// Subsections of this section (mutable).
+	pub fn subsections_mut(&mut self) -> &mut Vec<LinkingSubsection> {
+		&mut self.subsections
+	}
+
+	/// Symbol table, if there is one.
+	pub fn symbol_table(&self) -> Option<&[SymbolInfo]> {
+		self.subsections.iter().find_map(|subsection| match *subsection {
+			LinkingSubsection::SymbolTable(ref symbols) => Some(&symbols[..]),
+			_ => None,
+		})
+	}
+
+	/// Symbol table, if there is one (mutable).
+	pub fn symbol_table_mut(&mut self) -> Option<&mut Vec<SymbolInfo>> {
+		self.subsections.iter_mut().find_map(|subsection| match *subsection {
+			LinkingSubsection::SymbolTable(ref mut symbols) => Some(symbols),
+			_ => None,
+		})
+	}
+}
+
+impl LinkingSection {
+	/// Deserialize a linking section.
+	pub fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Error> {
+		let version: u32 = VarUint32::deserialize(rdr)?.into();
+		if version != LINKING_VERSION {
+			return Err(Error::UnsupportedLinkingVersion(version));
+		}
+
+		let mut subsections = Vec::new();
+		for (id, payload) in read_subsections(rdr)? {
+			subsections.push(match id {
+				WASM_SEGMENT_INFO => LinkingSubsection::SegmentInfo(parse_subsection(&payload, Reader::vec)?),
+				WASM_INIT_FUNCS => LinkingSubsection::InitFuncs(parse_subsection(&payload, Reader::vec)?),
+				WASM_COMDAT_INFO => LinkingSubsection::ComdatInfo(parse_subsection(&payload, Reader::vec)?),
+				WASM_SYMBOL_TABLE => LinkingSubsection::SymbolTable(parse_subsection(&payload, Reader::vec)?),
+				id => LinkingSubsection::Unknown { id, payload },
+			});
+		}
+
+		Ok(LinkingSection { subsections })
+	}
+}
+
+impl Serialize for LinkingSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		let mut section = Writer::new();
+		section.var_u32(LINKING_VERSION);
+		for subsection in self.subsections {
+			match subsection {
+				LinkingSubsection::SegmentInfo(segments) => section.subsection(WASM_SEGMENT_INFO, &counted(segments)?)?,
+				LinkingSubsection::InitFuncs(init_funcs) => section.subsection(WASM_INIT_FUNCS, &counted(init_funcs)?)?,
+				LinkingSubsection::ComdatInfo(comdats) => section.subsection(WASM_COMDAT_INFO, &counted(comdats)?)?,
+				LinkingSubsection::SymbolTable(symbols) => section.subsection(WASM_SYMBOL_TABLE, &counted(symbols)?)?,
+				LinkingSubsection::Unknown { id, payload } => section.subsection(id, &payload)?,
+			};
+		}
+		wtr.write(section.as_slice())?;
+		Ok(())
+	}
+}
+
+/// Subsection of the linking section.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LinkingSubsection {
+	/// Names, alignments and flags of the data segments.
+	SegmentInfo(Vec<SegmentInfo>),
+	/// Functions to call on startup.
+	InitFuncs(Vec<InitFunc>),
+	/// Groups of items of which the linker keeps one copy only.
+	ComdatInfo(Vec<Comdat>),
+	/// Symbols referred to by relocations.
+	SymbolTable(Vec<SymbolInfo>),
+	/// Subsection this crate does not know about, kept as is.
+	Unknown {
+		/// Subsection type.
+		id: u8,
+		/// Raw bytes of the subsection.
+		payload: Vec<u8>,
+	},
+}
+
+/// Entry of the symbol table.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SymbolInfo {
+	/// Combination of the `SymbolInfo::*` flags.
+	pub flags: u32,
+	/// Kind of the symbol, with the item it refers to.
+	pub kind: SymbolKind,
+}
+
+impl SymbolInfo {
+	/// Symbol is weak: a strong definition elsewhere takes precedence.
+	pub const BINDING_WEAK: u32 = 0x01;
+	/// Symbol is local to the object file.
+	pub const BINDING_LOCAL: u32 = 0x02;
+	/// Symbol is not exported from the linked module.
+	pub const VISIBILITY_HIDDEN: u32 = 0x04;
+	/// Symbol is not defined by this object file.
+	pub const UNDEFINED: u32 = 0x10;
+	/// Symbol is exported from the linked module.
+	pub const EXPORTED: u32 = 0x20;
+	/// Undefined symbol with a name of its own rather than the one of its import.
+	pub const EXPLICIT_NAME: u32 = 0x40;
+	/// Symbol is kept even if nothing refers to it.
+	pub const NO_STRIP: u32 = 0x80;
+
+	/// Creates a new symbol.
+	pub fn new(flags: u32, kind: SymbolKind) -> Self {
+		SymbolInfo { flags, kind }
+	}
+
+	/// Whether the symbol is defined elsewhere.
+	pub fn is_undefined(&self) -> bool {
+		self.flags & Self::UNDEFINED != 0
+	}
+
+	/// Name of the symbol, if it has one.
+	pub fn name(&self) -> Option<&str> {
+		match self.kind {
+			SymbolKind::Function { ref name, .. } |
+			SymbolKind::Global { ref name, .. } |
+			SymbolKind::Tag { ref name, .. } |
+			SymbolKind::Table { ref name, .. } => name.as_ref().map(String::as_str),
+			SymbolKind::Data { ref name, .. } => Some(name),
+			SymbolKind::Section { .. } => None,
+		}
+	}
+
+	fn has_name(flags: u32) -> bool {
+		flags & Self::UNDEFINED == 0 || flags & Self::EXPLICIT_NAME != 0
+	}
+}
+
+/// Kind of a symbol and the item it refers to.
+///
+/// Names of function, global, tag and table symbols are present exactly when the
+/// symbol is defined or has the `EXPLICIT_NAME` flag, and data symbols have a
+/// definition exactly when they are defined; serialization fails otherwise.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SymbolKind {
+	/// Function symbol.
+	Function {
+		/// Index in the function index space.
+		index: u32,
+		/// Name of the symbol.
+		name: Option<String>,
+	},
+	/// Data symbol.
+	Data {
+		/// Name of the symbol.
+		name: String,
+		/// Location of the data, if it is defined.
+		definition: Option<DataDefinition>,
+	},
+	/// Global symbol.
+	Global {
+		/// Index in the global index space.
+		index: u32,
+		/// Name of the symbol.
+		name: Option<String>,
+	},
+	/// Section symbol, used by relocations of debug sections.
+	Section {
+		/// Index of the section.
+		index: u32,
+	},
+	/// Tag symbol.
+	Tag {
+		/// Index in the tag index space.
+		index: u32,
+		/// Name of the symbol.
+		name: Option<String>,
+	},
+	/// Table symbol.
+	Table {
+		/// Index in the table index space.
+		index: u32,
+		/// Name of the symbol.
+		name: Option<String>,
+	},
+}
+
+/// Location of a defined data symbol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DataDefinition {
+	/// Index of the data segment.
+	pub segment: u32,
+	/// Offset within the segment.
+	pub offset: u64,
+	/// Size of the data.
+	pub size: u64,
+}
+
+impl Deserialize for SymbolInfo {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		let kind: u8 = Uint8::deserialize(rdr)?.into();
+		let flags: u32 = VarUint32::deserialize(rdr)?.into();
+
+		let indexed = |rdr: &mut R| -> Result<(u32, Option<String>), Error> {
+			let index = VarUint32::deserialize(rdr)?.into();
+			let name = if Self::has_name(flags) { Some(String::deserialize(rdr)?) } else { None };
+			Ok((index, name))
+		};
+		let kind = match kind {
+			SYMTAB_FUNCTION => {
+				let (index, name) = indexed(rdr)?;
+				SymbolKind::Function { index, name }
+			},
+			SYMTAB_DATA => {
+				let name = String::deserialize(rdr)?;
+				let definition = if flags & Self::UNDEFINED == 0 {
+					Some(DataDefinition {
+						segment: VarUint32::deserialize(rdr)?.into(),
+						offset: VarUint64::deserialize(rdr)?.into(),
+						size: VarUint64::deserialize(rdr)?.into(),
+					})
+				} else {
+					None
+				};
+				SymbolKind::Data { name, definition }
+			},
+			SYMTAB_GLOBAL => {
+				let (index, name) = indexed(rdr)?;
+				SymbolKind::Global { index, name }
+			},
+			SYMTAB_SECTION => SymbolKind::Section { index: VarUint32::deserialize(rdr)?.into() },
+			SYMTAB_TAG => {
+				let (index, name) = indexed(rdr)?;
+				SymbolKind::Tag { index, name }
+			},
+			SYMTAB_TABLE => {
+				let (index, name) = indexed(rdr)?;
+				SymbolKind::Table { index, name }
+			},
+			kind => return Err(Error::UnknownSymbolKind(kind)),
+		};
+
+		Ok(SymbolInfo { flags, kind })
+	}
+}
+
+impl Serialize for SymbolInfo {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		let flags = self.flags;
+		let indexed = |wtr: &mut W, kind: u8, index: u32, name: Option<String>| -> Result<(), Error> {
+			if name.is_some() != Self::has_name(flags) {
+				return Err(Error::InconsistentMetadata);
+			}
+			Uint8::from(kind).serialize(wtr)?;
+			VarUint32::from(flags).serialize(wtr)?;
+			VarUint32::from(index).serialize(wtr)?;
+			if let Some(name) = name {
+				name.serialize(wtr)?;
+			}
+			Ok(())
+		};
+
+		match self.kind {
+			SymbolKind::Function { index, name } => indexed(wtr, SYMTAB_FUNCTION, index, name),
+			SymbolKind::Data { name, definition } => {
+				if definition.is_some() == (flags & Self::UNDEFINED != 0) {
+					return Err(Error::InconsistentMetadata);
+				}
+				Uint8::from(SYMTAB_DATA).serialize(wtr)?;
+				VarUint32::from(flags).serialize(wtr)?;
+				name.serialize(wtr)?;
+				if let Some(definition) = definition {
+					VarUint32::from(definition.segment).serialize(wtr)?;
+					VarUint64::from(definition.offset).serialize(wtr)?;
+					VarUint64::from(definition.size).serialize(wtr)?;
+				}
+				Ok(())
+			},
+			SymbolKind::Global { index, name } => indexed(wtr, SYMTAB_GLOBAL, index, name),
+			SymbolKind::Section { index } => {
+				Uint8::from(SYMTAB_SECTION).serialize(wtr)?;
+				VarUint32::from(flags).serialize(wtr)?;
+				VarUint32::from(index).serialize(wtr)?;
+				Ok(())
+			},
+			SymbolKind::Tag { index, name } => indexed(wtr, SYMTAB_TAG, index, name),
+			SymbolKind::Table { index, name } => indexed(wtr, SYMTAB_TABLE, index, name),
+		}
+	}
+}
+
+/// Entry of the segment info subsection, describing the data segment of the same index.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SegmentInfo {
+	/// Name of the segment.
+	pub name: String,
+	/// Alignment of the segment, as a power of two.
+	pub alignment: u32,
+	/// Segment flags.
+	pub flags: u32,
+}
+
+impl Deserialize for SegmentInfo {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(SegmentInfo {
+			name: String::deserialize(rdr)?,
+			alignment: VarUint32::deserialize(rdr)?.into(),
+			flags: VarUint32::deserialize(rdr)?.into(),
+		})
+	}
+}
+
+impl Serialize for SegmentInfo {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		self.name.serialize(wtr)?;
+		VarUint32::from(self.alignment).serialize(wtr)?;
+		VarUint32::from(self.flags).serialize(wtr)?;
+		Ok(())
+	}
+}
+
+/// Function to call on startup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InitFunc {
+	/// Priority of the call; lower ones come first.
+	pub priority: u32,
+	/// Index of the function symbol in the symbol table.
+	pub symbol: u32,
+}
+
+impl Deserialize for InitFunc {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(InitFunc {
+			priority: VarUint32::deserialize(rdr)?.into(),
+			symbol: VarUint32::deserialize(rdr)?.into(),
+		})
+	}
+}
+
+impl Serialize for InitFunc {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		VarUint32::from(self.priority).serialize(wtr)?;
+		VarUint32::from(self.symbol).serialize(wtr)?;
+		Ok(())
+	}
+}
+
+/// Group of items of which the linker keeps the copy of the first object file only.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Comdat {
+	/// Name of the group.
+	pub name: String,
+	/// Flags of the group, currently always `0`.
+	pub flags: u32,
+	/// Items of the group.
+	pub symbols: Vec<ComdatSymbol>,
+}
+
+impl Deserialize for Comdat {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(Comdat {
+			name: String::deserialize(rdr)?,
+			flags: VarUint32::deserialize(rdr)?.into(),
+			symbols: CountedList::deserialize(rdr)?.into_inner(),
+		})
+	}
+}
+
+impl Serialize for Comdat {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		self.name.serialize(wtr)?;
+		VarUint32::from(self.flags).serialize(wtr)?;
+		CountedListWriter(self.symbols.len(), self.symbols.into_iter()).serialize(wtr)?;
+		Ok(())
+	}
+}
+
+/// Item of a comdat group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComdatSymbol {
+	/// Kind of the item.
+	pub kind: ComdatSymbolKind,
+	/// Index of the item in the index space of its kind.
+	pub index: u32,
+}
+
+/// Kind of an item of a comdat group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ComdatSymbolKind {
+	/// Data segment.
+	Data,
+	/// Function.
+	Function,
+	/// Global.
+	Global,
+	/// Tag.
+	Tag,
+	/// Table.
+	Table,
+	/// Custom section.
+	Section,
+}
+
+impl Deserialize for ComdatSymbol {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		let kind = match Uint8::deserialize(rdr)?.into() {
+			COMDAT_DATA => ComdatSymbolKind::Data,
+			COMDAT_FUNCTION => ComdatSymbolKind::Function,
+			COMDAT_GLOBAL => ComdatSymbolKind::Global,
+			COMDAT_TAG => ComdatSymbolKind::Tag,
+			COMDAT_TABLE => ComdatSymbolKind::Table,
+			COMDAT_SECTION => ComdatSymbolKind::Section,
+			kind => return Err(Error::UnknownSymbolKind(kind)),
+		};
+		Ok(ComdatSymbol { kind, index: VarUint32::deserialize(rdr)?.into() })
+	}
+}
+
+impl Serialize for ComdatSymbol {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		let kind = match self.kind {
+			ComdatSymbolKind::Data => COMDAT_DATA,
+			ComdatSymbolKind::Function => COMDAT_FUNCTION,
+			ComdatSymbolKind::Global => COMDAT_GLOBAL,
+			ComdatSymbolKind::Tag => COMDAT_TAG,
+			ComdatSymbolKind::Table => COMDAT_TABLE,
+			ComdatSymbolKind::Section => COMDAT_SECTION,
+		};
+		Uint8::from(kind).serialize(wtr)?;
+		VarUint32::from(self.index).serialize(wtr)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::{deserialize_buffer, deserialize_file, serialize, Module, Section};
+	use crate::builder;
+
+	fn linking_section() -> LinkingSection {
+		LinkingSection::new(vec![
+			LinkingSubsection::SymbolTable(vec![
+				SymbolInfo::new(0, SymbolKind::Function { index: 1, name: Some("run".into()) }),
+				SymbolInfo::new(SymbolInfo::UNDEFINED, SymbolKind::Function { index: 0, name: None }),
+				SymbolInfo::new(SymbolInfo::BINDING_LOCAL, SymbolKind::Data {
+					name: ".L.str".into(),
+					definition: Some(DataDefinition { segment: 0, offset: 4, size: 6 }),
+				}),
+				SymbolInfo::new(SymbolInfo::BINDING_LOCAL, SymbolKind::Section { index: 9 }),
+			]),
+			LinkingSubsection::SegmentInfo(vec![
+				SegmentInfo { name: ".rodata.str".into(), alignment: 0, flags: 1 },
+			]),
+			LinkingSubsection::InitFuncs(vec![InitFunc { priority: 65535, symbol: 0 }]),
+			LinkingSubsection::ComdatInfo(vec![Comdat {
+				name: "inline".into(),
+				flags: 0,
+				symbols: vec![ComdatSymbol { kind: ComdatSymbolKind::Function, index: 1 }],
+			}]),
+			LinkingSubsection::Unknown { id: 42, payload: vec![1, 2, 3] },
+		])
+	}
+
+	#[test]
+	fn linking_round_trip() {
+		let mut module = builder::module().build();
+		module.sections_mut().push(Section::Linking(linking_section()));
+		let bytes = serialize(module).expect("module to serialize");
+
+		let module: Module = deserialize_buffer(&bytes).expect("module to deserialize");
+		assert!(module.linking_section().is_none());
+		let module = module.parse_linking().expect("linking section to parse");
+		let linking = module.linking_section().expect("linking section");
+		assert_eq!(linking, &linking_section());
+		assert_eq!(linking.symbol_table().expect("symbol table")[0].name(), Some("run"));
+		assert_eq!(serialize(module).expect("module to serialize"), bytes);
+	}
+
+	#[test]
+	fn inconsistent_symbol_name() {
+		let symbol = SymbolInfo::new(SymbolInfo::UNDEFINED, SymbolKind::Global { index: 0, name: Some("g".into()) });
+		assert!(serialize(symbol).is_err());
+	}
+
+	#[test]
+	fn truncated_subsection() {
+		let payload = [2, WASM_INIT_FUNCS, 3, 1];
+		assert!(matches!(LinkingSection::deserialize(&mut &payload[..]), Err(Error::UnexpectedEof)));
+	}
+
+	#[test]
+	fn read_error() {
+		struct Failing<'a>(&'a [u8]);
+
+		impl<'a> std::io::Read for Failing<'a> {
+			fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+				if self.0.is_empty() {
+					return Err(std::io::Error::other("device failure"));
+				}
+				std::io::Read::read(&mut self.0, buf)
+			}
+		}
+
+		let section = LinkingSection::deserialize(&mut Failing(&[2]));
+		assert!(matches!(section, Err(Error::HeapOther(_))));
+	}
+
+	#[test]
+	fn legacy_linking() {
+		// Emitted by LLVM before the section was versioned.
+		let (errors, module) = deserialize_file("./res/cases/v1/relocatable.wasm")
+			.expect("module to deserialize")
+			.parse_linking()
+			.expect_err("legacy linking section to be rejected");
+		assert_eq!(errors.len(), 1);
+		match errors[0].1 {
+			Error::UnsupportedLinkingVersion(3) => {},
+			ref other => panic!("unexpected error: {:?}", other),
+		}
+		assert!(module.custom_sections().any(|custom| custom.name() == "linking"));
+	}
+}
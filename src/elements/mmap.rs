@@ -0,0 +1,94 @@
+use std::{convert::TryFrom, fs::File, os::unix::io::AsRawFd, path::Path, ptr, slice};
+use super::{Error, LazyModule, deserialize_buffer_lazy};
+
+/// Read-only memory map of a module file.
+///
+/// Modules parsed lazily from the map borrow their function bodies from it
+/// instead of copying them to the heap, so the code of a module stays in the page
+/// cache, shared between processes and reclaimable, until it is decoded.
+///
+/// Only function bodies are borrowed. Every other section, data segments and
+/// custom section payloads included, is decoded into the owned `Module` of the
+/// lazy module and so copied to the heap; modules dominated by data gain little
+/// from the map.
+#[derive(Debug)]
+pub struct MappedFile {
+	ptr: *mut libc::c_void,
+	len: usize,
+}
+
+// The mapping is read-only and owned by this value alone.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+	/// Map the file at `path`.
+	///
+	/// # Safety
+	///
+	/// The file must not be modified or truncated while it is mapped: the bytes
+	/// borrowed from the map would change, or accessing them would fault.
+	pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+		let file = File::open(path)
+			.map_err(|e| Error::HeapOther(format!("Can't read from the file: {:?}", e)))?;
+		let len = file.metadata()
+			.map_err(|e| Error::HeapOther(format!("Can't read from the file: {:?}", e)))?
+			.len();
+		// Files larger than the address space can't be mapped as a whole.
+		let len = usize::try_from(len)
+			.map_err(|_| Error::HeapOther(format!("Can't map the file: {} bytes exceed the address space", len)))?;
+		if len == 0 {
+			// Empty mappings are not allowed; an empty file fails to parse anyway.
+			return Ok(MappedFile { ptr: ptr::null_mut(), len: 0 });
+		}
+
+		let ptr = libc::mmap(ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0);
+		if ptr == libc::MAP_FAILED {
+			return Err(Error::HeapOther(format!("Can't map the file: {:?}", std::io::Error::last_os_error())));
+		}
+		Ok(MappedFile { ptr, len })
+	}
+
+	/// Contents of the file.
+	pub fn bytes(&self) -> &[u8] {
+		if self.len == 0 {
+			return &[];
+		}
+		// Safe since the mapping is valid and readable for `len` bytes until dropped.
+		unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+	}
+
+	/// Parse the module lazily, borrowing its function bodies from the map.
+	///
+	/// Data segments and custom section payloads are copied, see `MappedFile`
+	/// and `deserialize_buffer_lazy`.
+	pub fn lazy_module(&self) -> Result<LazyModule<'_>, Error> {
+		deserialize_buffer_lazy(self.bytes())
+	}
+}
+
+impl Drop for MappedFile {
+	fn drop(&mut self) {
+		if self.len != 0 {
+			unsafe { libc::munmap(self.ptr, self.len); }
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::MappedFile;
+	use crate::elements::{deserialize_file, Module};
+
+	#[test]
+	fn mapped_matches_file() {
+		let path = "./res/cases/v1/test5.wasm";
+		let module: Module = deserialize_file(path).expect("module to be deserialized");
+		let mapped = unsafe { MappedFile::open(path) }.expect("file to be mapped");
+		assert_eq!(mapped.bytes(), &std::fs::read(path).expect("file to be read")[..]);
+
+		let lazy = mapped.lazy_module().expect("module to be deserialized lazily");
+		assert_eq!(lazy.bodies().len(), module.code_section().expect("code section").bodies().len());
+		assert_eq!(lazy.into_module().expect("bodies to be decoded"), module);
+	}
+}
@@ -30,13 +30,38 @@ mod import_entry;
 mod export_entry;
 mod global_entry;
 mod ops;
+mod compact;
+mod structure;
 mod func;
 mod segment;
 mod index_map;
 mod name_section;
 mod reloc_section;
+mod linking_section;
+mod producers_section;
+mod target_features_section;
+mod dylink_section;
+mod config;
+mod limits;
+mod lazy;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+mod appender;
+mod estimate;
+mod const_eval;
+mod bounds;
+mod stats;
+mod diff;
+mod strip;
+mod debug_sections;
+mod introspect;
+mod canonical;
+mod digest;
+mod diagnose;
+mod text;
+pub mod codec;
 
-pub use self::module::{Module, peek_size, ImportCountType};
+pub use self::module::{Module, ModuleItem, ModuleReader, peek_size, ImportCountType};
 pub use self::section::{
 	Section, FunctionSection, CodeSection, MemorySection, DataSection,
 	ImportSection, ExportSection, GlobalSection, TypeSection, ElementSection,
@@ -50,7 +75,9 @@ pub use self::primitives::{
 	Uint64, VarUint64, CountedList, CountedWriter, CountedListWriter,
 };
 pub use self::types::{Type, ValueType, BlockType, FunctionType, TableElementType};
-pub use self::ops::{Instruction, Instructions, InitExpr, opcodes, BrTableData};
+pub use self::ops::{Instruction, Instructions, InitExpr, opcodes, BrTableData, MemoryOffset};
+pub use self::compact::{CompactInstructions, CompactIter};
+pub use self::structure::{BodyStructure, Block, BranchTarget, StructuredInstruction};
 
 #[cfg(feature="atomics")]
 pub use self::ops::AtomicsInstruction;
@@ -64,12 +91,38 @@ pub use self::ops::SignExtInstruction;
 #[cfg(feature="bulk")]
 pub use self::ops::BulkInstruction;
 
+#[cfg(feature="exceptions")]
+pub use self::ops::ExceptionsInstruction;
+
+#[cfg(feature="exceptions")]
+pub use self::import_entry::TagType;
+
+#[cfg(feature="exceptions")]
+pub use self::section::TagSection;
+
 #[cfg(any(feature="simd", feature="atomics"))]
 pub use self::ops::MemArg;
 
-pub use self::func::{Func, FuncBody, Local};
+pub use self::func::{Func, FuncBody, Local, CodeLocations};
 pub use self::segment::{ElementSegment, DataSegment};
 pub use self::index_map::IndexMap;
+pub use self::config::DeserializeConfig;
+pub use self::limits::ParseLimits;
+pub use self::lazy::{LazyModule, LazyFuncBody, deserialize_buffer_lazy};
+#[cfg(all(feature = "mmap", unix))]
+pub use self::mmap::MappedFile;
+pub use self::appender::SectionAppender;
+pub use self::estimate::ResourceEstimate;
+pub use self::const_eval::{RuntimeValue, ConstEvalError};
+pub use self::bounds::SegmentBoundsError;
+pub use self::stats::{ModuleStats, SectionStats, FunctionStats};
+pub use self::strip::StripConfig;
+pub use self::debug_sections::CodeOffsetMap;
+pub use self::canonical::{serialize_canonical, is_canonical};
+pub use self::digest::{Digest, SectionFilter};
+pub use self::introspect::{ExternType, ResolvedImport, ResolvedExport};
+pub use self::diff::{diff, ModuleDiff, FunctionDiff, FunctionChange, EntryChange, DataChange};
+pub use self::diagnose::{DetailedError, deserialize_buffer_detailed};
 pub use self::name_section::{
 	NameMap, NameSection, ModuleNameSubsection, FunctionNameSubsection,
 	LocalNameSubsection,
@@ -77,6 +130,15 @@ pub use self::name_section::{
 pub use self::reloc_section::{
 	RelocSection, RelocationEntry,
 };
+pub use self::linking_section::{
+	LinkingSection, LinkingSubsection, SymbolInfo, SymbolKind, DataDefinition, SegmentInfo,
+	InitFunc, Comdat, ComdatSymbol, ComdatSymbolKind,
+};
+pub use self::producers_section::{ProducersSection, ProducerValue};
+pub use self::target_features_section::{TargetFeaturesSection, TargetFeature, FeaturePrefix};
+pub use self::dylink_section::{
+	DylinkSection, DylinkSubsection, DylinkMemInfo, DylinkExportInfo, DylinkImportInfo,
+};
 
 /// Deserialization from serial i/o.
 pub trait Deserialize : Sized {
@@ -168,6 +230,19 @@ pub enum Error {
 	DuplicatedNameSubsections(u8),
 	/// Unknown name subsection type.
 	UnknownNameSubsectionType(u8),
+	/// Linking section of a version other than 2.
+	UnsupportedLinkingVersion(u32),
+	/// Unknown kind of a symbol or comdat member in the linking section.
+	UnknownSymbolKind(u8),
+	#[cfg(feature="exceptions")]
+	/// Unknown tag attribute (only exceptions, `0`, are defined).
+	UnknownTagAttribute(u8),
+	/// Module uses a proposal which is disabled by the `DeserializeConfig`, which this
+	/// build of the crate was compiled without, or which the crate does not support at
+	/// all, like `nontrapping_float_to_int`.
+	FeatureDisabled(&'static str),
+	/// Module exceeds the named `ParseLimits` limit.
+	LimitExceeded(&'static str),
 }
 
 impl fmt::Display for Error {
@@ -181,12 +256,12 @@ impl fmt::Display for Error {
 			}
 			Error::Other(msg) => write!(f, "{}", msg),
 			Error::HeapOther(ref msg) => write!(f, "{}", msg),
-			Error::UnknownValueType(ty) => write!(f, "Invalid or unknown value type {}", ty),
-			Error::UnknownTableElementType(ty) => write!(f, "Unknown table element type {}", ty),
+			Error::UnknownValueType(ty) => write!(f, "Invalid or unknown value type {:#04x}", ty as u8 & 0x7f),
+			Error::UnknownTableElementType(ty) => write!(f, "Unknown table element type {:#04x}", ty as u8 & 0x7f),
 			Error::NonUtf8String => write!(f, "Non-UTF-8 string"),
 			Error::UnknownExternalKind(kind) => write!(f, "Unknown external kind {}", kind),
 			Error::UnknownInternalKind(kind) => write!(f, "Unknown internal kind {}", kind),
-			Error::UnknownOpcode(opcode) => write!(f, "Unknown opcode {}", opcode),
+			Error::UnknownOpcode(opcode) => write!(f, "Unknown opcode {:#04x}", opcode),
 			#[cfg(feature="simd")]
 			Error::UnknownSimdOpcode(opcode) => write!(f, "Unknown SIMD opcode {}", opcode),
 			Error::InvalidVarUint1(val) => write!(f, "Not an unsigned 1-bit integer: {}", val),
@@ -202,12 +277,18 @@ impl fmt::Display for Error {
 			Error::InvalidMemoryReference(ref mem_ref) =>  write!(f, "Invalid memory reference ({})", mem_ref),
 			Error::InvalidTableReference(ref table_ref) =>  write!(f, "Invalid table reference ({})", table_ref),
 			Error::InvalidLimitsFlags(ref flags) =>  write!(f, "Invalid limits flags ({})", flags),
-			Error::UnknownFunctionForm(ref form) =>  write!(f, "Unknown function form ({})", form),
+			Error::UnknownFunctionForm(ref form) =>  write!(f, "Unknown function form ({:#04x})", form),
 			Error::InconsistentCode =>  write!(f, "Number of function body entries and signatures does not match"),
 			Error::InvalidSegmentFlags(n) =>  write!(f, "Invalid segment flags: {}", n),
 			Error::TooManyLocals => write!(f, "Too many locals"),
 			Error::DuplicatedNameSubsections(n) =>  write!(f, "Duplicated name subsections: {}", n),
 			Error::UnknownNameSubsectionType(n) => write!(f, "Unknown subsection type: {}", n),
+			Error::UnsupportedLinkingVersion(v) => write!(f, "Unsupported linking section version {}", v),
+			Error::UnknownSymbolKind(kind) => write!(f, "Unknown symbol kind {}", kind),
+			#[cfg(feature="exceptions")]
+			Error::UnknownTagAttribute(n) => write!(f, "Unknown tag attribute: {}", n),
+			Error::FeatureDisabled(feature) => write!(f, "Disabled feature used: {}", feature),
+			Error::LimitExceeded(limit) => write!(f, "Parse limit exceeded: {}", limit),
 		}
 	}
 }
@@ -249,13 +330,24 @@ impl ::std::error::Error for Error {
 			Error::TooManyLocals => "Too many locals",
 			Error::DuplicatedNameSubsections(_) =>  "Duplicated name subsections",
 			Error::UnknownNameSubsectionType(_) => "Unknown name subsections type",
+			Error::UnsupportedLinkingVersion(_) => "Unsupported linking section version",
+			Error::UnknownSymbolKind(_) => "Unknown symbol kind",
+			#[cfg(feature="exceptions")]
+			Error::UnknownTagAttribute(_) => "Unknown tag attribute",
+			Error::FeatureDisabled(_) => "Disabled feature used",
+			Error::LimitExceeded(_) => "Parse limit exceeded",
 		}
 	}
 }
 
 impl From<io::Error> for Error {
 	fn from(err: io::Error) -> Self {
-		Error::HeapOther(format!("I/O Error: {:?}", err))
+		match err {
+			io::Error::UnexpectedEof => Error::UnexpectedEof,
+			#[cfg(feature = "std")]
+			io::Error::IoError(ref err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Error::UnexpectedEof,
+			err => Error::HeapOther(format!("I/O Error: {:?}", err)),
+		}
 	}
 }
 
@@ -303,6 +395,25 @@ pub fn deserialize_buffer<T: Deserialize>(contents: &[u8]) -> Result<T, T::Error
 	Ok(result)
 }
 
+/// Deserialize module from serialized bytes, rejecting proposals disabled in `config`.
+pub fn deserialize_buffer_with_config(contents: &[u8], config: &DeserializeConfig) -> Result<Module, Error> {
+	let module = deserialize_buffer::<Module>(contents)?;
+	config.check(&module)?;
+	Ok(module)
+}
+
+/// Deserialize module from serialized bytes, rejecting it as soon as it exceeds `limits`.
+pub fn deserialize_buffer_with_limits(contents: &[u8], limits: &ParseLimits) -> Result<Module, Error> {
+	limits.deserialize_buffer(contents)
+}
+
+/// Deserialize module from serialized bytes, recording the code locations of every function body.
+///
+/// See `FuncBody::code_locations`.
+pub fn deserialize_buffer_with_locations(contents: &[u8]) -> Result<Module, Error> {
+	deserialize_buffer_lazy(contents)?.into_module_with(|body| body.decode_with_locations())
+}
+
 /// Create buffer with serialized value.
 pub fn serialize<T: Serialize>(val: T) -> Result<Vec<u8>, T::Error> {
 	let mut buf = Vec::new();
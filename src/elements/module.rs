@@ -1,21 +1,43 @@
+#![allow(
+	clippy::len_zero,
+	clippy::match_like_matches_macro,
+	clippy::needless_borrowed_reference,
+	clippy::needless_question_mark,
+	clippy::redundant_field_names,
+	clippy::single_match,
+)]
+
 use alloc::{borrow::ToOwned, vec::Vec, string::String};
 use crate::io;
 
-use super::{deserialize_buffer, serialize, Deserialize, Serialize, Error, Uint32, External};
+use super::{
+	deserialize_buffer, serialize, Deserialize, Serialize, Error, Uint32, External, Internal,
+	FunctionType, VarUint7, VarUint32, FuncBody, DataSegment,
+};
 use super::section::{
 	Section, CodeSection, TypeSection, ImportSection, ExportSection, FunctionSection,
 	GlobalSection, TableSection, ElementSection, DataSection, MemorySection,
-	CustomSection,
+	CustomSection, position,
 };
+#[cfg(feature="exceptions")]
+use super::section::TagSection;
 use super::name_section::NameSection;
 use super::reloc_section::RelocSection;
+use super::linking_section::LinkingSection;
+use super::producers_section::ProducersSection;
+use super::target_features_section::TargetFeaturesSection;
+use super::dylink_section::DylinkSection;
 
 use core::cmp;
 
 const WASM_MAGIC_NUMBER: [u8; 4] = [0x00, 0x61, 0x73, 0x6d];
 
+const CODE_SECTION_ID: u8 = 10;
+const DATA_SECTION_ID: u8 = 11;
+
 /// WebAssembly module
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Module {
 	magic: u32,
 	version: u32,
@@ -63,6 +85,78 @@ impl Module {
 		Ok(serialize::<Module>(self)?)
 	}
 
+	/// Render the module as stable, diff-friendly text, e.g. for snapshot tests.
+	///
+	/// The rendering is in the text format, so that text format parsers read it back,
+	/// except for the payloads of custom sections, which are only summarized.
+	pub fn to_text(&self) -> String {
+		super::text::render(self, false).expect("writing to a string never fails")
+	}
+
+	/// Render the module as `to_text` does, with the blocks, loops and ifs of function
+	/// bodies folded into s-expressions.
+	pub fn to_folded_text(&self) -> String {
+		super::text::render(self, true).expect("writing to a string never fails")
+	}
+
+	/// Estimate the resources an instance of the module needs, without instantiating it.
+	pub fn resource_estimate(&self) -> super::ResourceEstimate {
+		super::estimate::estimate(self)
+	}
+
+	/// Remove the custom sections selected by `config`, returning how many bytes this saves.
+	///
+	/// Sections are matched by name whether they are parsed or not, and relocation
+	/// sections of removed ones are removed with them. The module is left untouched if
+	/// a section to remove fails to serialize.
+	pub fn strip(&mut self, config: super::StripConfig) -> Result<usize, Error> {
+		super::strip::strip(self, config)
+	}
+
+	/// Sizes of the sections and function bodies and instruction counts of the module.
+	///
+	/// Sizes are the ones the module serializes to, so this needs every section to serialize.
+	pub fn stats(&self) -> Result<super::ModuleStats, Error> {
+		super::stats::stats(self)
+	}
+
+	/// Values of every global, imported ones first, given the values of the imported globals.
+	///
+	/// Globals may refer to immutable globals defined before them, as allowed by the
+	/// extended constant expressions.
+	pub fn global_values(&self, imported: &[super::RuntimeValue]) -> Result<Vec<super::RuntimeValue>, super::ConstEvalError> {
+		super::const_eval::global_values(self, imported)
+	}
+
+	/// Check that every active data and element segment fits into the initial size of
+	/// its memory or table, given the values of the imported globals.
+	///
+	/// Offsets must follow the rules of `check_segment_offsets`, and are evaluated with
+	/// the given values of the immutable imported globals.
+	///
+	/// All offending segments are reported, not only the first one.
+	pub fn check_segment_bounds(&self, imported_globals: &[super::RuntimeValue]) -> Result<(), Vec<super::SegmentBoundsError>> {
+		super::bounds::check(self, imported_globals)
+	}
+
+	/// Check that the offset of every active data and element segment is a valid
+	/// constant expression.
+	///
+	/// Offsets may refer to immutable imported globals only, and must be of type `i32`
+	/// (or `i64` for memories with `memory64`). Their values are not needed, so this
+	/// applies to modules before knowing what they will be instantiated with.
+	pub fn check_segment_offsets(&self) -> Result<(), Vec<super::SegmentBoundsError>> {
+		super::bounds::check_offsets(self)
+	}
+
+	/// Pairs of active data segments which write to overlapping bytes of the same memory.
+	///
+	/// Overlapping segments are valid, with later ones overwriting earlier ones, but
+	/// usually point at a miscomputed offset.
+	pub fn overlapping_data_segments(&self, imported_globals: &[super::RuntimeValue]) -> Result<Vec<(usize, usize)>, Vec<super::SegmentBoundsError>> {
+		super::bounds::overlapping_data(self, imported_globals)
+	}
+
 	/// Destructure the module, yielding sections
 	pub fn into_sections(self) -> Vec<Section> {
 		self.sections
@@ -102,7 +196,7 @@ impl Module {
 		}
 
 		// Assume that the module is already well-ordered.
-		if let Some(pos) = sections.iter().position(|s| section.order() < s.order()) {
+		if let Some(pos) = sections.iter().position(|s| section.position() < s.position()) {
 			sections.insert(pos, section);
 		} else {
 			sections.push(section);
@@ -256,6 +350,24 @@ impl Module {
 		None
 	}
 
+	/// Exception tags section reference, if any.
+	#[cfg(feature="exceptions")]
+	pub fn tag_section(&self) -> Option<&TagSection> {
+		for section in self.sections() {
+			if let &Section::Tag(ref section) = section { return Some(section); }
+		}
+		None
+	}
+
+	/// Exception tags section mutable reference, if any.
+	#[cfg(feature="exceptions")]
+	pub fn tag_section_mut(&mut self) -> Option<&mut TagSection> {
+		for section in self.sections_mut() {
+			if let Section::Tag(ref mut section) = *section { return Some(section); }
+		}
+		None
+	}
+
 	/// Functions signatures section reference, if any.
 	pub fn function_section(&self) -> Option<&FunctionSection> {
 		for section in self.sections() {
@@ -475,6 +587,182 @@ impl Module {
 		}
 	}
 
+	/// Linking section reference, if any.
+	///
+	/// NOTE: linking section is not parsed by default so `linking_section` could return None even if
+	/// linking section exists. Call `parse_linking` to parse linking section
+	pub fn linking_section(&self) -> Option<&LinkingSection> {
+		for section in self.sections() {
+			if let Section::Linking(ref sect) = *section { return Some(sect); }
+		}
+		None
+	}
+
+	/// Linking section mutable reference, if any.
+	///
+	/// NOTE: linking section is not parsed by default so `linking_section_mut` could return None even if
+	/// linking section exists. Call `parse_linking` to parse linking section
+	pub fn linking_section_mut(&mut self) -> Option<&mut LinkingSection> {
+		for section in self.sections_mut() {
+			if let Section::Linking(ref mut sect) = *section { return Some(sect); }
+		}
+		None
+	}
+
+	/// Try to parse linking section in place.
+	///
+	/// Corresponding custom section with proper header will convert to linking section
+	/// If it fails to be decoded, Err variant is returned with the list of
+	/// (index, Error) tuples of failed sections.
+	pub fn parse_linking(self) -> Result<Self, (Vec<(usize, Error)>, Self)> {
+		self.parse_custom_sections("linking", |payload| LinkingSection::deserialize(&mut io::Cursor::new(payload)).map(Section::Linking))
+	}
+
+	/// Dylink section reference, if any.
+	///
+	/// NOTE: dylink section is not parsed by default so `dylink_section` could return None even if
+	/// dylink section exists. Call `parse_dylink` to parse dylink section
+	pub fn dylink_section(&self) -> Option<&DylinkSection> {
+		for section in self.sections() {
+			if let Section::Dylink(ref sect) = *section { return Some(sect); }
+		}
+		None
+	}
+
+	/// Dylink section mutable reference, if any.
+	///
+	/// NOTE: dylink section is not parsed by default so `dylink_section_mut` could return None even if
+	/// dylink section exists. Call `parse_dylink` to parse dylink section
+	pub fn dylink_section_mut(&mut self) -> Option<&mut DylinkSection> {
+		for section in self.sections_mut() {
+			if let Section::Dylink(ref mut sect) = *section { return Some(sect); }
+		}
+		None
+	}
+
+	/// Try to parse dylink section in place.
+	///
+	/// Corresponding custom section named `dylink.0` will convert to dylink section
+	/// If it fails to be decoded, Err variant is returned with the list of
+	/// (index, Error) tuples of failed sections.
+	pub fn parse_dylink(self) -> Result<Self, (Vec<(usize, Error)>, Self)> {
+		self.parse_custom_sections("dylink.0", |payload| DylinkSection::deserialize(&mut io::Cursor::new(payload)).map(Section::Dylink))
+	}
+
+	/// Producers section reference, if any.
+	///
+	/// NOTE: producers section is not parsed by default so `producers_section` could return None even if
+	/// producers section exists. Call `parse_producers` to parse producers section
+	pub fn producers_section(&self) -> Option<&ProducersSection> {
+		for section in self.sections() {
+			if let Section::Producers(ref sect) = *section { return Some(sect); }
+		}
+		None
+	}
+
+	/// Producers section mutable reference, if any.
+	///
+	/// NOTE: producers section is not parsed by default so `producers_section_mut` could return None even if
+	/// producers section exists. Call `parse_producers` to parse producers section
+	pub fn producers_section_mut(&mut self) -> Option<&mut ProducersSection> {
+		for section in self.sections_mut() {
+			if let Section::Producers(ref mut sect) = *section { return Some(sect); }
+		}
+		None
+	}
+
+	/// Try to parse producers section in place.
+	///
+	/// Corresponding custom section with proper header will convert to producers section
+	/// If it fails to be decoded, Err variant is returned with the list of
+	/// (index, Error) tuples of failed sections.
+	pub fn parse_producers(self) -> Result<Self, (Vec<(usize, Error)>, Self)> {
+		self.parse_custom_sections("producers", |payload| parse_payload(payload).map(Section::Producers))
+	}
+
+	/// Target features section reference, if any.
+	///
+	/// NOTE: target features section is not parsed by default so `target_features_section` could return
+	/// None even if target features section exists. Call `parse_target_features` to parse it
+	pub fn target_features_section(&self) -> Option<&TargetFeaturesSection> {
+		for section in self.sections() {
+			if let Section::TargetFeatures(ref sect) = *section { return Some(sect); }
+		}
+		None
+	}
+
+	/// Target features section mutable reference, if any.
+	///
+	/// NOTE: target features section is not parsed by default so `target_features_section_mut` could
+	/// return None even if target features section exists. Call `parse_target_features` to parse it
+	pub fn target_features_section_mut(&mut self) -> Option<&mut TargetFeaturesSection> {
+		for section in self.sections_mut() {
+			if let Section::TargetFeatures(ref mut sect) = *section { return Some(sect); }
+		}
+		None
+	}
+
+	/// Try to parse target features section in place.
+	///
+	/// Corresponding custom section with proper header will convert to target features section
+	/// If it fails to be decoded, Err variant is returned with the list of
+	/// (index, Error) tuples of failed sections.
+	pub fn parse_target_features(self) -> Result<Self, (Vec<(usize, Error)>, Self)> {
+		self.parse_custom_sections("target_features", |payload| parse_payload(payload).map(Section::TargetFeatures))
+	}
+
+	/// Replace the custom sections named `name` by the sections `parse` decodes from their payloads.
+	fn parse_custom_sections<F>(mut self, name: &str, parse: F) -> Result<Self, (Vec<(usize, Error)>, Self)>
+		where F: Fn(&[u8]) -> Result<Section, Error>
+	{
+		let mut parse_errors = Vec::new();
+
+		for (i, section) in self.sections.iter_mut().enumerate() {
+			let parsed = match *section {
+				Section::Custom(ref custom) if custom.name() == name => parse(custom.payload()),
+				_ => continue,
+			};
+			match parsed {
+				Ok(parsed) => *section = parsed,
+				Err(e) => parse_errors.push((i, e)),
+			}
+		}
+
+		if parse_errors.is_empty() {
+			Ok(self)
+		} else {
+			Err((parse_errors, self))
+		}
+	}
+
+	/// Add this crate to the `processed-by` field of the producers section.
+	///
+	/// Meant to be called by tools rewriting a module with this crate. The producers
+	/// section is parsed in place, or added if there is none; if it fails to parse, it
+	/// is left alone and the error returned.
+	pub fn record_processed_by(&mut self) -> Result<(), Error> {
+		let position = self.sections.iter().position(|section| match *section {
+			Section::Producers(_) => true,
+			Section::Custom(ref custom) => custom.name() == "producers",
+			_ => false,
+		});
+		let index = match position {
+			Some(index) => index,
+			None => {
+				self.sections.push(Section::Producers(ProducersSection::default()));
+				self.sections.len() - 1
+			},
+		};
+		if let Section::Custom(ref custom) = self.sections[index] {
+			let producers_section = parse_payload(custom.payload())?;
+			self.sections[index] = Section::Producers(producers_section);
+		}
+		if let Section::Producers(ref mut producers_section) = self.sections[index] {
+			producers_section.add_processed_by(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+		}
+		Ok(())
+	}
+
 	/// Count imports by provided type.
 	pub fn import_count(&self, count_type: ImportCountType) -> usize {
 		self.import_section()
@@ -512,14 +800,85 @@ impl Module {
 		self.import_count(ImportCountType::Memory) +
 			self.memory_section().map(|ms| ms.entries().len()).unwrap_or(0)
 	}
+
+	/// Signature of the function with the given index in the function space
+	/// (imported functions first), if any.
+	pub fn function_type(&self, func_index: u32) -> Option<&FunctionType> {
+		self.signature(self.function_type_ref(func_index)?)
+	}
+
+	/// Signature of the function exported under the given name, if any.
+	pub fn export_signature(&self, name: &str) -> Option<&FunctionType> {
+		let entry = self.export_section()?.entries().iter().find(|entry| entry.field() == name)?;
+		match *entry.internal() {
+			Internal::Function(func_index) => self.function_type(func_index),
+			_ => None,
+		}
+	}
 }
 
-impl Deserialize for Module {
-	type Error = super::Error;
+/// Streaming reader of module sections.
+///
+/// Reads the module header on construction and then yields sections one by one,
+/// checking their order, so that a module can be inspected without keeping all of it
+/// in memory. `next_section` reads each section as a whole, while `next_item` yields
+/// the function bodies and data segments of the code and data sections one at a time,
+/// so that only one of them is in memory at once.
+///
+/// Unlike `Module::deserialize`, the reader cannot check that the code section
+/// matches the function section, since it does not keep the sections around.
+pub struct ModuleReader<'a, R: 'a> {
+	reader: &'a mut R,
+	version: u32,
+	last_section_order: u8,
+	/// Entries of the code or data section left to yield by `next_item`.
+	entries: Option<PendingEntries>,
+	done: bool,
+}
 
-	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
-		let mut sections = Vec::new();
+/// Part of a module read by `ModuleReader::next_item`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModuleItem {
+	/// Section other than the code and data sections.
+	Section(Section),
+	/// Start of the code section, with the number of function bodies it holds.
+	CodeSection(u32),
+	/// Next function body of the code section.
+	FuncBody(FuncBody),
+	/// Start of the data section, with the number of segments it holds.
+	DataSection(u32),
+	/// Next segment of the data section.
+	DataSegment(DataSegment),
+}
+
+/// Code or data section whose entries are being read.
+struct PendingEntries {
+	data: bool,
+	count: u32,
+	/// Bytes of the section left after the entries read so far.
+	length: usize,
+}
+
+/// Reader of at most `length` bytes, as left in a section.
+struct Limited<'r, R: 'r> {
+	reader: &'r mut R,
+	length: usize,
+}
+
+impl<'r, R: io::Read> io::Read for Limited<'r, R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+		if buf.len() > self.length {
+			return Err(io::Error::UnexpectedEof);
+		}
+		self.reader.read(buf)?;
+		self.length -= buf.len();
+		Ok(())
+	}
+}
 
+impl<'a, R: io::Read> ModuleReader<'a, R> {
+	/// Start reading a module, checking its magic number and version.
+	pub fn new(reader: &'a mut R) -> Result<Self, Error> {
 		let mut magic = [0u8; 4];
 		reader.read(&mut magic)?;
 		if magic != WASM_MAGIC_NUMBER {
@@ -532,29 +891,173 @@ impl Deserialize for Module {
 			return Err(Error::UnsupportedVersion(version));
 		}
 
-		let mut last_section_order = 0;
+		Ok(ModuleReader {
+			reader: reader,
+			version: version,
+			last_section_order: 0,
+			entries: None,
+			done: false,
+		})
+	}
 
-		loop {
-			match Section::deserialize(reader) {
-				Err(Error::UnexpectedEof) => { break; },
-				Err(e) => { return Err(e) },
-				Ok(section) => {
-					if section.order() != 0 {
-						if last_section_order > section.order() {
-							return Err(Error::SectionsOutOfOrder);
-						} else if last_section_order == section.order() {
-							return Err(Error::DuplicatedSections(last_section_order));
-						}
-						last_section_order = section.order();
-					}
-					sections.push(section);
+	/// Version of the module being read.
+	pub fn version(&self) -> u32 {
+		self.version
+	}
+
+	/// Read the next section, if any.
+	///
+	/// If `next_item` has started yielding the entries of a section, the rest of that
+	/// section is skipped.
+	pub fn next_section(&mut self) -> Result<Option<Section>, Error> {
+		let result = self.skip_entries().and_then(|()| self.read_section());
+		if result.is_err() {
+			self.done = true;
+		}
+		result
+	}
+
+	/// Read the next part of the module, if any: a section, or the next function body or
+	/// data segment once the code or data section has started.
+	pub fn next_item(&mut self) -> Result<Option<ModuleItem>, Error> {
+		let result = self.read_item();
+		if result.is_err() {
+			self.done = true;
+		}
+		result
+	}
+
+	fn read_item(&mut self) -> Result<Option<ModuleItem>, Error> {
+		if self.done {
+			return Ok(None);
+		}
+		if let Some(ref mut entries) = self.entries {
+			if entries.count == 0 {
+				if entries.length != 0 {
+					return Err(io::Error::InvalidData.into());
 				}
+				self.entries = None;
+			} else {
+				entries.count -= 1;
+				let mut reader = Limited { reader: &mut *self.reader, length: entries.length };
+				let item = if entries.data {
+					ModuleItem::DataSegment(DataSegment::deserialize(&mut reader)?)
+				} else {
+					ModuleItem::FuncBody(FuncBody::deserialize(&mut reader)?)
+				};
+				entries.length = reader.length;
+				return Ok(Some(item));
 			}
 		}
 
+		let id = match self.read_id()? {
+			Some(id) => id,
+			None => return Ok(None),
+		};
+		if id != CODE_SECTION_ID && id != DATA_SECTION_ID {
+			return self.read_section_with_id(id).map(|section| Some(ModuleItem::Section(section)));
+		}
+
+		let data = id == DATA_SECTION_ID;
+		// Orders of the data and code sections.
+		check_section_order(&mut self.last_section_order, if data { 0x0c } else { 0x0b })?;
+		let length: usize = VarUint32::deserialize(self.reader)?.into();
+		let mut reader = Limited { reader: &mut *self.reader, length };
+		let count: u32 = VarUint32::deserialize(&mut reader)?.into();
+		self.entries = Some(PendingEntries { data, count, length: reader.length });
+		Ok(Some(if data { ModuleItem::DataSection(count) } else { ModuleItem::CodeSection(count) }))
+	}
+
+	/// Skip what is left of the section whose entries are being read.
+	fn skip_entries(&mut self) -> Result<(), Error> {
+		let mut length = match self.entries.take() {
+			Some(entries) => entries.length,
+			None => return Ok(()),
+		};
+		let mut buf = [0u8; 1024];
+		while length > 0 {
+			let chunk = cmp::min(length, buf.len());
+			self.reader.read(&mut buf[..chunk])?;
+			length -= chunk;
+		}
+		Ok(())
+	}
+
+	/// Read the id of the next section, or `None` at the end of the module.
+	fn read_id(&mut self) -> Result<Option<u8>, Error> {
+		// Running out of input is the end of the module only between sections.
+		match VarUint7::deserialize(self.reader) {
+			Ok(id) => Ok(Some(id.into())),
+			Err(Error::UnexpectedEof) => {
+				self.done = true;
+				Ok(None)
+			},
+			Err(e) => Err(e),
+		}
+	}
+
+	fn read_section(&mut self) -> Result<Option<Section>, Error> {
+		if self.done {
+			return Ok(None);
+		}
+		match self.read_id()? {
+			Some(id) => self.read_section_with_id(id).map(Some),
+			None => Ok(None),
+		}
+	}
+
+	fn read_section_with_id(&mut self, id: u8) -> Result<Section, Error> {
+		let section = Section::deserialize_with_id(id, self.reader)?;
+		check_section_order(&mut self.last_section_order, section.order())?;
+		Ok(section)
+	}
+}
+
+/// Decode a custom section payload, which must be spanned by `T` as a whole.
+fn parse_payload<T: Deserialize<Error=Error>>(payload: &[u8]) -> Result<T, Error> {
+	let mut rdr = io::Cursor::new(payload);
+	let parsed = T::deserialize(&mut rdr)?;
+	if rdr.position() != payload.len() {
+		return Err(io::Error::InvalidData.into());
+	}
+	Ok(parsed)
+}
+
+/// Check that a section of the given order may follow the last non-custom section.
+pub(crate) fn check_section_order(last_section_order: &mut u8, order: u8) -> Result<(), Error> {
+	if order != 0 {
+		if *last_section_order == order {
+			return Err(Error::DuplicatedSections(order));
+		} else if position(*last_section_order) > position(order) {
+			return Err(Error::SectionsOutOfOrder);
+		}
+		*last_section_order = order;
+	}
+	Ok(())
+}
+
+impl<'a, R: io::Read> Iterator for ModuleReader<'a, R> {
+	type Item = Result<Section, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.next_section().transpose()
+	}
+}
+
+impl Deserialize for Module {
+	type Error = super::Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		let mut module_reader = ModuleReader::new(reader)?;
+
+		let mut sections = Vec::new();
+		while let Some(section) = module_reader.next_section()? {
+			sections.push(section);
+		}
+
 		let module = Module {
-			magic: u32::from_le_bytes(magic),
-			version: version,
+			magic: u32::from_le_bytes(WASM_MAGIC_NUMBER),
+			version: module_reader.version(),
 			sections: sections,
 		};
 
@@ -968,4 +1471,178 @@ mod integration_tests {
         let module_copy = Module::from_bytes(&module_copy).expect("failed to deserialize");
         assert_eq!(module, module_copy);
     }
+
+	#[test]
+	fn export_signature() {
+		use crate::builder;
+		use super::super::ValueType;
+
+		let module = builder::module()
+			.import()
+				.module("env")
+				.field("log")
+				.external().func(0)
+				.build()
+			.function()
+				.signature()
+					.with_params(vec![ValueType::I32, ValueType::I64])
+					.with_result(ValueType::I32)
+					.build()
+				.body().build()
+				.build()
+			.export()
+				.field("call")
+				.internal().func(1)
+				.build()
+			.export()
+				.field("log")
+				.internal().func(0)
+				.build()
+			.build();
+
+		let signature = module.export_signature("call").expect("call to be exported");
+		assert_eq!(signature.params(), &[ValueType::I32, ValueType::I64]);
+		assert_eq!(signature.results(), &[ValueType::I32]);
+		assert_eq!(signature.to_string(), "(func (param i32 i64) (result i32))");
+		assert_eq!(signature.rust_signature(), "fn(i32, i64) -> i32");
+
+		assert!(module.export_signature("missing").is_none());
+	}
+
+	#[test]
+	fn module_reader() {
+		use super::ModuleReader;
+
+		let module = deserialize_file("./res/cases/v1/test5.wasm").expect("Should be deserialized");
+
+		let mut file = ::std::fs::File::open("./res/cases/v1/test5.wasm").expect("file to open");
+		let sections = ModuleReader::new(&mut file)
+			.expect("header to be read")
+			.collect::<Result<Vec<_>, _>>()
+			.expect("sections to be read");
+		assert_eq!(module.sections(), &sections[..]);
+
+		let mut file = ::std::fs::File::open("./res/cases/v1/err-sections-after-custom.wasm").expect("file to open");
+		let last = ModuleReader::new(&mut file).expect("header to be read").last();
+		assert!(matches!(last, Some(Err(super::super::Error::SectionsOutOfOrder))));
+	}
+
+	#[cfg(feature="serde")]
+	#[test]
+	fn serde_impls() {
+		fn assert_serde<T: serde::Serialize + serde::de::DeserializeOwned>() {}
+		assert_serde::<Module>();
+	}
+
+	#[test]
+	fn module_reader_items() {
+		use super::{ModuleItem, ModuleReader};
+		use crate::builder;
+		use super::super::Instruction::*;
+
+		let module = builder::module()
+			.function().signature().build().body().build().build()
+			.function().signature().build().body().build().build()
+			.memory().with_min(1).build()
+			.data().offset(I32Const(0)).value(vec![1, 2]).build()
+			.data().offset(I32Const(8)).value(vec![3]).build()
+			.export().field("f").internal().func(1).build()
+			.build();
+		let bytes = serialize(module.clone()).expect("module to serialize");
+
+		let mut expected = Vec::new();
+		for section in module.sections() {
+			match *section {
+				Section::Code(ref code) => {
+					expected.push(ModuleItem::CodeSection(code.bodies().len() as u32));
+					expected.extend(code.bodies().iter().cloned().map(ModuleItem::FuncBody));
+				},
+				Section::Data(ref data) => {
+					expected.push(ModuleItem::DataSection(data.entries().len() as u32));
+					expected.extend(data.entries().iter().cloned().map(ModuleItem::DataSegment));
+				},
+				ref section => expected.push(ModuleItem::Section(section.clone())),
+			}
+		}
+		let mut reader = crate::io::Cursor::new(&bytes[..]);
+		let mut module_reader = ModuleReader::new(&mut reader).expect("header to be read");
+		let mut items = Vec::new();
+		while let Some(item) = module_reader.next_item().expect("item to be read") {
+			items.push(item);
+		}
+		assert_eq!(items, expected);
+
+		// Reading sections skips the rest of the section being read by items.
+		let mut reader = crate::io::Cursor::new(&bytes[..]);
+		let mut module_reader = ModuleReader::new(&mut reader).expect("header to be read");
+		while !matches!(module_reader.next_item().expect("item to be read"), Some(ModuleItem::CodeSection(2))) {}
+		assert!(matches!(module_reader.next_item(), Ok(Some(ModuleItem::FuncBody(_)))));
+		let data = module_reader.next_section().expect("section to be read");
+		assert_eq!(data.as_ref(), module.sections().iter().find(|s| matches!(s, Section::Data(_))));
+
+		// Entries may not run past the end of their section.
+		let mut truncated = bytes.clone();
+		let code = module.sections().iter().take_while(|s| !matches!(s, Section::Code(_)))
+			.map(|s| serialize(s.clone()).expect("section to serialize").len())
+			.sum::<usize>() + 8;
+		truncated[code + 1] -= 1;
+		let mut reader = crate::io::Cursor::new(&truncated[..]);
+		let mut module_reader = ModuleReader::new(&mut reader).expect("header to be read");
+		let error = loop {
+			match module_reader.next_item() {
+				Ok(Some(_)) => {},
+				Ok(None) => panic!("truncated code section to be rejected"),
+				Err(e) => break e,
+			}
+		};
+		assert!(matches!(error, super::super::Error::UnexpectedEof));
+		assert!(matches!(module_reader.next_item(), Ok(None)));
+	}
+
+    #[test]
+    fn malformed_corpus() {
+        use super::super::Error;
+
+        fn is_eof(err: &Error) -> bool {
+            matches!(err, Error::UnexpectedEof)
+        }
+
+        type Expected = fn(&Error) -> bool;
+
+        // Each fixture is a small malformed binary, paired with the error it must be
+        // rejected with.
+        let corpus: &[(&str, Expected)] = &[
+            ("err-bad-magic", |e| matches!(e, Error::InvalidMagic)),
+            ("err-bad-version", |e| matches!(e, Error::UnsupportedVersion(2))),
+            ("err-truncated-header", is_eof),
+            ("err-truncated-leb", is_eof),
+            ("err-int-too-long", |e| matches!(e, Error::InvalidVarInt7(_))),
+            ("err-leb-i32-too-long", |e| matches!(e, Error::InvalidVarInt32)),
+            ("err-leb-i32-too-long-2", |e| matches!(e, Error::InvalidVarInt32)),
+            ("err-leb-i64-too-long", |e| matches!(e, Error::InvalidVarInt64)),
+            // The overlong LEB128 is a memory offset, which can be 64-bit with memory64.
+            #[cfg(not(feature="memory64"))]
+            ("err-leb-u32-too-long", |e| matches!(e, Error::InvalidVarInt32)),
+            ("err-oversized-count", is_eof),
+            ("err-section-too-long", is_eof),
+            ("err-section-too-short", is_eof),
+            ("err-unknown-section", |e| matches!(e, Error::InvalidSectionId(0x7f))),
+            ("err-duplicate-section", |e| matches!(e, Error::DuplicatedSections(_))),
+            ("err-sections-after-custom", |e| matches!(e, Error::SectionsOutOfOrder)),
+            ("err-function-without-body", |e| matches!(e, Error::InconsistentCode)),
+            ("err-unterminated-block", is_eof),
+            ("err-unknown-opcode", |e| matches!(e, Error::UnknownOpcode(0xff))),
+            ("err-non-utf8-name", |e| matches!(e, Error::NonUtf8String)),
+            ("err-too-many-locals", |e| matches!(e, Error::TooManyLocals)),
+        ];
+
+        for &(name, expected) in corpus {
+            let path = format!("./res/cases/v1/{}.wasm", name);
+            match deserialize_file(&path) {
+                Ok(_) => panic!("{} should not deserialize", name),
+                Err(ref e) if expected(e) => {},
+                Err(e) => panic!("{} failed with unexpected error: {:?}", name, e),
+            }
+        }
+    }
 }
@@ -1,3 +1,5 @@
+#![allow(clippy::ptr_arg, clippy::redundant_pattern_matching, clippy::while_let_loop)]
+
 use alloc::{string::String, vec::Vec};
 use crate::io;
 
@@ -10,6 +12,7 @@ const NAME_TYPE_LOCAL: u8 = 2;
 
 /// Debug name information.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NameSection {
 	/// Module name subsection.
 	module: Option<ModuleNameSubsection>,
@@ -152,6 +155,7 @@ impl Serialize for NameSection {
 
 /// The name of this module.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleNameSubsection {
 	name: String,
 }
@@ -192,6 +196,7 @@ impl Deserialize for ModuleNameSubsection {
 
 /// The names of the functions in this module.
 #[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionNameSubsection {
 	names: NameMap,
 }
@@ -227,6 +232,7 @@ impl Serialize for FunctionNameSubsection {
 
 /// The names of the local variables in this module's functions.
 #[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalNameSubsection {
 	local_names: IndexMap<NameMap>,
 }
@@ -1,15 +1,21 @@
+#![allow(clippy::bool_assert_comparison, clippy::match_like_matches_macro, clippy::no_effect)]
+
 use alloc::{boxed::Box, vec::Vec};
 use crate::io;
 use super::{
 	Serialize, Deserialize, Error,
-	Uint8, VarUint32, CountedList, BlockType,
+	Uint8, VarUint32, BlockType,
 	Uint32, Uint64, CountedListWriter,
 	VarInt32, VarInt64,
 };
+#[cfg(feature="memory64")]
+use super::VarUint64;
 use core::fmt;
+use core::ops::Range;
 
 /// List of instructions (usually inside a block section).
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Instructions(Vec<Instruction>);
 
 impl Instructions {
@@ -28,16 +34,67 @@ impl Instructions {
 
 	/// Individual instructions, mutable.
 	pub fn elements_mut(&mut self) -> &mut Vec<Instruction> { &mut self.0 }
+
+	/// Wrap the instructions in `range` into a new block of type `block_type`.
+	///
+	/// Branch depths within the range are adjusted, so that branches to labels outside
+	/// of it still reach them. The range must hold whole blocks only, and no `else` or
+	/// `catch` of a block around it; otherwise nothing is changed and an error is
+	/// returned.
+	pub fn wrap_in_block(&mut self, range: Range<usize>, block_type: BlockType) -> Result<(), Error> {
+		let code = self.0.get_mut(range.clone()).ok_or(Error::Other("range is out of bounds"))?;
+
+		let mut level = 0u32;
+		for instruction in code.iter() {
+			if level == 0 && instruction.continues_block() {
+				return Err(Error::Other("range continues a block it does not open"));
+			}
+			if instruction.is_terminal() {
+				level = level.checked_sub(1).ok_or(Error::Other("range closes a block it does not open"))?;
+			}
+			if instruction.is_block() {
+				level += 1;
+			}
+		}
+		if level != 0 {
+			return Err(Error::Other("range opens a block it does not close"));
+		}
+
+		for instruction in code.iter_mut() {
+			// `delegate` closes its `try` first, and its depth is relative to the outside.
+			if instruction.is_terminal() {
+				level -= 1;
+			}
+			instruction.shift_branch_depths(level, 1);
+			if instruction.is_block() {
+				level += 1;
+			}
+		}
+
+		self.0.insert(range.end, Instruction::End);
+		self.0.insert(range.start, Instruction::Block(block_type));
+		Ok(())
+	}
 }
 
 impl Deserialize for Instructions {
 	type Error = Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		Instructions::deserialize_observed(reader, |_| {})
+	}
+}
+
+impl Instructions {
+	/// Deserialize instructions, calling `observe` with the reader before each one.
+	pub(crate) fn deserialize_observed<R, F>(reader: &mut R, mut observe: F) -> Result<Self, Error>
+		where R: io::Read, F: FnMut(&R)
+	{
 		let mut instructions = Vec::new();
 		let mut block_count = 1usize;
 
 		loop {
+			observe(reader);
 			let instruction = Instruction::deserialize(reader)?;
 			if instruction.is_terminal() {
 				block_count -= 1;
@@ -57,6 +114,7 @@ impl Deserialize for Instructions {
 
 /// Initialization expression.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InitExpr(Vec<Instruction>);
 
 impl InitExpr {
@@ -104,6 +162,7 @@ impl Deserialize for InitExpr {
 
 /// Instruction.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs)]
 pub enum Instruction {
 	Unreachable,
@@ -132,29 +191,30 @@ pub enum Instruction {
 
 	// All store/load instructions operate with 'memory immediates'
 	// which represented here as (flag, offset) tuple
-	I32Load(u32, u32),
-	I64Load(u32, u32),
-	F32Load(u32, u32),
-	F64Load(u32, u32),
-	I32Load8S(u32, u32),
-	I32Load8U(u32, u32),
-	I32Load16S(u32, u32),
-	I32Load16U(u32, u32),
-	I64Load8S(u32, u32),
-	I64Load8U(u32, u32),
-	I64Load16S(u32, u32),
-	I64Load16U(u32, u32),
-	I64Load32S(u32, u32),
-	I64Load32U(u32, u32),
-	I32Store(u32, u32),
-	I64Store(u32, u32),
-	F32Store(u32, u32),
-	F64Store(u32, u32),
-	I32Store8(u32, u32),
-	I32Store16(u32, u32),
-	I64Store8(u32, u32),
-	I64Store16(u32, u32),
-	I64Store32(u32, u32),
+	// With memory64 the offsets are 64-bit.
+	I32Load(u32, MemoryOffset),
+	I64Load(u32, MemoryOffset),
+	F32Load(u32, MemoryOffset),
+	F64Load(u32, MemoryOffset),
+	I32Load8S(u32, MemoryOffset),
+	I32Load8U(u32, MemoryOffset),
+	I32Load16S(u32, MemoryOffset),
+	I32Load16U(u32, MemoryOffset),
+	I64Load8S(u32, MemoryOffset),
+	I64Load8U(u32, MemoryOffset),
+	I64Load16S(u32, MemoryOffset),
+	I64Load16U(u32, MemoryOffset),
+	I64Load32S(u32, MemoryOffset),
+	I64Load32U(u32, MemoryOffset),
+	I32Store(u32, MemoryOffset),
+	I64Store(u32, MemoryOffset),
+	F32Store(u32, MemoryOffset),
+	F64Store(u32, MemoryOffset),
+	I32Store8(u32, MemoryOffset),
+	I32Store16(u32, MemoryOffset),
+	I64Store8(u32, MemoryOffset),
+	I64Store16(u32, MemoryOffset),
+	I64Store32(u32, MemoryOffset),
 
 	CurrentMemory(u8),
 	GrowMemory(u8),
@@ -306,15 +366,20 @@ pub enum Instruction {
 
 	#[cfg(feature="bulk")]
 	Bulk(BulkInstruction),
+
+	#[cfg(feature="exceptions")]
+	Exceptions(ExceptionsInstruction),
 }
 
 #[allow(missing_docs)]
 #[cfg(feature="atomics")]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AtomicsInstruction {
 	AtomicWake(MemArg),
 	I32AtomicWait(MemArg),
 	I64AtomicWait(MemArg),
+	AtomicFence,
 
 	I32AtomicLoad(MemArg),
 	I64AtomicLoad(MemArg),
@@ -391,6 +456,7 @@ pub enum AtomicsInstruction {
 #[allow(missing_docs)]
 #[cfg(feature="simd")]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SimdInstruction {
 	V128Const(Box<[u8; 16]>),
 	V128Load(MemArg),
@@ -545,9 +611,23 @@ pub enum SimdInstruction {
 	I64x2TruncUF64x2Sat,
 }
 
+#[allow(missing_docs)]
+#[cfg(feature="exceptions")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExceptionsInstruction {
+	Try(BlockType),
+	Catch(u32),
+	CatchAll,
+	Throw(u32),
+	Rethrow(u32),
+	Delegate(u32),
+}
+
 #[allow(missing_docs)]
 #[cfg(feature="sign_ext")]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SignExtInstruction {
 	I32Extend8S,
 	I32Extend16S,
@@ -559,6 +639,7 @@ pub enum SignExtInstruction {
 #[allow(missing_docs)]
 #[cfg(feature="bulk")]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BulkInstruction {
 	MemoryInit(u32),
 	MemoryDrop(u32),
@@ -571,37 +652,127 @@ pub enum BulkInstruction {
 
 #[cfg(any(feature="simd", feature="atomics"))]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs)]
 pub struct MemArg {
 	pub align: u8,
-	pub offset: u32,
+	pub offset: MemoryOffset,
 }
 
+/// Offset of a memory access, added to its address.
+///
+/// Offsets are 64-bit if the `memory64` feature is enabled, as 64-bit memories can be
+/// accessed past 4 GiB, and 32-bit otherwise.
+#[cfg(feature="memory64")]
+pub type MemoryOffset = u64;
+
+/// Offset of a memory access, added to its address.
+///
+/// Offsets are 64-bit if the `memory64` feature is enabled, as 64-bit memories can be
+/// accessed past 4 GiB, and 32-bit otherwise.
+#[cfg(not(feature="memory64"))]
+pub type MemoryOffset = u32;
+
+#[cfg(feature="memory64")]
+type VarOffset = VarUint64;
+#[cfg(not(feature="memory64"))]
+type VarOffset = VarUint32;
+
+/// Number of `br_table` targets space is reserved for before any of them is decoded.
+const BR_TABLE_PREALLOCATION: usize = 4096;
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs)]
 pub struct BrTableData {
 	pub table: Box<[u32]>,
 	pub default: u32,
 }
 
+impl BrTableData {
+	/// New table branching to `targets[i]` for index `i`, and to `default` otherwise.
+	pub fn new(targets: Vec<u32>, default: u32) -> Self {
+		BrTableData { table: targets.into_boxed_slice(), default }
+	}
+
+	/// Branch depths selected by index.
+	pub fn targets(&self) -> &[u32] { &self.table }
+
+	/// Branch depths selected by index, mutable.
+	pub fn targets_mut(&mut self) -> &mut [u32] { &mut self.table }
+
+	/// Branch depth for indices past the targets.
+	pub fn default(&self) -> u32 { self.default }
+
+	/// Branch depth for indices past the targets, mutable.
+	pub fn default_mut(&mut self) -> &mut u32 { &mut self.default }
+
+	/// Every branch depth, the targets followed by the default.
+	pub fn depths(&self) -> impl Iterator<Item=u32> + '_ {
+		self.table.iter().cloned().chain(core::iter::once(self.default))
+	}
+
+	/// Deepen every branch depth of at least `from` by `by`.
+	pub fn shift_depths(&mut self, from: u32, by: u32) {
+		for depth in self.table.iter_mut().chain(core::iter::once(&mut self.default)) {
+			if *depth >= from {
+				*depth = depth.saturating_add(by);
+			}
+		}
+	}
+}
+
 impl Instruction {
 	/// Is this instruction starts the new block (which should end with terminal instruction).
 	pub fn is_block(&self) -> bool {
 		match self {
 			&Instruction::Block(_) | &Instruction::Loop(_) | &Instruction::If(_) => true,
+			#[cfg(feature="exceptions")]
+			&Instruction::Exceptions(ExceptionsInstruction::Try(_)) => true,
 			_ => false,
 		}
 	}
 
 	/// Is this instruction determines the termination of instruction sequence?
 	///
-	/// `true` for `Instruction::End`
+	/// `true` for `Instruction::End` (and for `delegate`, which closes a `try` block).
 	pub fn is_terminal(&self) -> bool {
 		match self {
 			&Instruction::End => true,
+			#[cfg(feature="exceptions")]
+			&Instruction::Exceptions(ExceptionsInstruction::Delegate(_)) => true,
 			_ => false,
 		}
 	}
+
+	/// Whether this instruction starts another part of the enclosing block, like `else`.
+	fn continues_block(&self) -> bool {
+		match *self {
+			Instruction::Else => true,
+			#[cfg(feature="exceptions")]
+			Instruction::Exceptions(ExceptionsInstruction::Catch(_)) |
+			Instruction::Exceptions(ExceptionsInstruction::CatchAll) => true,
+			_ => false,
+		}
+	}
+
+	/// Deepen every branch depth of this instruction which is at least `from` by `by`.
+	///
+	/// Used when wrapping code in new blocks: branches out of the wrapped code must
+	/// skip the added labels, while branches to labels within it stay as they are.
+	pub fn shift_branch_depths(&mut self, from: u32, by: u32) {
+		let depth = match *self {
+			Instruction::Br(ref mut depth) | Instruction::BrIf(ref mut depth) => depth,
+			Instruction::BrTable(ref mut table) => return table.shift_depths(from, by),
+			#[cfg(feature="exceptions")]
+			Instruction::Exceptions(ExceptionsInstruction::Rethrow(ref mut depth)) |
+			Instruction::Exceptions(ExceptionsInstruction::Delegate(ref mut depth)) => depth,
+			_ => return,
+		};
+		if *depth >= from {
+			*depth = depth.saturating_add(by);
+		}
+	}
 }
 
 #[allow(missing_docs)]
@@ -794,12 +965,23 @@ pub mod opcodes {
 		pub const I64_EXTEND32_S: u8 = 0xc4;
 	}
 
+	#[cfg(feature="exceptions")]
+	pub mod exceptions {
+		pub const TRY: u8 = 0x06;
+		pub const CATCH: u8 = 0x07;
+		pub const THROW: u8 = 0x08;
+		pub const RETHROW: u8 = 0x09;
+		pub const DELEGATE: u8 = 0x18;
+		pub const CATCH_ALL: u8 = 0x19;
+	}
+
 	#[cfg(feature="atomics")]
 	pub mod atomics {
 		pub const ATOMIC_PREFIX: u8 = 0xfe;
 		pub const ATOMIC_WAKE: u8 = 0x00;
 		pub const I32_ATOMIC_WAIT: u8 = 0x01;
 		pub const I64_ATOMIC_WAIT: u8 = 0x02;
+		pub const ATOMIC_FENCE: u8 = 0x03;
 
 		pub const I32_ATOMIC_LOAD: u8 = 0x10;
 		pub const I64_ATOMIC_LOAD: u8 = 0x11;
@@ -1062,6 +1244,9 @@ impl Deserialize for Instruction {
 		#[cfg(feature="sign_ext")]
 		use self::opcodes::sign_ext::*;
 
+		#[cfg(feature="exceptions")]
+		use self::opcodes::exceptions::*;
+
 		let val: u8 = Uint8::deserialize(reader)?.into();
 
 		Ok(
@@ -1077,14 +1262,16 @@ impl Deserialize for Instruction {
 				BR => Br(VarUint32::deserialize(reader)?.into()),
 				BRIF => BrIf(VarUint32::deserialize(reader)?.into()),
 				BRTABLE => {
-					let t1: Vec<u32> = CountedList::<VarUint32>::deserialize(reader)?
-						.into_inner()
-						.into_iter()
-						.map(Into::into)
-						.collect();
+					let count: usize = VarUint32::deserialize(reader)?.into();
+					// The count is not trusted for more than a small preallocation, so that
+					// a few bytes can't claim gigabytes.
+					let mut table = Vec::with_capacity(count.min(BR_TABLE_PREALLOCATION));
+					for _ in 0..count {
+						table.push(VarUint32::deserialize(reader)?.into());
+					}
 
 					BrTable(Box::new(BrTableData {
-						table: t1.into_boxed_slice(),
+						table: table.into_boxed_slice(),
 						default: VarUint32::deserialize(reader)?.into(),
 					}))
 				},
@@ -1111,95 +1298,95 @@ impl Deserialize for Instruction {
 
 				I32LOAD => I32Load(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I64LOAD => I64Load(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				F32LOAD => F32Load(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				F64LOAD => F64Load(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I32LOAD8S => I32Load8S(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I32LOAD8U => I32Load8U(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I32LOAD16S => I32Load16S(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I32LOAD16U => I32Load16U(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I64LOAD8S => I64Load8S(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I64LOAD8U => I64Load8U(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I64LOAD16S => I64Load16S(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I64LOAD16U => I64Load16U(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I64LOAD32S => I64Load32S(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I64LOAD32U => I64Load32U(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I32STORE => I32Store(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I64STORE => I64Store(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				F32STORE => F32Store(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				F64STORE => F64Store(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I32STORE8 => I32Store8(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I32STORE16 => I32Store16(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I64STORE8 => I64Store8(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I64STORE16 => I64Store16(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 				I64STORE32 => I64Store32(
 					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+					VarOffset::deserialize(reader)?.into()),
 
 
 				CURRENTMEMORY => {
@@ -1362,6 +1549,19 @@ impl Deserialize for Instruction {
 					_ => return Err(Error::UnknownOpcode(val)),
 				}
 
+				#[cfg(feature="exceptions")]
+				TRY => Exceptions(ExceptionsInstruction::Try(BlockType::deserialize(reader)?)),
+				#[cfg(feature="exceptions")]
+				CATCH => Exceptions(ExceptionsInstruction::Catch(VarUint32::deserialize(reader)?.into())),
+				#[cfg(feature="exceptions")]
+				CATCH_ALL => Exceptions(ExceptionsInstruction::CatchAll),
+				#[cfg(feature="exceptions")]
+				THROW => Exceptions(ExceptionsInstruction::Throw(VarUint32::deserialize(reader)?.into())),
+				#[cfg(feature="exceptions")]
+				RETHROW => Exceptions(ExceptionsInstruction::Rethrow(VarUint32::deserialize(reader)?.into())),
+				#[cfg(feature="exceptions")]
+				DELEGATE => Exceptions(ExceptionsInstruction::Delegate(VarUint32::deserialize(reader)?.into())),
+
 				#[cfg(feature="atomics")]
 				atomics::ATOMIC_PREFIX => return deserialize_atomic(reader),
 
@@ -1371,6 +1571,26 @@ impl Deserialize for Instruction {
 				#[cfg(feature="bulk")]
 				bulk::BULK_PREFIX => return deserialize_bulk(reader),
 
+				// Opcodes of proposals left out of this build are still recognized, so that
+				// a module using one is reported as such rather than as malformed.
+				#[cfg(not(feature="sign_ext"))]
+				0xc0..=0xc4 => return Err(Error::FeatureDisabled("sign_ext")),
+				#[cfg(not(feature="exceptions"))]
+				0x06..=0x09 | 0x18 | 0x19 => return Err(Error::FeatureDisabled("exceptions")),
+				#[cfg(not(feature="atomics"))]
+				0xfe => return Err(Error::FeatureDisabled("atomics")),
+				#[cfg(not(feature="simd"))]
+				0xfd => return Err(Error::FeatureDisabled("simd")),
+				#[cfg(not(feature="bulk"))]
+				0xfc => {
+					let val: u8 = Uint8::deserialize(reader)?.into();
+					return Err(Error::FeatureDisabled(match val {
+						// Saturating float to int conversions share the prefix.
+						0x00..=0x07 => "nontrapping_float_to_int",
+						_ => "bulk_memory",
+					}));
+				},
+
 				_ => { return Err(Error::UnknownOpcode(val)); }
 			}
 		)
@@ -1383,6 +1603,15 @@ fn deserialize_atomic<R: io::Read>(reader: &mut R) -> Result<Instruction, Error>
 	use self::opcodes::atomics::*;
 
 	let val: u8 = Uint8::deserialize(reader)?.into();
+	if val == ATOMIC_FENCE {
+		// `atomic.fence` carries a single reserved byte instead of a memarg.
+		let reserved: u8 = Uint8::deserialize(reader)?.into();
+		if reserved != 0 {
+			return Err(Error::Other("atomic.fence reserved byte should be zero"));
+		}
+		return Ok(Instruction::Atomics(AtomicFence));
+	}
+
 	let mem = MemArg::deserialize(reader)?;
 	Ok(Instruction::Atomics(match val {
 		ATOMIC_WAKE => AtomicWake(mem),
@@ -1420,6 +1649,14 @@ fn deserialize_atomic<R: io::Read>(reader: &mut R) -> Result<Instruction, Error>
 		I64_ATOMIC_RMW_SUB16U => I64AtomicRmwSub16u(mem),
 		I64_ATOMIC_RMW_SUB32U => I64AtomicRmwSub32u(mem),
 
+		I32_ATOMIC_RMW_AND => I32AtomicRmwAnd(mem),
+		I64_ATOMIC_RMW_AND => I64AtomicRmwAnd(mem),
+		I32_ATOMIC_RMW_AND8U => I32AtomicRmwAnd8u(mem),
+		I32_ATOMIC_RMW_AND16U => I32AtomicRmwAnd16u(mem),
+		I64_ATOMIC_RMW_AND8U => I64AtomicRmwAnd8u(mem),
+		I64_ATOMIC_RMW_AND16U => I64AtomicRmwAnd16u(mem),
+		I64_ATOMIC_RMW_AND32U => I64AtomicRmwAnd32u(mem),
+
 		I32_ATOMIC_RMW_OR => I32AtomicRmwOr(mem),
 		I64_ATOMIC_RMW_OR => I64AtomicRmwOr(mem),
 		I32_ATOMIC_RMW_OR8U => I32AtomicRmwOr8u(mem),
@@ -1669,6 +1906,9 @@ fn deserialize_bulk<R: io::Read>(reader: &mut R) -> Result<Instruction, Error> {
 			TableCopy
 		}
 
+		// Saturating float to int conversions, which are not supported.
+		0x00..=0x07 => return Err(Error::FeatureDisabled("nontrapping_float_to_int")),
+
 		_ => return Err(Error::UnknownOpcode(val)),
 	}))
 }
@@ -1679,7 +1919,7 @@ impl Deserialize for MemArg {
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
 		let align = Uint8::deserialize(reader)?;
-		let offset = VarUint32::deserialize(reader)?;
+		let offset = VarOffset::deserialize(reader)?;
 		Ok(MemArg { align: align.into(), offset: offset.into() })
 	}
 }
@@ -1753,7 +1993,7 @@ impl Serialize for Instruction {
 			BrTable(ref table) => op!(writer, BRTABLE, {
 				let list_writer = CountedListWriter::<VarUint32, _>(
 					table.table.len(),
-					table.table.into_iter().map(|x| VarUint32::from(*x)),
+					table.table.iter().map(|x| VarUint32::from(*x)),
 				);
 				list_writer.serialize(writer)?;
 				VarUint32::from(table.default).serialize(writer)?;
@@ -1785,95 +2025,95 @@ impl Serialize for Instruction {
 			}),
 			I32Load(flags, offset) => op!(writer, I32LOAD, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I64Load(flags, offset) => op!(writer, I64LOAD, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			F32Load(flags, offset) => op!(writer, F32LOAD, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			F64Load(flags, offset) => op!(writer, F64LOAD, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I32Load8S(flags, offset) => op!(writer, I32LOAD8S, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I32Load8U(flags, offset) => op!(writer, I32LOAD8U, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I32Load16S(flags, offset) => op!(writer, I32LOAD16S, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I32Load16U(flags, offset) => op!(writer, I32LOAD16U, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I64Load8S(flags, offset) => op!(writer, I64LOAD8S, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I64Load8U(flags, offset) => op!(writer, I64LOAD8U, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I64Load16S(flags, offset) => op!(writer, I64LOAD16S, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I64Load16U(flags, offset) => op!(writer, I64LOAD16U, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I64Load32S(flags, offset) => op!(writer, I64LOAD32S, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I64Load32U(flags, offset) => op!(writer, I64LOAD32U, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I32Store(flags, offset) => op!(writer, I32STORE, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I64Store(flags, offset) => op!(writer, I64STORE, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			F32Store(flags, offset) => op!(writer, F32STORE, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			F64Store(flags, offset) => op!(writer, F64STORE, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I32Store8(flags, offset) => op!(writer, I32STORE8, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I32Store16(flags, offset) => op!(writer, I32STORE16, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I64Store8(flags, offset) => op!(writer, I64STORE8, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I64Store16(flags, offset) => op!(writer, I64STORE16, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			I64Store32(flags, offset) => op!(writer, I64STORE32, {
 				VarUint32::from(flags).serialize(writer)?;
-				VarUint32::from(offset).serialize(writer)?;
+				VarOffset::from(offset).serialize(writer)?;
 			}),
 			CurrentMemory(flag) => op!(writer, CURRENTMEMORY, {
 				Uint8::from(flag).serialize(writer)?;
@@ -2033,6 +2273,26 @@ impl Serialize for Instruction {
 				SignExtInstruction::I64Extend32S => op!(writer, sign_ext::I64_EXTEND32_S),
 			}
 
+			#[cfg(feature="exceptions")]
+			Exceptions(a) => match a {
+				ExceptionsInstruction::Try(block_type) => op!(writer, exceptions::TRY, {
+					block_type.serialize(writer)?;
+				}),
+				ExceptionsInstruction::Catch(tag) => op!(writer, exceptions::CATCH, {
+					VarUint32::from(tag).serialize(writer)?;
+				}),
+				ExceptionsInstruction::CatchAll => op!(writer, exceptions::CATCH_ALL),
+				ExceptionsInstruction::Throw(tag) => op!(writer, exceptions::THROW, {
+					VarUint32::from(tag).serialize(writer)?;
+				}),
+				ExceptionsInstruction::Rethrow(depth) => op!(writer, exceptions::RETHROW, {
+					VarUint32::from(depth).serialize(writer)?;
+				}),
+				ExceptionsInstruction::Delegate(depth) => op!(writer, exceptions::DELEGATE, {
+					VarUint32::from(depth).serialize(writer)?;
+				}),
+			}
+
 			#[cfg(feature="atomics")]
 			Atomics(a) => return a.serialize(writer),
 
@@ -2059,6 +2319,7 @@ impl Serialize for AtomicsInstruction {
 			AtomicWake(m) => atomic!(writer, ATOMIC_WAKE, m),
 			I32AtomicWait(m) => atomic!(writer, I32_ATOMIC_WAIT, m),
 			I64AtomicWait(m) => atomic!(writer, I64_ATOMIC_WAIT, m),
+			AtomicFence => { writer.write(&[ATOMIC_PREFIX, ATOMIC_FENCE, 0x00])?; },
 
 			I32AtomicLoad(m) => atomic!(writer, I32_ATOMIC_LOAD, m),
 			I64AtomicLoad(m) => atomic!(writer, I64_ATOMIC_LOAD, m),
@@ -2336,7 +2597,7 @@ impl Serialize for MemArg {
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		Uint8::from(self.align).serialize(writer)?;
-		VarUint32::from(self.offset).serialize(writer)?;
+		VarOffset::from(self.offset).serialize(writer)?;
 		Ok(())
 	}
 }
@@ -2605,6 +2866,17 @@ impl fmt::Display for Instruction {
 				SignExtInstruction::I64Extend32S => write!(f, "i64.extend32_s"),
 			}
 
+			#[cfg(feature="exceptions")]
+			Exceptions(ref i) => match *i {
+				ExceptionsInstruction::Try(BlockType::NoResult) => fmt_op!(f, "try"),
+				ExceptionsInstruction::Try(BlockType::Value(value_type)) => fmt_op!(f, "try", value_type),
+				ExceptionsInstruction::Catch(tag) => fmt_op!(f, "catch", tag),
+				ExceptionsInstruction::CatchAll => fmt_op!(f, "catch_all"),
+				ExceptionsInstruction::Throw(tag) => fmt_op!(f, "throw", tag),
+				ExceptionsInstruction::Rethrow(depth) => fmt_op!(f, "rethrow", depth),
+				ExceptionsInstruction::Delegate(depth) => fmt_op!(f, "delegate", depth),
+			}
+
 			#[cfg(feature="atomics")]
 			Atomics(ref i) => i.fmt(f),
 
@@ -2626,6 +2898,7 @@ impl fmt::Display for AtomicsInstruction {
 			AtomicWake(_) => write!(f, "atomic.wake"),
 			I32AtomicWait(_) => write!(f, "i32.atomic.wait"),
 			I64AtomicWait(_) => write!(f, "i64.atomic.wait"),
+			AtomicFence => write!(f, "atomic.fence"),
 
 			I32AtomicLoad(_) => write!(f, "i32.atomic.load"),
 			I64AtomicLoad(_) => write!(f, "i64.atomic.load"),
@@ -2937,6 +3210,10 @@ fn display() {
 
 #[test]
 fn size_off() {
+	// 64-bit offsets make the memory arguments of SIMD instructions larger.
+	#[cfg(all(feature="simd", feature="memory64"))]
+	assert!(::std::mem::size_of::<Instruction>() <= 32);
+	#[cfg(not(all(feature="simd", feature="memory64")))]
 	assert!(::std::mem::size_of::<Instruction>() <= 24);
 }
 
@@ -2949,3 +3226,200 @@ fn instructions_hashset() {
 		vec![Call(1), Block(Value(ValueType::I32)), Drop].into_iter().collect();
 	assert_eq!(set.contains(&Drop), true)
 }
+
+#[cfg(feature="atomics")]
+#[test]
+fn atomics_roundtrip() {
+	use self::AtomicsInstruction::*;
+	use super::{deserialize_buffer, serialize};
+
+	let mem = MemArg { align: 2, offset: 8 };
+	for instruction in [
+		AtomicWake(mem.clone()),
+		I32AtomicWait(mem.clone()),
+		AtomicFence,
+		I32AtomicRmwAnd(mem.clone()),
+		I64AtomicRmwAnd32u(mem.clone()),
+		I64AtomicRmwCmpxchg(mem),
+	] {
+		let instruction = Instruction::Atomics(instruction);
+		let buf = serialize(instruction.clone()).expect("serialization to succeed");
+		let deserialized: Instruction = deserialize_buffer(&buf).expect("deserialization to succeed");
+		assert_eq!(instruction, deserialized);
+	}
+}
+
+#[cfg(feature="atomics")]
+#[test]
+fn atomic_fence_encoding() {
+	use super::{deserialize_buffer, serialize};
+
+	let buf = serialize(Instruction::Atomics(AtomicsInstruction::AtomicFence)).expect("serialization to succeed");
+	assert_eq!(buf, vec![0xfe, 0x03, 0x00]);
+	assert_eq!("atomic.fence", format!("{}", Instruction::Atomics(AtomicsInstruction::AtomicFence)));
+
+	assert!(deserialize_buffer::<Instruction>(&[0xfe, 0x03, 0x01]).is_err());
+}
+
+#[cfg(feature="exceptions")]
+#[test]
+fn exceptions_blocks() {
+	use self::ExceptionsInstruction::*;
+	use self::Instruction::{End, Nop};
+	use super::deserialize_buffer;
+
+	let code = &[
+		0x06, 0x40,       // try
+		  0x08, 0x00,     //   throw 0
+		0x07, 0x00,       // catch 0
+		  0x09, 0x00,     //   rethrow 0
+		0x19,             // catch_all
+		  0x06, 0x40,     //   try
+		    0x01,         //     nop
+		  0x18, 0x01,     //   delegate 1
+		0x0b,             // end
+		0x0b,             // end of function
+	];
+	let instructions: Instructions = deserialize_buffer(code).expect("instructions to deserialize");
+	assert_eq!(instructions.elements(), &[
+		Instruction::Exceptions(Try(BlockType::NoResult)),
+		Instruction::Exceptions(Throw(0)),
+		Instruction::Exceptions(Catch(0)),
+		Instruction::Exceptions(Rethrow(0)),
+		Instruction::Exceptions(CatchAll),
+		Instruction::Exceptions(Try(BlockType::NoResult)),
+		Nop,
+		Instruction::Exceptions(Delegate(1)),
+		End,
+		End,
+	][..]);
+
+	let serialized = super::serialize(instructions).expect("instructions to serialize");
+	assert_eq!(&serialized[..], &code[..]);
+	assert_eq!("delegate 1", format!("{}", Instruction::Exceptions(Delegate(1))));
+}
+
+#[cfg(not(feature="simd"))]
+#[test]
+fn disabled_proposal_opcode() {
+	use super::Error;
+
+	match super::deserialize_buffer::<Instruction>(&[0xfd, 0x00]) {
+		Err(Error::FeatureDisabled("simd")) => {},
+		other => panic!("unexpected result: {:?}", other),
+	}
+}
+
+#[test]
+fn saturating_truncations_are_unsupported() {
+	use super::Error;
+
+	match super::deserialize_buffer::<Instruction>(&[0xfc, 0x00]) {
+		Err(Error::FeatureDisabled("nontrapping_float_to_int")) => {},
+		other => panic!("unexpected result: {:?}", other),
+	}
+	#[cfg(not(feature="bulk"))]
+	match super::deserialize_buffer::<Instruction>(&[0xfc, 0x0b, 0x00]) {
+		Err(Error::FeatureDisabled("bulk_memory")) => {},
+		other => panic!("unexpected result: {:?}", other),
+	}
+}
+
+#[test]
+fn float_constants_are_little_endian() {
+	// Bit patterns are written byte by byte, so the encoding does not depend on the host.
+	let bytes = super::serialize(Instruction::F32Const(0x3f80_0000)).expect("instruction to serialize");
+	assert_eq!(bytes, vec![0x43, 0x00, 0x00, 0x80, 0x3f]);
+	let bytes = super::serialize(Instruction::F64Const(0x3ff0_0000_0000_0000)).expect("instruction to serialize");
+	assert_eq!(bytes, vec![0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f]);
+	assert_eq!(super::deserialize_buffer::<Instruction>(&bytes).expect("instruction to deserialize"), Instruction::F64Const(0x3ff0_0000_0000_0000));
+}
+
+#[cfg(feature="memory64")]
+#[test]
+fn memory64_offsets() {
+	let instruction = Instruction::I64Load(3, 0x1_0000_0000);
+	let bytes = super::serialize(instruction.clone()).expect("instruction to serialize");
+	assert_eq!(bytes, vec![0x29, 0x03, 0x80, 0x80, 0x80, 0x80, 0x10]);
+	assert_eq!(super::deserialize_buffer::<Instruction>(&bytes).expect("instruction to deserialize"), instruction);
+}
+
+#[test]
+fn large_br_table() {
+	use super::BrTableData;
+
+	let targets: Vec<u32> = (0..100_000).map(|target| target % 300).collect();
+	let instruction = Instruction::BrTable(Box::new(BrTableData::new(targets, 7)));
+	let bytes = super::serialize(instruction.clone()).expect("instruction to serialize");
+	assert_eq!(super::deserialize_buffer::<Instruction>(&bytes).expect("instruction to deserialize"), instruction);
+
+	// A count far beyond the bytes is rejected without allocating for it.
+	assert!(super::deserialize_buffer::<Instruction>(&[0x0e, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x00]).is_err());
+}
+
+#[test]
+fn wrap_in_block() {
+	use super::BrTableData;
+	use self::Instruction::*;
+
+	let mut instructions = Instructions::new(vec![
+		Block(BlockType::NoResult),
+		Br(0),
+		Br(1),
+		BrTable(Box::new(BrTableData::new(vec![0, 1], 2))),
+		End,
+		Br(0),
+		End,
+	]);
+	instructions.wrap_in_block(0..6, BlockType::NoResult).expect("range to be wrapped");
+	assert_eq!(instructions.elements(), &[
+		Block(BlockType::NoResult),
+		Block(BlockType::NoResult),
+		Br(0),
+		Br(2),
+		BrTable(Box::new(BrTableData::new(vec![0, 2], 3))),
+		End,
+		Br(1),
+		End,
+		End,
+	][..]);
+
+	let original = instructions.clone();
+	assert!(instructions.wrap_in_block(1..3, BlockType::NoResult).is_err());
+	assert!(instructions.wrap_in_block(5..7, BlockType::NoResult).is_err());
+	assert_eq!(instructions, original);
+
+	let mut instructions = Instructions::new(vec![
+		I32Const(0),
+		If(BlockType::NoResult),
+		Nop,
+		Else,
+		Nop,
+		End,
+		End,
+	]);
+	let original = instructions.clone();
+	assert!(instructions.wrap_in_block(2..5, BlockType::NoResult).is_err());
+	assert!(instructions.wrap_in_block(3..4, BlockType::NoResult).is_err());
+	assert_eq!(instructions, original);
+	instructions.wrap_in_block(1..6, BlockType::NoResult).expect("range to be wrapped");
+}
+
+#[cfg(feature="exceptions")]
+#[test]
+fn wrap_in_block_splitting_try() {
+	use self::Instruction::*;
+
+	let mut instructions = Instructions::new(vec![
+		Exceptions(ExceptionsInstruction::Try(BlockType::NoResult)),
+		Nop,
+		Exceptions(ExceptionsInstruction::Catch(0)),
+		Nop,
+		Exceptions(ExceptionsInstruction::CatchAll),
+		End,
+		End,
+	]);
+	assert!(instructions.wrap_in_block(1..4, BlockType::NoResult).is_err());
+	assert!(instructions.wrap_in_block(3..5, BlockType::NoResult).is_err());
+	instructions.wrap_in_block(0..6, BlockType::NoResult).expect("range to be wrapped");
+}
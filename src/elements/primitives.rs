@@ -1,3 +1,10 @@
+#![allow(
+	clippy::collapsible_if,
+	clippy::legacy_numeric_constants,
+	clippy::redundant_field_names,
+	clippy::redundant_pattern,
+)]
+
 use alloc::{string::String, vec::Vec};
 use crate::{io, elements};
 use super::{Error, Deserialize, Serialize};
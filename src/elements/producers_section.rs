@@ -0,0 +1,241 @@
+use alloc::{string::String, vec::Vec};
+use crate::io;
+
+use super::{CountedList, CountedListWriter, Deserialize, Error, Serialize, VarUint32};
+
+const FIELD_LANGUAGE: &str = "language";
+const FIELD_PROCESSED_BY: &str = "processed-by";
+const FIELD_SDK: &str = "sdk";
+
+/// Tools which produced a module, per the `producers` section of the tool conventions.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProducersSection {
+	/// Source languages.
+	language: Vec<ProducerValue>,
+
+	/// Tools which produced or rewrote the module.
+	processed_by: Vec<ProducerValue>,
+
+	/// SDKs the module was built with.
+	sdk: Vec<ProducerValue>,
+}
+
+/// Name and version of a language, tool or SDK.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProducerValue {
+	/// Name of the producer.
+	pub name: String,
+	/// Version of the producer, possibly empty.
+	pub version: String,
+}
+
+impl ProducerValue {
+	/// Creates a new producer value.
+	pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+		ProducerValue { name: name.into(), version: version.into() }
+	}
+}
+
+impl ProducersSection {
+	/// Creates a new producers section.
+	pub fn new(language: Vec<ProducerValue>, processed_by: Vec<ProducerValue>, sdk: Vec<ProducerValue>) -> Self {
+		ProducersSection { language, processed_by, sdk }
+	}
+
+	/// Source languages.
+	pub fn language(&self) -> &[ProducerValue] {
+		&self.language
+	}
+
+	/// Source languages (mutable).
+	pub fn language_mut(&mut self) -> &mut Vec<ProducerValue> {
+		&mut self.language
+	}
+
+	/// Tools which produced or rewrote the module.
+	pub fn processed_by(&self) -> &[ProducerValue] {
+		&self.processed_by
+	}
+
+	/// Tools which produced or rewrote the module (mutable).
+	pub fn processed_by_mut(&mut self) -> &mut Vec<ProducerValue> {
+		&mut self.processed_by
+	}
+
+	/// SDKs the module was built with.
+	pub fn sdk(&self) -> &[ProducerValue] {
+		&self.sdk
+	}
+
+	/// SDKs the module was built with (mutable).
+	pub fn sdk_mut(&mut self) -> &mut Vec<ProducerValue> {
+		&mut self.sdk
+	}
+
+	/// Record that the module was processed by `name` at `version`.
+	///
+	/// An existing entry with the same name gets its version updated, since a
+	/// name may only appear once per field.
+	pub fn add_processed_by(&mut self, name: &str, version: &str) {
+		match self.processed_by.iter_mut().find(|value| value.name == name) {
+			Some(value) => value.version = version.into(),
+			None => self.processed_by.push(ProducerValue::new(name, version)),
+		}
+	}
+
+	/// Add the producers of `other`, as when linking two modules.
+	///
+	/// Values are unioned per field; for names present in both, the version of
+	/// `self` is kept.
+	pub fn merge(&mut self, other: ProducersSection) {
+		fn union(values: &mut Vec<ProducerValue>, other: Vec<ProducerValue>) {
+			for value in other {
+				if !values.iter().any(|existing| existing.name == value.name) {
+					values.push(value);
+				}
+			}
+		}
+
+		union(&mut self.language, other.language);
+		union(&mut self.processed_by, other.processed_by);
+		union(&mut self.sdk, other.sdk);
+	}
+
+	/// Whether no field has any value.
+	pub fn is_empty(&self) -> bool {
+		self.language.is_empty() && self.processed_by.is_empty() && self.sdk.is_empty()
+	}
+}
+
+impl Deserialize for ProducersSection {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		let mut section = ProducersSection::default();
+		let mut seen = [false; 3];
+
+		let field_count: u32 = VarUint32::deserialize(rdr)?.into();
+		for _ in 0..field_count {
+			let name = String::deserialize(rdr)?;
+			let index = match name.as_str() {
+				FIELD_LANGUAGE => 0,
+				FIELD_PROCESSED_BY => 1,
+				FIELD_SDK => 2,
+				_ => return Err(Error::Other("unknown producers field")),
+			};
+			if seen[index] {
+				return Err(Error::Other("duplicated producers field"));
+			}
+			seen[index] = true;
+
+			let values: Vec<ProducerValue> = CountedList::deserialize(rdr)?.into_inner();
+			*match index {
+				0 => &mut section.language,
+				1 => &mut section.processed_by,
+				_ => &mut section.sdk,
+			} = values;
+		}
+
+		Ok(section)
+	}
+}
+
+impl Serialize for ProducersSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		let fields: Vec<_> = vec![
+			(FIELD_LANGUAGE, self.language),
+			(FIELD_PROCESSED_BY, self.processed_by),
+			(FIELD_SDK, self.sdk),
+		]
+			.into_iter()
+			.filter(|(_, values)| !values.is_empty())
+			.collect();
+
+		VarUint32::from(fields.len()).serialize(wtr)?;
+		for (name, values) in fields {
+			String::from(name).serialize(wtr)?;
+			CountedListWriter(values.len(), values.into_iter()).serialize(wtr)?;
+		}
+		Ok(())
+	}
+}
+
+impl Deserialize for ProducerValue {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(ProducerValue {
+			name: String::deserialize(rdr)?,
+			version: String::deserialize(rdr)?,
+		})
+	}
+}
+
+impl Serialize for ProducerValue {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		self.name.serialize(wtr)?;
+		self.version.serialize(wtr)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::{deserialize_buffer, serialize, Module};
+	use crate::builder;
+
+	#[test]
+	fn producers_round_trip() {
+		let producers = ProducersSection::new(
+			vec![ProducerValue::new("Rust", "")],
+			vec![ProducerValue::new("rustc", "1.70.0")],
+			vec![],
+		);
+		let mut module = builder::module().build();
+		module.set_custom_section("producers", serialize(producers.clone()).expect("section to serialize"));
+
+		let bytes = serialize(module).expect("module to serialize");
+		let module: Module = deserialize_buffer(&bytes).expect("module to deserialize");
+		let module = module.parse_producers().expect("producers section to parse");
+		assert_eq!(module.producers_section(), Some(&producers));
+	}
+
+	#[test]
+	fn record_processed_by() {
+		let mut module = builder::module().build();
+		module.record_processed_by().expect("no producers section to fail parsing");
+		module.record_processed_by().expect("producers section to be updated");
+
+		let producers = module.producers_section().expect("producers section");
+		assert_eq!(producers.processed_by(), &[ProducerValue::new("parity-wasm", env!("CARGO_PKG_VERSION"))][..]);
+	}
+
+	#[test]
+	fn merge() {
+		let mut first = ProducersSection::new(vec![], vec![ProducerValue::new("clang", "15")], vec![]);
+		first.merge(ProducersSection::new(
+			vec![ProducerValue::new("C", "")],
+			vec![ProducerValue::new("clang", "16"), ProducerValue::new("wasm-opt", "110")],
+			vec![],
+		));
+		assert_eq!(first.language(), &[ProducerValue::new("C", "")][..]);
+		assert_eq!(
+			first.processed_by(),
+			&[ProducerValue::new("clang", "15"), ProducerValue::new("wasm-opt", "110")][..],
+		);
+	}
+
+	#[test]
+	fn duplicated_field() {
+		// Two empty `sdk` fields.
+		let payload = [2, 3, b's', b'd', b'k', 0, 3, b's', b'd', b'k', 0];
+		assert!(ProducersSection::deserialize(&mut &payload[..]).is_err());
+	}
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::option_as_ref_deref, clippy::single_match)]
+
 use alloc::{string::String, vec::Vec};
 use crate::io;
 
@@ -11,9 +13,14 @@ const MEMORY_ADDR_SLEB: u8 = 4;
 const MEMORY_ADDR_I32: u8 = 5;
 const TYPE_INDEX_LEB: u8 = 6;
 const GLOBAL_INDEX_LEB: u8 = 7;
+const FUNCTION_OFFSET_I32: u8 = 8;
+const SECTION_OFFSET_I32: u8 = 9;
+const TAG_INDEX_LEB: u8 = 10;
+const GLOBAL_INDEX_I32: u8 = 13;
 
 /// Relocation information.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RelocSection {
 	/// Name of this section.
 	name: String,
@@ -122,6 +129,7 @@ impl Serialize for RelocSection {
 
 /// Relocation entry.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RelocationEntry {
 	/// Function index.
 	FunctionIndexLeb {
@@ -203,6 +211,48 @@ pub enum RelocationEntry {
 		/// Index of the global symbol in the symbol table.
 		index: u32,
 	},
+
+	/// Byte offset within a function's code, used by debug information.
+	FunctionOffsetI32 {
+		/// Offset of the value to rewrite.
+		offset: u32,
+
+		/// Index of the function symbol in the symbol table.
+		index: u32,
+
+		/// Addend to add to the offset.
+		addend: i32,
+	},
+
+	/// Byte offset within a custom section, used by debug information.
+	SectionOffsetI32 {
+		/// Offset of the value to rewrite.
+		offset: u32,
+
+		/// Index of the section symbol in the symbol table.
+		index: u32,
+
+		/// Addend to add to the offset.
+		addend: i32,
+	},
+
+	/// Tag index.
+	TagIndexLeb {
+		/// Offset of the value to rewrite.
+		offset: u32,
+
+		/// Index of the tag symbol in the symbol table.
+		index: u32,
+	},
+
+	/// Global index, as a 32-bit integer.
+	GlobalIndexI32 {
+		/// Offset of the value to rewrite.
+		offset: u32,
+
+		/// Index of the global symbol in the symbol table.
+		index: u32,
+	},
 }
 
 impl Deserialize for RelocationEntry {
@@ -253,6 +303,28 @@ impl Deserialize for RelocationEntry {
 				index: VarUint32::deserialize(rdr)?.into(),
 			}),
 
+			FUNCTION_OFFSET_I32 => Ok(RelocationEntry::FunctionOffsetI32 {
+				offset: VarUint32::deserialize(rdr)?.into(),
+				index: VarUint32::deserialize(rdr)?.into(),
+				addend: VarInt32::deserialize(rdr)?.into(),
+			}),
+
+			SECTION_OFFSET_I32 => Ok(RelocationEntry::SectionOffsetI32 {
+				offset: VarUint32::deserialize(rdr)?.into(),
+				index: VarUint32::deserialize(rdr)?.into(),
+				addend: VarInt32::deserialize(rdr)?.into(),
+			}),
+
+			TAG_INDEX_LEB => Ok(RelocationEntry::TagIndexLeb {
+				offset: VarUint32::deserialize(rdr)?.into(),
+				index: VarUint32::deserialize(rdr)?.into(),
+			}),
+
+			GLOBAL_INDEX_I32 => Ok(RelocationEntry::GlobalIndexI32 {
+				offset: VarUint32::deserialize(rdr)?.into(),
+				index: VarUint32::deserialize(rdr)?.into(),
+			}),
+
 			entry_type => Err(Error::UnknownValueType(entry_type as i8)),
 		}
 	}
@@ -313,6 +385,32 @@ impl Serialize for RelocationEntry {
 				VarUint32::from(offset).serialize(wtr)?;
 				VarUint32::from(index).serialize(wtr)?;
 			},
+
+			RelocationEntry::FunctionOffsetI32 { offset, index, addend } => {
+				VarUint7::from(FUNCTION_OFFSET_I32).serialize(wtr)?;
+				VarUint32::from(offset).serialize(wtr)?;
+				VarUint32::from(index).serialize(wtr)?;
+				VarInt32::from(addend).serialize(wtr)?;
+			},
+
+			RelocationEntry::SectionOffsetI32 { offset, index, addend } => {
+				VarUint7::from(SECTION_OFFSET_I32).serialize(wtr)?;
+				VarUint32::from(offset).serialize(wtr)?;
+				VarUint32::from(index).serialize(wtr)?;
+				VarInt32::from(addend).serialize(wtr)?;
+			},
+
+			RelocationEntry::TagIndexLeb { offset, index } => {
+				VarUint7::from(TAG_INDEX_LEB).serialize(wtr)?;
+				VarUint32::from(offset).serialize(wtr)?;
+				VarUint32::from(index).serialize(wtr)?;
+			},
+
+			RelocationEntry::GlobalIndexI32 { offset, index } => {
+				VarUint7::from(GLOBAL_INDEX_I32).serialize(wtr)?;
+				VarUint32::from(offset).serialize(wtr)?;
+				VarUint32::from(index).serialize(wtr)?;
+			},
 		}
 
 		Ok(())
@@ -321,7 +419,7 @@ impl Serialize for RelocationEntry {
 
 #[cfg(test)]
 mod tests {
-	use super::super::{Section, deserialize_file};
+	use super::super::{Section, Serialize, Deserialize, deserialize_file};
 	use super::RelocationEntry;
 
 	#[test]
@@ -344,4 +442,20 @@ mod tests {
 		}
 		assert!(found, "There should be a reloc section in relocatable.wasm");
 	}
+
+	#[test]
+	fn debug_entries_round_trip() {
+		let entries = vec![
+			RelocationEntry::FunctionOffsetI32 { offset: 8, index: 1, addend: 12 },
+			RelocationEntry::SectionOffsetI32 { offset: 16, index: 2, addend: -4 },
+			RelocationEntry::TagIndexLeb { offset: 24, index: 3 },
+			RelocationEntry::GlobalIndexI32 { offset: 32, index: 4 },
+		];
+		for entry in entries {
+			let mut buffer = Vec::new();
+			entry.serialize(&mut buffer).expect("entry to serialize");
+			let decoded = RelocationEntry::deserialize(&mut &buffer[..]).expect("entry to deserialize");
+			assert_eq!(decoded, entry);
+		}
+	}
 }
@@ -1,3 +1,13 @@
+#![allow(
+	clippy::match_like_matches_macro,
+	clippy::needless_borrowed_reference,
+	clippy::redundant_field_names,
+	clippy::single_match,
+	clippy::unnecessary_cast,
+	clippy::useless_conversion,
+	clippy::zero_prefixed_literal,
+)]
+
 use alloc::{vec::Vec, borrow::ToOwned, string::String};
 use crate::{io, elements};
 use super::{
@@ -23,8 +33,14 @@ use super::{
 };
 
 use super::types::Type;
+#[cfg(feature="exceptions")]
+use super::import_entry::TagType;
 use super::name_section::NameSection;
 use super::reloc_section::RelocSection;
+use super::linking_section::LinkingSection;
+use super::producers_section::ProducersSection;
+use super::target_features_section::TargetFeaturesSection;
+use super::dylink_section::DylinkSection;
 
 #[cfg(feature = "reduced-stack-buffer")]
 const ENTRIES_BUFFER_LENGTH: usize = 256;
@@ -34,6 +50,7 @@ const ENTRIES_BUFFER_LENGTH: usize = 16384;
 
 /// Section in the WebAssembly module.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Section {
 	/// Section is unparsed.
 	Unparsed {
@@ -54,6 +71,9 @@ pub enum Section {
 	Table(TableSection),
 	/// Memory definition section.
 	Memory(MemorySection),
+	/// Exception tags section.
+	#[cfg(feature="exceptions")]
+	Tag(TagSection),
 	/// Global entries section.
 	Global(GlobalSection),
 	/// Export definitions.
@@ -78,20 +98,38 @@ pub enum Section {
 	/// Also note that currently there are serialization (but not de-serialization)
 	///   issues with this section (#198).
 	Reloc(RelocSection),
+	/// Linking section of a relocatable object file.
+	///
+	/// Note that initially it is not parsed until `parse_linking` is called explicitly.
+	Linking(LinkingSection),
+	/// Producers section.
+	///
+	/// Note that initially it is not parsed until `parse_producers` is called explicitly.
+	Producers(ProducersSection),
+	/// Target features section.
+	///
+	/// Note that initially it is not parsed until `parse_target_features` is called explicitly.
+	TargetFeatures(TargetFeaturesSection),
+	/// Dynamic linking section (`dylink.0`).
+	///
+	/// Note that initially it is not parsed until `parse_dylink` is called explicitly.
+	Dylink(DylinkSection),
 }
 
 impl Deserialize for Section {
 	type Error = Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
-		let id = match VarUint7::deserialize(reader) {
-			// todo: be more selective detecting no more section
-			Err(_) => { return Err(Error::UnexpectedEof); },
-			Ok(id) => id,
-		};
+		let id = VarUint7::deserialize(reader)?;
+		Section::deserialize_with_id(id.into(), reader)
+	}
+}
 
+impl Section {
+	/// Read the rest of a section whose id was already read.
+	pub(crate) fn deserialize_with_id<R: io::Read>(id: u8, reader: &mut R) -> Result<Self, Error> {
 		Ok(
-			match id.into() {
+			match id {
 				0 => {
 					Section::Custom(CustomSection::deserialize(reader)?.into())
 				},
@@ -137,6 +175,10 @@ impl Deserialize for Section {
 					section_reader.close()?;
 					Section::DataCount(count.into())
 				},
+				#[cfg(feature="exceptions")]
+				13 => {
+					Section::Tag(TagSection::deserialize(reader)?)
+				},
 				invalid_id => {
 					return Err(Error::InvalidSectionId(invalid_id))
 				},
@@ -178,6 +220,11 @@ impl Serialize for Section {
 				VarUint7::from(0x05).serialize(writer)?;
 				memory_section.serialize(writer)?;
 			},
+			#[cfg(feature="exceptions")]
+			Section::Tag(tag_section) => {
+				VarUint7::from(0x0d).serialize(writer)?;
+				tag_section.serialize(writer)?;
+			},
 			Section::Global(global_section) => {
 				VarUint7::from(0x06).serialize(writer)?;
 				global_section.serialize(writer)?;
@@ -222,6 +269,38 @@ impl Serialize for Section {
 				VarUint7::from(0x00).serialize(writer)?;
 				reloc_section.serialize(writer)?;
 			},
+			Section::Linking(linking_section) => {
+				VarUint7::from(0x00).serialize(writer)?;
+				let custom = CustomSection {
+					name: "linking".to_owned(),
+					payload: serialize(linking_section)?,
+				};
+				custom.serialize(writer)?;
+			},
+			Section::Producers(producers_section) => {
+				VarUint7::from(0x00).serialize(writer)?;
+				let custom = CustomSection {
+					name: "producers".to_owned(),
+					payload: serialize(producers_section)?,
+				};
+				custom.serialize(writer)?;
+			},
+			Section::TargetFeatures(target_features_section) => {
+				VarUint7::from(0x00).serialize(writer)?;
+				let custom = CustomSection {
+					name: "target_features".to_owned(),
+					payload: serialize(target_features_section)?,
+				};
+				custom.serialize(writer)?;
+			},
+			Section::Dylink(dylink_section) => {
+				VarUint7::from(0x00).serialize(writer)?;
+				let custom = CustomSection {
+					name: "dylink.0".to_owned(),
+					payload: serialize(dylink_section)?,
+				};
+				custom.serialize(writer)?;
+			},
 		}
 		Ok(())
 	}
@@ -244,10 +323,34 @@ impl Section {
 			Section::DataCount(_) => 0x0a,
 			Section::Code(_) => 0x0b,
 			Section::Data(_) => 0x0c,
+			#[cfg(feature="exceptions")]
+			Section::Tag(_) => TAG_ORDER,
 			Section::Name(_) => 0x00,
 			Section::Reloc(_) => 0x00,
+			Section::Linking(_) => 0x00,
+			Section::Producers(_) => 0x00,
+			Section::TargetFeatures(_) => 0x00,
+			Section::Dylink(_) => 0x00,
 		}
 	}
+
+	/// Key ordering the sections of a module, custom sections aside.
+	pub(crate) fn position(&self) -> u8 {
+		position(self.order())
+	}
+}
+
+/// Order of the tag section, which comes after the data section in `order` but goes
+/// between the memory and global sections in a module.
+const TAG_ORDER: u8 = 0x0d;
+
+/// Position of the sections of the given `order` in a module.
+pub(crate) fn position(order: u8) -> u8 {
+	if order == TAG_ORDER {
+		0x05 * 2 + 1
+	} else {
+		order * 2
+	}
 }
 
 pub(crate) struct SectionReader {
@@ -298,6 +401,7 @@ fn read_entries<R: io::Read, T: Deserialize<Error=elements::Error>>(reader: &mut
 
 /// Custom section.
 #[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CustomSection {
 	name: String,
 	payload: Vec<u8>,
@@ -359,6 +463,7 @@ impl Serialize for CustomSection {
 
 /// Section with type declarations.
 #[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeSection(Vec<Type>);
 
 impl TypeSection {
@@ -404,6 +509,7 @@ impl Serialize for TypeSection {
 
 /// Section of the imports definition.
 #[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImportSection(Vec<ImportEntry>);
 
 impl ImportSection {
@@ -463,6 +569,7 @@ impl Serialize for ImportSection {
 
 /// Section with function signatures definition.
 #[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionSection(Vec<Func>);
 
 impl FunctionSection {
@@ -508,6 +615,7 @@ impl Serialize for FunctionSection {
 
 /// Section with table definition (currently only one is allowed).
 #[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TableSection(Vec<TableType>);
 
 impl TableSection {
@@ -553,6 +661,7 @@ impl Serialize for TableSection {
 
 /// Section with table definition (currently only one entry is allowed).
 #[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemorySection(Vec<MemoryType>);
 
 impl MemorySection {
@@ -596,8 +705,59 @@ impl Serialize for MemorySection {
 	}
 }
 
+/// Section with exception tag definitions.
+#[cfg(feature="exceptions")]
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TagSection(Vec<TagType>);
+
+#[cfg(feature="exceptions")]
+impl TagSection {
+	/// List of all tag entries in the section
+	pub fn entries(&self) -> &[TagType] {
+		&self.0
+	}
+
+	///  New tag section with tag types.
+	pub fn with_entries(entries: Vec<TagType>) -> Self {
+		TagSection(entries)
+	}
+
+	/// Mutable list of all tag entries in the section.
+	pub fn entries_mut(&mut self) -> &mut Vec<TagType> {
+		&mut self.0
+	}
+}
+
+#[cfg(feature="exceptions")]
+impl Deserialize for TagSection {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		Ok(TagSection(read_entries(reader)?))
+	}
+}
+
+#[cfg(feature="exceptions")]
+impl Serialize for TagSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		let mut counted_writer = CountedWriter::new(writer);
+		let data = self.0;
+		let counted_list = CountedListWriter::<TagType, _>(
+			data.len(),
+			data.into_iter().map(Into::into),
+		);
+		counted_list.serialize(&mut counted_writer)?;
+		counted_writer.done()?;
+		Ok(())
+	}
+}
+
 /// Globals definition section.
 #[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalSection(Vec<GlobalEntry>);
 
 impl GlobalSection {
@@ -643,6 +803,7 @@ impl Serialize for GlobalSection {
 
 /// List of exports definition.
 #[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExportSection(Vec<ExportEntry>);
 
 impl ExportSection {
@@ -688,6 +849,7 @@ impl Serialize for ExportSection {
 
 /// Section with function bodies of the module.
 #[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CodeSection(Vec<FuncBody>);
 
 impl CodeSection {
@@ -733,6 +895,7 @@ impl Serialize for CodeSection {
 
 /// Element entries section.
 #[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ElementSection(Vec<ElementSegment>);
 
 impl ElementSection {
@@ -778,6 +941,7 @@ impl Serialize for ElementSection {
 
 /// Data entries definitions.
 #[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DataSection(Vec<DataSegment>);
 
 impl DataSection {
@@ -1154,4 +1318,44 @@ mod tests {
 
 		assert_eq!(serialized, vec![08u8, 01u8, 00u8]);
 	}
+
+	#[cfg(feature="exceptions")]
+	#[test]
+	fn tag_section() {
+		use super::{TagSection, TagType};
+
+		let section = Section::Tag(TagSection::with_entries(vec![TagType::new(0), TagType::new(3)]));
+		let serialized = serialize(section.clone()).expect("Tag section to serialize");
+
+		assert_eq!(serialized, vec![
+			0x0d,       // tag section id
+			5u8,        // 5 bytes overall
+			0x02,       // 2 tags
+			  0x00, 0x00, // exception, type #0
+			  0x00, 0x03, // exception, type #3
+		]);
+
+		let deserialized: Section = deserialize_buffer(&serialized).expect("Tag section to deserialize");
+		assert_eq!(section, deserialized);
+	}
+
+	#[cfg(feature="exceptions")]
+	#[test]
+	fn tag_section_position() {
+		use super::super::{Error, GlobalEntry, InitExpr, GlobalType, Module, Instruction, TagType, ValueType};
+		use super::{GlobalSection, MemorySection, TagSection};
+
+		let tags = Section::Tag(TagSection::with_entries(vec![TagType::new(0)]));
+		let global = GlobalEntry::new(GlobalType::new(ValueType::I32, false), InitExpr::new(vec![Instruction::I32Const(0), Instruction::End]));
+		let mut module = Module::new(vec![
+			Section::Memory(MemorySection::default()),
+			Section::Global(GlobalSection::with_entries(vec![global])),
+		]);
+		module.insert_section(tags.clone()).expect("tag section to be inserted");
+		assert_eq!(module.sections().iter().map(|s| s.order()).collect::<Vec<_>>(), vec![5, 13, 6]);
+
+		let bytes = serialize(module.clone()).expect("module to serialize");
+		assert_eq!(deserialize_buffer::<Module>(&bytes).expect("module to deserialize"), module);
+		assert!(matches!(module.insert_section(tags), Err(Error::DuplicatedSections(13))));
+	}
 }
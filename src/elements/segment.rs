@@ -1,3 +1,5 @@
+#![allow(clippy::redundant_field_names)]
+
 use alloc::vec::Vec;
 use crate::io;
 use super::{Deserialize, Serialize, Error, VarUint32, CountedList, InitExpr, CountedListWriter};
@@ -17,6 +19,7 @@ const VALUES_BUFFER_LENGTH: usize = 16384;
 
 /// Entry in the element section.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ElementSegment {
 	index: u32,
 	offset: Option<InitExpr>,
@@ -48,6 +51,9 @@ impl ElementSegment {
 	/// Table index (currently valid only value of `0`)
 	pub fn index(&self) -> u32 { self.index }
 
+	/// Table index (mutable)
+	pub fn index_mut(&mut self) -> &mut u32 { &mut self.index }
+
 	/// An i32 initializer expression that computes the offset at which to place the elements.
 	///
 	/// Note that this return `None` if the segment is `passive`.
@@ -159,6 +165,7 @@ impl Serialize for ElementSegment {
 
 /// Data segment definition.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DataSegment {
 	index: u32,
 	offset: Option<InitExpr>,
@@ -184,6 +191,9 @@ impl DataSegment {
 	/// Linear memory index (currently the only valid value is `0`).
 	pub fn index(&self) -> u32 { self.index }
 
+	/// Linear memory index (mutable).
+	pub fn index_mut(&mut self) -> &mut u32 { &mut self.index }
+
 	/// An i32 initializer expression that computes the offset at which to place the data.
 	///
 	/// Note that this return `None` if the segment is `passive`.
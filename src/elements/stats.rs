@@ -0,0 +1,177 @@
+use alloc::{string::{String, ToString}, vec::Vec, collections::BTreeMap};
+use core::fmt;
+use super::{Module, Section, Error, ImportCountType, serialize};
+use super::diagnose::section_name;
+
+/// Size and composition of a module, as returned by `Module::stats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleStats {
+	/// Size of the serialized module, header included, in bytes.
+	pub total_size: usize,
+	/// Every section in module order.
+	pub sections: Vec<SectionStats>,
+	/// Every defined function, largest first.
+	pub functions: Vec<FunctionStats>,
+	/// Number of uses of each instruction mnemonic over all function bodies, most used first.
+	pub opcodes: Vec<(String, usize)>,
+	/// Number of imports.
+	pub imports: usize,
+	/// Number of exports.
+	pub exports: usize,
+}
+
+/// Size of a section.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionStats {
+	/// Section id, `0` for custom sections.
+	pub id: u8,
+	/// Name of the section kind, or of the custom section.
+	pub name: String,
+	/// Serialized size, header included, in bytes.
+	pub size: usize,
+}
+
+/// Size of a function body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionStats {
+	/// Index in the function index space.
+	pub index: u32,
+	/// Name from the name section, if it is parsed and has one.
+	pub name: Option<String>,
+	/// Serialized size of the body, size prefix included, in bytes.
+	pub size: usize,
+	/// Number of instructions.
+	pub instructions: usize,
+}
+
+fn section_id_and_name(section: &Section) -> (u8, String) {
+	match *section {
+		Section::Custom(ref custom) => (0, custom.name().into()),
+		Section::Name(_) => (0, "name".into()),
+		Section::Reloc(ref reloc) => (0, reloc.name().into()),
+		Section::Linking(_) => (0, "linking".into()),
+		Section::Producers(_) => (0, "producers".into()),
+		Section::TargetFeatures(_) => (0, "target_features".into()),
+		Section::Dylink(_) => (0, "dylink.0".into()),
+		Section::Unparsed { id, .. } => (id, section_name(id).into()),
+		Section::Type(_) => (1, section_name(1).into()),
+		Section::Import(_) => (2, section_name(2).into()),
+		Section::Function(_) => (3, section_name(3).into()),
+		Section::Table(_) => (4, section_name(4).into()),
+		Section::Memory(_) => (5, section_name(5).into()),
+		Section::Global(_) => (6, section_name(6).into()),
+		Section::Export(_) => (7, section_name(7).into()),
+		Section::Start(_) => (8, section_name(8).into()),
+		Section::Element(_) => (9, section_name(9).into()),
+		Section::Code(_) => (10, section_name(10).into()),
+		Section::Data(_) => (11, section_name(11).into()),
+		Section::DataCount(_) => (12, section_name(12).into()),
+		#[cfg(feature="exceptions")]
+		Section::Tag(_) => (13, section_name(13).into()),
+	}
+}
+
+/// Mnemonic of an instruction, without its immediates.
+fn mnemonic(instruction: &super::Instruction) -> String {
+	let text = instruction.to_string();
+	match text.find(' ') {
+		Some(end) => text[..end].into(),
+		None => text,
+	}
+}
+
+pub(crate) fn stats(module: &Module) -> Result<ModuleStats, Error> {
+	let mut sections = Vec::with_capacity(module.sections().len());
+	for section in module.sections() {
+		let (id, name) = section_id_and_name(section);
+		sections.push(SectionStats { id, name, size: serialize(section.clone())?.len() });
+	}
+
+	let imported = module.import_count(ImportCountType::Function) as u32;
+	let names = module.names_section().and_then(|names| names.functions());
+	let mut functions = Vec::new();
+	let mut opcodes = BTreeMap::new();
+	for (index, body) in module.code_section().map(|s| s.bodies()).unwrap_or(&[]).iter().enumerate() {
+		let index = imported + index as u32;
+		for instruction in body.code().elements() {
+			*opcodes.entry(mnemonic(instruction)).or_insert(0) += 1;
+		}
+		functions.push(FunctionStats {
+			index,
+			name: names.and_then(|names| names.names().get(index)).cloned(),
+			size: serialize(body.clone())?.len(),
+			instructions: body.code().elements().len(),
+		});
+	}
+	functions.sort_by(|a, b| b.size.cmp(&a.size).then(a.index.cmp(&b.index)));
+	let mut opcodes: Vec<_> = opcodes.into_iter().collect();
+	opcodes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+	Ok(ModuleStats {
+		// Magic and version.
+		total_size: 8 + sections.iter().map(|section| section.size).sum::<usize>(),
+		sections,
+		functions,
+		opcodes,
+		imports: module.import_section().map(|s| s.entries().len()).unwrap_or(0),
+		exports: module.export_section().map(|s| s.entries().len()).unwrap_or(0),
+	})
+}
+
+impl fmt::Display for ModuleStats {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{} bytes, {} imports, {} exports", self.total_size, self.imports, self.exports)?;
+		writeln!(f, "sections:")?;
+		for section in &self.sections {
+			writeln!(f, "  {:>8}  {} ({})", section.size, section.name, section.id)?;
+		}
+		writeln!(f, "functions:")?;
+		for function in &self.functions {
+			write!(f, "  {:>8}  #{}", function.size, function.index)?;
+			if let Some(ref name) = function.name {
+				write!(f, " {}", name)?;
+			}
+			writeln!(f, " ({} instructions)", function.instructions)?;
+		}
+		writeln!(f, "opcodes:")?;
+		for &(ref opcode, count) in &self.opcodes {
+			writeln!(f, "  {:>8}  {}", count, opcode)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::builder;
+	use crate::elements::{Instruction::*, Instructions};
+
+	#[test]
+	fn stats() {
+		let module = builder::module()
+			.import().module("env").field("log").external().func(0).build()
+			.function()
+				.signature().build()
+				.body().build()
+				.build()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![I32Const(1), I32Const(2), I32Add, Drop, End]))
+					.build()
+				.build()
+			.export().field("main").internal().func(2).build()
+			.build();
+		let bytes = module.clone().to_bytes().expect("module to serialize");
+		let stats = module.stats().expect("module to serialize");
+
+		assert_eq!(stats.total_size, bytes.len());
+		let names: Vec<_> = stats.sections.iter().map(|section| section.name.as_str()).collect();
+		assert_eq!(names, vec!["type", "import", "function", "export", "code"]);
+		assert_eq!(stats.functions.iter().map(|function| function.index).collect::<Vec<_>>(), vec![2, 1]);
+		assert_eq!(stats.functions[0].instructions, 5);
+		assert_eq!(&stats.opcodes[..2], &[("end".to_string(), 2), ("i32.const".to_string(), 2)]);
+		assert_eq!((stats.imports, stats.exports), (1, 1));
+		assert!(stats.to_string().starts_with(&format!("{} bytes, 1 imports, 1 exports\n", bytes.len())));
+	}
+}
@@ -0,0 +1,128 @@
+use super::{Error, Module, Section, serialize};
+use super::debug_sections::is_debug_section;
+
+/// Which custom sections `Module::strip` removes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StripConfig {
+	/// Remove the name section.
+	pub names: bool,
+	/// Remove DWARF sections, those named `.debug_*`, and `external_debug_info`.
+	pub debug: bool,
+	/// Remove the producers section.
+	pub producers: bool,
+	/// Remove the `target_features` section.
+	pub target_features: bool,
+	/// Remove every custom section, including relocation and linking metadata.
+	pub all_custom: bool,
+}
+
+impl Default for StripConfig {
+	/// Remove everything but the custom sections needed for linking or loading.
+	fn default() -> Self {
+		StripConfig {
+			names: true,
+			debug: true,
+			producers: true,
+			target_features: true,
+			all_custom: false,
+		}
+	}
+}
+
+impl StripConfig {
+	/// Whether the section is to be removed.
+	///
+	/// Relocation sections go along with the custom section they apply to.
+	fn strips(&self, section: &Section) -> bool {
+		let name = match *section {
+			Section::Custom(ref custom) => Some(custom.name()),
+			Section::Name(_) => Some("name"),
+			Section::Producers(_) => Some("producers"),
+			Section::TargetFeatures(_) => Some("target_features"),
+			Section::Reloc(_) | Section::Linking(_) | Section::Dylink(_) => None,
+			_ => return false,
+		};
+		self.all_custom
+			|| name.is_some_and(|name| self.strips_name(name))
+			|| reloc_target(section).is_some_and(|target| self.strips_name(target))
+	}
+
+	fn strips_name(&self, name: &str) -> bool {
+		(self.names && name == "name")
+			|| (self.debug && (is_debug_section(name) || name == "external_debug_info"))
+			|| (self.producers && name == "producers")
+			|| (self.target_features && name == "target_features")
+	}
+}
+
+/// Name of the section whose relocations `section` holds, if it is a relocation
+/// section: `CODE` or `DATA`, or the name of a custom section.
+fn reloc_target(section: &Section) -> Option<&str> {
+	match *section {
+		Section::Reloc(ref reloc) => reloc.relocation_section_name().or_else(|| reloc.name().strip_prefix("reloc.")),
+		Section::Custom(ref custom) => custom.name().strip_prefix("reloc."),
+		_ => None,
+	}
+}
+
+pub(crate) fn strip(module: &mut Module, config: StripConfig) -> Result<usize, Error> {
+	// Sizes first, so that the module is left untouched if any fails to serialize.
+	let mut saved = 0;
+	for section in module.sections().iter().filter(|section| config.strips(section)) {
+		saved += serialize(section.clone())?.len();
+	}
+	module.sections_mut().retain(|section| !config.strips(section));
+	Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::StripConfig;
+	use crate::builder;
+	use crate::elements::{serialize, CustomSection, Section};
+
+	fn module() -> crate::elements::Module {
+		let mut module = builder::module()
+			.function().signature().build().body().build().build()
+			.build();
+		for name in &["name", ".debug_info", "producers", "target_features", "sourceMappingURL"] {
+			module.set_custom_section(*name, vec![0; 4]);
+		}
+		module
+	}
+
+	#[test]
+	fn strip_default() {
+		let mut module = module();
+		let size = serialize(module.clone()).expect("module to serialize").len();
+		let saved = module.strip(StripConfig::default()).expect("sections to serialize");
+
+		assert_eq!(serialize(module.clone()).expect("module to serialize").len(), size - saved);
+		let customs: Vec<_> = module.custom_sections().map(CustomSection::name).collect();
+		assert_eq!(customs, vec!["sourceMappingURL"]);
+	}
+
+	#[test]
+	fn strip_relocations() {
+		use crate::elements::RelocSection;
+
+		let mut module = module();
+		module.set_custom_section("reloc..debug_info", vec![0; 4]);
+		module.set_custom_section("reloc.sourceMappingURL", vec![0; 4]);
+		let payload = [0, 11, b'.', b'd', b'e', b'b', b'u', b'g', b'_', b'l', b'i', b'n', b'e', 0];
+		let reloc = RelocSection::deserialize("reloc.DEBUG".into(), &mut &payload[..]).expect("relocations to parse");
+		module.sections_mut().push(Section::Reloc(reloc));
+
+		module.strip(StripConfig::default()).expect("sections to serialize");
+		let customs: Vec<_> = module.custom_sections().map(CustomSection::name).collect();
+		assert_eq!(customs, vec!["sourceMappingURL", "reloc.sourceMappingURL"]);
+		assert!(module.sections().iter().all(|section| !matches!(*section, Section::Reloc(_))));
+	}
+
+	#[test]
+	fn strip_all_custom() {
+		let mut module = module();
+		module.strip(StripConfig { all_custom: true, ..StripConfig::default() }).expect("sections to serialize");
+		assert!(module.sections().iter().all(|section| !matches!(*section, Section::Custom(_))));
+	}
+}
@@ -0,0 +1,281 @@
+use alloc::vec::Vec;
+use super::{Error, Instruction, Instructions};
+
+/// Block of a function body, opened by `block`, `loop`, `if` or `try`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+	start: usize,
+	else_: Option<usize>,
+	end: usize,
+	is_loop: bool,
+	parent: Option<usize>,
+}
+
+impl Block {
+	/// Index of the instruction opening the block.
+	pub fn start(&self) -> usize { self.start }
+
+	/// Index of the `else` of an `if` block, if it has one.
+	pub fn else_(&self) -> Option<usize> { self.else_ }
+
+	/// Index of the `end` closing the block.
+	pub fn end(&self) -> usize { self.end }
+
+	/// Whether the block is a loop, which branches to it restart.
+	pub fn is_loop(&self) -> bool { self.is_loop }
+
+	/// Index of the enclosing block in `BodyStructure::blocks`.
+	pub fn parent(&self) -> Option<usize> { self.parent }
+
+	/// Index of the instruction a branch to the block continues at: the `loop` itself,
+	/// or the `end` of any other block.
+	pub fn label(&self) -> usize {
+		if self.is_loop { self.start } else { self.end }
+	}
+}
+
+/// Target of a branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchTarget {
+	/// Block, by index in `BodyStructure::blocks`.
+	Block(usize),
+	/// Function body itself; the branch returns from the function.
+	Function,
+}
+
+/// Block structure of a function body.
+///
+/// A block's own opening instruction, `else` and `end` count as outside of it: their
+/// depth and enclosing block are those of the block's parent. Branch depths count
+/// from the instruction's enclosing block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyStructure {
+	/// Blocks, ordered by their start.
+	blocks: Vec<Block>,
+	/// Innermost block enclosing each instruction.
+	enclosing: Vec<Option<usize>>,
+}
+
+impl BodyStructure {
+	/// Structure of `instructions`, which must end with the `end` of the function body.
+	///
+	/// Unbalanced blocks and branches out of the body are an error.
+	pub fn new(instructions: &[Instruction]) -> Result<Self, Error> {
+		let mut blocks: Vec<Block> = Vec::new();
+		let mut enclosing = Vec::with_capacity(instructions.len());
+		let mut open: Option<usize> = None;
+		let mut depth = 0u32;
+		let mut closed = false;
+
+		for (index, instruction) in instructions.iter().enumerate() {
+			if closed {
+				return Err(Error::Other("instructions past the end of the body"));
+			}
+			if instruction.is_terminal() {
+				match open {
+					Some(block) => {
+						blocks[block].end = index;
+						open = blocks[block].parent;
+						depth -= 1;
+					},
+					None => closed = true,
+				}
+				enclosing.push(open);
+				continue;
+			}
+			if let Instruction::Else = *instruction {
+				let block = open
+					.filter(|&block| matches!(instructions[blocks[block].start], Instruction::If(_)))
+					.ok_or(Error::Other("else outside of an if block"))?;
+				if blocks[block].else_.is_some() {
+					return Err(Error::Other("second else in an if block"));
+				}
+				blocks[block].else_ = Some(index);
+				enclosing.push(blocks[block].parent);
+				continue;
+			}
+
+			enclosing.push(open);
+			if !branch_depths(instruction).all(|branch_depth| branch_depth <= depth) {
+				return Err(Error::Other("branch out of the function body"));
+			}
+			if instruction.is_block() {
+				blocks.push(Block {
+					start: index,
+					else_: None,
+					end: index,
+					is_loop: matches!(*instruction, Instruction::Loop(_)),
+					parent: open,
+				});
+				open = Some(blocks.len() - 1);
+				depth += 1;
+			}
+		}
+		if !closed {
+			return Err(Error::Other("body is not closed by an end"));
+		}
+
+		Ok(BodyStructure { blocks, enclosing })
+	}
+
+	/// Blocks of the body, ordered by their start.
+	pub fn blocks(&self) -> &[Block] { &self.blocks }
+
+	/// Innermost block enclosing the instruction at `index`, by index in `blocks`.
+	pub fn enclosing(&self, index: usize) -> Option<usize> {
+		self.enclosing.get(index).cloned().flatten()
+	}
+
+	/// Number of blocks enclosing the instruction at `index`.
+	pub fn depth(&self, index: usize) -> u32 {
+		let mut depth = 0;
+		let mut block = self.enclosing(index);
+		while let Some(current) = block {
+			depth += 1;
+			block = self.blocks[current].parent;
+		}
+		depth
+	}
+
+	/// Block opened by the instruction at `index`, by index in `blocks`.
+	pub fn opened_by(&self, index: usize) -> Option<usize> {
+		self.blocks.binary_search_by_key(&index, |block| block.start).ok()
+	}
+
+	/// Target of a branch by `depth` from the instruction at `index`.
+	pub fn branch_target(&self, index: usize, depth: u32) -> Option<BranchTarget> {
+		let mut block = self.enclosing(index);
+		for _ in 0..depth {
+			block = self.blocks[block?].parent;
+		}
+		Some(match block {
+			Some(block) => BranchTarget::Block(block),
+			None => BranchTarget::Function,
+		})
+	}
+
+	/// Iterate over `instructions` along with their structure.
+	///
+	/// `instructions` must be the ones the structure was built from.
+	pub fn iter<'a>(&'a self, instructions: &'a [Instruction]) -> impl Iterator<Item=StructuredInstruction<'a>> + 'a {
+		instructions.iter().enumerate().map(move |(index, instruction)| StructuredInstruction {
+			index,
+			instruction,
+			structure: self,
+		})
+	}
+}
+
+/// Instruction along with its place in the block structure.
+#[derive(Debug, Clone, Copy)]
+pub struct StructuredInstruction<'a> {
+	index: usize,
+	instruction: &'a Instruction,
+	structure: &'a BodyStructure,
+}
+
+impl<'a> StructuredInstruction<'a> {
+	/// Index of the instruction in the body.
+	pub fn index(&self) -> usize { self.index }
+
+	/// The instruction itself.
+	pub fn instruction(&self) -> &'a Instruction { self.instruction }
+
+	/// Number of blocks enclosing the instruction.
+	pub fn depth(&self) -> u32 { self.structure.depth(self.index) }
+
+	/// Innermost block enclosing the instruction.
+	pub fn enclosing(&self) -> Option<&'a Block> {
+		self.structure.enclosing(self.index).map(|block| &self.structure.blocks[block])
+	}
+
+	/// Block the instruction opens.
+	pub fn opens(&self) -> Option<&'a Block> {
+		self.structure.opened_by(self.index).map(|block| &self.structure.blocks[block])
+	}
+
+	/// Targets of a branch instruction, in the order of its depths.
+	///
+	/// Empty for instructions other than `br`, `br_if` and `br_table`.
+	pub fn targets(&self) -> Vec<BranchTarget> {
+		branch_depths(self.instruction)
+			.map(|depth| self.structure.branch_target(self.index, depth)
+				.expect("branch depths are checked by `BodyStructure::new`; qed"))
+			.collect()
+	}
+}
+
+/// Depths of a branch instruction.
+fn branch_depths(instruction: &Instruction) -> impl Iterator<Item=u32> + '_ {
+	let (depth, table) = match *instruction {
+		Instruction::Br(depth) | Instruction::BrIf(depth) => (Some(depth), None),
+		Instruction::BrTable(ref table) => (None, Some(table.depths())),
+		_ => (None, None),
+	};
+	depth.into_iter().chain(table.into_iter().flatten())
+}
+
+impl Instructions {
+	/// Block structure of the instructions of a function body.
+	///
+	/// See `BodyStructure::new`.
+	pub fn structure(&self) -> Result<BodyStructure, Error> {
+		BodyStructure::new(self.elements())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{BodyStructure, BranchTarget};
+	use crate::elements::{BlockType, BrTableData, Instruction::*};
+
+	#[test]
+	fn nested_blocks() {
+		let code = vec![
+			Block(BlockType::NoResult),
+			Loop(BlockType::NoResult),
+			I32Const(0),
+			BrIf(1),
+			I32Const(0),
+			If(BlockType::NoResult),
+			Br(1),
+			Else,
+			Nop,
+			End,
+			End,
+			End,
+			I32Const(0),
+			BrTable(Box::new(BrTableData::new(vec![0], 0))),
+			End,
+		];
+		let structure = BodyStructure::new(&code).expect("body to be balanced");
+		assert_eq!(structure.blocks().len(), 3);
+		let if_block = structure.blocks()[2];
+		assert_eq!((if_block.start(), if_block.else_(), if_block.end()), (5, Some(7), 9));
+		assert_eq!(structure.blocks()[1].label(), 1);
+		assert_eq!(structure.blocks()[0].label(), 11);
+
+		let items: Vec<_> = structure.iter(&code).collect();
+		assert_eq!(items[3].depth(), 2);
+		assert_eq!(items[3].targets(), vec![BranchTarget::Block(0)]);
+		assert_eq!(items[6].depth(), 3);
+		assert_eq!(items[6].targets(), vec![BranchTarget::Block(1)]);
+		assert_eq!(items[7].depth(), 2);
+		assert_eq!(items[9].depth(), 2);
+		assert_eq!(items[13].targets(), vec![BranchTarget::Function, BranchTarget::Function]);
+		assert_eq!(structure.branch_target(3, 2), Some(BranchTarget::Function));
+		assert_eq!(structure.branch_target(3, 3), None);
+		assert_eq!(items[5].opens().map(|block| block.end()), Some(9));
+		assert_eq!(items[8].enclosing().map(|block| block.start()), Some(5));
+	}
+
+	#[test]
+	fn malformed() {
+		assert!(BodyStructure::new(&[Block(BlockType::NoResult), End]).is_err());
+		assert!(BodyStructure::new(&[End, Nop]).is_err());
+		assert!(BodyStructure::new(&[Br(1), End]).is_err());
+		assert!(BodyStructure::new(&[Else, End]).is_err());
+		assert!(BodyStructure::new(&[Block(BlockType::NoResult), Else, End, End]).is_err());
+		assert!(BodyStructure::new(&[I32Const(0), If(BlockType::NoResult), Else, Else, End, End]).is_err());
+	}
+}
@@ -0,0 +1,245 @@
+use alloc::{string::String, vec::Vec};
+use crate::io;
+
+use super::{CountedList, CountedListWriter, DeserializeConfig, Deserialize, Error, Serialize, Uint8};
+
+/// How a module relates to a feature, per the `target_features` section of the tool conventions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FeaturePrefix {
+	/// `+`: the module uses the feature.
+	Used,
+	/// `=`: the module uses the feature, and every module linked with it must too.
+	Required,
+	/// `-`: the module does not use the feature, and no module linked with it may.
+	Disallowed,
+}
+
+impl FeaturePrefix {
+	/// Byte encoding the prefix.
+	pub fn byte(self) -> u8 {
+		match self {
+			FeaturePrefix::Used => b'+',
+			FeaturePrefix::Required => b'=',
+			FeaturePrefix::Disallowed => b'-',
+		}
+	}
+
+	/// Prefix encoded by `byte`, if any.
+	pub fn from_byte(byte: u8) -> Option<Self> {
+		match byte {
+			b'+' => Some(FeaturePrefix::Used),
+			b'=' => Some(FeaturePrefix::Required),
+			b'-' => Some(FeaturePrefix::Disallowed),
+			_ => None,
+		}
+	}
+}
+
+/// Entry of the target features section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TargetFeature {
+	/// How the module relates to the feature.
+	pub prefix: FeaturePrefix,
+	/// Name of the feature, like `simd128` or `bulk-memory`.
+	pub name: String,
+}
+
+impl TargetFeature {
+	/// Creates a new target feature entry.
+	pub fn new(prefix: FeaturePrefix, name: impl Into<String>) -> Self {
+		TargetFeature { prefix, name: name.into() }
+	}
+}
+
+/// Features a module was compiled with, per the `target_features` section of the tool conventions.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TargetFeaturesSection {
+	features: Vec<TargetFeature>,
+}
+
+impl TargetFeaturesSection {
+	/// Creates a new target features section.
+	pub fn new(features: Vec<TargetFeature>) -> Self {
+		TargetFeaturesSection { features }
+	}
+
+	/// Feature entries.
+	pub fn features(&self) -> &[TargetFeature] {
+		&self.features
+	}
+
+	/// Feature entries (mutable).
+	pub fn features_mut(&mut self) -> &mut Vec<TargetFeature> {
+		&mut self.features
+	}
+
+	/// Prefix of the feature named `name`, if the section lists it.
+	pub fn prefix(&self, name: &str) -> Option<FeaturePrefix> {
+		self.features.iter().find(|feature| feature.name == name).map(|feature| feature.prefix)
+	}
+
+	/// Add the features of `other`, as when linking two modules.
+	///
+	/// Features are unioned, with `Required` taking precedence over `Used`. Fails with
+	/// the name of the first feature which one module uses and the other disallows, or
+	/// which one module requires and the other does not list; `self` may then be left
+	/// partially merged.
+	pub fn merge(&mut self, other: TargetFeaturesSection) -> Result<(), String> {
+		for feature in &self.features {
+			if feature.prefix == FeaturePrefix::Required && other.prefix(&feature.name).is_none() {
+				return Err(feature.name.clone());
+			}
+		}
+		for feature in other.features {
+			let existing = match self.features.iter_mut().find(|existing| existing.name == feature.name) {
+				Some(existing) => existing,
+				None if feature.prefix == FeaturePrefix::Required => return Err(feature.name),
+				None => {
+					self.features.push(feature);
+					continue;
+				},
+			};
+			match (existing.prefix, feature.prefix) {
+				(FeaturePrefix::Disallowed, FeaturePrefix::Disallowed) => {},
+				(FeaturePrefix::Disallowed, _) | (_, FeaturePrefix::Disallowed) => return Err(feature.name),
+				(_, FeaturePrefix::Required) => existing.prefix = FeaturePrefix::Required,
+				(_, FeaturePrefix::Used) => {},
+			}
+		}
+		Ok(())
+	}
+
+	/// Check that every feature the module uses is accepted by `config`.
+	///
+	/// Only features matching a proposal of `DeserializeConfig` are checked; others,
+	/// like `mutable-globals`, are ignored. The error names the first offending
+	/// proposal, as `DeserializeConfig::check` does.
+	pub fn check(&self, config: &DeserializeConfig) -> Result<(), Error> {
+		for feature in &self.features {
+			if feature.prefix == FeaturePrefix::Disallowed {
+				continue;
+			}
+			let (enabled, proposal) = match feature.name.as_str() {
+				"sign-ext" => (config.sign_ext, "sign_ext"),
+				"simd128" => (config.simd, "simd"),
+				"bulk-memory" => (config.bulk_memory, "bulk_memory"),
+				"atomics" => (config.atomics, "atomics"),
+				"multivalue" => (config.multi_value, "multi_value"),
+				"exception-handling" => (config.exceptions, "exceptions"),
+				"memory64" => (config.memory64, "memory64"),
+				"extended-const" => (config.extended_const, "extended_const"),
+				_ => continue,
+			};
+			if !enabled {
+				return Err(Error::FeatureDisabled(proposal));
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Deserialize for TargetFeaturesSection {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(TargetFeaturesSection { features: CountedList::deserialize(rdr)?.into_inner() })
+	}
+}
+
+impl Serialize for TargetFeaturesSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		CountedListWriter(self.features.len(), self.features.into_iter()).serialize(wtr)
+	}
+}
+
+impl Deserialize for TargetFeature {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		let prefix = FeaturePrefix::from_byte(Uint8::deserialize(rdr)?.into())
+			.ok_or(Error::Other("unknown target feature prefix"))?;
+		Ok(TargetFeature { prefix, name: String::deserialize(rdr)? })
+	}
+}
+
+impl Serialize for TargetFeature {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		Uint8::from(self.prefix.byte()).serialize(wtr)?;
+		self.name.serialize(wtr)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::{deserialize_buffer, serialize, Module};
+	use crate::builder;
+
+	#[test]
+	fn target_features_round_trip() {
+		let features = TargetFeaturesSection::new(vec![
+			TargetFeature::new(FeaturePrefix::Used, "mutable-globals"),
+			TargetFeature::new(FeaturePrefix::Used, "sign-ext"),
+			TargetFeature::new(FeaturePrefix::Disallowed, "atomics"),
+		]);
+		let mut module = builder::module().build();
+		module.set_custom_section("target_features", serialize(features.clone()).expect("section to serialize"));
+
+		let bytes = serialize(module).expect("module to serialize");
+		let module: Module = deserialize_buffer(&bytes).expect("module to deserialize");
+		let module = module.parse_target_features().expect("target features section to parse");
+		assert_eq!(module.target_features_section(), Some(&features));
+		assert_eq!(features.prefix("atomics"), Some(FeaturePrefix::Disallowed));
+	}
+
+	#[test]
+	fn check() {
+		let features = TargetFeaturesSection::new(vec![
+			TargetFeature::new(FeaturePrefix::Used, "mutable-globals"),
+			TargetFeature::new(FeaturePrefix::Required, "bulk-memory"),
+			TargetFeature::new(FeaturePrefix::Disallowed, "simd128"),
+		]);
+		let mut config = DeserializeConfig::mvp();
+		assert!(matches!(features.check(&config), Err(Error::FeatureDisabled("bulk_memory"))));
+		config.bulk_memory = true;
+		assert!(features.check(&config).is_ok());
+	}
+
+	#[test]
+	fn merge() {
+		let mut features = TargetFeaturesSection::new(vec![
+			TargetFeature::new(FeaturePrefix::Used, "mutable-globals"),
+			TargetFeature::new(FeaturePrefix::Disallowed, "atomics"),
+		]);
+		features.merge(TargetFeaturesSection::new(vec![
+			TargetFeature::new(FeaturePrefix::Required, "mutable-globals"),
+			TargetFeature::new(FeaturePrefix::Used, "sign-ext"),
+		])).expect("features to merge");
+		assert_eq!(features.features(), &[
+			TargetFeature::new(FeaturePrefix::Required, "mutable-globals"),
+			TargetFeature::new(FeaturePrefix::Disallowed, "atomics"),
+			TargetFeature::new(FeaturePrefix::Used, "sign-ext"),
+		][..]);
+
+		let used = TargetFeaturesSection::new(vec![
+			TargetFeature::new(FeaturePrefix::Used, "atomics"),
+			TargetFeature::new(FeaturePrefix::Used, "mutable-globals"),
+		]);
+		assert_eq!(features.clone().merge(used), Err("atomics".into()));
+		assert_eq!(features.merge(TargetFeaturesSection::default()), Err("mutable-globals".into()));
+	}
+
+	#[test]
+	fn unknown_prefix() {
+		let payload = [1, b'?', 4, b's', b'i', b'm', b'd'];
+		assert!(TargetFeaturesSection::deserialize(&mut &payload[..]).is_err());
+	}
+}
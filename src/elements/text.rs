@@ -0,0 +1,709 @@
+use alloc::{borrow::Cow, format, string::{String, ToString}, vec::Vec};
+use core::fmt::{self, Write};
+use super::{
+	Module, Section, Type, External, Internal, Instruction, InitExpr, ResizableLimits, FuncBody,
+	TableElementType, BlockType,
+};
+
+/// Render the module as text, one item per line, in the order of its sections.
+///
+/// Indices are spelled out in comments, so that adding or removing an item shows
+/// up in a diff only where it happened. With `folded`, control constructs of function
+/// bodies are rendered as s-expressions.
+pub(crate) fn render(module: &Module, folded: bool) -> Result<String, fmt::Error> {
+	let mut out = String::new();
+	writeln!(out, "(module")?;
+
+	let mut func_index = module.import_count(super::ImportCountType::Function);
+	let mut global_index = module.import_count(super::ImportCountType::Global);
+	for section in module.sections() {
+		match *section {
+			Section::Type(ref type_section) => {
+				for (index, Type::Function(func)) in type_section.types().iter().enumerate() {
+					writeln!(out, "  (type (;{};) {})", index, func)?;
+				}
+			},
+			Section::Import(ref import_section) => {
+				let (mut funcs, mut globals) = (0, 0);
+				for entry in import_section.entries() {
+					write!(out, "  (import {:?} {:?} ", entry.module(), entry.field())?;
+					match *entry.external() {
+						External::Function(type_ref) => {
+							write!(out, "(func (;{};) (type {}))", funcs, type_ref)?;
+							funcs += 1;
+						},
+						External::Table(ref table) => {
+							write!(out, "(table ")?;
+							write_limits(&mut out, table.limits())?;
+							write_elem_type(&mut out, table.elem_type())?;
+							write!(out, ")")?;
+						},
+						External::Memory(ref memory) => {
+							write!(out, "(memory ")?;
+							write_limits(&mut out, memory.limits())?;
+							write!(out, ")")?;
+						},
+						External::Global(ref global) => {
+							write!(out, "(global (;{};) ", globals)?;
+							write_global_type(&mut out, global.content_type(), global.is_mutable())?;
+							write!(out, ")")?;
+							globals += 1;
+						},
+						#[cfg(feature="exceptions")]
+						External::Tag(ref tag) => write!(out, "(tag (type {}))", tag.type_ref())?,
+					}
+					writeln!(out, ")")?;
+				}
+			},
+			// Function declarations are rendered together with their bodies.
+			Section::Function(_) => {},
+			Section::Table(ref table_section) => {
+				for table in table_section.entries() {
+					write!(out, "  (table ")?;
+					write_limits(&mut out, table.limits())?;
+					write_elem_type(&mut out, table.elem_type())?;
+					writeln!(out, ")")?;
+				}
+			},
+			Section::Memory(ref memory_section) => {
+				for memory in memory_section.entries() {
+					write!(out, "  (memory ")?;
+					write_limits(&mut out, memory.limits())?;
+					writeln!(out, ")")?;
+				}
+			},
+			#[cfg(feature="exceptions")]
+			Section::Tag(ref tag_section) => {
+				for tag in tag_section.entries() {
+					writeln!(out, "  (tag (type {}))", tag.type_ref())?;
+				}
+			},
+			Section::Global(ref global_section) => {
+				for entry in global_section.entries() {
+					write!(out, "  (global (;{};) ", global_index)?;
+					let global_type = entry.global_type();
+					write_global_type(&mut out, global_type.content_type(), global_type.is_mutable())?;
+					write!(out, " ")?;
+					write_init_expr(&mut out, entry.init_expr(), false)?;
+					writeln!(out, ")")?;
+					global_index += 1;
+				}
+			},
+			Section::Export(ref export_section) => {
+				for entry in export_section.entries() {
+					let (kind, index) = match *entry.internal() {
+						Internal::Function(index) => ("func", index),
+						Internal::Table(index) => ("table", index),
+						Internal::Memory(index) => ("memory", index),
+						Internal::Global(index) => ("global", index),
+						#[cfg(feature="exceptions")]
+						Internal::Tag(index) => ("tag", index),
+					};
+					writeln!(out, "  (export {:?} ({} {}))", entry.field(), kind, index)?;
+				}
+			},
+			Section::Start(index) => writeln!(out, "  (start {})", index)?,
+			Section::Element(ref element_section) => {
+				for segment in element_section.entries() {
+					write!(out, "  (elem")?;
+					if let Some(ref offset) = *segment.offset() {
+						// Table 0 is implied, which also keeps the segment in its original encoding.
+						if segment.index() != 0 {
+							write!(out, " (table {})", segment.index())?;
+						}
+						write!(out, " ")?;
+						write_init_expr(&mut out, offset, true)?;
+					}
+					write!(out, " func")?;
+					for member in segment.members() {
+						write!(out, " {}", member)?;
+					}
+					writeln!(out, ")")?;
+				}
+			},
+			Section::DataCount(count) => writeln!(out, "  (;datacount {};)", count)?,
+			Section::Code(ref code_section) => {
+				let declarations = module.function_section().map(|fs| fs.entries()).unwrap_or(&[]);
+				for (body, func) in code_section.bodies().iter().zip(declarations) {
+					writeln!(out, "  (func (;{};) (type {})", func_index, func.type_ref())?;
+					if folded {
+						write_folded_body(&mut out, body)?;
+					} else {
+						write_body(&mut out, body)?;
+					}
+					writeln!(out, "  )")?;
+					func_index += 1;
+				}
+			},
+			Section::Data(ref data_section) => {
+				for segment in data_section.entries() {
+					write!(out, "  (data")?;
+					if let Some(ref offset) = *segment.offset() {
+						write!(out, " (memory {}) ", segment.index())?;
+						write_init_expr(&mut out, offset, true)?;
+					}
+					write!(out, " \"")?;
+					for byte in segment.value() {
+						write!(out, "\\{:02x}", byte)?;
+					}
+					writeln!(out, "\")")?;
+				}
+			},
+			Section::Custom(ref custom) =>
+				writeln!(out, "  (@custom {:?} (;{} bytes;))", custom.name(), custom.payload().len())?,
+			Section::Name(_) => writeln!(out, "  (@custom \"name\")")?,
+			Section::Reloc(ref reloc) =>
+				writeln!(out, "  (@custom {:?} (;{} entries;))", reloc.name(), reloc.entries().len())?,
+			Section::Linking(ref linking) =>
+				writeln!(out, "  (@custom \"linking\" (;{} subsections;))", linking.subsections().len())?,
+			Section::Producers(_) => writeln!(out, "  (@custom \"producers\")")?,
+			Section::TargetFeatures(ref target_features) =>
+				writeln!(out, "  (@custom \"target_features\" (;{} features;))", target_features.features().len())?,
+			Section::Dylink(ref dylink) =>
+				writeln!(out, "  (@custom \"dylink.0\" (;{} subsections;))", dylink.subsections().len())?,
+			Section::Unparsed { id, ref payload } =>
+				writeln!(out, "  (;section {}: {} bytes;)", id, payload.len())?,
+		}
+	}
+
+	writeln!(out, ")")?;
+	Ok(out)
+}
+
+fn write_limits(out: &mut String, limits: &ResizableLimits) -> fmt::Result {
+	#[cfg(feature="memory64")]
+	{
+		if limits.is_64() {
+			write!(out, "i64 ")?;
+		}
+	}
+	#[cfg(feature="memory64")]
+	let (initial, maximum) = (limits.initial_64(), limits.maximum_64());
+	#[cfg(not(feature="memory64"))]
+	let (initial, maximum) = (limits.initial(), limits.maximum());
+	write!(out, "{}", initial)?;
+	if let Some(maximum) = maximum {
+		write!(out, " {}", maximum)?;
+	}
+	#[cfg(feature="atomics")]
+	{
+		if limits.shared() {
+			write!(out, " shared")?;
+		}
+	}
+	Ok(())
+}
+
+fn write_elem_type(out: &mut String, elem_type: TableElementType) -> fmt::Result {
+	match elem_type {
+		TableElementType::AnyFunc => write!(out, " funcref"),
+	}
+}
+
+fn write_global_type(out: &mut String, content_type: super::ValueType, is_mutable: bool) -> fmt::Result {
+	if is_mutable {
+		write!(out, "(mut {})", content_type)
+	} else {
+		write!(out, "{}", content_type)
+	}
+}
+
+/// Write an initializer, folded if it is a single instruction; otherwise as a plain
+/// sequence, wrapped in `(offset …)` for segments.
+fn write_init_expr(out: &mut String, init_expr: &InitExpr, offset: bool) -> fmt::Result {
+	// The trailing `end` is implied.
+	let code = init_expr.code();
+	let code = match code.last() {
+		Some(&Instruction::End) => &code[..code.len() - 1],
+		_ => code,
+	};
+	let (open, close) = match code.len() {
+		1 => ("(", ")"),
+		_ if offset => ("(offset ", ")"),
+		_ => ("", ""),
+	};
+	write!(out, "{}", open)?;
+	for (index, instruction) in code.iter().enumerate() {
+		if index > 0 {
+			write!(out, " ")?;
+		}
+		write_instruction(out, instruction)?;
+	}
+	write!(out, "{}", close)
+}
+
+/// Locals of a function which engines accept at most, as in the JavaScript API limits.
+const MAX_SPELLED_LOCALS: u32 = 50_000;
+
+fn write_locals(out: &mut String, body: &FuncBody) -> fmt::Result {
+	for local in body.locals() {
+		// The text format has no counts, so each local is spelled out, unless there are
+		// more than engines accept in a whole function.
+		if local.count() > MAX_SPELLED_LOCALS {
+			writeln!(out, "    (local {}) (;x{};)", local.value_type(), local.count())?;
+			continue;
+		}
+		write!(out, "    (local")?;
+		for _ in 0..local.count() {
+			write!(out, " {}", local.value_type())?;
+		}
+		writeln!(out, ")")?;
+	}
+	Ok(())
+}
+
+/// Current name of an instruction renamed since the `Display` mnemonics were chosen.
+fn mnemonic(legacy: &str) -> Cow<'_, str> {
+	let renamed = match legacy {
+		"get_local" => "local.get",
+		"set_local" => "local.set",
+		"tee_local" => "local.tee",
+		"get_global" => "global.get",
+		"set_global" => "global.set",
+		"current_memory" => "memory.size",
+		"grow_memory" => "memory.grow",
+		_ => match legacy.split_once('/') {
+			// Conversions: `i32.trunc_s/f32` is `i32.trunc_f32_s`, `i32.wrap/i64` is `i32.wrap_i64`.
+			Some((op, from)) => {
+				let (from, sat) = match from.strip_suffix(":sat") {
+					Some(from) => (from, "_sat"),
+					None => (from, ""),
+				};
+				return Cow::Owned(match op.strip_suffix("_s").map(|op| (op, "_s"))
+					.or_else(|| op.strip_suffix("_u").map(|op| (op, "_u")))
+				{
+					Some((op, sign)) => format!("{}{}_{}{}", op, sat, from, sign),
+					None => format!("{}{}_{}", op, sat, from),
+				});
+			},
+			None => legacy,
+		},
+	};
+	Cow::Borrowed(renamed)
+}
+
+/// Alignment, as a power of two, of a plain load or store.
+fn alignment(instruction: &Instruction) -> Option<u32> {
+	use super::Instruction::*;
+	match *instruction {
+		I32Load(flags, _) | I64Load(flags, _) | F32Load(flags, _) | F64Load(flags, _) |
+		I32Load8S(flags, _) | I32Load8U(flags, _) | I32Load16S(flags, _) | I32Load16U(flags, _) |
+		I64Load8S(flags, _) | I64Load8U(flags, _) | I64Load16S(flags, _) | I64Load16U(flags, _) |
+		I64Load32S(flags, _) | I64Load32U(flags, _) |
+		I32Store(flags, _) | I64Store(flags, _) | F32Store(flags, _) | F64Store(flags, _) |
+		I32Store8(flags, _) | I32Store16(flags, _) | I64Store8(flags, _) | I64Store16(flags, _) |
+		I64Store32(flags, _) => Some(flags),
+		_ => None,
+	}
+}
+
+/// Natural alignment, as a power of two, of the memory access `mnemonic`.
+fn natural_alignment(mnemonic: &str) -> u32 {
+	if mnemonic.ends_with("8") || mnemonic.ends_with("8_s") || mnemonic.ends_with("8_u") {
+		0
+	} else if mnemonic.ends_with("16") || mnemonic.ends_with("16_s") || mnemonic.ends_with("16_u") {
+		1
+	} else if mnemonic.ends_with("32") || mnemonic.ends_with("32_s") || mnemonic.ends_with("32_u") || mnemonic.starts_with("i32.") || mnemonic.starts_with("f32.") {
+		2
+	} else {
+		3
+	}
+}
+
+fn write_float<W: Write, T: fmt::Debug>(out: &mut W, value: T, negative: bool, class: FloatClass) -> fmt::Result {
+	let sign = if negative { "-" } else { "" };
+	match class {
+		FloatClass::Nan(payload) => write!(out, "{}nan:0x{:x}", sign, payload),
+		FloatClass::Infinite => write!(out, "{}inf", sign),
+		FloatClass::Finite => write!(out, "{:?}", value),
+	}
+}
+
+/// Kind of a float constant, as far as the text format spells them differently.
+enum FloatClass {
+	Nan(u64),
+	Infinite,
+	Finite,
+}
+
+/// Instruction rendered by `write_instruction`.
+struct Text<'a>(&'a Instruction);
+
+impl<'a> fmt::Display for Text<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write_instruction(f, self.0)
+	}
+}
+
+/// Write `instruction` in the text format, which differs from its `Display` output in
+/// the immediates of some instructions and in the names of others.
+fn write_instruction<W: Write>(out: &mut W, instruction: &Instruction) -> fmt::Result {
+	let (name, block_type) = match *instruction {
+		Instruction::Block(block_type) => ("block", block_type),
+		Instruction::Loop(block_type) => ("loop", block_type),
+		Instruction::If(block_type) => ("if", block_type),
+		#[cfg(feature="exceptions")]
+		Instruction::Exceptions(super::ExceptionsInstruction::Try(block_type)) => ("try", block_type),
+		Instruction::BrTable(ref table) => {
+			write!(out, "br_table")?;
+			for target in table.table.iter().chain(Some(&table.default)) {
+				write!(out, " {}", target)?;
+			}
+			return Ok(());
+		},
+		Instruction::CallIndirect(type_ref, 0) => return write!(out, "call_indirect (type {})", type_ref),
+		Instruction::CallIndirect(type_ref, table) => return write!(out, "call_indirect {} (type {})", table, type_ref),
+		Instruction::F32Const(bits) => {
+			let value = f32::from_bits(bits);
+			let class = match value {
+				_ if value.is_nan() => FloatClass::Nan((bits & 0x7f_ffff) as u64),
+				_ if value.is_infinite() => FloatClass::Infinite,
+				_ => FloatClass::Finite,
+			};
+			write!(out, "f32.const ")?;
+			return write_float(out, value, value.is_sign_negative(), class);
+		},
+		Instruction::F64Const(bits) => {
+			let value = f64::from_bits(bits);
+			let class = match value {
+				_ if value.is_nan() => FloatClass::Nan(bits & 0xf_ffff_ffff_ffff),
+				_ if value.is_infinite() => FloatClass::Infinite,
+				_ => FloatClass::Finite,
+			};
+			write!(out, "f64.const ")?;
+			return write_float(out, value, value.is_sign_negative(), class);
+		},
+		_ => {
+			let text = instruction.to_string();
+			let (legacy, immediates) = text.split_once(' ').unwrap_or((&text, ""));
+			let name = mnemonic(legacy);
+			write!(out, "{}", name)?;
+			if !immediates.is_empty() {
+				write!(out, " {}", immediates)?;
+			}
+			if let Some(flags) = alignment(instruction) {
+				match 1u64.checked_shl(flags) {
+					_ if flags == natural_alignment(&name) => {},
+					Some(align) => write!(out, " align={}", align)?,
+					// Only valid modules are expected to round-trip.
+					None => write!(out, " (;align 2^{};)", flags)?,
+				}
+			}
+			return Ok(());
+		},
+	};
+	write!(out, "{}", name)?;
+	if let BlockType::Value(value_type) = block_type {
+		write!(out, " (result {})", value_type)?;
+	}
+	Ok(())
+}
+
+fn write_body(out: &mut String, body: &FuncBody) -> fmt::Result {
+	write_locals(out, body)?;
+
+	let mut depth = 0usize;
+	let code = body.code().elements();
+	for (index, instruction) in code.iter().enumerate() {
+		let indent = match *instruction {
+			// The function's own `end` is rendered as the closing parenthesis.
+			Instruction::End if depth == 0 && index + 1 == code.len() => break,
+			Instruction::End => {
+				depth = depth.saturating_sub(1);
+				depth
+			},
+			Instruction::Else => depth.saturating_sub(1),
+			#[cfg(feature="exceptions")]
+			Instruction::Exceptions(super::ExceptionsInstruction::Catch(_)) |
+			Instruction::Exceptions(super::ExceptionsInstruction::CatchAll) => depth.saturating_sub(1),
+			#[cfg(feature="exceptions")]
+			Instruction::Exceptions(super::ExceptionsInstruction::Delegate(_)) => {
+				depth = depth.saturating_sub(1);
+				depth
+			},
+			_ => depth,
+		};
+		writeln!(out, "    {:width$}{}", "", Text(instruction), width = indent * 2)?;
+		if instruction.is_block() {
+			depth += 1;
+		}
+	}
+	Ok(())
+}
+
+/// Construct left open while rendering a folded body.
+enum Open {
+	/// `block` or `loop`, closed by one parenthesis.
+	Block,
+	/// `if` or `try`, closed by the parentheses of a clause and of the construct.
+	Clauses,
+}
+
+fn write_folded_body(out: &mut String, body: &FuncBody) -> fmt::Result {
+	write_locals(out, body)?;
+
+	let mut open = Vec::new();
+	let mut indent = 0usize;
+	let line = |out: &mut String, indent: usize, text: fmt::Arguments<'_>| writeln!(out, "    {:width$}{}", "", text, width = indent * 2);
+	let code = body.code().elements();
+	for (index, instruction) in code.iter().enumerate() {
+		// `else`, `catch` and `catch_all` close a clause and open the next one.
+		let is_clause = match *instruction {
+			Instruction::Else => true,
+			#[cfg(feature="exceptions")]
+			Instruction::Exceptions(super::ExceptionsInstruction::Catch(_)) |
+			Instruction::Exceptions(super::ExceptionsInstruction::CatchAll) => true,
+			_ => false,
+		};
+		match *instruction {
+			_ if is_clause && matches!(open.last(), Some(Open::Clauses)) => {
+				line(out, indent - 1, format_args!(")"))?;
+				line(out, indent - 1, format_args!("({}", Text(instruction)))?;
+			},
+			Instruction::End if open.is_empty() && index + 1 == code.len() => break,
+			Instruction::End if !open.is_empty() => {
+				if let Some(Open::Clauses) = open.pop() {
+					indent -= 1;
+					line(out, indent, format_args!(")"))?;
+				}
+				indent -= 1;
+				line(out, indent, format_args!(")"))?;
+			},
+			#[cfg(feature="exceptions")]
+			Instruction::Exceptions(super::ExceptionsInstruction::Delegate(_)) if matches!(open.last(), Some(Open::Clauses)) => {
+				open.pop();
+				line(out, indent - 1, format_args!(")"))?;
+				line(out, indent - 1, format_args!("({})", Text(instruction)))?;
+				indent -= 2;
+				line(out, indent, format_args!(")"))?;
+			},
+			Instruction::If(_) => {
+				line(out, indent, format_args!("({}", Text(instruction)))?;
+				line(out, indent + 1, format_args!("(then"))?;
+				open.push(Open::Clauses);
+				indent += 2;
+			},
+			#[cfg(feature="exceptions")]
+			Instruction::Exceptions(super::ExceptionsInstruction::Try(_)) => {
+				line(out, indent, format_args!("({}", Text(instruction)))?;
+				line(out, indent + 1, format_args!("(do"))?;
+				open.push(Open::Clauses);
+				indent += 2;
+			},
+			_ if instruction.is_block() => {
+				line(out, indent, format_args!("({}", Text(instruction)))?;
+				open.push(Open::Block);
+				indent += 1;
+			},
+			// Unbalanced instructions are rendered as they are.
+			_ => line(out, indent, format_args!("{}", Text(instruction)))?,
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::builder;
+	use super::super::{Instruction, Instructions, ValueType, BlockType, Local};
+
+	#[test]
+	fn snapshot() {
+		let module = builder::module()
+			.import()
+				.module("env")
+				.field("log")
+				.external().func(0)
+				.build()
+			.function()
+				.signature().with_param(ValueType::I32).build()
+				.body()
+					.with_locals(vec![Local::new(2, ValueType::I64)])
+					.with_instructions(Instructions::new(vec![
+						Instruction::Block(BlockType::NoResult),
+						Instruction::GetLocal(0),
+						Instruction::If(BlockType::NoResult),
+						Instruction::Br(1),
+						Instruction::Else,
+						Instruction::Nop,
+						Instruction::End,
+						Instruction::End,
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.memory().with_min(1).build()
+			.export()
+				.field("run")
+				.internal().func(1)
+				.build()
+			.data()
+				.offset(Instruction::I32Const(16))
+				.value(vec![0, 0xff])
+				.build()
+			.build();
+
+		assert_eq!(module.to_text(), r#"(module
+  (type (;0;) (func (param i32)))
+  (import "env" "log" (func (;0;) (type 0)))
+  (memory 1)
+  (export "run" (func 1))
+  (func (;1;) (type 0)
+    (local i64 i64)
+    block
+      local.get 0
+      if
+        br 1
+      else
+        nop
+      end
+    end
+  )
+  (data (memory 0) (i32.const 16) "\00\ff")
+)
+"#);
+	}
+
+	#[test]
+	fn folded() {
+		let module = builder::module()
+			.function()
+				.signature().with_param(ValueType::I32).build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						Instruction::Loop(BlockType::NoResult),
+						Instruction::GetLocal(0),
+						Instruction::If(BlockType::Value(ValueType::I32)),
+						Instruction::I32Const(1),
+						Instruction::Else,
+						Instruction::Br(1),
+						Instruction::End,
+						Instruction::Drop,
+						Instruction::End,
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.build();
+
+		assert_eq!(module.to_folded_text(), r#"(module
+  (type (;0;) (func (param i32)))
+  (func (;0;) (type 0)
+    (loop
+      local.get 0
+      (if (result i32)
+        (then
+          i32.const 1
+        )
+        (else
+          br 1
+        )
+      )
+      drop
+    )
+  )
+)
+"#);
+	}
+
+	/// Text which a text format parser encodes back to the module's own bytes.
+	#[test]
+	fn text_format() {
+		use super::super::{serialize, BrTableData};
+
+		let module = builder::module()
+			.import().module("env").field("base").external().global(ValueType::I32, false).build()
+			.function()
+				.signature().with_param(ValueType::I32).with_result(ValueType::F64).build()
+				.body()
+					.with_locals(vec![Local::new(2, ValueType::I64)])
+					.with_instructions(Instructions::new(vec![
+						Instruction::Block(BlockType::Value(ValueType::I32)),
+						Instruction::GetLocal(0),
+						Instruction::BrTable(Box::new(BrTableData::new(vec![0, 0], 0))),
+						Instruction::End,
+						Instruction::I64Load32S(0, 8),
+						Instruction::SetLocal(1),
+						Instruction::I32Const(0),
+						Instruction::CallIndirect(0, 0),
+						Instruction::F32Const(0x7fc0_0001),
+						Instruction::F64PromoteF32,
+						Instruction::F64Const(0x3ff8_0000_0000_0000),
+						Instruction::F64Add,
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.table().with_min(1).with_element(0, vec![0]).build()
+			.export().field("run").internal().func(0).build()
+			.build();
+
+		let text = r#"(module
+  (type (;0;) (func (param i32) (result f64)))
+  (import "env" "base" (global (;0;) i32))
+  (table 1 funcref)
+  (export "run" (func 0))
+  (elem (i32.const 0) func 0)
+  (func (;0;) (type 0)
+    (local i64 i64)
+    block (result i32)
+      local.get 0
+      br_table 0 0 0
+    end
+    i64.load32_s offset=8 align=1
+    local.set 1
+    i32.const 0
+    call_indirect (type 0)
+    f32.const nan:0x400001
+    f64.promote_f32
+    f64.const 1.5
+    f64.add
+  )
+)
+"#;
+		// What the `wat` crate encodes the text to.
+		let encoded = [
+			0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7c,
+			0x02, 0x0d, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x04, 0x62, 0x61, 0x73, 0x65, 0x03, 0x7f, 0x00, 0x03,
+			0x02, 0x01, 0x00, 0x04, 0x04, 0x01, 0x70, 0x00, 0x01, 0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e,
+			0x00, 0x00, 0x09, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, 0x00, 0x0a, 0x2a, 0x01, 0x28, 0x01,
+			0x02, 0x7e, 0x02, 0x7f, 0x20, 0x00, 0x0e, 0x02, 0x00, 0x00, 0x00, 0x0b, 0x34, 0x00, 0x08, 0x21,
+			0x01, 0x41, 0x00, 0x11, 0x00, 0x00, 0x43, 0x01, 0x00, 0xc0, 0x7f, 0xbb, 0x44, 0x00, 0x00, 0x00,
+			0x00, 0x00, 0x00, 0xf8, 0x3f, 0xa0, 0x0b,
+		];
+		assert_eq!(module.to_text(), text);
+		assert_eq!(serialize(module).expect("module to serialize"), &encoded[..]);
+	}
+
+	#[cfg(feature="exceptions")]
+	#[test]
+	fn folded_try() {
+		use super::super::ExceptionsInstruction::*;
+
+		let module = builder::module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						Instruction::Exceptions(Try(BlockType::NoResult)),
+						Instruction::Exceptions(Try(BlockType::NoResult)),
+						Instruction::Nop,
+						Instruction::Exceptions(Delegate(0)),
+						Instruction::Exceptions(Catch(0)),
+						Instruction::Exceptions(CatchAll),
+						Instruction::Nop,
+						Instruction::End,
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.build();
+
+		let text = module.to_folded_text();
+		let body: Vec<&str> = text.lines().skip(3).map(str::trim).collect();
+		assert_eq!(body, vec![
+			"(try", "(do", "(try", "(do", "nop", ")", "(delegate 0)", ")",
+			")", "(catch 0", ")", "(catch_all", "nop", ")", ")", ")", ")",
+		]);
+	}
+}
@@ -1,4 +1,6 @@
-use alloc::vec::Vec;
+#![allow(clippy::useless_conversion)]
+
+use alloc::{string::String, vec::Vec};
 use crate::io;
 use super::{
 	Deserialize, Serialize, Error, VarUint7, VarInt7, CountedList,
@@ -8,6 +10,7 @@ use core::fmt;
 
 /// Type definition in types section. Currently can be only of the function type.
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
 	/// Function type.
 	Function(FunctionType),
@@ -33,6 +36,7 @@ impl Serialize for Type {
 
 /// Value type.
 #[derive(Clone, Copy, Debug, PartialEq, Hash, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ValueType {
 	/// 32-bit signed integer
 	I32,
@@ -97,6 +101,7 @@ impl fmt::Display for ValueType {
 
 /// Block type which is basically `ValueType` + NoResult (to define blocks that have no return type)
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlockType {
 	/// Value-type specified block type
 	Value(ValueType),
@@ -143,6 +148,7 @@ impl Serialize for BlockType {
 
 /// Function signature type.
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionType {
 	form: u8,
 	params: Vec<ValueType>,
@@ -178,6 +184,57 @@ impl FunctionType {
 	pub fn results(&self) -> &[ValueType] { &self.results }
 	/// Mutable type in the function signature, if any.
 	pub fn results_mut(&mut self) -> &mut Vec<ValueType> { &mut self.results }
+
+	/// Signature rendered as a Rust function type, e.g. `fn(i32, f64) -> i64`.
+	pub fn rust_signature(&self) -> String {
+		fn rust_type(value_type: &ValueType) -> &'static str {
+			match *value_type {
+				ValueType::I32 => "i32",
+				ValueType::I64 => "i64",
+				ValueType::F32 => "f32",
+				ValueType::F64 => "f64",
+				#[cfg(feature="simd")]
+				ValueType::V128 => "u128",
+			}
+		}
+
+		let params: Vec<&str> = self.params.iter().map(rust_type).collect();
+		let mut signature = format!("fn({})", params.join(", "));
+		match self.results.len() {
+			0 => {},
+			1 => {
+				signature.push_str(" -> ");
+				signature.push_str(rust_type(&self.results[0]));
+			},
+			_ => {
+				let results: Vec<&str> = self.results.iter().map(rust_type).collect();
+				signature.push_str(&format!(" -> ({})", results.join(", ")));
+			},
+		}
+		signature
+	}
+}
+
+/// Signature in text format, e.g. `(func (param i32 f64) (result i64))`.
+impl fmt::Display for FunctionType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "(func")?;
+		if !self.params.is_empty() {
+			write!(f, " (param")?;
+			for param in &self.params {
+				write!(f, " {}", param)?;
+			}
+			write!(f, ")")?;
+		}
+		if !self.results.is_empty() {
+			write!(f, " (result")?;
+			for result in &self.results {
+				write!(f, " {}", result)?;
+			}
+			write!(f, ")")?;
+		}
+		write!(f, ")")
+	}
 }
 
 impl Deserialize for FunctionType {
@@ -230,6 +287,7 @@ impl Serialize for FunctionType {
 
 /// Table element type.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TableElementType {
 	/// A reference to a function with any signature.
 	AnyFunc,
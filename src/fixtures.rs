@@ -0,0 +1,118 @@
+//! Minimal valid modules exercising one feature each, for tests.
+//!
+//! Every generator returns a fresh module built with the `builder`, small
+//! enough to inspect by hand and to serialize into a test case.
+
+use alloc::vec::Vec;
+use crate::builder;
+use crate::elements::{Module, Instruction, Instructions, BlockType, ValueType};
+
+/// Module exporting one item of each kind: function `func`, table `table`,
+/// memory `memory` and global `global`.
+pub fn exports_each_kind() -> Module {
+	builder::module()
+		.function()
+			.signature().build()
+			.body().build()
+			.build()
+		.table().with_min(1).build()
+		.memory().with_min(1).build()
+		.global()
+			.value_type().i32()
+			.init_expr(Instruction::I32Const(0))
+			.build()
+		.export().field("func").internal().func(0).build()
+		.export().field("table").internal().table(0).build()
+		.export().field("memory").internal().memory(0).build()
+		.export().field("global").internal().global(0).build()
+		.build()
+}
+
+/// Module defining one memory of `pages` pages, without a maximum.
+pub fn memory(pages: u32) -> Module {
+	builder::module()
+		.memory().with_min(pages).build()
+		.build()
+}
+
+/// Module whose start function traps, so that instantiating it fails.
+pub fn start_trap() -> Module {
+	builder::module()
+		.function()
+			.main()
+			.signature().build()
+			.body()
+				.with_instructions(Instructions::new(vec![Instruction::Unreachable, Instruction::End]))
+				.build()
+			.build()
+		.build()
+}
+
+/// Module with one function whose body nests `depth` blocks.
+pub fn nested_blocks(depth: usize) -> Module {
+	let mut instructions = Vec::with_capacity(2 * depth + 1);
+	for _ in 0..depth {
+		instructions.push(Instruction::Block(BlockType::NoResult));
+	}
+	for _ in 0..depth + 1 {
+		instructions.push(Instruction::End);
+	}
+	builder::module()
+		.function()
+			.signature().build()
+			.body().with_instructions(Instructions::new(instructions)).build()
+			.build()
+		.build()
+}
+
+/// Module with one exported function `run` taking `params` and returning `result`,
+/// whose body is `instructions` without the final `end`.
+pub fn function(params: Vec<ValueType>, result: Option<ValueType>, mut instructions: Vec<Instruction>) -> Module {
+	instructions.push(Instruction::End);
+	let signature = builder::signature().with_params(params);
+	let signature = match result {
+		Some(result) => signature.with_result(result),
+		None => signature,
+	};
+	builder::module()
+		.function()
+			.with_signature(signature.build_sig())
+			.body().with_instructions(Instructions::new(instructions)).build()
+			.build()
+		.export().field("run").internal().func(0).build()
+		.build()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::elements::{deserialize_buffer, Internal, Section};
+
+	fn round_trip(module: Module) -> Module {
+		let bytes = module.clone().to_bytes().expect("fixture to serialize");
+		let decoded: Module = deserialize_buffer(&bytes).expect("fixture to deserialize");
+		assert_eq!(decoded, module);
+		decoded
+	}
+
+	#[test]
+	fn fixtures_round_trip() {
+		let module = round_trip(exports_each_kind());
+		let kinds: Vec<_> = module.export_section().expect("export section").entries()
+			.iter()
+			.map(|export| *export.internal())
+			.collect();
+		assert_eq!(kinds, vec![Internal::Function(0), Internal::Table(0), Internal::Memory(0), Internal::Global(0)]);
+
+		let module = round_trip(memory(3));
+		assert_eq!(module.memory_section().expect("memory section").entries()[0].limits().initial(), 3);
+
+		let module = round_trip(start_trap());
+		assert!(module.sections().contains(&Section::Start(0)));
+
+		let module = round_trip(nested_blocks(3));
+		assert_eq!(module.code_section().expect("code section").bodies()[0].code().elements().len(), 7);
+
+		round_trip(function(vec![ValueType::I32], Some(ValueType::I32), vec![Instruction::GetLocal(0)]));
+	}
+}
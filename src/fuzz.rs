@@ -0,0 +1,192 @@
+//! Round-trip invariants for fuzzing the decoder and encoder.
+//!
+//! `round_trip` and `analyze` are meant to be called on arbitrary input by a fuzzer;
+//! see `fuzz/fuzz_targets` for the cargo-fuzz harnesses.
+
+use alloc::vec::Vec;
+use core::fmt;
+use crate::{analysis, optimize, transform};
+use crate::elements::{DeserializeConfig, Error, Module, deserialize_buffer, diff, serialize, serialize_canonical};
+
+/// Violation of the round-trip invariant.
+#[derive(Debug, Clone)]
+pub enum RoundTripError {
+	/// Decoded module fails to serialize.
+	Serialize(Error),
+	/// Serialized module fails to decode again.
+	Deserialize(Error),
+	/// Module decoded again differs from the one it was serialized from.
+	ModuleMismatch,
+	/// Serializing the module decoded again gives different bytes.
+	BytesMismatch,
+}
+
+impl fmt::Display for RoundTripError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			RoundTripError::Serialize(ref error) => write!(f, "Decoded module fails to serialize: {}", error),
+			RoundTripError::Deserialize(ref error) => write!(f, "Serialized module fails to decode: {}", error),
+			RoundTripError::ModuleMismatch => write!(f, "Module changes when serialized and decoded again"),
+			RoundTripError::BytesMismatch => write!(f, "Module serializes to different bytes when decoded again"),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for RoundTripError {}
+
+fn reserialize(module: Module) -> Result<(Vec<u8>, Module), RoundTripError> {
+	let bytes = serialize(module).map_err(RoundTripError::Serialize)?;
+	let module = deserialize_buffer(&bytes).map_err(RoundTripError::Deserialize)?;
+	Ok((bytes, module))
+}
+
+/// Check that a module decoded from `data` survives being serialized and decoded again.
+///
+/// The input itself need not be in its canonical encoding, but once serialized the
+/// module must decode to an equal module, which must serialize to the same bytes.
+/// Input which does not decode is not an error.
+pub fn round_trip(data: &[u8]) -> Result<(), RoundTripError> {
+	let module: Module = match deserialize_buffer(data) {
+		Ok(module) => module,
+		Err(_) => return Ok(()),
+	};
+	let (bytes, decoded) = reserialize(module.clone())?;
+	if decoded != module {
+		return Err(RoundTripError::ModuleMismatch);
+	}
+	let (bytes_again, _) = reserialize(decoded)?;
+	if bytes_again != bytes {
+		return Err(RoundTripError::BytesMismatch);
+	}
+	Ok(())
+}
+
+/// Run the checks, analyses and transforms of the crate on a module decoded from `data`.
+///
+/// They must not panic on any module which decodes, however inconsistent its indices,
+/// branches or initializer expressions are, so that untrusted modules can be inspected
+/// safely; their results are discarded. Input which does not decode is skipped.
+pub fn analyze(data: &[u8]) {
+	let module: Module = match deserialize_buffer(data) {
+		Ok(module) => module,
+		Err(_) => return,
+	};
+
+	let _ = module.to_text();
+	let _ = module.to_folded_text();
+	let _ = module.resource_estimate();
+	let _ = module.stats();
+	let _ = module.global_values(&[]);
+	let _ = module.check_segment_bounds(&[]);
+	let _ = module.check_segment_offsets();
+	let _ = module.overlapping_data_segments(&[]);
+	let _ = DeserializeConfig::mvp().check(&module);
+	for index in 0..=module.functions_space() as u32 {
+		let _ = module.function_type(index);
+	}
+	for index in 0..=module.globals_space() as u32 {
+		let _ = module.global_type(index);
+	}
+	let _ = module.imports().count();
+	let _ = module.exports().count();
+	for export in module.export_section().map(|s| s.entries()).unwrap_or(&[]) {
+		let _ = module.export_signature(export.field());
+	}
+	for body in module.code_section().map(|s| s.bodies()).unwrap_or(&[]) {
+		if let Ok(cfg) = analysis::ControlFlowGraph::new(body) {
+			let _ = cfg.dominators();
+		}
+	}
+	let graph = analysis::call_graph(&module);
+	let _ = graph.reachable(&analysis::entry_points(&module));
+	let _ = analysis::estimate_costs(&module, &analysis::CostTable::default());
+	let _ = diff(&module, &module);
+
+	let _ = module.clone().parse_names();
+	let _ = module.clone().parse_producers();
+	let _ = module.clone().parse_target_features();
+	let _ = module.clone().parse_linking();
+	let _ = module.clone().parse_dylink();
+	let _ = transform::dedupe_types(&mut module.clone());
+	let _ = transform::pack_data_segments(&mut module.clone());
+	let _ = transform::eliminate_dead_code(&mut module.clone(), &[]);
+	optimize::inline(&mut module.clone(), 16);
+	let _ = optimize::fold_immutable_globals(&mut module.clone());
+	#[cfg(feature="bulk")]
+	optimize::lower_memory_intrinsics(&mut module.clone());
+	let _ = serialize_canonical(module.clone());
+	let _ = transform::merge(module.clone(), "env", module, "env");
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{analyze, round_trip};
+	use crate::builder;
+	use crate::elements::{
+		deserialize_buffer, serialize, BlockType, DataSegment, InitExpr, Instruction::*, Instructions, Module, Section,
+	};
+
+	#[test]
+	fn corpus() {
+		for entry in std::fs::read_dir("./res/cases/v1").expect("corpus to be listed") {
+			let path = entry.expect("corpus entry").path();
+			// Too slow for a debug build; the other cases cover the same encodings.
+			if path.ends_with("clang.wasm") {
+				continue;
+			}
+			let bytes = std::fs::read(&path).expect("file to be read");
+			if let Err(error) = round_trip(&bytes) {
+				panic!("{}: {}", path.display(), error);
+			}
+		}
+	}
+
+	#[test]
+	fn garbage_is_not_an_error() {
+		assert!(round_trip(b"\0asm\x01\0\0\0\x01\xff").is_ok());
+	}
+
+	#[test]
+	fn analyze_corrupted_corpus() {
+		// Small deterministic corruptions, most of which still decode.
+		for name in &["const.wasm", "ifelse.wasm", "names.wasm", "offset.wasm", "start_mut.wasm", "test.wasm"] {
+			let bytes = std::fs::read(format!("./res/cases/v1/{}", name)).expect("file to be read");
+			let mut seed = 0x2545_f491_4f6c_dd1du64;
+			for _ in 0..500 {
+				seed ^= seed << 13;
+				seed ^= seed >> 7;
+				seed ^= seed << 17;
+				let mut data = bytes.clone();
+				data[(seed >> 8) as usize % bytes.len()] = (seed >> 40) as u8;
+				analyze(&data);
+			}
+		}
+	}
+
+	#[test]
+	fn analyze_unresolved_indices() {
+		let bodies = vec![
+			vec![Call(u32::MAX), CallIndirect(u32::MAX, 0), GetGlobal(u32::MAX), Drop, End],
+			// Branch from within a block out of the function.
+			vec![Block(BlockType::NoResult), Br(1), End, End],
+			vec![I32Const(0), If(BlockType::NoResult), Else, Else, End, End],
+			vec![Else, End],
+		];
+		for code in bodies {
+			let mut module = builder::module()
+				.function().signature().build().body().with_instructions(Instructions::new(code)).build().build()
+				.export().field("main").internal().func(0).build()
+				.export().field("missing").internal().func(u32::MAX).build()
+				.table().with_min(1).with_element(0, vec![0, u32::MAX]).build()
+				.memory().build()
+				.with_data_segment(DataSegment::new(0, Some(InitExpr::new(vec![I32Add, End])), vec![1]))
+				.with_data_segment(DataSegment::new(0, Some(InitExpr::new(vec![GetGlobal(u32::MAX), End])), vec![1]))
+				.build();
+			module.insert_section(Section::Start(u32::MAX)).expect("no start section yet");
+			let bytes = serialize(module).expect("module to serialize");
+			deserialize_buffer::<Module>(&bytes).expect("module to deserialize");
+			analyze(&bytes);
+		}
+	}
+}
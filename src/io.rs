@@ -3,6 +3,8 @@
 //! Basically it just a replacement for the std::io that is usable from
 //! the `no_std` environment.
 
+#![allow(clippy::enum_variant_names)]
+
 #[cfg(feature="std")]
 use std::io;
 
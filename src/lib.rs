@@ -8,11 +8,21 @@ extern crate alloc;
 
 pub mod elements;
 pub mod builder;
+pub mod transform;
+pub mod optimize;
+pub mod analysis;
+pub mod fixtures;
+pub mod fuzz;
 mod io;
 
 pub use elements::{
 	Error as SerializationError,
 	deserialize_buffer,
+	deserialize_buffer_with_config,
+	deserialize_buffer_with_limits,
+	deserialize_buffer_lazy,
+	deserialize_buffer_with_locations,
+	deserialize_buffer_detailed,
 	serialize,
 	peek_size,
 };
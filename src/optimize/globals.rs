@@ -0,0 +1,145 @@
+use alloc::vec::Vec;
+use crate::elements::{ImportCountType, Instruction, Module, RuntimeValue, Section};
+use crate::transform::{
+	IndexSpace, InstructionVisitor, ModuleVisitor, RelocationMap, RemapError, SpaceMap,
+	is_linking_section, walk_instruction, walk_module,
+};
+
+fn constant(value: RuntimeValue) -> Instruction {
+	match value {
+		RuntimeValue::I32(value) => Instruction::I32Const(value),
+		RuntimeValue::I64(value) => Instruction::I64Const(value),
+		RuntimeValue::F32(bits) => Instruction::F32Const(bits),
+		RuntimeValue::F64(bits) => Instruction::F64Const(bits),
+	}
+}
+
+/// Replaces reads of constant globals, and records which globals are still referenced.
+struct Folder<'a> {
+	values: &'a [Option<RuntimeValue>],
+	used: Vec<bool>,
+}
+
+impl<'a> InstructionVisitor for Folder<'a> {
+	type Error = RemapError;
+
+	fn visit_index(&mut self, space: IndexSpace, index: &mut u32) -> Result<(), RemapError> {
+		if space == IndexSpace::Global {
+			// Invalid indices are left for the remapping to report.
+			if let Some(used) = self.used.get_mut(*index as usize) {
+				*used = true;
+			}
+		}
+		Ok(())
+	}
+
+	fn visit_instruction(&mut self, instruction: &mut Instruction) -> Result<(), RemapError> {
+		if let Instruction::GetGlobal(index) = *instruction {
+			if let Some(&Some(value)) = self.values.get(index as usize) {
+				*instruction = constant(value);
+				return Ok(());
+			}
+		}
+		walk_instruction(self, instruction)
+	}
+}
+
+impl<'a> ModuleVisitor for Folder<'a> {}
+
+/// Replace every `get_global` of an immutable global with a constant initializer by
+/// the constant, and remove the globals which are no longer referenced.
+///
+/// Initializers are constant if they are made of constants and of reads of other such
+/// globals, as in extended constant expressions. Reads are replaced in function bodies,
+/// global initializers and segment offsets. Imported globals are never folded, nor
+/// removed; neither are mutable ones, nor exported ones. The remaining globals are
+/// re-indexed to close the gaps, and the number of removed ones is returned.
+///
+/// If any global is removed, relocation, linking and dynamic linking sections are
+/// removed too, parsed or not, since they would no longer match the module.
+pub fn fold_immutable_globals(module: &mut Module) -> Result<usize, RemapError> {
+	let imported = module.import_count(ImportCountType::Global);
+	let mut values: Vec<Option<RuntimeValue>> = vec![None; imported];
+	for entry in module.global_section().map(|s| s.entries()).unwrap_or(&[]) {
+		let value = if entry.global_type().is_mutable() {
+			None
+		} else {
+			entry.init_expr().evaluate(|index| values.get(index as usize).cloned().unwrap_or(None)).ok()
+		};
+		values.push(value);
+	}
+
+	let mut folder = Folder { values: &values, used: vec![false; values.len()] };
+	walk_module(&mut folder, module)?;
+	let keep: Vec<bool> = values.iter().zip(&folder.used).map(|(value, &used)| value.is_none() || used).collect();
+	let removed = keep.iter().filter(|&&keep| !keep).count();
+	if removed == 0 {
+		return Ok(0);
+	}
+
+	if let Some(global_section) = module.global_section_mut() {
+		let mut index = imported;
+		global_section.entries_mut().retain(|_| {
+			index += 1;
+			keep[index - 1]
+		});
+	}
+	module.sections_mut().retain(|section| match *section {
+		Section::Global(ref s) => !s.entries().is_empty(),
+		ref section => !is_linking_section(section),
+	});
+
+	let mut next = 0;
+	let globals = SpaceMap::new(keep.iter().map(|&keep| if keep {
+		next += 1;
+		Some(next - 1)
+	} else {
+		None
+	}).collect());
+	RelocationMap { globals, ..Default::default() }.apply(module)?;
+	Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::fold_immutable_globals;
+	use crate::builder;
+	use crate::elements::{Instruction::*, Instructions, Internal, ValueType};
+
+	#[test]
+	fn fold_and_remove() {
+		let mut module = builder::module()
+			.import().module("env").field("base").external().global(ValueType::I32, false).build()
+			.global().value_type().i32().init_expr(I32Const(7)).build()
+			.global().value_type().i64().init_expr(I64Const(-1)).build()
+			.global().value_type().i32().mutable().init_expr(I32Const(0)).build()
+			.global().value_type().f32().init_expr(F32Const(0x3f80_0000)).build()
+			.export().field("one").internal().global(4).build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![
+					GetGlobal(0), Drop,
+					GetGlobal(1), Drop,
+					GetGlobal(2), Drop,
+					GetGlobal(3), Drop,
+					GetGlobal(4), Drop,
+					End,
+				])).build()
+				.build()
+			.build();
+
+		assert_eq!(fold_immutable_globals(&mut module), Ok(2));
+		let globals = module.global_section().expect("global section").entries();
+		assert_eq!(globals.len(), 2);
+		assert_eq!(module.export_section().expect("export section").entries()[0].internal(), &Internal::Global(2));
+		assert_eq!(module.code_section().expect("code section").bodies()[0].code().elements(), &[
+			GetGlobal(0), Drop,
+			I32Const(7), Drop,
+			I64Const(-1), Drop,
+			GetGlobal(1), Drop,
+			F32Const(0x3f80_0000), Drop,
+			End,
+		]);
+
+		assert_eq!(fold_immutable_globals(&mut module), Ok(0));
+	}
+}
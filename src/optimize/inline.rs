@@ -0,0 +1,332 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use crate::elements::{
+	Module, FuncBody, Instruction, BlockType, Local, ValueType, ImportCountType,
+};
+use crate::transform::{Position, PositionMap};
+
+/// Function body which may be copied into its callers.
+struct Candidate {
+	/// Index of the body in the code section.
+	body: u32,
+	params: Vec<ValueType>,
+	locals: Vec<ValueType>,
+	block_type: BlockType,
+	/// Instructions of the body, the final `end` included.
+	code: Vec<Instruction>,
+}
+
+impl Candidate {
+	fn new(module: &Module, func_index: u32, body: &FuncBody, threshold: usize) -> Option<Self> {
+		let func_type = module.function_type(func_index)?;
+		let block_type = match *func_type.results() {
+			[] => BlockType::NoResult,
+			[value_type] => BlockType::Value(value_type),
+			_ => return None,
+		};
+
+		let declared = body.locals().iter().fold(0u64, |acc, local| acc + local.count() as u64);
+		let size = body.code().elements().len().saturating_sub(1) as u64 + declared;
+		if size > threshold as u64 {
+			return None;
+		}
+
+		let mut locals = Vec::new();
+		for local in body.locals() {
+			// Locals are zeroed on every inlined call, which is not done for `v128` ones.
+			#[cfg(feature="simd")]
+			{
+				if local.value_type() == ValueType::V128 {
+					return None;
+				}
+			}
+			for _ in 0..local.count() {
+				locals.push(local.value_type());
+			}
+		}
+
+		// Only leaves are inlined, so that inlining never recurses.
+		for instruction in body.code().elements() {
+			match *instruction {
+				Instruction::Call(_) | Instruction::CallIndirect(..) => return None,
+				#[cfg(feature="exceptions")]
+				Instruction::Exceptions(_) => return None,
+				_ => {},
+			}
+		}
+
+		Some(Candidate {
+			body: func_index - module.import_count(ImportCountType::Function) as u32,
+			params: func_type.params().to_vec(),
+			locals,
+			block_type,
+			code: body.code().elements().to_vec(),
+		})
+	}
+
+	/// Emit the body in place of a call, with its locals starting at `base`.
+	///
+	/// Instructions copied from the body keep their positions in it, the ones setting
+	/// up the call take the position of the call.
+	fn expand(&self, base: u32, call: Position, out: &mut Vec<Instruction>, positions: &mut Vec<Option<Position>>) {
+		for index in (0..self.params.len() as u32).rev() {
+			out.push(Instruction::SetLocal(base + index));
+		}
+		for (index, &value_type) in self.locals.iter().enumerate() {
+			out.push(match value_type {
+				ValueType::I32 => Instruction::I32Const(0),
+				ValueType::I64 => Instruction::I64Const(0),
+				ValueType::F32 => Instruction::F32Const(0),
+				ValueType::F64 => Instruction::F64Const(0),
+				#[cfg(feature="simd")]
+				ValueType::V128 => unreachable!("candidates have no v128 locals"),
+			});
+			out.push(Instruction::SetLocal(base + (self.params.len() + index) as u32));
+		}
+
+		// The block stands in for the function's own label, so branches keep their depths
+		// and the body's final `end` closes it.
+		out.push(Instruction::Block(self.block_type));
+		positions.resize(out.len(), Some(call));
+		let mut depth = 0;
+		for (index, instruction) in self.code.iter().enumerate() {
+			let instruction = match *instruction {
+				Instruction::GetLocal(index) => Instruction::GetLocal(base + index),
+				Instruction::SetLocal(index) => Instruction::SetLocal(base + index),
+				Instruction::TeeLocal(index) => Instruction::TeeLocal(base + index),
+				Instruction::Return => Instruction::Br(depth),
+				ref other => other.clone(),
+			};
+			if instruction.is_block() {
+				depth += 1;
+			} else if instruction.is_terminal() {
+				depth = depth.saturating_sub(1);
+			}
+			out.push(instruction);
+			positions.push(Some(Position::new(self.body, index as u32)));
+		}
+	}
+}
+
+/// Inline calls to small functions, returning the number of call sites replaced.
+///
+/// A function is inlined if it is defined by the module, calls no other function, has
+/// at most one result, and its instructions plus declared locals number at most
+/// `threshold`. Each call is replaced by a block holding a copy of the body, with the
+/// arguments moved into new locals of the caller and `return`s turned into branches
+/// out of the block. Callers inlining the same function more than once share its locals.
+///
+/// Inlined functions are kept, since they may still be exported or called indirectly;
+/// `transform::eliminate_dead_code` removes the ones which are no longer used.
+pub fn inline(module: &mut Module, threshold: usize) -> usize {
+	inline_with_positions(module, threshold).0
+}
+
+/// Inline calls to small functions like `inline`, also mapping the instructions of
+/// the result to the ones they are copied from.
+pub fn inline_with_positions(module: &mut Module, threshold: usize) -> (usize, PositionMap) {
+	let identity = PositionMap::identity(module);
+	let imported = module.import_count(ImportCountType::Function) as u32;
+	let bodies = module.code_section().map(|s| s.bodies()).unwrap_or(&[]);
+
+	let mut candidates = BTreeMap::new();
+	let mut param_counts = Vec::with_capacity(bodies.len());
+	for (index, body) in bodies.iter().enumerate() {
+		let func_index = imported + index as u32;
+		param_counts.push(module.function_type(func_index).map(|func_type| func_type.params().len() as u32));
+		if let Some(candidate) = Candidate::new(module, func_index, body, threshold) {
+			candidates.insert(func_index, candidate);
+		}
+	}
+	if candidates.is_empty() {
+		return (0, identity);
+	}
+
+	let mut inlined = 0;
+	let mut positions = Vec::with_capacity(bodies.len());
+	let bodies = match module.code_section_mut() {
+		Some(code_section) => code_section.bodies_mut(),
+		None => return (0, identity),
+	};
+	for (index, (body, param_count)) in bodies.iter_mut().zip(param_counts).enumerate() {
+		let identity = (0..body.code().elements().len())
+			.map(|instruction| Some(Position::new(index as u32, instruction as u32)))
+			.collect();
+		let param_count = match param_count {
+			Some(param_count) => param_count,
+			None => {
+				positions.push(identity);
+				continue;
+			},
+		};
+		match inline_calls(body, index as u32, param_count, &candidates) {
+			Some((count, body_positions)) => {
+				inlined += count;
+				positions.push(body_positions);
+			},
+			None => positions.push(identity),
+		}
+	}
+	(inlined, PositionMap::new(positions))
+}
+
+/// Inline calls in the `index`th body, returning the number of call sites replaced
+/// and the original positions of its instructions, or `None` if nothing is inlined.
+fn inline_calls(
+	body: &mut FuncBody,
+	index: u32,
+	param_count: u32,
+	candidates: &BTreeMap<u32, Candidate>,
+) -> Option<(usize, Vec<Option<Position>>)> {
+	let calls = body.code().elements().iter().any(|instruction| match *instruction {
+		Instruction::Call(index) => candidates.contains_key(&index),
+		_ => false,
+	});
+	if !calls {
+		return None;
+	}
+
+	let declared = body.locals().iter().fold(0u64, |acc, local| acc + local.count() as u64);
+	let mut next_local = param_count as u64 + declared;
+	let mut bases = BTreeMap::new();
+	let mut inlined = 0;
+
+	let code = core::mem::take(body.code_mut().elements_mut());
+	let mut out = Vec::with_capacity(code.len());
+	let mut positions = Vec::with_capacity(code.len());
+	for (instruction_index, instruction) in code.into_iter().enumerate() {
+		let position = Position::new(index, instruction_index as u32);
+		let candidate = match instruction {
+			Instruction::Call(callee) => candidates.get(&callee).map(|candidate| (callee, candidate)),
+			_ => None,
+		};
+		let (callee, candidate) = match candidate {
+			Some(candidate) => candidate,
+			None => {
+				out.push(instruction);
+				positions.push(Some(position));
+				continue;
+			},
+		};
+
+		let base = match bases.get(&callee) {
+			Some(&base) => base,
+			None => {
+				let count = (candidate.params.len() + candidate.locals.len()) as u64;
+				// Locals are indexed by `u32`, a caller which would run out of them keeps the call.
+				if next_local + count > u32::MAX as u64 {
+					out.push(instruction);
+					positions.push(Some(position));
+					continue;
+				}
+				for &value_type in candidate.params.iter().chain(&candidate.locals) {
+					body.locals_mut().push(Local::new(1, value_type));
+				}
+				let base = next_local as u32;
+				next_local += count;
+				bases.insert(callee, base);
+				base
+			},
+		};
+		candidate.expand(base, position, &mut out, &mut positions);
+		inlined += 1;
+	}
+	*body.code_mut().elements_mut() = out;
+	Some((inlined, positions))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{inline, inline_with_positions};
+	use crate::builder;
+	use crate::transform::Position;
+	use crate::elements::{Module, Instruction::*, Instructions, ValueType, BlockType, Local};
+
+	fn module(callee: Instructions) -> Module {
+		builder::module()
+			.function()
+				.signature().with_params(vec![ValueType::I32, ValueType::I32]).with_result(ValueType::I32).build()
+				.body()
+					.with_locals(vec![Local::new(1, ValueType::I64)])
+					.with_instructions(callee)
+					.build()
+				.build()
+			.function()
+				.signature().with_param(ValueType::I32).with_result(ValueType::I32).build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						GetLocal(0),
+						I32Const(2),
+						Call(0),
+						End,
+					]))
+					.build()
+				.build()
+			.build()
+	}
+
+	#[test]
+	fn inlines_leaf() {
+		let mut module = module(Instructions::new(vec![GetLocal(0), GetLocal(1), I32Add, End]));
+		assert_eq!(inline(&mut module, 8), 1);
+
+		let caller = &module.code_section().expect("code section").bodies()[1];
+		assert_eq!(
+			caller.locals(),
+			&[Local::new(1, ValueType::I32), Local::new(1, ValueType::I32), Local::new(1, ValueType::I64)][..],
+		);
+		assert_eq!(caller.code().elements(), &[
+			GetLocal(0),
+			I32Const(2),
+			SetLocal(2),
+			SetLocal(1),
+			I64Const(0),
+			SetLocal(3),
+			Block(BlockType::Value(ValueType::I32)),
+			GetLocal(1),
+			GetLocal(2),
+			I32Add,
+			End,
+			End,
+		][..]);
+	}
+
+	#[test]
+	fn positions() {
+		let mut module = module(Instructions::new(vec![GetLocal(0), GetLocal(1), I32Add, End]));
+		let (inlined, map) = inline_with_positions(&mut module, 8);
+		assert_eq!(inlined, 1);
+
+		// The prologue stands for the call, the copied `i32.add` for the callee's own.
+		assert_eq!(map.original(Position::new(1, 1)), Some(Position::new(1, 1)));
+		assert_eq!(map.original(Position::new(1, 2)), Some(Position::new(1, 2)));
+		assert_eq!(map.original(Position::new(1, 6)), Some(Position::new(1, 2)));
+		assert_eq!(map.original(Position::new(1, 9)), Some(Position::new(0, 2)));
+		assert_eq!(map.original(Position::new(1, 11)), Some(Position::new(1, 3)));
+		assert_eq!(map.original(Position::new(0, 2)), Some(Position::new(0, 2)));
+	}
+
+	#[test]
+	fn return_becomes_branch() {
+		let mut module = module(Instructions::new(vec![
+			GetLocal(0),
+			If(BlockType::NoResult),
+			GetLocal(1),
+			Return,
+			End,
+			I32Const(0),
+			End,
+		]));
+		assert_eq!(inline(&mut module, 8), 1);
+
+		let caller = &module.code_section().expect("code section").bodies()[1];
+		assert!(caller.code().elements().contains(&Br(1)));
+		assert!(!caller.code().elements().contains(&Return));
+	}
+
+	#[test]
+	fn threshold() {
+		let mut module = module(Instructions::new(vec![GetLocal(0), GetLocal(1), I32Add, End]));
+		assert_eq!(inline(&mut module, 3), 0);
+		assert!(module.code_section().expect("code section").bodies()[1].code().elements().contains(&Call(0)));
+	}
+}
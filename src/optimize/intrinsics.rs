@@ -0,0 +1,173 @@
+use alloc::vec::Vec;
+use crate::elements::{
+	BulkInstruction, External, FuncBody, ImportCountType, Instruction, Local, Module, ValueType,
+};
+
+/// Function names recognized as memory intrinsics, and the bulk memory instruction
+/// doing their work.
+const INTRINSICS: &[(&str, BulkInstruction)] = &[
+	("memcpy", BulkInstruction::MemoryCopy),
+	("memmove", BulkInstruction::MemoryCopy),
+	("memset", BulkInstruction::MemoryFill),
+];
+
+fn intrinsic(name: &str) -> Option<BulkInstruction> {
+	INTRINSICS.iter().find(|&&(intrinsic, _)| intrinsic == name).map(|(_, instruction)| instruction.clone())
+}
+
+/// Instruction replacing calls of every function of `module` which is an intrinsic.
+fn intrinsics(module: &Module) -> Vec<Option<BulkInstruction>> {
+	let mut functions: Vec<Option<BulkInstruction>> = module.import_section().map(|s| s.entries()).unwrap_or(&[]).iter()
+		.filter(|entry| matches!(*entry.external(), External::Function(_)))
+		.map(|entry| intrinsic(entry.field()))
+		.collect();
+	functions.resize(module.functions_space(), None);
+	if let Some(names) = module.names_section().and_then(|names| names.functions()) {
+		for (index, name) in names.names() {
+			let imported = (index as usize) < module.import_count(ImportCountType::Function);
+			if let (false, Some(function)) = (imported, functions.get_mut(index as usize)) {
+				*function = intrinsic(name);
+			}
+		}
+	}
+
+	// `(dest, value or src, n) -> dest`, as in C.
+	for (index, function) in functions.iter_mut().enumerate() {
+		let signature = module.function_type(index as u32);
+		if !signature.is_some_and(|signature| signature.params() == [ValueType::I32; 3] && signature.results() == [ValueType::I32]) {
+			*function = None;
+		}
+	}
+	functions
+}
+
+/// Replace calls of `memcpy`, `memmove` and `memset` by `memory.copy` and `memory.fill`.
+///
+/// Functions are recognized by their name: the field name of imported ones, and the
+/// name in the name section of others, which are trusted to behave like their C
+/// counterparts on memory `0`; their bodies are left for `eliminate_dead_code` to
+/// remove. The name section is debug information which nothing checks, so a defined
+/// function merely named `memcpy` is replaced as well: only run this on modules whose
+/// names come from their compiler. The result of a call, the destination address, is
+/// kept in a new local unless it is dropped right away. Returns the number of replaced
+/// calls.
+///
+/// Unlike a loop copying bytes, the bulk memory instructions trap before writing
+/// anything if any byte is out of bounds, and engines run them at native speed. They
+/// also trap when `n` is `0` but an address is past the end of memory, where the C
+/// functions do nothing.
+pub fn lower_memory_intrinsics(module: &mut Module) -> usize {
+	if module.memory_space() == 0 {
+		return 0;
+	}
+	let functions = intrinsics(module);
+	if functions.iter().all(Option::is_none) {
+		return 0;
+	}
+
+	let imported = module.import_count(ImportCountType::Function);
+	let mut param_counts = Vec::new();
+	for index in 0..module.code_section().map(|s| s.bodies().len()).unwrap_or(0) {
+		param_counts.push(module.function_type((imported + index) as u32).map(|signature| signature.params().len()));
+	}
+
+	let mut replaced = 0;
+	let bodies = module.code_section_mut().map(|s| s.bodies_mut().as_mut_slice()).unwrap_or(&mut []);
+	for (body, params) in bodies.iter_mut().zip(param_counts) {
+		if let Some(params) = params {
+			replaced += lower_body(body, params, &functions);
+		}
+	}
+	replaced
+}
+
+fn lower_body(body: &mut FuncBody, params: usize, functions: &[Option<BulkInstruction>]) -> usize {
+	let code = core::mem::take(body.code_mut().elements_mut());
+	let mut out = Vec::with_capacity(code.len());
+	let mut replaced = 0;
+	// First of the three locals keeping the operands, once added.
+	let mut scratch = None;
+	let mut code = code.into_iter().peekable();
+	while let Some(instruction) = code.next() {
+		let bulk = match instruction {
+			Instruction::Call(index) => functions.get(index as usize).cloned().unwrap_or(None),
+			_ => None,
+		};
+		let bulk = match bulk {
+			Some(bulk) => Instruction::Bulk(bulk),
+			None => {
+				out.push(instruction);
+				continue;
+			},
+		};
+
+		replaced += 1;
+		if code.peek() == Some(&Instruction::Drop) {
+			code.next();
+			out.push(bulk);
+			continue;
+		}
+		let first = *scratch.get_or_insert_with(|| {
+			let first = params as u32 + body.locals().iter().map(|local| local.count()).sum::<u32>();
+			body.locals_mut().push(Local::new(3, ValueType::I32));
+			first
+		});
+		out.extend_from_slice(&[
+			Instruction::SetLocal(first + 2),
+			Instruction::SetLocal(first + 1),
+			Instruction::TeeLocal(first),
+			Instruction::GetLocal(first + 1),
+			Instruction::GetLocal(first + 2),
+			bulk,
+			Instruction::GetLocal(first),
+		]);
+	}
+	*body.code_mut().elements_mut() = out;
+	replaced
+}
+
+#[cfg(test)]
+mod tests {
+	use super::lower_memory_intrinsics;
+	use crate::builder;
+	use crate::elements::{
+		BulkInstruction::*, FunctionNameSubsection, Instruction::*, Instructions, Local, NameSection, Section,
+		ValueType,
+	};
+
+	#[test]
+	fn calls() {
+		let mut module = builder::module()
+			.memory().with_min(1).build()
+			.function()
+				.signature().with_params(vec![ValueType::I32; 3]).with_result(ValueType::I32).build()
+				.body().build()
+				.build()
+			.import().module("env").field("memcpy").external().func(0).build()
+			.function()
+				.signature().with_param(ValueType::I32).build()
+				.body().with_locals(vec![Local::new(1, ValueType::I64)]).with_instructions(Instructions::new(vec![
+					GetLocal(0), I32Const(0), I32Const(16), Call(0), Drop,
+					GetLocal(0), I32Const(0), I32Const(16), Call(1), SetLocal(0),
+					GetLocal(0), I32Const(8), I32Const(16), Call(0), Drop,
+					End,
+				])).build()
+				.build()
+			.build();
+		let mut names = FunctionNameSubsection::default();
+		names.names_mut().insert(1, "memset".into());
+		module.sections_mut().push(Section::Name(NameSection::new(None, Some(names), None)));
+
+		assert_eq!(lower_memory_intrinsics(&mut module), 3);
+		let body = &module.code_section().expect("code section").bodies()[1];
+		assert_eq!(body.locals(), &[Local::new(1, ValueType::I64), Local::new(3, ValueType::I32)]);
+		assert_eq!(body.code().elements(), &[
+			GetLocal(0), I32Const(0), I32Const(16), Bulk(MemoryCopy),
+			GetLocal(0), I32Const(0), I32Const(16),
+			SetLocal(4), SetLocal(3), TeeLocal(2), GetLocal(3), GetLocal(4), Bulk(MemoryFill), GetLocal(2),
+			SetLocal(0),
+			GetLocal(0), I32Const(8), I32Const(16), Bulk(MemoryCopy),
+			End,
+		]);
+	}
+}
@@ -0,0 +1,13 @@
+//! Optimizations of modules and function bodies.
+
+mod globals;
+mod inline;
+#[cfg(feature="bulk")]
+mod intrinsics;
+mod peephole;
+
+pub use self::globals::fold_immutable_globals;
+pub use self::inline::{inline, inline_with_positions};
+#[cfg(feature="bulk")]
+pub use self::intrinsics::lower_memory_intrinsics;
+pub use self::peephole::peephole;
@@ -0,0 +1,171 @@
+use alloc::vec::Vec;
+use crate::elements::{FuncBody, Instruction};
+use crate::elements::Instruction::*;
+
+/// Simplify short instruction sequences of `body` which have a cheaper equivalent.
+///
+/// The sequences are the ones naive code generators tend to emit:
+///
+/// - integer arithmetic, bitwise operations and comparisons of two constants are
+///   folded into one constant; divisions and remainders are left alone, since they
+///   may trap;
+/// - additions, subtractions, bitwise ors, xors, shifts and rotations by a constant
+///   `0`, as well as multiplications by `1`, are removed;
+/// - constants and reads of locals or globals which are immediately dropped are
+///   removed, and `tee_local` followed by `drop` becomes `set_local`;
+/// - a float negation of a negation is removed, and so is a double `eqz` in front of
+///   `if` or `br_if`.
+///
+/// Only instructions without side effects outside the value stack are removed, and
+/// control flow is never changed, so the body stays valid and behaves the same.
+pub fn peephole(body: &mut FuncBody) {
+	let code = core::mem::take(body.code_mut().elements_mut());
+	let mut out = Vec::with_capacity(code.len());
+	for instruction in code {
+		out.push(instruction);
+		// Simplifying the tail may expose another simplification in front of it.
+		while simplify_tail(&mut out) {}
+	}
+	*body.code_mut().elements_mut() = out;
+}
+
+fn simplify_tail(code: &mut Vec<Instruction>) -> bool {
+	let len = code.len();
+	let replacement = match code.as_slice() {
+		[.., I32Const(a), I32Const(b), op] => fold_i32(*a, *b, op).map(|folded| (3, Some(folded))),
+		[.., I64Const(a), I64Const(b), op] => fold_i64(*a, *b, op).map(|folded| (3, Some(folded))),
+		[.., I32Const(a), I32Eqz] => Some((2, Some(I32Const((*a == 0) as i32)))),
+		[.., I64Const(a), I64Eqz] => Some((2, Some(I32Const((*a == 0) as i32)))),
+
+		[.., I32Const(0), I32Add] | [.., I32Const(0), I32Sub] | [.., I32Const(0), I32Or] |
+		[.., I32Const(0), I32Xor] | [.., I32Const(0), I32Shl] | [.., I32Const(0), I32ShrS] |
+		[.., I32Const(0), I32ShrU] | [.., I32Const(0), I32Rotl] | [.., I32Const(0), I32Rotr] |
+		[.., I32Const(1), I32Mul] => Some((2, None)),
+		[.., I64Const(0), I64Add] | [.., I64Const(0), I64Sub] | [.., I64Const(0), I64Or] |
+		[.., I64Const(0), I64Xor] | [.., I64Const(0), I64Shl] | [.., I64Const(0), I64ShrS] |
+		[.., I64Const(0), I64ShrU] | [.., I64Const(0), I64Rotl] | [.., I64Const(0), I64Rotr] |
+		[.., I64Const(1), I64Mul] => Some((2, None)),
+
+		[.., I32Const(_), Drop] | [.., I64Const(_), Drop] | [.., F32Const(_), Drop] |
+		[.., F64Const(_), Drop] | [.., GetLocal(_), Drop] | [.., GetGlobal(_), Drop] => Some((2, None)),
+		[.., TeeLocal(index), Drop] => Some((2, Some(SetLocal(*index)))),
+
+		[.., F32Neg, F32Neg] | [.., F64Neg, F64Neg] => Some((2, None)),
+		[.., I32Eqz, I32Eqz, If(block_type)] => Some((3, Some(If(*block_type)))),
+		[.., I32Eqz, I32Eqz, BrIf(depth)] => Some((3, Some(BrIf(*depth)))),
+		_ => None,
+	};
+
+	match replacement {
+		Some((removed, replacement)) => {
+			code.truncate(len - removed);
+			code.extend(replacement);
+			true
+		},
+		None => false,
+	}
+}
+
+fn fold_i32(a: i32, b: i32, op: &Instruction) -> Option<Instruction> {
+	let (ua, ub) = (a as u32, b as u32);
+	let value = match *op {
+		I32Add => a.wrapping_add(b),
+		I32Sub => a.wrapping_sub(b),
+		I32Mul => a.wrapping_mul(b),
+		I32And => a & b,
+		I32Or => a | b,
+		I32Xor => a ^ b,
+		I32Shl => a.wrapping_shl(ub),
+		I32ShrS => a.wrapping_shr(ub),
+		I32ShrU => ua.wrapping_shr(ub) as i32,
+		I32Rotl => ua.rotate_left(ub % 32) as i32,
+		I32Rotr => ua.rotate_right(ub % 32) as i32,
+		I32Eq => (a == b) as i32,
+		I32Ne => (a != b) as i32,
+		I32LtS => (a < b) as i32,
+		I32LtU => (ua < ub) as i32,
+		I32GtS => (a > b) as i32,
+		I32GtU => (ua > ub) as i32,
+		I32LeS => (a <= b) as i32,
+		I32LeU => (ua <= ub) as i32,
+		I32GeS => (a >= b) as i32,
+		I32GeU => (ua >= ub) as i32,
+		_ => return None,
+	};
+	Some(I32Const(value))
+}
+
+fn fold_i64(a: i64, b: i64, op: &Instruction) -> Option<Instruction> {
+	let (ua, ub) = (a as u64, b as u64);
+	let shift = (ub % 64) as u32;
+	let value = match *op {
+		I64Add => a.wrapping_add(b),
+		I64Sub => a.wrapping_sub(b),
+		I64Mul => a.wrapping_mul(b),
+		I64And => a & b,
+		I64Or => a | b,
+		I64Xor => a ^ b,
+		I64Shl => a.wrapping_shl(shift),
+		I64ShrS => a.wrapping_shr(shift),
+		I64ShrU => ua.wrapping_shr(shift) as i64,
+		I64Rotl => ua.rotate_left(shift) as i64,
+		I64Rotr => ua.rotate_right(shift) as i64,
+		// Comparisons yield an `i32`.
+		I64Eq => return Some(I32Const((a == b) as i32)),
+		I64Ne => return Some(I32Const((a != b) as i32)),
+		I64LtS => return Some(I32Const((a < b) as i32)),
+		I64LtU => return Some(I32Const((ua < ub) as i32)),
+		I64GtS => return Some(I32Const((a > b) as i32)),
+		I64GtU => return Some(I32Const((ua > ub) as i32)),
+		I64LeS => return Some(I32Const((a <= b) as i32)),
+		I64LeU => return Some(I32Const((ua <= ub) as i32)),
+		I64GeS => return Some(I32Const((a >= b) as i32)),
+		I64GeU => return Some(I32Const((ua >= ub) as i32)),
+		_ => return None,
+	};
+	Some(I64Const(value))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::peephole;
+	use crate::elements::{FuncBody, Instruction::*, Instructions, BlockType};
+
+	fn optimized(code: Vec<crate::elements::Instruction>) -> Vec<crate::elements::Instruction> {
+		let mut body = FuncBody::new(Vec::new(), Instructions::new(code));
+		peephole(&mut body);
+		body.code().elements().to_vec()
+	}
+
+	#[test]
+	fn identities() {
+		assert_eq!(optimized(vec![GetLocal(0), I32Const(0), I32Add, End]), vec![GetLocal(0), End]);
+		assert_eq!(optimized(vec![GetLocal(0), I64Const(1), I64Mul, End]), vec![GetLocal(0), End]);
+		assert_eq!(optimized(vec![GetLocal(0), Drop, End]), vec![End]);
+		assert_eq!(optimized(vec![I32Const(1), TeeLocal(0), Drop, End]), vec![I32Const(1), SetLocal(0), End]);
+		assert_eq!(optimized(vec![GetLocal(0), F32Neg, F32Neg, End]), vec![GetLocal(0), End]);
+		assert_eq!(
+			optimized(vec![GetLocal(0), I32Eqz, I32Eqz, If(BlockType::NoResult), End, End]),
+			vec![GetLocal(0), If(BlockType::NoResult), End, End],
+		);
+	}
+
+	#[test]
+	fn constant_folding() {
+		assert_eq!(
+			optimized(vec![I32Const(2), I32Const(3), I32Mul, I32Const(1), I32Sub, End]),
+			vec![I32Const(5), End],
+		);
+		assert_eq!(optimized(vec![I32Const(-1), I32Const(33), I32ShrU, End]), vec![I32Const(i32::MAX), End]);
+		assert_eq!(optimized(vec![I64Const(1), I64Const(2), I64LtS, End]), vec![I32Const(1), End]);
+		assert_eq!(optimized(vec![I32Const(i32::MAX), I32Const(1), I32Add, End]), vec![I32Const(i32::MIN), End]);
+		// Folding into a constant which is then dropped removes the whole computation.
+		assert_eq!(optimized(vec![I64Const(4), I64Const(5), I64Add, Drop, End]), vec![End]);
+	}
+
+	#[test]
+	fn trapping_left_alone() {
+		let code = vec![I32Const(1), I32Const(0), I32DivU, End];
+		assert_eq!(optimized(code.clone()), code);
+	}
+}
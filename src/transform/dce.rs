@@ -0,0 +1,393 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use crate::elements::{Module, Section, External, Internal, Instruction};
+use crate::analysis::call_graph;
+use super::remap::{RelocationMap, RemapError, SpaceMap, is_linking_section};
+use super::visit::{IndexSpace, InstructionVisitor, walk_instruction};
+
+/// Error of eliminating dead code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeadCodeError {
+	/// Module has no export of this name to start from.
+	MissingExport(String),
+	/// Module refers to an item which does not exist.
+	InvalidIndex,
+	/// Function and code sections have different numbers of entries.
+	InconsistentFunctionCount,
+}
+
+impl fmt::Display for DeadCodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			DeadCodeError::MissingExport(ref name) => write!(f, "Module has no export named {}", name),
+			DeadCodeError::InvalidIndex => write!(f, "Module refers to an item which does not exist"),
+			DeadCodeError::InconsistentFunctionCount => write!(f, "Function and code sections have different lengths"),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for DeadCodeError {}
+
+impl From<RemapError> for DeadCodeError {
+	fn from(_: RemapError) -> Self {
+		DeadCodeError::InvalidIndex
+	}
+}
+
+/// Live items of every index space which can shrink.
+struct Liveness {
+	types: Vec<bool>,
+	functions: Vec<bool>,
+	globals: Vec<bool>,
+	elements: Vec<bool>,
+	data: Vec<bool>,
+	pending: Vec<(IndexSpace, u32)>,
+}
+
+impl Liveness {
+	fn mark(&mut self, space: IndexSpace, index: u32) -> Result<(), DeadCodeError> {
+		let live = match space {
+			IndexSpace::Type => &mut self.types,
+			IndexSpace::Function => &mut self.functions,
+			IndexSpace::Global => &mut self.globals,
+			IndexSpace::Element => &mut self.elements,
+			IndexSpace::Data => &mut self.data,
+			// Tables, memories and tags are always kept.
+			IndexSpace::Table | IndexSpace::Memory => return Ok(()),
+			#[cfg(feature="exceptions")]
+			IndexSpace::Tag => return Ok(()),
+		};
+		let slot = live.get_mut(index as usize).ok_or(DeadCodeError::InvalidIndex)?;
+		if !*slot {
+			*slot = true;
+			self.pending.push((space, index));
+		}
+		Ok(())
+	}
+
+	fn mark_instructions(&mut self, instructions: &[Instruction]) -> Result<(), DeadCodeError> {
+		for instruction in instructions {
+			// Marking leaves the instruction as it is.
+			walk_instruction(self, &mut instruction.clone())?;
+		}
+		Ok(())
+	}
+}
+
+impl InstructionVisitor for Liveness {
+	type Error = DeadCodeError;
+
+	fn visit_index(&mut self, space: IndexSpace, index: &mut u32) -> Result<(), DeadCodeError> {
+		match space {
+			// Calls are followed through the call graph, which leaves out missing callees.
+			IndexSpace::Function if (*index as usize) < self.functions.len() => Ok(()),
+			IndexSpace::Function => Err(DeadCodeError::InvalidIndex),
+			_ => self.mark(space, *index),
+		}
+	}
+}
+
+/// Map keeping the live items in order.
+fn compacting_map(live: &[bool]) -> SpaceMap {
+	let mut next = 0;
+	SpaceMap::new(live.iter().map(|&live| if live {
+		next += 1;
+		Some(next - 1)
+	} else {
+		None
+	}).collect())
+}
+
+fn retain_by_index<T>(entries: &mut Vec<T>, live: &[bool]) {
+	let mut index = 0;
+	entries.retain(|_| {
+		index += 1;
+		live[index - 1]
+	});
+}
+
+/// Remove everything from `module` which cannot be reached from the exports named in `roots`.
+///
+/// Functions, function types, globals, element segments and data segments are removed
+/// unless they are used, directly or through others, by one of the root exports, the
+/// start function, or an active segment; all remaining indices are rewritten to close
+/// the gaps. Imported functions and globals which are not used are removed as well.
+/// Function and global exports not named in `roots` are removed, while tables, memories
+/// and tags are always kept, along with their exports and the active segments
+/// initializing them.
+///
+/// Names of removed functions are dropped from the name section. Relocation, linking
+/// and dynamic linking sections are removed, parsed or not, since they would no longer
+/// match the module.
+pub fn eliminate_dead_code(module: &mut Module, roots: &[&str]) -> Result<(), DeadCodeError> {
+	let defined_functions = module.function_section().map(|s| s.entries().len()).unwrap_or(0);
+	if module.code_section().map(|s| s.bodies().len()).unwrap_or(0) != defined_functions {
+		return Err(DeadCodeError::InconsistentFunctionCount);
+	}
+	let imported_functions = module.import_count(crate::elements::ImportCountType::Function);
+	let imported_globals = module.import_count(crate::elements::ImportCountType::Global);
+	let mut liveness = Liveness {
+		types: vec![false; module.type_section().map(|s| s.types().len()).unwrap_or(0)],
+		functions: vec![false; imported_functions + defined_functions],
+		globals: vec![false; imported_globals + module.global_section().map(|s| s.entries().len()).unwrap_or(0)],
+		elements: vec![false; module.elements_section().map(|s| s.entries().len()).unwrap_or(0)],
+		data: vec![false; module.data_section().map(|s| s.entries().len()).unwrap_or(0)],
+		pending: Vec::new(),
+	};
+
+	let exports = module.export_section().map(|s| s.entries()).unwrap_or(&[]);
+	for &root in roots {
+		let export = exports.iter().find(|export| export.field() == root)
+			.ok_or_else(|| DeadCodeError::MissingExport(root.into()))?;
+		match *export.internal() {
+			Internal::Function(index) => liveness.mark(IndexSpace::Function, index)?,
+			Internal::Global(index) => liveness.mark(IndexSpace::Global, index)?,
+			_ => {},
+		}
+	}
+	if let Some(start) = module.start_section() {
+		liveness.mark(IndexSpace::Function, start)?;
+	}
+	for (index, segment) in module.elements_section().map(|s| s.entries()).unwrap_or(&[]).iter().enumerate() {
+		if segment.offset().is_some() {
+			liveness.mark(IndexSpace::Element, index as u32)?;
+		}
+	}
+	for (index, segment) in module.data_section().map(|s| s.entries()).unwrap_or(&[]).iter().enumerate() {
+		if segment.offset().is_some() {
+			liveness.mark(IndexSpace::Data, index as u32)?;
+		}
+	}
+	#[cfg(feature="exceptions")]
+	{
+		for entry in module.import_section().map(|s| s.entries()).unwrap_or(&[]) {
+			if let External::Tag(ref tag) = *entry.external() {
+				liveness.mark(IndexSpace::Type, tag.type_ref())?;
+			}
+		}
+		for tag in module.tag_section().map(|s| s.entries()).unwrap_or(&[]) {
+			liveness.mark(IndexSpace::Type, tag.type_ref())?;
+		}
+	}
+
+	let graph = call_graph(module);
+	while let Some((space, index)) = liveness.pending.pop() {
+		match space {
+			IndexSpace::Function => {
+				let type_ref = module.function_type_ref(index).ok_or(DeadCodeError::InvalidIndex)?;
+				liveness.mark(IndexSpace::Type, type_ref)?;
+				if let Some(defined) = (index as usize).checked_sub(imported_functions) {
+					let body = module.code_section().and_then(|s| s.bodies().get(defined))
+						.ok_or(DeadCodeError::InvalidIndex)?;
+					liveness.mark_instructions(body.code().elements())?;
+				}
+				// Functions reached by `call_indirect` are the members of live segments.
+				for edge in graph.callees(index).iter().filter(|edge| !edge.indirect) {
+					liveness.mark(IndexSpace::Function, edge.callee)?;
+				}
+			},
+			IndexSpace::Global => {
+				if let Some(defined) = (index as usize).checked_sub(imported_globals) {
+					let entry = module.global_section().and_then(|s| s.entries().get(defined))
+						.ok_or(DeadCodeError::InvalidIndex)?;
+					liveness.mark_instructions(entry.init_expr().code())?;
+				}
+			},
+			IndexSpace::Element => {
+				let segment = module.elements_section().and_then(|s| s.entries().get(index as usize))
+					.ok_or(DeadCodeError::InvalidIndex)?;
+				if let Some(ref offset) = *segment.offset() {
+					liveness.mark_instructions(offset.code())?;
+				}
+				for &member in segment.members() {
+					liveness.mark(IndexSpace::Function, member)?;
+				}
+			},
+			IndexSpace::Data => {
+				let segment = module.data_section().and_then(|s| s.entries().get(index as usize))
+					.ok_or(DeadCodeError::InvalidIndex)?;
+				if let Some(ref offset) = *segment.offset() {
+					liveness.mark_instructions(offset.code())?;
+				}
+			},
+			_ => {},
+		}
+	}
+
+	let (live_imported_functions, live_defined_functions) = liveness.functions.split_at(imported_functions);
+	let (live_imported_globals, live_defined_globals) = liveness.globals.split_at(imported_globals);
+	let data_count = liveness.data.iter().filter(|&&live| live).count() as u32;
+	let sections = module.sections_mut();
+	for section in sections.iter_mut() {
+		match *section {
+			Section::Type(ref mut type_section) => retain_by_index(type_section.types_mut(), &liveness.types),
+			Section::Import(ref mut import_section) => {
+				let (mut functions, mut globals) = (0, 0);
+				import_section.entries_mut().retain(|entry| match *entry.external() {
+					External::Function(_) => {
+						functions += 1;
+						live_imported_functions[functions - 1]
+					},
+					External::Global(_) => {
+						globals += 1;
+						live_imported_globals[globals - 1]
+					},
+					_ => true,
+				});
+			},
+			Section::Function(ref mut function_section) =>
+				retain_by_index(function_section.entries_mut(), live_defined_functions),
+			Section::Code(ref mut code_section) =>
+				retain_by_index(code_section.bodies_mut(), live_defined_functions),
+			Section::Global(ref mut global_section) =>
+				retain_by_index(global_section.entries_mut(), live_defined_globals),
+			Section::Element(ref mut element_section) =>
+				retain_by_index(element_section.entries_mut(), &liveness.elements),
+			Section::Data(ref mut data_section) =>
+				retain_by_index(data_section.entries_mut(), &liveness.data),
+			Section::DataCount(ref mut count) => *count = data_count,
+			Section::Export(ref mut export_section) =>
+				export_section.entries_mut().retain(|export| match *export.internal() {
+					Internal::Function(_) | Internal::Global(_) => roots.contains(&export.field()),
+					_ => true,
+				}),
+			_ => {},
+		}
+	}
+	sections.retain(|section| match *section {
+		Section::Type(ref s) => !s.types().is_empty(),
+		Section::Import(ref s) => !s.entries().is_empty(),
+		Section::Function(ref s) => !s.entries().is_empty(),
+		Section::Code(ref s) => !s.bodies().is_empty(),
+		Section::Global(ref s) => !s.entries().is_empty(),
+		Section::Element(ref s) => !s.entries().is_empty(),
+		Section::Data(ref s) => !s.entries().is_empty(),
+		Section::Export(ref s) => !s.entries().is_empty(),
+		ref section => !is_linking_section(section),
+	});
+
+	let relocations = RelocationMap {
+		types: compacting_map(&liveness.types),
+		functions: compacting_map(&liveness.functions),
+		globals: compacting_map(&liveness.globals),
+		elements: compacting_map(&liveness.elements),
+		data: compacting_map(&liveness.data),
+		..Default::default()
+	};
+	relocations.apply(module)?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{eliminate_dead_code, DeadCodeError};
+	use crate::builder;
+	use crate::elements::{Module, Instruction, Instructions, ValueType, Internal};
+
+	fn module() -> Module {
+		builder::module()
+			.import().module("env").field("unused").external().func(1).build()
+			.import().module("env").field("log").external().func(0).build()
+			.function()
+				.signature().with_param(ValueType::I32).build()
+				.body().build()
+				.build()
+			.function()
+				.signature().with_result(ValueType::I64).build()
+				.body()
+					.with_instructions(Instructions::new(vec![Instruction::I64Const(0), Instruction::End]))
+					.build()
+				.build()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						Instruction::I32Const(7),
+						Instruction::Call(1),
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.function()
+				.signature().with_params(vec![ValueType::F32]).build()
+				.body().build()
+				.build()
+			.export().field("main").internal().func(4).build()
+			.export().field("helper").internal().func(3).build()
+			.build()
+	}
+
+	#[test]
+	fn removes_unreachable() {
+		let mut module = module();
+		eliminate_dead_code(&mut module, &["main"]).expect("dead code to be eliminated");
+
+		// Only `log` and `main` remain, along with their types.
+		assert_eq!(module.import_section().expect("import section").entries().len(), 1);
+		assert_eq!(module.function_section().expect("function section").entries().len(), 1);
+		assert_eq!(module.type_section().expect("type section").types().len(), 2);
+		assert_eq!(
+			module.code_section().expect("code section").bodies()[0].code().elements(),
+			&[Instruction::I32Const(7), Instruction::Call(0), Instruction::End][..],
+		);
+		let exports = module.export_section().expect("export section").entries();
+		assert_eq!(exports.len(), 1);
+		assert_eq!(*exports[0].internal(), Internal::Function(1));
+		assert!(module.to_bytes().is_ok());
+	}
+
+	#[test]
+	fn keeps_table_members() {
+		let mut module = builder::module()
+			.function()
+				.signature().build()
+				.body().build()
+				.build()
+			.function()
+				.signature().build()
+				.body().build()
+				.build()
+			.table().with_min(1).with_element(0, vec![1]).build()
+			.memory().with_min(1).build()
+			.export().field("table").internal().table(0).build()
+			.export().field("memory").internal().memory(0).build()
+			.build();
+		eliminate_dead_code(&mut module, &[]).expect("dead code to be eliminated");
+
+		assert_eq!(module.function_section().expect("function section").entries().len(), 1);
+		assert_eq!(module.elements_section().expect("element section").entries()[0].members(), &[0][..]);
+		let exports = module.export_section().expect("export section").entries();
+		assert_eq!(exports.iter().map(|export| *export.internal()).collect::<Vec<_>>(), [Internal::Table(0), Internal::Memory(0)]);
+	}
+
+	#[test]
+	fn drops_linking_customs() {
+		let mut module = module();
+		module.set_custom_section("linking", vec![2]);
+		module.set_custom_section("reloc.CODE", vec![0]);
+		module.set_custom_section("dylink.0", vec![0]);
+		module.set_custom_section("producers", vec![0]);
+		eliminate_dead_code(&mut module, &["main"]).expect("dead code to be eliminated");
+
+		assert_eq!(module.custom_sections().map(|custom| custom.name()).collect::<Vec<_>>(), ["producers"]);
+	}
+
+	#[test]
+	fn inconsistent_function_count() {
+		let mut module = module();
+		module.code_section_mut().expect("code section").bodies_mut().pop();
+		assert_eq!(
+			eliminate_dead_code(&mut module, &["main"]),
+			Err(DeadCodeError::InconsistentFunctionCount),
+		);
+	}
+
+	#[test]
+	fn missing_export() {
+		assert_eq!(
+			eliminate_dead_code(&mut module(), &["absent"]),
+			Err(DeadCodeError::MissingExport("absent".into())),
+		);
+	}
+}
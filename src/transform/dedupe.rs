@@ -0,0 +1,93 @@
+use alloc::vec::Vec;
+use crate::elements::{Module, Type};
+use super::merge::find_or_push_type;
+use super::remap::{RelocationMap, RemapError, SpaceMap};
+
+/// Merge identical function types of `module` into one type section entry each.
+///
+/// Every type keeps the index of its first occurrence relative to the other kept
+/// types, and all references to types are rewritten, `call_indirect` included.
+/// Returns the number of removed types. A reference to a type which does not exist
+/// is an error, and leaves the module unchanged.
+pub fn dedupe_types(module: &mut Module) -> Result<usize, RemapError> {
+	let old_types = match module.type_section() {
+		Some(type_section) => type_section.types(),
+		None => return Ok(0),
+	};
+
+	let mut types = Vec::new();
+	let mut mapping = Vec::with_capacity(old_types.len());
+	for Type::Function(func_type) in old_types {
+		mapping.push(Some(find_or_push_type(&mut types, func_type)));
+	}
+	let removed = old_types.len() - types.len();
+	if removed == 0 {
+		return Ok(0);
+	}
+
+	let relocations = RelocationMap {
+		types: SpaceMap::new(mapping),
+		..Default::default()
+	};
+	let mut rewritten = module.clone();
+	relocations.apply(&mut rewritten)?;
+	*rewritten.type_section_mut().expect("type section is checked above; qed").types_mut() = types;
+	*module = rewritten;
+	Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::dedupe_types;
+	use crate::builder;
+	use crate::elements::{Func, FunctionType, Instruction::*, Instructions, Type, Section, ValueType};
+
+	#[test]
+	fn merges_duplicates() {
+		let mut module = builder::module()
+			.function()
+				.signature().with_param(ValueType::I32).build()
+				.body()
+					.with_instructions(Instructions::new(vec![I32Const(0), CallIndirect(2, 0), End]))
+					.build()
+				.build()
+			.build();
+		// The builder reuses types, so add the duplicates by hand.
+		let func_type = FunctionType::new(vec![ValueType::I32], vec![]);
+		let types = module.type_section_mut().expect("type section").types_mut();
+		types.push(Type::Function(FunctionType::new(vec![], vec![ValueType::I64])));
+		types.push(Type::Function(func_type.clone()));
+		for section in module.sections_mut() {
+			if let Section::Function(ref mut functions) = *section {
+				functions.entries_mut().push(Func::new(2));
+			}
+		}
+
+		assert_eq!(dedupe_types(&mut module), Ok(1));
+		assert_eq!(module.type_section().expect("type section").types().len(), 2);
+		let type_refs: Vec<u32> = module.function_section().expect("function section").entries()
+			.iter().map(|func| func.type_ref()).collect();
+		assert_eq!(type_refs, vec![0, 0]);
+		assert_eq!(
+			module.code_section().expect("code section").bodies()[0].code().elements()[1],
+			CallIndirect(0, 0),
+		);
+	}
+
+	#[test]
+	fn invalid_reference() {
+		let mut module = builder::module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![I32Const(0), CallIndirect(7, 0), End]))
+					.build()
+				.build()
+			.build();
+		module.type_section_mut().expect("type section").types_mut().push(Type::Function(FunctionType::default()));
+
+		let original = module.clone();
+		assert!(dedupe_types(&mut module).is_err());
+		assert_eq!(module, original);
+	}
+}
@@ -0,0 +1,835 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use crate::elements::{
+	Module, Section, Type, FunctionType, External, Internal, ImportEntry, ExportEntry,
+	TypeSection, ImportSection, FunctionSection, TableSection, MemorySection, GlobalSection,
+	ExportSection, ElementSection, CodeSection, DataSection, NameSection, ProducersSection,
+	TargetFeaturesSection, CustomSection, Func, FuncBody, InitExpr, Instruction, Instructions, Deserialize,
+	RuntimeValue, deserialize_buffer,
+};
+use super::remap::{RelocationMap, RemapError, SpaceMap};
+use super::visit::IndexSpace;
+
+/// Error of merging two modules.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeError {
+	/// Import is resolved by an export of the other module of a different kind or type.
+	IncompatibleImport {
+		/// Module name of the import.
+		module: String,
+		/// Field name of the import.
+		field: String,
+	},
+	/// Imports of the two modules resolve to each other without reaching a definition.
+	ImportCycle {
+		/// Module name of the import.
+		module: String,
+		/// Field name of the import.
+		field: String,
+	},
+	/// Both modules export an item under this name.
+	DuplicateExport(String),
+	/// The merged module would have more than one memory.
+	MultipleMemories,
+	/// The merged module would have more than one table.
+	MultipleTables,
+	/// Active data or element segments of both modules may write the same part of the
+	/// merged memory or table: their ranges overlap, or one of them has an offset other
+	/// than a constant.
+	OverlappingSegments,
+	/// Module refers to an item which does not exist.
+	InvalidIndex,
+	/// Module uses a proposal which merging does not support.
+	Unsupported(&'static str),
+	/// Producers or target features custom section of this name fails to parse.
+	MalformedSection(String),
+	/// One module uses this target feature and the other disallows it, or one module
+	/// requires it and the other does not list it.
+	ConflictingFeature(String),
+}
+
+impl fmt::Display for MergeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			MergeError::IncompatibleImport { ref module, ref field } =>
+				write!(f, "Import {}.{} is resolved by an incompatible export", module, field),
+			MergeError::ImportCycle { ref module, ref field } =>
+				write!(f, "Import {}.{} never resolves to a definition", module, field),
+			MergeError::DuplicateExport(ref name) => write!(f, "Both modules export {}", name),
+			MergeError::MultipleMemories => write!(f, "Merged module would have more than one memory"),
+			MergeError::MultipleTables => write!(f, "Merged module would have more than one table"),
+			MergeError::OverlappingSegments => write!(f, "Segments of both modules may overlap"),
+			MergeError::InvalidIndex => write!(f, "Module refers to an item which does not exist"),
+			MergeError::Unsupported(feature) => write!(f, "Merging modules using {} is not supported", feature),
+			MergeError::MalformedSection(ref name) => write!(f, "Custom section {} fails to parse", name),
+			MergeError::ConflictingFeature(ref name) => write!(f, "Modules disagree on target feature {}", name),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for MergeError {}
+
+impl From<RemapError> for MergeError {
+	fn from(_: RemapError) -> Self {
+		MergeError::InvalidIndex
+	}
+}
+
+const SPACES: [IndexSpace; 4] = [IndexSpace::Function, IndexSpace::Global, IndexSpace::Table, IndexSpace::Memory];
+
+fn space_slot(space: IndexSpace) -> usize {
+	match space {
+		IndexSpace::Function => 0,
+		IndexSpace::Global => 1,
+		IndexSpace::Table => 2,
+		IndexSpace::Memory => 3,
+		_ => unreachable!("only importable spaces are merged through imports"),
+	}
+}
+
+fn import_space(external: &External) -> Result<IndexSpace, MergeError> {
+	match *external {
+		External::Function(_) => Ok(IndexSpace::Function),
+		External::Global(_) => Ok(IndexSpace::Global),
+		External::Table(_) => Ok(IndexSpace::Table),
+		External::Memory(_) => Ok(IndexSpace::Memory),
+		#[cfg(feature="exceptions")]
+		External::Tag(_) => Err(MergeError::Unsupported("exceptions")),
+	}
+}
+
+fn export_target(internal: &Internal) -> Result<(IndexSpace, u32), MergeError> {
+	match *internal {
+		Internal::Function(index) => Ok((IndexSpace::Function, index)),
+		Internal::Global(index) => Ok((IndexSpace::Global, index)),
+		Internal::Table(index) => Ok((IndexSpace::Table, index)),
+		Internal::Memory(index) => Ok((IndexSpace::Memory, index)),
+		#[cfg(feature="exceptions")]
+		Internal::Tag(_) => Err(MergeError::Unsupported("exceptions")),
+	}
+}
+
+fn defined_count(module: &Module, space: IndexSpace) -> usize {
+	match space {
+		IndexSpace::Function => module.function_section().map(|s| s.entries().len()).unwrap_or(0),
+		IndexSpace::Global => module.global_section().map(|s| s.entries().len()).unwrap_or(0),
+		IndexSpace::Table => module.table_section().map(|s| s.entries().len()).unwrap_or(0),
+		IndexSpace::Memory => module.memory_section().map(|s| s.entries().len()).unwrap_or(0),
+		_ => 0,
+	}
+}
+
+/// Index layout of the merged module, as seen from both input modules.
+struct Layout<'a> {
+	modules: [&'a Module; 2],
+	/// Import entries of each module.
+	imports: [&'a [ImportEntry]; 2],
+	/// Import entry indices of each module, by index space slot.
+	imported: [[Vec<usize>; 4]; 2],
+	/// Index in the other module of the item resolving each import, if any.
+	resolution: [Vec<Option<u32>>; 2],
+	/// Index in the merged module of each import left unresolved; only valid for those.
+	import_position: [Vec<u32>; 2],
+	/// First index of the items defined by each module in the merged module, by slot.
+	defined_base: [[u32; 4]; 2],
+}
+
+impl<'a> Layout<'a> {
+	fn new(modules: [&'a Module; 2], names: [&str; 2], type_maps: &[Vec<Option<u32>>; 2]) -> Result<(Self, Vec<ImportEntry>), MergeError> {
+		let imports = [
+			modules[0].import_section().map(|s| s.entries()).unwrap_or(&[]),
+			modules[1].import_section().map(|s| s.entries()).unwrap_or(&[]),
+		];
+
+		let mut imported: [[Vec<usize>; 4]; 2] = Default::default();
+		for m in 0..2 {
+			for (position, entry) in imports[m].iter().enumerate() {
+				imported[m][space_slot(import_space(entry.external())?)].push(position);
+			}
+		}
+
+		let mut resolution: [Vec<Option<u32>>; 2] = Default::default();
+		for m in 0..2 {
+			let other = modules[1 - m];
+			for entry in imports[m] {
+				let export = other
+					.export_section()
+					.filter(|_| entry.module() == names[1 - m])
+					.and_then(|s| s.entries().iter().find(|export| export.field() == entry.field()));
+				let export = match export {
+					Some(export) => export,
+					None => {
+						resolution[m].push(None);
+						continue;
+					},
+				};
+
+				let incompatible = || MergeError::IncompatibleImport {
+					module: entry.module().into(),
+					field: entry.field().into(),
+				};
+				let (space, index) = export_target(export.internal())?;
+				if space != import_space(entry.external())? {
+					return Err(incompatible());
+				}
+				match *entry.external() {
+					External::Function(type_ref) => {
+						let expected = modules[m].signature(type_ref).ok_or(MergeError::InvalidIndex)?;
+						let actual = other.function_type(index).ok_or(MergeError::InvalidIndex)?;
+						if expected != actual {
+							return Err(incompatible());
+						}
+					},
+					External::Global(expected) => {
+						let actual = other.global_type(index).ok_or(MergeError::InvalidIndex)?;
+						if expected.content_type() != actual.content_type() || expected.is_mutable() != actual.is_mutable() {
+							return Err(incompatible());
+						}
+					},
+					_ => {},
+				}
+				resolution[m].push(Some(index));
+			}
+		}
+
+		// Unresolved imports of both modules are kept, identical ones only once.
+		let mut merged_imports: Vec<ImportEntry> = Vec::new();
+		let mut import_position: [Vec<u32>; 2] = Default::default();
+		let mut import_counts = [0u32; 4];
+		for m in 0..2 {
+			for (position, entry) in imports[m].iter().enumerate() {
+				if resolution[m][position].is_some() {
+					import_position[m].push(0);
+					continue;
+				}
+
+				let mut entry = entry.clone();
+				if let External::Function(ref mut type_ref) = *entry.external_mut() {
+					*type_ref = type_maps[m]
+						.get(*type_ref as usize)
+						.cloned()
+						.unwrap_or(None)
+						.ok_or(MergeError::InvalidIndex)?;
+				}
+				let space = import_space(entry.external())?;
+
+				let existing = merged_imports
+					.iter()
+					.filter(|merged| import_space(merged.external()).ok() == Some(space))
+					.position(|merged| *merged == entry);
+				match existing {
+					Some(index) => import_position[m].push(index as u32),
+					None => {
+						import_position[m].push(import_counts[space_slot(space)]);
+						import_counts[space_slot(space)] += 1;
+						merged_imports.push(entry);
+					},
+				}
+			}
+		}
+
+		let mut defined_base = [[0u32; 4]; 2];
+		for space in SPACES.iter().cloned() {
+			let slot = space_slot(space);
+			defined_base[0][slot] = import_counts[slot];
+			defined_base[1][slot] = import_counts[slot] + defined_count(modules[0], space) as u32;
+		}
+
+		let memories = import_counts[space_slot(IndexSpace::Memory)] as usize
+			+ defined_count(modules[0], IndexSpace::Memory)
+			+ defined_count(modules[1], IndexSpace::Memory);
+		if memories > 1 {
+			return Err(MergeError::MultipleMemories);
+		}
+		let tables = import_counts[space_slot(IndexSpace::Table)] as usize
+			+ defined_count(modules[0], IndexSpace::Table)
+			+ defined_count(modules[1], IndexSpace::Table);
+		if tables > 1 {
+			return Err(MergeError::MultipleTables);
+		}
+
+		let layout = Layout { modules, imports, imported, resolution, import_position, defined_base };
+		Ok((layout, merged_imports))
+	}
+
+	/// Index in the merged module of item `index` of module `m`.
+	fn resolve(&self, m: usize, space: IndexSpace, index: u32) -> Result<u32, MergeError> {
+		let (mut m, mut index) = (m, index);
+		let slot = space_slot(space);
+		// Every step crosses to an import of the other module, so a chain longer than
+		// all imports together can only be a cycle.
+		for _ in 0..=(self.imports[0].len() + self.imports[1].len()) {
+			let imported = &self.imported[m][slot];
+			if (index as usize) >= imported.len() {
+				let defined = index as usize - imported.len();
+				if defined >= defined_count(self.modules[m], space) {
+					return Err(MergeError::InvalidIndex);
+				}
+				return Ok(self.defined_base[m][slot] + defined as u32);
+			}
+
+			let position = imported[index as usize];
+			match self.resolution[m][position] {
+				None => return Ok(self.import_position[m][position]),
+				Some(target) => {
+					m = 1 - m;
+					index = target;
+				},
+			}
+		}
+
+		let entry = &self.imports[m][self.imported[m][slot][index as usize]];
+		Err(MergeError::ImportCycle { module: entry.module().into(), field: entry.field().into() })
+	}
+
+	fn space_map(&self, m: usize, space: IndexSpace) -> Result<SpaceMap, MergeError> {
+		let count = self.imported[m][space_slot(space)].len() + defined_count(self.modules[m], space);
+		let map = (0..count as u32)
+			.map(|index| self.resolve(m, space, index).map(Some))
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(SpaceMap::new(map))
+	}
+}
+
+pub(super) fn find_or_push_type(types: &mut Vec<Type>, func_type: &FunctionType) -> u32 {
+	let existing = types.iter().position(|Type::Function(existing)| existing == func_type);
+	match existing {
+		Some(index) => index as u32,
+		None => {
+			types.push(Type::Function(func_type.clone()));
+			types.len() as u32 - 1
+		},
+	}
+}
+
+fn check_supported(_module: &Module) -> Result<(), MergeError> {
+	#[cfg(feature="exceptions")]
+	{
+		if _module.tag_section().is_some() {
+			return Err(MergeError::Unsupported("exceptions"));
+		}
+	}
+	Ok(())
+}
+
+/// Statically link two modules into one.
+///
+/// Every import of either module from the other one, as named by `first_name` and
+/// `second_name`, is resolved by the export of the other module of the same field name;
+/// the kinds and, for functions and globals, the types must match. The remaining
+/// imports of both modules are kept, identical ones only once.
+///
+/// The merged module holds the imports, then the items defined by `first`, then those
+/// defined by `second`, in every index space, with all references rewritten. Function
+/// types are deduplicated. Exports of both modules are kept, including the ones used
+/// for resolution. If both modules have a start function, a new start function calling
+/// both in order is added.
+///
+/// At most one memory and one table may remain, so modules which both define or
+/// import a memory can only be merged if one of them imports it from the other; merging
+/// fails with `MergeError::MultipleMemories` or `MergeError::MultipleTables` otherwise.
+/// Data and element segments are concatenated as they are, since the module code
+/// refers to the addresses they are written at. Merging fails with
+/// `MergeError::OverlappingSegments` if active segments of both modules may write the
+/// same bytes of the memory or elements of the table.
+///
+/// Name sections are merged, the names of the second module following its indices.
+/// Producers and target features sections, parsed or
+/// not, are merged into one each, failing if the modules disagree on a feature. Other
+/// custom sections are copied, except for name sections which fail to parse and
+/// relocation, linking and dynamic linking sections, which would no longer match the
+/// module.
+pub fn merge(first: Module, first_name: &str, second: Module, second_name: &str) -> Result<Module, MergeError> {
+	check_supported(&first)?;
+	check_supported(&second)?;
+	let first = first.parse_names().unwrap_or_else(|(_, module)| module);
+	let second = second.parse_names().unwrap_or_else(|(_, module)| module);
+
+	let mut types = Vec::new();
+	let mut type_maps: [Vec<Option<u32>>; 2] = Default::default();
+	for (m, module) in [&first, &second].iter().enumerate() {
+		for Type::Function(func_type) in module.type_section().map(|s| s.types()).unwrap_or(&[]) {
+			type_maps[m].push(Some(find_or_push_type(&mut types, func_type)));
+		}
+	}
+
+	let element_counts = [
+		first.elements_section().map(|s| s.entries().len()).unwrap_or(0),
+		second.elements_section().map(|s| s.entries().len()).unwrap_or(0),
+	];
+	let data_counts = [
+		first.data_section().map(|s| s.entries().len()).unwrap_or(0),
+		second.data_section().map(|s| s.entries().len()).unwrap_or(0),
+	];
+
+	let (layout, merged_imports) = Layout::new([&first, &second], [first_name, second_name], &type_maps)?;
+	let mut relocations: [RelocationMap; 2] = Default::default();
+	for m in 0..2 {
+		relocations[m].types = SpaceMap::new(type_maps[m].clone());
+		relocations[m].functions = layout.space_map(m, IndexSpace::Function)?;
+		relocations[m].globals = layout.space_map(m, IndexSpace::Global)?;
+		relocations[m].tables = layout.space_map(m, IndexSpace::Table)?;
+		relocations[m].memories = layout.space_map(m, IndexSpace::Memory)?;
+	}
+	// Segments of the second module follow the ones of the first.
+	relocations[1].elements = SpaceMap::new((0..element_counts[1]).map(|i| Some((element_counts[0] + i) as u32)).collect());
+	relocations[1].data = SpaceMap::new((0..data_counts[1]).map(|i| Some((data_counts[0] + i) as u32)).collect());
+	drop(layout);
+
+	let mut modules = [first, second];
+	for m in 0..2 {
+		relocations[m].apply(&mut modules[m])?;
+	}
+
+	let mut functions = Vec::new();
+	let mut tables = Vec::new();
+	let mut memories = Vec::new();
+	let mut globals = Vec::new();
+	let mut exports: Vec<ExportEntry> = Vec::new();
+	let mut starts = Vec::new();
+	let mut elements = Vec::new();
+	let mut has_data_count = false;
+	let mut bodies = Vec::new();
+	let mut data = Vec::new();
+	let mut names: Option<NameSection> = None;
+	let mut producers: Option<ProducersSection> = None;
+	let mut target_features: Option<TargetFeaturesSection> = None;
+	let mut customs = Vec::new();
+	// Number of element and data segments of the first module.
+	let mut first_segments = (0, 0);
+
+	for (m, module) in modules.iter_mut().enumerate() {
+		if m == 1 {
+			first_segments = (elements.len(), data.len());
+		}
+		for section in module.sections_mut().drain(..) {
+			match section {
+				Section::Function(s) => functions.extend(s.entries().iter().cloned()),
+				Section::Table(s) => tables.extend(s.entries().iter().cloned()),
+				Section::Memory(s) => memories.extend(s.entries().iter().cloned()),
+				Section::Global(s) => globals.extend(s.entries().iter().cloned()),
+				Section::Export(s) => {
+					for entry in s.entries() {
+						if exports.iter().any(|existing| existing.field() == entry.field()) {
+							return Err(MergeError::DuplicateExport(entry.field().into()));
+						}
+						exports.push(entry.clone());
+					}
+				},
+				Section::Start(index) => starts.push(index),
+				Section::Element(s) => elements.extend(s.entries().iter().cloned()),
+				Section::DataCount(_) => has_data_count = true,
+				Section::Code(s) => bodies.extend(s.bodies().iter().cloned()),
+				Section::Data(s) => data.extend(s.entries().iter().cloned()),
+				Section::Name(name_section) => names = Some(match names.take() {
+					None => name_section,
+					Some(existing) => merge_names(existing, name_section),
+				}),
+				Section::Producers(producers_section) => merge_producers(&mut producers, producers_section),
+				Section::TargetFeatures(s) => merge_target_features(&mut target_features, s)?,
+				Section::Custom(custom) => match custom.name() {
+					"producers" => merge_producers(&mut producers, parse_custom(&custom)?),
+					"target_features" => merge_target_features(&mut target_features, parse_custom(&custom)?)?,
+					name if is_stale_custom(name) => {},
+					_ => customs.push(Section::Custom(custom)),
+				},
+				Section::Unparsed { id, payload } => customs.push(Section::Unparsed { id, payload }),
+				_ => {},
+			}
+		}
+	}
+
+	check_disjoint(elements.iter().map(|segment| (segment.index(), segment.offset().as_ref(), segment.members().len())), first_segments.0)?;
+	check_disjoint(data.iter().map(|segment| (segment.index(), segment.offset().as_ref(), segment.value().len())), first_segments.1)?;
+
+	let start = match starts.len() {
+		0 => None,
+		1 => Some(starts[0]),
+		_ => {
+			let type_ref = find_or_push_type(&mut types, &FunctionType::default());
+			let index = merged_imports.iter().filter(|entry| matches!(*entry.external(), External::Function(_))).count() + functions.len();
+			let mut code: Vec<Instruction> = starts.iter().map(|&start| Instruction::Call(start)).collect();
+			code.push(Instruction::End);
+			functions.push(Func::new(type_ref));
+			bodies.push(FuncBody::new(Vec::new(), Instructions::new(code)));
+			Some(index as u32)
+		},
+	};
+
+	let mut sections = Vec::new();
+	if !types.is_empty() {
+		sections.push(Section::Type(TypeSection::with_types(types)));
+	}
+	if !merged_imports.is_empty() {
+		sections.push(Section::Import(ImportSection::with_entries(merged_imports)));
+	}
+	if !functions.is_empty() {
+		sections.push(Section::Function(FunctionSection::with_entries(functions)));
+	}
+	if !tables.is_empty() {
+		sections.push(Section::Table(TableSection::with_entries(tables)));
+	}
+	if !memories.is_empty() {
+		sections.push(Section::Memory(MemorySection::with_entries(memories)));
+	}
+	if !globals.is_empty() {
+		sections.push(Section::Global(GlobalSection::with_entries(globals)));
+	}
+	if !exports.is_empty() {
+		sections.push(Section::Export(ExportSection::with_entries(exports)));
+	}
+	if let Some(start) = start {
+		sections.push(Section::Start(start));
+	}
+	if !elements.is_empty() {
+		sections.push(Section::Element(ElementSection::with_entries(elements)));
+	}
+	if has_data_count {
+		sections.push(Section::DataCount(data.len() as u32));
+	}
+	if !bodies.is_empty() {
+		sections.push(Section::Code(CodeSection::with_bodies(bodies)));
+	}
+	if !data.is_empty() {
+		sections.push(Section::Data(DataSection::with_entries(data)));
+	}
+	if let Some(names) = names {
+		sections.push(Section::Name(names));
+	}
+	if let Some(producers) = producers {
+		sections.push(Section::Producers(producers));
+	}
+	if let Some(target_features) = target_features {
+		sections.push(Section::TargetFeatures(target_features));
+	}
+	sections.extend(customs);
+
+	Ok(Module::new(sections))
+}
+
+/// Check that no active segment of the second module, starting at `second`, writes
+/// what one of the first module does.
+fn check_disjoint<'s, I>(segments: I, second: usize) -> Result<(), MergeError>
+	where I: Iterator<Item=(u32, Option<&'s InitExpr>, usize)>
+{
+	let mut ranges = Vec::new();
+	for (position, (index, offset, len)) in segments.enumerate() {
+		let offset = match offset {
+			Some(offset) => offset,
+			None => continue,
+		};
+		let start = match offset.evaluate(|_| None) {
+			Ok(RuntimeValue::I32(start)) => start as u32 as u64,
+			Ok(RuntimeValue::I64(start)) => start as u64,
+			_ => return Err(MergeError::OverlappingSegments),
+		};
+		if len != 0 {
+			ranges.push((index, start, start + len as u64, position >= second));
+		}
+	}
+	ranges.sort_unstable();
+
+	// End of the ranges of each module seen so far in the current memory or table.
+	let mut ends = [0u64; 2];
+	let mut current = None;
+	for (index, start, end, of_second) in ranges {
+		if current != Some(index) {
+			current = Some(index);
+			ends = [0, 0];
+		}
+		let m = of_second as usize;
+		if start < ends[1 - m] {
+			return Err(MergeError::OverlappingSegments);
+		}
+		ends[m] = ends[m].max(end);
+	}
+	Ok(())
+}
+
+/// Whether the custom section named `name` describes the module it is in, so that it
+/// would no longer match the merged one.
+fn is_stale_custom(name: &str) -> bool {
+	name == "name" || name == "linking" || name.starts_with("reloc.") || name == "dylink" || name == "dylink.0"
+}
+
+fn parse_custom<T: Deserialize<Error=crate::elements::Error>>(custom: &CustomSection) -> Result<T, MergeError> {
+	deserialize_buffer(custom.payload()).map_err(|_| MergeError::MalformedSection(custom.name().into()))
+}
+
+fn merge_producers(merged: &mut Option<ProducersSection>, producers: ProducersSection) {
+	match *merged {
+		None => *merged = Some(producers),
+		Some(ref mut existing) => existing.merge(producers),
+	}
+}
+
+fn merge_target_features(merged: &mut Option<TargetFeaturesSection>, features: TargetFeaturesSection) -> Result<(), MergeError> {
+	match *merged {
+		None => *merged = Some(features),
+		Some(ref mut existing) => existing.merge(features).map_err(MergeError::ConflictingFeature)?,
+	}
+	Ok(())
+}
+
+/// Names of the first module are kept for the imports of the second resolved to them.
+fn merge_names(mut first: NameSection, second: NameSection) -> NameSection {
+	if first.module().is_none() {
+		*first.module_mut() = second.module().cloned();
+	}
+	if let Some(functions) = second.functions() {
+		let merged = first.functions_mut().get_or_insert_with(Default::default);
+		for (index, name) in functions.names().iter() {
+			if !merged.names().contains_key(index) {
+				merged.names_mut().insert(index, name.clone());
+			}
+		}
+	}
+	if let Some(locals) = second.locals() {
+		let merged = first.locals_mut().get_or_insert_with(Default::default);
+		for (index, names) in locals.local_names().iter() {
+			if !merged.local_names().contains_key(index) {
+				merged.local_names_mut().insert(index, names.clone());
+			}
+		}
+	}
+	first
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{merge, MergeError};
+	use crate::builder;
+	use crate::elements::{
+		Module, Instruction, Instructions, ValueType, Internal, External, deserialize_buffer, serialize,
+		FeaturePrefix, FunctionNameSubsection, NameSection, ProducersSection, Section, TargetFeature,
+		TargetFeaturesSection,
+	};
+
+	fn library() -> Module {
+		builder::module()
+			.function()
+				.signature().with_params(vec![ValueType::I32, ValueType::I32]).with_result(ValueType::I32).build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						Instruction::GetLocal(0),
+						Instruction::GetLocal(1),
+						Instruction::I32Add,
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.memory().with_min(1).build()
+			.export().field("add").internal().func(0).build()
+			.export().field("memory").internal().memory(0).build()
+			.build()
+	}
+
+	fn application(log_type: ValueType) -> Module {
+		builder::module()
+			.import().module("env").field("log").external().func(0).build()
+			.import().module("env").field("add").external().func(1).build()
+			.import().module("env").field("memory").external().memory(1, None).build()
+			.function()
+				.signature().with_param(log_type).build()
+				.body().build()
+				.build()
+			.function()
+				.signature().with_params(vec![ValueType::I32, ValueType::I32]).with_result(ValueType::I32).build()
+				.body().build()
+				.build()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						Instruction::I32Const(1),
+						Instruction::I32Const(2),
+						Instruction::Call(1),
+						Instruction::Call(0),
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.export().field("main").internal().func(4).build()
+			.build()
+	}
+
+	#[test]
+	fn resolves_imports() {
+		let merged = merge(library(), "env", application(ValueType::I32), "application").expect("modules to merge");
+
+		let imports = merged.import_section().expect("log to stay imported").entries();
+		assert_eq!(imports.len(), 1);
+		assert_eq!(imports[0].field(), "log");
+
+		// log is the only import, then add from the library, then the application's own.
+		let main = merged.export_section().expect("exports").entries()
+			.iter()
+			.find(|entry| entry.field() == "main")
+			.map(|entry| *entry.internal());
+		assert_eq!(main, Some(Internal::Function(4)));
+		let body = &merged.code_section().expect("code").bodies()[3];
+		assert_eq!(body.code().elements()[2..4], [Instruction::Call(1), Instruction::Call(0)]);
+
+		// (i32, i32) -> i32 is shared by add and its import.
+		assert_eq!(merged.type_section().expect("types").types().len(), 3);
+		assert_eq!(merged.memory_space(), 1);
+
+		let bytes = merged.to_bytes().expect("merged module to serialize");
+		deserialize_buffer::<Module>(&bytes).expect("merged module to deserialize");
+	}
+
+	fn log_add() -> Module {
+		let mut module = application(ValueType::I32);
+		module.import_section_mut().expect("imports").entries_mut().pop();
+		module
+	}
+
+	#[test]
+	fn imports_matched_by_module_name() {
+		let merged = merge(library(), "lib", log_add(), "application").expect("modules to merge");
+		let imports: Vec<&str> = merged.import_section().expect("imports").entries()
+			.iter().map(|entry| entry.field()).collect();
+		assert_eq!(imports, ["log", "add"]);
+
+		let merged = merge(library(), "env", log_add(), "application").expect("modules to merge");
+		assert_eq!(merged.import_section().expect("imports").entries().len(), 1);
+	}
+
+	#[test]
+	fn overlapping_segments() {
+		let with_data = |offset| builder::module()
+			.import().module("env").field("memory").external().memory(1, None).build()
+			.data().offset(Instruction::I32Const(offset)).value(vec![1, 2, 3, 4]).build()
+			.data().offset(Instruction::I32Const(offset + 2)).value(vec![5]).build()
+			.build();
+		let merged = merge(with_data(0), "first", with_data(4), "second").expect("modules to merge");
+		assert_eq!(merged.data_section().expect("data").entries().len(), 4);
+
+		match merge(with_data(0), "first", with_data(3), "second") {
+			Err(MergeError::OverlappingSegments) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn names() {
+		let with_name = |name: &str| {
+			let mut module = builder::module()
+				.function().signature().build().body().build().build()
+				.build();
+			let mut names = FunctionNameSubsection::default();
+			names.names_mut().insert(0, name.into());
+			let names = NameSection::new(None, Some(names), None);
+			module.set_custom_section("name", serialize(names).expect("names to serialize"));
+			module
+		};
+		let merged = merge(with_name("f"), "first", with_name("g"), "second").expect("modules to merge");
+		let names = merged.names_section().expect("names").functions().expect("function names").names();
+		assert_eq!(names.get(0).map(String::as_str), Some("f"));
+		assert_eq!(names.get(1).map(String::as_str), Some("g"));
+	}
+
+	#[test]
+	fn shared_imports_deduplicated() {
+		let log = || builder::module()
+			.import().module("env").field("log").external().func(0).build()
+			.function()
+				.signature().with_param(ValueType::I32).build()
+				.body().build()
+				.build()
+			.build();
+		let merged = merge(log(), "first", log(), "second").expect("modules to merge");
+
+		let imports = merged.import_section().expect("imports").entries();
+		assert_eq!(imports.len(), 1);
+		assert_eq!(*imports[0].external(), External::Function(0));
+		assert_eq!(merged.functions_space(), 3);
+	}
+
+	#[test]
+	fn incompatible_import() {
+		let mut application = application(ValueType::I32);
+		let imports = application.import_section_mut().expect("imports").entries_mut();
+		*imports[1].external_mut() = External::Function(0);
+		match merge(library(), "env", application, "application") {
+			Err(MergeError::IncompatibleImport { ref field, .. }) if field == "add" => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn duplicate_export() {
+		let exporting = || builder::module()
+			.function()
+				.signature().build()
+				.body().build()
+				.build()
+			.export().field("run").internal().func(0).build()
+			.build();
+		match merge(exporting(), "first", exporting(), "second") {
+			Err(MergeError::DuplicateExport(ref name)) if name == "run" => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn multiple_memories() {
+		match merge(library(), "first", library(), "second") {
+			Err(MergeError::MultipleMemories) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn start_functions_chained() {
+		let with_start = || builder::module()
+			.function()
+				.signature().build()
+				.body().build()
+				.build()
+			.with_section(crate::elements::Section::Start(0))
+			.build();
+		let merged = merge(with_start(), "first", with_start(), "second").expect("modules to merge");
+
+		assert_eq!(merged.start_section(), Some(2));
+		let body = &merged.code_section().expect("code").bodies()[2];
+		assert_eq!(body.code().elements(), &[Instruction::Call(0), Instruction::Call(1), Instruction::End][..]);
+		assert_eq!(merged.type_section().expect("types").types().len(), 1);
+	}
+
+	#[test]
+	fn custom_sections() {
+		let with_customs = |tool: &str, feature: TargetFeature| {
+			let mut producers = ProducersSection::default();
+			producers.add_processed_by(tool, "1.0");
+			let mut module = builder::module().build();
+			module.set_custom_section("producers", serialize(producers).expect("producers to serialize"));
+			let features = TargetFeaturesSection::new(vec![feature]);
+			module.sections_mut().push(Section::TargetFeatures(features));
+			module.set_custom_section("linking", vec![2]);
+			module.set_custom_section(format!("reloc.{}", tool), vec![0]);
+			module.set_custom_section(tool, vec![]);
+			module
+		};
+		let merged = merge(
+			with_customs("first", TargetFeature::new(FeaturePrefix::Used, "sign-ext")), "first",
+			with_customs("second", TargetFeature::new(FeaturePrefix::Used, "simd128")), "second",
+		).expect("modules to merge");
+
+		let tools: Vec<&str> = merged.producers_section().expect("producers").processed_by()
+			.iter().map(|value| value.name.as_str()).collect();
+		assert_eq!(tools, ["first", "second"]);
+		assert_eq!(merged.target_features_section().expect("target features").features().len(), 2);
+		let customs: Vec<&str> = merged.custom_sections().map(|custom| custom.name()).collect();
+		assert_eq!(customs, ["first", "second"]);
+
+		match merge(
+			with_customs("first", TargetFeature::new(FeaturePrefix::Used, "atomics")), "first",
+			with_customs("second", TargetFeature::new(FeaturePrefix::Disallowed, "atomics")), "second",
+		) {
+			Err(MergeError::ConflictingFeature(ref name)) if name == "atomics" => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+}
@@ -0,0 +1,23 @@
+//! Transformations of whole modules.
+
+mod visit;
+mod remap;
+mod merge;
+mod dce;
+mod dedupe;
+mod pack;
+mod rename;
+mod positions;
+
+pub use self::remap::{remap_indices, RelocationMap, SpaceMap, RemapError};
+pub(crate) use self::remap::is_linking_section;
+pub use self::merge::{merge, MergeError};
+pub use self::dce::{eliminate_dead_code, DeadCodeError};
+pub use self::dedupe::dedupe_types;
+pub use self::pack::pack_data_segments;
+pub use self::rename::{rename, Renames, RenameError};
+pub use self::positions::{rewrite_instructions, Position, PositionMap};
+pub use self::visit::{
+	IndexSpace, InstructionVisitor, ModuleVisitor, walk_module, walk_instruction, walk_instructions,
+	walk_function_body, walk_init_expr, walk_import, walk_export, walk_element_segment, walk_data_segment,
+};
@@ -0,0 +1,309 @@
+use alloc::vec::Vec;
+use crate::elements::{DataSegment, External, InitExpr, Instruction, Module, RuntimeValue, Section};
+use super::remap::{RelocationMap, RemapError, SpaceMap};
+use super::visit::{IndexSpace, InstructionVisitor, ModuleVisitor, walk_module};
+
+/// Size of a memory page, in bytes.
+const PAGE_SIZE: u64 = 65536;
+
+/// Longest run of zeros kept inside a segment, about the size of the header of
+/// another segment.
+const MAX_ZERO_RUN: u64 = 8;
+
+/// Records the data segments referred to by instructions.
+struct DataReferences(Vec<bool>);
+
+impl InstructionVisitor for DataReferences {
+	type Error = RemapError;
+
+	fn visit_index(&mut self, space: IndexSpace, index: &mut u32) -> Result<(), RemapError> {
+		if space == IndexSpace::Data {
+			// Invalid indices are left for the remapping to report.
+			if let Some(referenced) = self.0.get_mut(*index as usize) {
+				*referenced = true;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl ModuleVisitor for DataReferences {}
+
+/// Active segments of one memory, as written at instantiation.
+struct Memory {
+	imported: bool,
+	size: u64,
+	/// Whether the offsets are `i64` constants, as for 64-bit memories.
+	wide: bool,
+	/// Offset and contents of every segment, in module order.
+	segments: Vec<(u64, Vec<u8>)>,
+	/// Whether the segments can be packed.
+	packable: bool,
+}
+
+impl Memory {
+	/// Contents of the memory after instantiation, as disjoint runs of bytes in order of
+	/// their offsets. Bytes not covered by any run are left as they are.
+	fn image(&self) -> Vec<(u64, Vec<u8>)> {
+		let mut spans: Vec<(u64, u64)> = self.segments.iter()
+			.filter(|&(_, value)| !value.is_empty())
+			.map(|&(offset, ref value)| (offset, offset + value.len() as u64))
+			.collect();
+		spans.sort_unstable();
+		let mut runs: Vec<(u64, Vec<u8>)> = Vec::new();
+		for (start, end) in spans {
+			match runs.last_mut() {
+				Some(&mut (offset, ref mut bytes)) if start <= offset + bytes.len() as u64 => {
+					let len = (end - offset) as usize;
+					if bytes.len() < len {
+						bytes.resize(len, 0);
+					}
+				},
+				_ => runs.push((start, vec![0; (end - start) as usize])),
+			}
+		}
+		// Later segments overwrite earlier ones.
+		for &(offset, ref value) in &self.segments {
+			if value.is_empty() {
+				continue;
+			}
+			let run = runs.partition_point(|&(start, _)| start <= offset) - 1;
+			let (start, ref mut bytes) = runs[run];
+			let at = (offset - start) as usize;
+			bytes[at..at + value.len()].copy_from_slice(value);
+		}
+		runs
+	}
+
+	/// Fewest segments producing the same memory contents.
+	///
+	/// Memories defined by the module start out zeroed, so zeros need not be written:
+	/// runs of them are trimmed from the ends of segments, and segments are split at
+	/// long ones; short gaps between segments are filled to merge them instead. Imported
+	/// memories may hold anything, so their segments are only merged where they touch.
+	fn packed(&self) -> Vec<(u64, Vec<u8>)> {
+		let image = self.image();
+		if self.imported {
+			return image;
+		}
+
+		let mut packed: Vec<(u64, Vec<u8>)> = Vec::new();
+		for (start, bytes) in image {
+			let mut position = 0;
+			while position < bytes.len() {
+				if bytes[position] == 0 {
+					position += 1;
+					continue;
+				}
+				let end = bytes[position..].iter().position(|&byte| byte == 0).map_or(bytes.len(), |len| position + len);
+				let offset = start + position as u64;
+				match packed.last_mut() {
+					Some(&mut (previous, ref mut value)) if offset - (previous + value.len() as u64) <= MAX_ZERO_RUN => {
+						value.resize((offset - previous) as usize, 0);
+						value.extend_from_slice(&bytes[position..end]);
+					},
+					_ => packed.push((offset, bytes[position..end].to_vec())),
+				}
+				position = end;
+			}
+		}
+		packed
+	}
+
+	fn segment(&self, index: u32, offset: u64, value: Vec<u8>) -> DataSegment {
+		let offset = if self.wide { Instruction::I64Const(offset as i64) } else { Instruction::I32Const(offset as u32 as i32) };
+		DataSegment::new(index, Some(InitExpr::new(vec![offset, Instruction::End])), value)
+	}
+}
+
+/// Rewrite the active data segments of `module` to minimize their serialized size.
+///
+/// Segments of a memory which touch or overlap are merged, with later segments
+/// overwriting earlier ones as at instantiation. For memories the module defines,
+/// zeros are not written at all: leading and trailing zeros are trimmed, segments are
+/// split at runs of more than a few zeros, segments of zeros only are removed, and
+/// segments separated by only a few bytes are merged. The packed segments of a memory
+/// take the place of its first segment.
+///
+/// Memories are left alone if any of their active segments has an offset other than a
+/// constant, does not fit the initial size of a defined memory, or is referred to by
+/// `memory.init` or `data.drop`. The other segments are re-indexed, and the data count
+/// section updated.
+///
+/// The size of imported memories is only known at instantiation, so their segments
+/// are packed whatever their offsets. If a segment is out of bounds there, instantiation
+/// traps either way, but the bytes written to the memory before the trap, which stay
+/// visible to other instances sharing it, may differ from those of the original module.
+pub fn pack_data_segments(module: &mut Module) -> Result<(), RemapError> {
+	let imports = module.import_section().map(|s| s.entries()).unwrap_or(&[]);
+	let mut memories: Vec<Memory> = imports.iter()
+		.filter_map(|entry| match *entry.external() {
+			External::Memory(ref memory) => Some((true, memory.limits().initial())),
+			_ => None,
+		})
+		.chain(module.memory_section().map(|s| s.entries()).unwrap_or(&[]).iter()
+			.map(|memory| (false, memory.limits().initial())))
+		.map(|(imported, pages)| Memory {
+			imported,
+			size: pages as u64 * PAGE_SIZE,
+			wide: false,
+			segments: Vec::new(),
+			packable: true,
+		})
+		.collect();
+
+	let segment_count = module.data_section().map(|s| s.entries().len()).unwrap_or(0);
+	let mut references = DataReferences(vec![false; segment_count]);
+	walk_module(&mut references, module)?;
+
+	let entries = module.data_section().map(|s| s.entries()).unwrap_or(&[]);
+	// Memory of every active segment, whether or not it can be packed.
+	let mut active = Vec::with_capacity(entries.len());
+	for (index, entry) in entries.iter().enumerate() {
+		let offset = match *entry.offset() {
+			Some(ref offset) => offset,
+			None => {
+				active.push(None);
+				continue;
+			},
+		};
+		let memory = match memories.get_mut(entry.index() as usize) {
+			Some(memory) => memory,
+			// Left for validation to report.
+			None => {
+				active.push(None);
+				continue;
+			},
+		};
+		active.push(Some(entry.index()));
+		let (offset, wide) = match offset.evaluate(|_| None) {
+			Ok(RuntimeValue::I32(offset)) => (offset as u32 as u64, false),
+			Ok(RuntimeValue::I64(offset)) => (offset as u64, true),
+			_ => {
+				memory.packable = false;
+				continue;
+			},
+		};
+		let fits = offset.checked_add(entry.value().len() as u64).is_some_and(|end| memory.imported || end <= memory.size);
+		if !fits || references.0[index] || (!memory.segments.is_empty() && memory.wide != wide) {
+			memory.packable = false;
+		}
+		memory.wide = wide;
+		memory.segments.push((offset, entry.value().to_vec()));
+	}
+
+	let mut packed_segments = Vec::with_capacity(entries.len());
+	let mut mapping = Vec::with_capacity(entries.len());
+	let mut placed = vec![false; memories.len()];
+	for (entry, memory_index) in entries.iter().zip(active) {
+		let memory_index = match memory_index {
+			Some(index) if memories[index as usize].packable => index,
+			_ => {
+				mapping.push(Some(packed_segments.len() as u32));
+				packed_segments.push(entry.clone());
+				continue;
+			},
+		};
+		mapping.push(None);
+		if !placed[memory_index as usize] {
+			placed[memory_index as usize] = true;
+			let memory = &memories[memory_index as usize];
+			for (offset, value) in memory.packed() {
+				packed_segments.push(memory.segment(memory_index, offset, value));
+			}
+		}
+	}
+
+	let data_count = packed_segments.len() as u32;
+	let sections = module.sections_mut();
+	for section in sections.iter_mut() {
+		match *section {
+			Section::Data(ref mut data_section) => *data_section.entries_mut() = core::mem::take(&mut packed_segments),
+			Section::DataCount(ref mut count) => *count = data_count,
+			_ => {},
+		}
+	}
+	sections.retain(|section| match *section {
+		Section::Data(ref s) => !s.entries().is_empty(),
+		_ => true,
+	});
+
+	RelocationMap { data: SpaceMap::new(mapping), ..Default::default() }.apply(module)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::pack_data_segments;
+	use crate::builder;
+	use crate::elements::{Instruction::*, Module, ValueType};
+
+	fn segments(module: &Module) -> Vec<(i32, Vec<u8>)> {
+		module.data_section().map(|s| s.entries()).unwrap_or(&[]).iter().map(|segment| {
+			let offset = match segment.offset().as_ref().map(|offset| offset.code()) {
+				Some(&[I32Const(offset), End]) => offset,
+				other => panic!("unexpected offset {:?}", other),
+			};
+			(offset, segment.value().to_vec())
+		}).collect()
+	}
+
+	fn with_segments(module: builder::ModuleBuilder, segments: &[(i32, &[u8])]) -> Module {
+		segments.iter().fold(module, |module, &(offset, value)| {
+			module.data().offset(I32Const(offset)).value(value.to_vec()).build()
+		}).build()
+	}
+
+	#[test]
+	fn defined_memory() {
+		let mut zeros_around = vec![0; 20];
+		zeros_around.push(9);
+		zeros_around.extend_from_slice(&[0; 50]);
+		zeros_around.push(8);
+		zeros_around.extend_from_slice(&[0; 10]);
+		let mut module = with_segments(builder::module().memory().with_min(1).build(), &[
+			(0, &[1, 2, 0, 0]),
+			(100, &zeros_around),
+			(4, &[3]),
+			(1000, &[0; 64]),
+			(1, &[7]),
+			(10, &[4]),
+		]);
+
+		pack_data_segments(&mut module).expect("segments to pack");
+		assert_eq!(segments(&module), vec![
+			(0, vec![1, 7, 0, 0, 3, 0, 0, 0, 0, 0, 4]),
+			(120, vec![9]),
+			(171, vec![8]),
+		]);
+	}
+
+	#[test]
+	fn imported_memory() {
+		let mut module = with_segments(
+			builder::module().import().module("env").field("memory").external().memory(1, None).build(),
+			&[(0, &[0, 1]), (2, &[0]), (10, &[5, 0])],
+		);
+
+		pack_data_segments(&mut module).expect("segments to pack");
+		assert_eq!(segments(&module), vec![(0, vec![0, 1, 0]), (10, vec![5, 0])]);
+	}
+
+	#[test]
+	fn unpackable() {
+		let mut module = builder::module()
+			.import().module("env").field("base").external().global(ValueType::I32, false).build()
+			.memory().with_min(1).build()
+			.data().offset(I32Const(0)).value(vec![0, 1]).build()
+			.data().offset(I32Const(2)).value(vec![2]).build()
+			.data().offset_global(0).value(vec![3]).build()
+			.build();
+		let original = module.clone();
+		pack_data_segments(&mut module).expect("segments to pack");
+		assert_eq!(module, original);
+
+		let mut module = with_segments(builder::module().memory().with_min(1).build(), &[(65535, &[1, 0])]);
+		let original = module.clone();
+		pack_data_segments(&mut module).expect("segments to pack");
+		assert_eq!(module, original);
+	}
+}
@@ -0,0 +1,144 @@
+use alloc::vec::Vec;
+use crate::elements::{Instruction, Instructions, Module};
+
+/// Position of an instruction: the index of its body in the code section and its
+/// index in the body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+	/// Index of the body in the code section.
+	pub body: u32,
+	/// Index of the instruction in the body.
+	pub instruction: u32,
+}
+
+impl Position {
+	/// Position of the `instruction`th instruction of the `body`th body.
+	pub fn new(body: u32, instruction: u32) -> Self {
+		Position { body, instruction }
+	}
+
+	/// Offset of the instruction in the module bytes.
+	///
+	/// Only known if `module` was deserialized with `deserialize_buffer_with_locations`
+	/// and the body was not replaced since.
+	pub fn offset(&self, module: &Module) -> Option<usize> {
+		let body = module.code_section()?.bodies().get(self.body as usize)?;
+		body.code_locations()?.instructions().get(self.instruction as usize).cloned()
+	}
+}
+
+/// Map from instruction positions after a pass to the positions they come from.
+///
+/// Instructions a pass inserts have no original position. Maps of consecutive passes
+/// are combined with `then`, so that positions in the final module, such as those of
+/// runtime traps, can be reported against the module as it was compiled.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PositionMap {
+	/// Original position of each instruction, by body.
+	bodies: Vec<Vec<Option<Position>>>,
+}
+
+impl PositionMap {
+	/// Map every instruction of `module` to itself.
+	pub fn identity(module: &Module) -> Self {
+		let bodies = module.code_section().map(|s| s.bodies()).unwrap_or(&[]);
+		PositionMap {
+			bodies: bodies.iter().enumerate()
+				.map(|(body, func_body)| {
+					(0..func_body.code().elements().len())
+						.map(|instruction| Some(Position::new(body as u32, instruction as u32)))
+						.collect()
+				})
+				.collect(),
+		}
+	}
+
+	/// Map with the original position of each instruction, by body.
+	pub fn new(bodies: Vec<Vec<Option<Position>>>) -> Self {
+		PositionMap { bodies }
+	}
+
+	/// Original position of the instruction at `position`, if it has one.
+	pub fn original(&self, position: Position) -> Option<Position> {
+		*self.bodies.get(position.body as usize)?.get(position.instruction as usize)?
+	}
+
+	/// Map of this pass followed by the pass `later` mapped.
+	///
+	/// Positions after `later` are mapped to the positions before this pass.
+	pub fn then(&self, later: &PositionMap) -> PositionMap {
+		PositionMap {
+			bodies: later.bodies.iter()
+				.map(|body| body.iter().map(|position| position.and_then(|position| self.original(position))).collect())
+				.collect(),
+		}
+	}
+}
+
+/// Rewrite every instruction of every body of `module`, keeping track of positions.
+///
+/// `f` is given the index of the body and each of its instructions in turn, and pushes
+/// the instructions replacing it, possibly none or the instruction itself, to the vector.
+/// Each pushed instruction is mapped to the position of the instruction it replaces.
+pub fn rewrite_instructions<F>(module: &mut Module, mut f: F) -> PositionMap
+	where F: FnMut(u32, Instruction, &mut Vec<Instruction>)
+{
+	let mut bodies = Vec::new();
+	if let Some(code_section) = module.code_section_mut() {
+		for (index, func_body) in code_section.bodies_mut().iter_mut().enumerate() {
+			let index = index as u32;
+			let old = core::mem::take(func_body.code_mut().elements_mut());
+			let mut new = Vec::with_capacity(old.len());
+			let mut positions = Vec::with_capacity(old.len());
+			for (instruction_index, instruction) in old.into_iter().enumerate() {
+				f(index, instruction, &mut new);
+				positions.resize(new.len(), Some(Position::new(index, instruction_index as u32)));
+			}
+			*func_body.code_mut() = Instructions::new(new);
+			bodies.push(positions);
+		}
+	}
+	PositionMap { bodies }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{rewrite_instructions, Position, PositionMap};
+	use crate::builder;
+	use crate::elements::{deserialize_buffer_with_locations, serialize, Instruction::*, Instructions};
+
+	#[test]
+	fn composed_passes() {
+		let mut module = builder::module()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![Nop, Unreachable, End])).build()
+				.build()
+			.build();
+		let compiled = deserialize_buffer_with_locations(&serialize(module.clone()).expect("module to serialize"))
+			.expect("module to deserialize");
+
+		// Drop every `nop`, then count calls before every instruction.
+		let first = rewrite_instructions(&mut module, |_, instruction, out| {
+			if instruction != Nop {
+				out.push(instruction);
+			}
+		});
+		let second = rewrite_instructions(&mut module, |_, instruction, out| {
+			out.push(Call(0));
+			out.push(instruction);
+		});
+		assert_eq!(
+			module.code_section().expect("code section").bodies()[0].code().elements(),
+			&[Call(0), Unreachable, Call(0), End],
+		);
+
+		let map = PositionMap::identity(&compiled).then(&first).then(&second);
+		assert_eq!(map.original(Position::new(0, 1)), Some(Position::new(0, 1)));
+		assert_eq!(map.original(Position::new(0, 2)), Some(Position::new(0, 2)));
+		assert_eq!(map.original(Position::new(0, 4)), None);
+
+		let trap = map.original(Position::new(0, 1)).expect("trap to have a position");
+		let offset = trap.offset(&compiled).expect("module to have locations");
+		assert_eq!(serialize(compiled).expect("module to serialize")[offset], 0x00);
+	}
+}
@@ -0,0 +1,283 @@
+use alloc::vec::Vec;
+use core::{fmt, mem};
+use crate::elements::{Module, Section};
+use super::visit::{IndexSpace, InstructionVisitor, ModuleVisitor, walk_module};
+
+/// Reference to an index which has no mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemapError {
+	/// Index space of the reference.
+	pub space: IndexSpace,
+	/// Index referred to.
+	pub index: u32,
+}
+
+impl fmt::Display for RemapError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "No mapping for index {} of {:?} space", self.index, self.space)
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for RemapError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Mapping {
+	Identity,
+	Shifted { from: u32, by: u32 },
+	Table(Vec<Option<u32>>),
+}
+
+/// Mapping from old to new indices of one index space, identity by default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpaceMap(Mapping);
+
+impl Default for SpaceMap {
+	fn default() -> Self {
+		SpaceMap(Mapping::Identity)
+	}
+}
+
+impl SpaceMap {
+	/// Mapping of every index to itself.
+	pub fn identity() -> Self {
+		SpaceMap::default()
+	}
+
+	/// Mapping of old index `i` to `map[i]`.
+	///
+	/// Removed items map to `None`; so does any index past the end of `map`.
+	pub fn new(map: Vec<Option<u32>>) -> Self {
+		SpaceMap(Mapping::Table(map))
+	}
+
+	/// Mapping moving indices from `from` on up by `by`, as after inserting `by` items at `from`.
+	pub fn shifted(from: u32, by: u32) -> Self {
+		SpaceMap(Mapping::Shifted { from, by })
+	}
+
+	/// New index of the item at `index`, if it is kept.
+	pub fn get(&self, index: u32) -> Option<u32> {
+		match self.0 {
+			Mapping::Identity => Some(index),
+			Mapping::Shifted { from, by } if index >= from => index.checked_add(by),
+			Mapping::Shifted { .. } => Some(index),
+			Mapping::Table(ref map) => map.get(index as usize).cloned().unwrap_or(None),
+		}
+	}
+}
+
+/// Old to new index mappings of every index space, identity by default.
+///
+/// Built by the caller after moving, inserting or removing items of a module, and
+/// then applied to bring every reference to them up to date.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RelocationMap {
+	/// Mapping of function types.
+	pub types: SpaceMap,
+	/// Mapping of functions, imported ones included.
+	pub functions: SpaceMap,
+	/// Mapping of globals, imported ones included.
+	pub globals: SpaceMap,
+	/// Mapping of tables, imported ones included.
+	pub tables: SpaceMap,
+	/// Mapping of memories, imported ones included.
+	pub memories: SpaceMap,
+	/// Mapping of element segments.
+	pub elements: SpaceMap,
+	/// Mapping of data segments.
+	pub data: SpaceMap,
+	/// Mapping of exception tags, imported ones included.
+	#[cfg(feature="exceptions")]
+	pub tags: SpaceMap,
+}
+
+impl RelocationMap {
+	fn map(&self, space: IndexSpace, index: u32) -> Result<u32, RemapError> {
+		let map = match space {
+			IndexSpace::Type => &self.types,
+			IndexSpace::Function => &self.functions,
+			IndexSpace::Global => &self.globals,
+			IndexSpace::Table => &self.tables,
+			IndexSpace::Memory => &self.memories,
+			IndexSpace::Element => &self.elements,
+			IndexSpace::Data => &self.data,
+			#[cfg(feature="exceptions")]
+			IndexSpace::Tag => &self.tags,
+		};
+		map.get(index).ok_or(RemapError { space, index })
+	}
+
+	fn remap(&self, space: IndexSpace, index: &mut u32) -> Result<(), RemapError> {
+		*index = self.map(space, *index)?;
+		Ok(())
+	}
+
+	/// Rewrite every reference to an index in `module` according to the mappings.
+	///
+	/// This covers imports, the function and tag sections, global initializers,
+	/// exports, the start function, element and data segments, function bodies and the
+	/// function and local names of a parsed name section. Only references are
+	/// rewritten; the definitions themselves are neither moved nor removed. Names of
+	/// functions without a mapping are dropped, while any other reference without a
+	/// mapping is an error, and leaves the module partially rewritten.
+	pub fn apply(&self, module: &mut Module) -> Result<(), RemapError> {
+		walk_module(&mut Remapper(self), module)?;
+		if let Some(name_section) = module.names_section_mut() {
+			if let Some(ref mut functions) = *name_section.functions_mut() {
+				let names = mem::take(functions.names_mut());
+				*functions.names_mut() = names
+					.into_iter()
+					.filter_map(|(index, name)| self.functions.get(index).map(|index| (index, name)))
+					.collect();
+			}
+			if let Some(ref mut locals) = *name_section.locals_mut() {
+				let local_names = mem::take(locals.local_names_mut());
+				*locals.local_names_mut() = local_names
+					.into_iter()
+					.filter_map(|(index, names)| self.functions.get(index).map(|index| (index, names)))
+					.collect();
+			}
+		}
+		Ok(())
+	}
+}
+
+struct Remapper<'a>(&'a RelocationMap);
+
+impl<'a> InstructionVisitor for Remapper<'a> {
+	type Error = RemapError;
+
+	fn visit_index(&mut self, space: IndexSpace, index: &mut u32) -> Result<(), RemapError> {
+		self.0.remap(space, index)
+	}
+}
+
+impl<'a> ModuleVisitor for Remapper<'a> {}
+
+/// Rewrite every reference to an index in `module` according to `map`.
+///
+/// See `RelocationMap::apply`.
+pub fn remap_indices(module: &mut Module, map: &RelocationMap) -> Result<(), RemapError> {
+	map.apply(module)
+}
+
+/// Whether `section` holds relocation or linking metadata referring to indices of the
+/// module, parsed or not, which is left stale once the indices shift.
+pub(crate) fn is_linking_section(section: &Section) -> bool {
+	match *section {
+		Section::Reloc(_) | Section::Linking(_) | Section::Dylink(_) => true,
+		Section::Custom(ref custom) => {
+			let name = custom.name();
+			name == "linking" || name.starts_with("reloc.") || name == "dylink" || name == "dylink.0"
+		},
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{remap_indices, RelocationMap, RemapError, SpaceMap};
+	use crate::builder;
+	use crate::elements::{
+		Module, Section, Instruction, Instructions, Internal, ImportEntry, External,
+	};
+	use crate::transform::IndexSpace;
+
+	fn module() -> Module {
+		builder::module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![Instruction::Call(1), Instruction::End]))
+					.build()
+				.build()
+			.function()
+				.main()
+				.signature().build()
+				.body().build()
+				.build()
+			.table().with_min(2).with_element(0, vec![0, 1]).build()
+			.export().field("run").internal().func(0).build()
+			.build()
+	}
+
+	#[test]
+	fn import_insertion() {
+		let mut module = module();
+		let type_ref = module.function_section().expect("function section").entries()[0].type_ref();
+		module.sections_mut().insert(0, Section::Import(Default::default()));
+		module.import_section_mut().expect("import section").entries_mut()
+			.push(ImportEntry::new("env".into(), "hook".into(), External::Function(type_ref)));
+
+		let map = RelocationMap { functions: SpaceMap::shifted(0, 1), ..Default::default() };
+		remap_indices(&mut module, &map).expect("indices to be remapped");
+
+		assert_eq!(
+			module.code_section().expect("code section").bodies()[0].code().elements(),
+			&[Instruction::Call(2), Instruction::End][..],
+		);
+		assert_eq!(module.start_section(), Some(2));
+		assert_eq!(module.elements_section().expect("element section").entries()[0].members(), &[1, 2][..]);
+		assert_eq!(
+			*module.export_section().expect("export section").entries()[0].internal(),
+			Internal::Function(1),
+		);
+	}
+
+	#[cfg(feature="exceptions")]
+	#[test]
+	fn tags() {
+		use crate::elements::{
+			BlockType, ExceptionsInstruction::*, ExportEntry, ExportSection, TagSection, TagType,
+		};
+
+		let mut module = builder::module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						Instruction::Exceptions(Try(BlockType::NoResult)),
+						Instruction::Exceptions(Throw(0)),
+						Instruction::Exceptions(Catch(1)),
+						Instruction::End,
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.build();
+		module.sections_mut().push(Section::Tag(TagSection::with_entries(vec![TagType::new(0), TagType::new(0)])));
+		module.sections_mut().push(Section::Export(ExportSection::with_entries(vec![
+			ExportEntry::new("error".into(), Internal::Tag(1)),
+		])));
+
+		let map = RelocationMap { tags: SpaceMap::shifted(0, 1), ..Default::default() };
+		remap_indices(&mut module, &map).expect("indices to be remapped");
+
+		assert_eq!(module.code_section().expect("code section").bodies()[0].code().elements()[1..3], [
+			Instruction::Exceptions(Throw(1)),
+			Instruction::Exceptions(Catch(2)),
+		]);
+		assert_eq!(*module.export_section().expect("export section").entries()[0].internal(), Internal::Tag(2));
+	}
+
+	#[test]
+	fn missing_mapping() {
+		let map = RelocationMap { functions: SpaceMap::new(vec![Some(0)]), ..Default::default() };
+		assert_eq!(
+			remap_indices(&mut module(), &map),
+			Err(RemapError { space: IndexSpace::Function, index: 1 }),
+		);
+	}
+
+	#[test]
+	fn space_map() {
+		assert_eq!(SpaceMap::identity().get(7), Some(7));
+		assert_eq!(SpaceMap::shifted(2, 3).get(1), Some(1));
+		assert_eq!(SpaceMap::shifted(2, 3).get(2), Some(5));
+		assert_eq!(SpaceMap::shifted(1, 1).get(u32::MAX), None);
+		assert_eq!(SpaceMap::new(vec![None, Some(0)]).get(0), None);
+		assert_eq!(SpaceMap::new(vec![None, Some(0)]).get(1), Some(0));
+		assert_eq!(SpaceMap::new(vec![None, Some(0)]).get(2), None);
+	}
+}
@@ -0,0 +1,222 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use crate::elements::{Module, Internal};
+
+/// Pattern matching a name exactly, or every name with a prefix if it ends with `*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+	from: String,
+	to: String,
+}
+
+impl Pattern {
+	fn new(from: &str, to: &str) -> Self {
+		Pattern { from: from.into(), to: to.into() }
+	}
+
+	/// Name `name` is renamed to, if the pattern matches it.
+	///
+	/// With `old_*` renamed to `new_*`, the part matched by `*` is kept. A replacement
+	/// without `*` replaces the whole name.
+	fn apply(&self, name: &str) -> Option<String> {
+		match self.from.strip_suffix('*') {
+			Some(prefix) => {
+				let rest = name.strip_prefix(prefix)?;
+				Some(match self.to.strip_suffix('*') {
+					Some(to_prefix) => format!("{}{}", to_prefix, rest),
+					None => self.to.clone(),
+				})
+			},
+			None if name == self.from => Some(self.to.clone()),
+			None => None,
+		}
+	}
+}
+
+/// Set of renames of import and export names, applied by `rename`.
+///
+/// Rules are tried in the order they were added and the first matching one is applied,
+/// so each name is renamed at most once. Names may end with `*` to match every name
+/// with that prefix.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Renames {
+	import_modules: Vec<Pattern>,
+	imports: Vec<(Pattern, Pattern)>,
+	exports: Vec<Pattern>,
+}
+
+impl Renames {
+	/// Empty set of renames.
+	pub fn new() -> Self {
+		Renames::default()
+	}
+
+	/// Rename the module name of imports from module `from` to `to`, keeping their field names.
+	pub fn import_module(mut self, from: &str, to: &str) -> Self {
+		self.import_modules.push(Pattern::new(from, to));
+		self
+	}
+
+	/// Rename imports of `from_field` from module `from_module` to `to_field` from `to_module`.
+	///
+	/// Such a rule takes precedence over the `import_module` ones.
+	pub fn import(mut self, from_module: &str, from_field: &str, to_module: &str, to_field: &str) -> Self {
+		self.imports.push((Pattern::new(from_module, to_module), Pattern::new(from_field, to_field)));
+		self
+	}
+
+	/// Rename exports from `from` to `to`.
+	pub fn export(mut self, from: &str, to: &str) -> Self {
+		self.exports.push(Pattern::new(from, to));
+		self
+	}
+
+	fn rename_import(&self, module: &str, field: &str) -> Option<(String, String)> {
+		for (module_pattern, field_pattern) in &self.imports {
+			if let (Some(module), Some(field)) = (module_pattern.apply(module), field_pattern.apply(field)) {
+				return Some((module, field));
+			}
+		}
+		self.import_modules.iter()
+			.find_map(|pattern| pattern.apply(module))
+			.map(|module| (module, field.into()))
+	}
+
+	fn rename_export(&self, field: &str) -> Option<String> {
+		self.exports.iter().find_map(|pattern| pattern.apply(field))
+	}
+}
+
+/// Error of renaming exports.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenameError {
+	/// Two exports of different items would share this name.
+	ExportCollision {
+		/// Name of both exports.
+		field: String,
+		/// Item exported first.
+		first: Internal,
+		/// Item exported second.
+		second: Internal,
+	},
+}
+
+impl fmt::Display for RenameError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			RenameError::ExportCollision { ref field, first, second } =>
+				write!(f, "Export {} would refer to both {:?} and {:?}", field, first, second),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for RenameError {}
+
+/// Rename the imports and exports of `module` per `renames`.
+///
+/// Exports which end up sharing a name are merged if they refer to the same item, the
+/// first one being kept. Returns the number of renamed imports and exports. If exports
+/// of different items would share a name, the module is left unchanged.
+pub fn rename(module: &mut Module, renames: &Renames) -> Result<usize, RenameError> {
+	let mut renamed = 0;
+
+	let mut exports = Vec::new();
+	if let Some(export_section) = module.export_section() {
+		for entry in export_section.entries() {
+			let field = match renames.rename_export(entry.field()) {
+				Some(field) => {
+					renamed += 1;
+					field
+				},
+				None => entry.field().into(),
+			};
+			let internal = *entry.internal();
+			match exports.iter().find(|&(existing, _): &&(String, Internal)| *existing == field) {
+				Some(&(_, first)) if first == internal => {},
+				Some(&(_, first)) => return Err(RenameError::ExportCollision { field, first, second: internal }),
+				None => exports.push((field, internal)),
+			}
+		}
+	}
+	if let Some(export_section) = module.export_section_mut() {
+		let entries = export_section.entries_mut();
+		entries.truncate(exports.len());
+		for (entry, (field, internal)) in entries.iter_mut().zip(exports) {
+			*entry.field_mut() = field;
+			*entry.internal_mut() = internal;
+		}
+	}
+
+	if let Some(import_section) = module.import_section_mut() {
+		for entry in import_section.entries_mut() {
+			if let Some((module_name, field)) = renames.rename_import(entry.module(), entry.field()) {
+				*entry.module_mut() = module_name;
+				*entry.field_mut() = field;
+				renamed += 1;
+			}
+		}
+	}
+
+	Ok(renamed)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{rename, Renames, RenameError};
+	use crate::builder;
+	use crate::elements::Internal;
+
+	fn module() -> crate::elements::Module {
+		builder::module()
+			.import().module("env").field("old_abi_read").external().func(0).build()
+			.import().module("env").field("old_abi_write").external().func(0).build()
+			.import().module("env").field("log").external().func(0).build()
+			.import().module("host").field("abort").external().func(0).build()
+			.function().signature().build().body().build().build()
+			.export().field("call").internal().func(4).build()
+			.export().field("deploy").internal().func(4).build()
+			.export().field("memory_v1").internal().func(0).build()
+			.build()
+	}
+
+	#[test]
+	fn rename_imports() {
+		let mut module = module();
+		let renames = Renames::new()
+			.import("env", "old_abi_*", "env", "new_abi_*")
+			.import("env", "log", "debug", "print")
+			.import_module("host", "env");
+		assert_eq!(rename(&mut module, &renames), Ok(4));
+
+		let names: Vec<_> = module.import_section().expect("import section").entries().iter()
+			.map(|entry| format!("{}.{}", entry.module(), entry.field()))
+			.collect();
+		assert_eq!(names, vec!["env.new_abi_read", "env.new_abi_write", "debug.print", "env.abort"]);
+	}
+
+	#[test]
+	fn merge_exports() {
+		let mut module = module();
+		assert_eq!(rename(&mut module, &Renames::new().export("deploy", "call")), Ok(1));
+		let exports: Vec<_> = module.export_section().expect("export section").entries().iter()
+			.map(|entry| entry.field().to_string())
+			.collect();
+		assert_eq!(exports, vec!["call", "memory_v1"]);
+	}
+
+	#[test]
+	fn export_collision() {
+		let mut module = module();
+		let original = module.clone();
+		assert_eq!(
+			rename(&mut module, &Renames::new().export("memory_*", "call")),
+			Err(RenameError::ExportCollision {
+				field: "call".into(),
+				first: Internal::Function(4),
+				second: Internal::Function(0),
+			}),
+		);
+		assert_eq!(module, original);
+	}
+}
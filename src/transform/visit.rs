@@ -0,0 +1,402 @@
+use core::convert::TryFrom;
+use crate::elements::{
+	Module, Section, Instruction, InitExpr, FuncBody, ImportEntry, ExportEntry, GlobalEntry,
+	ElementSegment, DataSegment, External, Internal, ImportCountType,
+};
+use super::remap::RemapError;
+
+/// Index space of module items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexSpace {
+	/// Function types.
+	Type,
+	/// Functions, imported ones first.
+	Function,
+	/// Globals, imported ones first.
+	Global,
+	/// Tables, imported ones first.
+	Table,
+	/// Memories, imported ones first.
+	Memory,
+	/// Element segments.
+	Element,
+	/// Data segments.
+	Data,
+	/// Exception tags, imported ones first.
+	#[cfg(feature="exceptions")]
+	Tag,
+}
+
+/// Visitor of instructions and the indices they refer to.
+///
+/// Every method has a default implementation which walks into the visited item,
+/// so an implementation only overrides the methods it is interested in. Overriding
+/// methods can call the matching `walk_*` function to keep walking.
+pub trait InstructionVisitor {
+	/// Error aborting the walk.
+	///
+	/// The walk itself fails with a `RemapError` if the table index of a
+	/// `call_indirect` is rewritten past 255, which its single byte cannot hold.
+	type Error: From<RemapError>;
+
+	/// Visit a reference to the item `index` of `space`, which may be rewritten.
+	fn visit_index(&mut self, _space: IndexSpace, _index: &mut u32) -> Result<(), Self::Error> {
+		Ok(())
+	}
+
+	/// Visit a single instruction.
+	fn visit_instruction(&mut self, instruction: &mut Instruction) -> Result<(), Self::Error> {
+		walk_instruction(self, instruction)
+	}
+}
+
+/// Visitor of every item of a module holding indices or code.
+///
+/// `walk_module` drives the visitor through the sections in their order.
+pub trait ModuleVisitor: InstructionVisitor {
+	/// Visit an import entry.
+	fn visit_import(&mut self, entry: &mut ImportEntry) -> Result<(), Self::Error> {
+		walk_import(self, entry)
+	}
+
+	/// Visit an export entry.
+	fn visit_export(&mut self, entry: &mut ExportEntry) -> Result<(), Self::Error> {
+		walk_export(self, entry)
+	}
+
+	/// Visit the global defined at `global_index`.
+	fn visit_global(&mut self, _global_index: u32, entry: &mut GlobalEntry) -> Result<(), Self::Error> {
+		walk_init_expr(self, entry.init_expr_mut())
+	}
+
+	/// Visit an element segment.
+	fn visit_element_segment(&mut self, segment: &mut ElementSegment) -> Result<(), Self::Error> {
+		walk_element_segment(self, segment)
+	}
+
+	/// Visit a data segment.
+	fn visit_data_segment(&mut self, segment: &mut DataSegment) -> Result<(), Self::Error> {
+		walk_data_segment(self, segment)
+	}
+
+	/// Visit the body of the function defined at `func_index`.
+	///
+	/// The body may be replaced as a whole, e.g. to inject instructions.
+	fn visit_function_body(&mut self, _func_index: u32, body: &mut FuncBody) -> Result<(), Self::Error> {
+		walk_function_body(self, body)
+	}
+}
+
+/// Visit the indices `instruction` refers to.
+pub fn walk_instruction<V>(visitor: &mut V, instruction: &mut Instruction) -> Result<(), V::Error>
+	where V: InstructionVisitor + ?Sized
+{
+	match *instruction {
+		Instruction::Call(ref mut index) => visitor.visit_index(IndexSpace::Function, index),
+		Instruction::CallIndirect(ref mut type_ref, ref mut table) => {
+			visitor.visit_index(IndexSpace::Type, type_ref)?;
+			// The table index is a single byte in this encoding.
+			let mut index = *table as u32;
+			visitor.visit_index(IndexSpace::Table, &mut index)?;
+			*table = u8::try_from(index).map_err(|_| RemapError { space: IndexSpace::Table, index: *table as u32 })?;
+			Ok(())
+		},
+		Instruction::GetGlobal(ref mut index) |
+		Instruction::SetGlobal(ref mut index) => visitor.visit_index(IndexSpace::Global, index),
+		#[cfg(feature="bulk")]
+		Instruction::Bulk(ref mut bulk) => {
+			use crate::elements::BulkInstruction::*;
+			match *bulk {
+				MemoryInit(ref mut index) | MemoryDrop(ref mut index) =>
+					visitor.visit_index(IndexSpace::Data, index),
+				TableInit(ref mut index) | TableDrop(ref mut index) =>
+					visitor.visit_index(IndexSpace::Element, index),
+				_ => Ok(()),
+			}
+		},
+		#[cfg(feature="exceptions")]
+		Instruction::Exceptions(ref mut exceptions) => {
+			use crate::elements::ExceptionsInstruction::*;
+			match *exceptions {
+				Throw(ref mut index) | Catch(ref mut index) => visitor.visit_index(IndexSpace::Tag, index),
+				_ => Ok(()),
+			}
+		},
+		_ => Ok(()),
+	}
+}
+
+/// Visit every instruction of `instructions`.
+pub fn walk_instructions<V>(visitor: &mut V, instructions: &mut [Instruction]) -> Result<(), V::Error>
+	where V: InstructionVisitor + ?Sized
+{
+	for instruction in instructions {
+		visitor.visit_instruction(instruction)?;
+	}
+	Ok(())
+}
+
+/// Visit every instruction of a function body.
+///
+/// The code locations of the body are kept unless an instruction is rewritten.
+pub fn walk_function_body<V>(visitor: &mut V, body: &mut FuncBody) -> Result<(), V::Error>
+	where V: InstructionVisitor + ?Sized
+{
+	let (instructions, locations) = body.code_mut_with_locations();
+	if locations.is_none() {
+		return walk_instructions(visitor, instructions.elements_mut());
+	}
+	for instruction in instructions.elements_mut() {
+		let before = instruction.clone();
+		let visited = visitor.visit_instruction(instruction);
+		if visited.is_err() || *instruction != before {
+			*locations = None;
+		}
+		visited?;
+	}
+	Ok(())
+}
+
+/// Visit every instruction of `init_expr`.
+pub fn walk_init_expr<V>(visitor: &mut V, init_expr: &mut InitExpr) -> Result<(), V::Error>
+	where V: InstructionVisitor + ?Sized
+{
+	walk_instructions(visitor, init_expr.code_mut())
+}
+
+/// Visit the type an import entry refers to.
+pub fn walk_import<V>(visitor: &mut V, entry: &mut ImportEntry) -> Result<(), V::Error>
+	where V: ModuleVisitor + ?Sized
+{
+	match *entry.external_mut() {
+		External::Function(ref mut type_ref) => visitor.visit_index(IndexSpace::Type, type_ref),
+		#[cfg(feature="exceptions")]
+		External::Tag(ref mut tag) => visitor.visit_index(IndexSpace::Type, tag.type_ref_mut()),
+		_ => Ok(()),
+	}
+}
+
+/// Visit the item an export entry refers to.
+pub fn walk_export<V>(visitor: &mut V, entry: &mut ExportEntry) -> Result<(), V::Error>
+	where V: ModuleVisitor + ?Sized
+{
+	match *entry.internal_mut() {
+		Internal::Function(ref mut index) => visitor.visit_index(IndexSpace::Function, index),
+		Internal::Table(ref mut index) => visitor.visit_index(IndexSpace::Table, index),
+		Internal::Memory(ref mut index) => visitor.visit_index(IndexSpace::Memory, index),
+		Internal::Global(ref mut index) => visitor.visit_index(IndexSpace::Global, index),
+		#[cfg(feature="exceptions")]
+		Internal::Tag(ref mut index) => visitor.visit_index(IndexSpace::Tag, index),
+	}
+}
+
+/// Visit the table, offset and members of an element segment.
+pub fn walk_element_segment<V>(visitor: &mut V, segment: &mut ElementSegment) -> Result<(), V::Error>
+	where V: ModuleVisitor + ?Sized
+{
+	// Passive segments are not tied to a table.
+	if let Some(ref mut offset) = *segment.offset_mut() {
+		walk_init_expr(visitor, offset)?;
+	}
+	if segment.offset().is_some() {
+		visitor.visit_index(IndexSpace::Table, segment.index_mut())?;
+	}
+	for member in segment.members_mut() {
+		visitor.visit_index(IndexSpace::Function, member)?;
+	}
+	Ok(())
+}
+
+/// Visit the memory and offset of a data segment.
+pub fn walk_data_segment<V>(visitor: &mut V, segment: &mut DataSegment) -> Result<(), V::Error>
+	where V: ModuleVisitor + ?Sized
+{
+	if let Some(ref mut offset) = *segment.offset_mut() {
+		walk_init_expr(visitor, offset)?;
+	}
+	if segment.offset().is_some() {
+		visitor.visit_index(IndexSpace::Memory, segment.index_mut())?;
+	}
+	Ok(())
+}
+
+/// Drive `visitor` through every section of `module`.
+///
+/// Besides the items with their own `visit_*` method, the type references of the
+/// function and tag sections and the start function are visited as indices. Custom
+/// sections, including names and relocations, are not visited.
+pub fn walk_module<V>(visitor: &mut V, module: &mut Module) -> Result<(), V::Error>
+	where V: ModuleVisitor + ?Sized
+{
+	let imported_functions = module.import_count(ImportCountType::Function) as u32;
+	let imported_globals = module.import_count(ImportCountType::Global) as u32;
+	for section in module.sections_mut() {
+		match *section {
+			Section::Import(ref mut import_section) => {
+				for entry in import_section.entries_mut() {
+					visitor.visit_import(entry)?;
+				}
+			},
+			Section::Function(ref mut function_section) => {
+				for func in function_section.entries_mut() {
+					visitor.visit_index(IndexSpace::Type, func.type_ref_mut())?;
+				}
+			},
+			#[cfg(feature="exceptions")]
+			Section::Tag(ref mut tag_section) => {
+				for tag in tag_section.entries_mut() {
+					visitor.visit_index(IndexSpace::Type, tag.type_ref_mut())?;
+				}
+			},
+			Section::Global(ref mut global_section) => {
+				for (index, entry) in global_section.entries_mut().iter_mut().enumerate() {
+					visitor.visit_global(imported_globals + index as u32, entry)?;
+				}
+			},
+			Section::Export(ref mut export_section) => {
+				for entry in export_section.entries_mut() {
+					visitor.visit_export(entry)?;
+				}
+			},
+			Section::Start(ref mut index) => visitor.visit_index(IndexSpace::Function, index)?,
+			Section::Element(ref mut element_section) => {
+				for segment in element_section.entries_mut() {
+					visitor.visit_element_segment(segment)?;
+				}
+			},
+			Section::Code(ref mut code_section) => {
+				for (index, body) in code_section.bodies_mut().iter_mut().enumerate() {
+					visitor.visit_function_body(imported_functions + index as u32, body)?;
+				}
+			},
+			Section::Data(ref mut data_section) => {
+				for segment in data_section.entries_mut() {
+					visitor.visit_data_segment(segment)?;
+				}
+			},
+			_ => {},
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{walk_module, walk_instructions, IndexSpace, InstructionVisitor, ModuleVisitor};
+	use super::super::remap::RemapError;
+	use crate::elements::{deserialize_buffer_with_locations, serialize, FuncBody, Instruction, Instructions, Module};
+	use crate::builder;
+
+	/// Counts function references and prepends a `nop` to every body.
+	#[derive(Default)]
+	struct Tracer {
+		function_references: usize,
+	}
+
+	impl InstructionVisitor for Tracer {
+		type Error = RemapError;
+
+		fn visit_index(&mut self, space: IndexSpace, _index: &mut u32) -> Result<(), RemapError> {
+			if space == IndexSpace::Function {
+				self.function_references += 1;
+			}
+			Ok(())
+		}
+	}
+
+	impl ModuleVisitor for Tracer {
+		fn visit_function_body(&mut self, _func_index: u32, body: &mut FuncBody) -> Result<(), RemapError> {
+			body.code_mut().elements_mut().insert(0, Instruction::Nop);
+			walk_instructions(self, body.code_mut().elements_mut())
+		}
+	}
+
+	fn module() -> Module {
+		builder::module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![Instruction::Call(0), Instruction::End]))
+					.build()
+				.build()
+			.export().field("run").internal().func(0).build()
+			.build()
+	}
+
+	#[test]
+	fn visits_references_and_bodies() {
+		let mut module = module();
+		let mut tracer = Tracer::default();
+		walk_module(&mut tracer, &mut module).expect("walk never fails");
+
+		// The call and the export.
+		assert_eq!(tracer.function_references, 2);
+		assert_eq!(
+			module.code_section().expect("code section").bodies()[0].code().elements(),
+			&[Instruction::Nop, Instruction::Call(0), Instruction::End][..],
+		);
+	}
+
+	#[test]
+	fn error_aborts_walk() {
+		struct Refuse;
+		impl InstructionVisitor for Refuse {
+			type Error = RemapError;
+			fn visit_index(&mut self, space: IndexSpace, index: &mut u32) -> Result<(), RemapError> {
+				Err(RemapError { space, index: *index })
+			}
+		}
+		impl ModuleVisitor for Refuse {}
+
+		assert_eq!(walk_module(&mut Refuse, &mut module()), Err(RemapError { space: IndexSpace::Type, index: 0 }));
+	}
+
+	/// Moves every reference of `space` by `by`.
+	struct Shift {
+		space: IndexSpace,
+		by: u32,
+	}
+
+	impl InstructionVisitor for Shift {
+		type Error = RemapError;
+
+		fn visit_index(&mut self, space: IndexSpace, index: &mut u32) -> Result<(), RemapError> {
+			if space == self.space {
+				*index += self.by;
+			}
+			Ok(())
+		}
+	}
+
+	impl ModuleVisitor for Shift {}
+
+	#[test]
+	fn table_index_overflow() {
+		let mut module = builder::module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						Instruction::I32Const(0),
+						Instruction::CallIndirect(0, 200),
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.build();
+		let mut shift = Shift { space: IndexSpace::Table, by: 100 };
+		assert_eq!(walk_module(&mut shift, &mut module), Err(RemapError { space: IndexSpace::Table, index: 200 }));
+	}
+
+	#[test]
+	fn keeps_locations_of_unchanged_bodies() {
+		let bytes = serialize(module()).expect("module to serialize");
+		let mut module: Module = deserialize_buffer_with_locations(&bytes).expect("module to deserialize");
+
+		walk_module(&mut Shift { space: IndexSpace::Global, by: 1 }, &mut module).expect("walk to succeed");
+		assert!(module.code_section().expect("code section").bodies()[0].code_locations().is_some());
+
+		walk_module(&mut Shift { space: IndexSpace::Function, by: 1 }, &mut module).expect("walk to succeed");
+		assert!(module.code_section().expect("code section").bodies()[0].code_locations().is_none());
+	}
+}
@@ -0,0 +1,73 @@
+//! Runs the `parity-wasm` command line tool on the fixtures of `res/cases/v1`.
+
+#![cfg(feature = "cli")]
+
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_parity-wasm"))
+		.args(args)
+		.output()
+		.expect("command line tool to run")
+}
+
+fn stdout(output: &Output) -> String {
+	String::from_utf8(output.stdout.clone()).expect("output to be UTF-8")
+}
+
+fn stderr(output: &Output) -> String {
+	String::from_utf8(output.stderr.clone()).expect("errors to be UTF-8")
+}
+
+#[test]
+fn dump() {
+	let output = run(&["dump", "./res/cases/v1/hello.wasm"]);
+	assert!(output.status.success(), "{}", stderr(&output));
+	let text = stdout(&output);
+	assert!(text.starts_with("(module\n"));
+	assert!(text.contains("(import \"env\" \"DYNAMICTOP_PTR\" (global (;0;) i32))"));
+}
+
+#[test]
+fn validate() {
+	let output = run(&["validate", "./res/cases/v1/hello.wasm"]);
+	assert!(output.status.success(), "{}", stderr(&output));
+	assert_eq!(stdout(&output), "./res/cases/v1/hello.wasm: valid\n");
+
+	let output = run(&["validate", "./res/cases/v1/err-bad-magic.wasm"]);
+	assert_eq!(output.status.code(), Some(1));
+	assert!(stdout(&output).is_empty());
+	assert!(stderr(&output).starts_with("./res/cases/v1/err-bad-magic.wasm: Invalid magic number"));
+}
+
+#[test]
+fn strip() {
+	let path = std::env::temp_dir().join(format!("parity-wasm-cli-strip-{}.wasm", std::process::id()));
+	let path = path.to_str().expect("temporary path to be UTF-8");
+
+	let output = run(&["strip", "./res/cases/v1/with_names.wasm", path]);
+	assert!(output.status.success(), "{}", stderr(&output));
+	assert!(stdout(&output).ends_with(" bytes saved\n"));
+
+	let output = run(&["names", path]);
+	std::fs::remove_file(path).expect("stripped module to be removed");
+	assert!(output.status.success(), "{}", stderr(&output));
+	assert!(stdout(&output).is_empty());
+}
+
+#[test]
+fn names() {
+	let output = run(&["names", "./res/cases/v1/with_names.wasm"]);
+	assert!(output.status.success(), "{}", stderr(&output));
+	let names = stdout(&output);
+	assert!(names.starts_with("0 elog\n1 ext_free\n"));
+}
+
+#[test]
+fn usage() {
+	for args in &[&[][..], &["dump"][..], &["strip", "in.wasm", "out.wasm", "--unknown"][..]] {
+		let output = run(args);
+		assert_eq!(output.status.code(), Some(1));
+		assert!(stderr(&output).starts_with("Usage: parity-wasm <command>"));
+	}
+}